@@ -184,7 +184,85 @@ CREATE TRIGGER update_user_profiles_updated_at BEFORE UPDATE ON user_profiles FO
 CREATE TRIGGER update_tags_updated_at BEFORE UPDATE ON tags FOR EACH ROW EXECUTE PROCEDURE update_updated_at_column();
 CREATE TRIGGER update_entries_updated_at BEFORE UPDATE ON entries FOR EACH ROW EXECUTE PROCEDURE update_updated_at_column();
 
--- 16. Grant permissions
+-- 16. Change log for delta sync
+-- Every write to an entry appends a row for each user who can read it: the author and every
+-- holder of an access key. Clients page through their rows after their cursor (`seq`) and
+-- only download entries whose content hash differs from their copy. Deleted entries, and
+-- entries a reader lost access to, leave a tombstone row, so the log outlives the entry and
+-- has no foreign key to it.
+CREATE TABLE entry_changes (
+  seq BIGSERIAL PRIMARY KEY,
+  entry_id UUID NOT NULL,
+  author_id UUID REFERENCES user_profiles(id) ON DELETE CASCADE NOT NULL,
+  reader_id UUID REFERENCES user_profiles(id) ON DELETE CASCADE NOT NULL,
+  deleted BOOLEAN NOT NULL DEFAULT false,
+  content_hash TEXT,
+  changed_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+ALTER TABLE entry_changes ENABLE ROW LEVEL SECURITY;
+CREATE POLICY "Readers can view their entry changes" ON entry_changes FOR SELECT USING (reader_id = get_current_user_id());
+CREATE INDEX idx_entry_changes_reader_seq ON entry_changes(reader_id, seq);
+
+-- How far each device of a user has read the log. `GET /sync/changes?device_id=...` records
+-- the last `seq` it returned to the device. Rows below every cursor of a reader may be
+-- compacted away; a device whose `since` falls before the oldest remaining row gets 410 and
+-- reads the log from the start.
+CREATE TABLE sync_cursors (
+  user_id UUID REFERENCES user_profiles(id) ON DELETE CASCADE NOT NULL,
+  device_id TEXT NOT NULL,
+  seq BIGINT NOT NULL DEFAULT 0,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+
+  PRIMARY KEY (user_id, device_id)
+);
+ALTER TABLE sync_cursors ENABLE ROW LEVEL SECURITY;
+CREATE POLICY "Users can manage their own sync cursors" ON sync_cursors FOR ALL USING (user_id = get_current_user_id());
+
+-- Hashes the encrypted content as clients do: base64 SHA-512, without line breaks.
+CREATE OR REPLACE FUNCTION entry_content_hash(content TEXT)
+RETURNS TEXT AS $$
+  SELECT replace(encode(sha512(convert_to(content, 'UTF8')), 'base64'), E'\n', '');
+$$ LANGUAGE sql IMMUTABLE;
+
+-- Runs before a delete, while the access keys that cascade with the entry still exist.
+-- SECURITY DEFINER so the author's write can log rows for readers and see their keys.
+CREATE OR REPLACE FUNCTION record_entry_change()
+RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP = 'DELETE' THEN
+    INSERT INTO entry_changes (entry_id, author_id, reader_id, deleted)
+    SELECT OLD.id, OLD.author_id, reader_id, true
+    FROM (SELECT OLD.author_id AS reader_id UNION SELECT user_id FROM entry_access_keys WHERE entry_id = OLD.id) readers;
+    RETURN OLD;
+  END IF;
+  INSERT INTO entry_changes (entry_id, author_id, reader_id, content_hash)
+  SELECT NEW.id, NEW.author_id, reader_id, entry_content_hash(NEW.encrypted_content)
+  FROM (SELECT NEW.author_id AS reader_id UNION SELECT user_id FROM entry_access_keys WHERE entry_id = NEW.id) readers;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER;
+
+CREATE TRIGGER record_entries_change AFTER INSERT OR UPDATE ON entries FOR EACH ROW EXECUTE PROCEDURE record_entry_change();
+CREATE TRIGGER record_entries_delete BEFORE DELETE ON entries FOR EACH ROW EXECUTE PROCEDURE record_entry_change();
+
+-- Granting access logs the entry for its new reader; revoking it leaves them a tombstone.
+CREATE OR REPLACE FUNCTION record_access_change()
+RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP = 'DELETE' THEN
+    INSERT INTO entry_changes (entry_id, author_id, reader_id, deleted)
+    SELECT id, author_id, OLD.user_id, true FROM entries WHERE id = OLD.entry_id AND author_id <> OLD.user_id;
+    RETURN OLD;
+  END IF;
+  INSERT INTO entry_changes (entry_id, author_id, reader_id, content_hash)
+  SELECT id, author_id, NEW.user_id, entry_content_hash(encrypted_content) FROM entries WHERE id = NEW.entry_id AND author_id <> NEW.user_id;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER;
+
+CREATE TRIGGER record_access_keys_change AFTER INSERT OR DELETE ON entry_access_keys FOR EACH ROW EXECUTE PROCEDURE record_access_change();
+
+-- 17. Grant permissions
 -- These permissions are for a generic 'authenticated_user' role that your API will use.
 -- You must create this role and grant it to your database user.
 -- Example: CREATE ROLE authenticated_user; GRANT authenticated_user TO my_api_user;
@@ -0,0 +1,566 @@
+//! Typed client for the Diaryx REST API.
+//!
+//! Every request carries the session's bearer token and `X-User-ID` header.
+//! Successful responses are unwrapped from the API's `{ "data": ... }`
+//! envelope; failures become [`Error::Api`] with the server's message, or
+//! [`Error::Cloud`] when the server could not be reached.
+//!
+//! Transient failures (connection errors, 429 and 5xx) are retried with
+//! exponential backoff. `POST` is not idempotent, so it is only retried when
+//! the request never reached the server or was rate limited.
+
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod models;
+
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri_plugin_http::reqwest::{Client, Method, StatusCode};
+
+use crate::error::{Error, Result};
+use models::{
+    ChangePage, Entry, EntryAccessKey, EntryPayload, NewAccessKey, NewTag, NewUserTag,
+    OwnerKeyUpdate, ProfileKeys, SharedEntryInfo, Tag, TagUpdate, UserProfile, UserTag,
+};
+
+/// Who the client acts as.
+#[derive(Clone)]
+pub struct Credentials {
+    pub user_id: String,
+    pub access_token: String,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("user_id", &self.user_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// How often and how patiently transient failures are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retrying after the `attempt`-th (1-based) failure.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+#[derive(Deserialize)]
+struct Envelope<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct CreatedEntry {
+    entry: Entry,
+}
+
+/// Authenticated API client. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    client: Client,
+    base_url: String,
+    credentials: Credentials,
+    retry: RetryPolicy,
+}
+
+impl ApiClient {
+    pub fn new(base_url: &str, credentials: Credentials) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            credentials,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.credentials.user_id
+    }
+
+    // --- user_profiles ---
+
+    pub async fn get_profile(&self, user_id: &str) -> Result<UserProfile> {
+        self.get(&format!("/users/{user_id}")).await
+    }
+
+    /// Publish the user's public key and password envelope.
+    pub async fn update_profile_keys(&self, keys: &ProfileKeys) -> Result<()> {
+        let path = format!("/users/{}", self.credentials.user_id);
+        self.call(Method::PUT, &path, Some(keys)).await
+    }
+
+    // --- entries ---
+
+    /// Entries visible to the user, including their own.
+    pub async fn list_entries(&self) -> Result<Vec<Entry>> {
+        self.get("/entries").await
+    }
+
+    pub async fn get_entry(&self, entry_id: &str) -> Result<Entry> {
+        self.get(&format!("/entries/{entry_id}")).await
+    }
+
+    pub async fn create_entry(&self, payload: &EntryPayload) -> Result<Entry> {
+        let created: CreatedEntry = self.data(Method::POST, "/entries", Some(payload)).await?;
+        Ok(created.entry)
+    }
+
+    pub async fn update_entry(&self, entry_id: &str, payload: &EntryPayload) -> Result<Entry> {
+        self.data(Method::PUT, &format!("/entries/{entry_id}"), Some(payload))
+            .await
+    }
+
+    pub async fn update_owner_key(&self, entry_id: &str, key: &OwnerKeyUpdate) -> Result<()> {
+        self.call(Method::PUT, &format!("/entries/{entry_id}"), Some(key))
+            .await
+    }
+
+    pub async fn delete_entry(&self, entry_id: &str) -> Result<()> {
+        self.call(Method::DELETE, &format!("/entries/{entry_id}"), None::<&()>)
+            .await
+    }
+
+    /// Entries other users shared with this user.
+    pub async fn shared_with_me(&self) -> Result<Vec<Entry>> {
+        self.get("/entries/shared-with-me").await
+    }
+
+    /// Who an owned entry is shared with, and their access keys.
+    pub async fn shared_info(&self, entry_id: &str) -> Result<SharedEntryInfo> {
+        self.get(&format!("/entries/{entry_id}/shared")).await
+    }
+
+    // --- entry_changes ---
+
+    /// Up to `limit` changes to entries the user can read after `since`,
+    /// oldest first: their own and those shared with them.
+    ///
+    /// `device_id` lets the server track how far each device has read.
+    pub async fn list_changes(
+        &self,
+        since: Option<&str>,
+        device_id: &str,
+        limit: usize,
+    ) -> Result<ChangePage> {
+        let limit = limit.to_string();
+        let mut query = vec![("device_id", device_id), ("limit", limit.as_str())];
+        if let Some(since) = since {
+            query.push(("since", since));
+        }
+        self.get_query("/sync/changes", &query).await
+    }
+
+    // --- entry_access_keys ---
+
+    /// The user's own access key for `entry_id`, if it was shared with them.
+    pub async fn access_key(&self, entry_id: &str) -> Result<Option<EntryAccessKey>> {
+        match self.get(&format!("/entry-access-keys/{entry_id}")).await {
+            Ok(key) => Ok(Some(key)),
+            Err(Error::Api { status: 404, .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Insert or replace access keys of `entry_id`.
+    pub async fn put_access_keys(&self, entry_id: &str, keys: &[NewAccessKey]) -> Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let body = json!({ "entry_id": entry_id, "access_keys": keys });
+        self.call(Method::POST, "/entry-access-keys/batch", Some(&body))
+            .await
+    }
+
+    pub async fn revoke_access(&self, entry_id: &str, user_id: &str) -> Result<()> {
+        let path = format!("/entry-access-keys/{entry_id}/{user_id}");
+        self.call(Method::DELETE, &path, None::<&()>).await
+    }
+
+    pub async fn revoke_all_access(&self, entry_id: &str) -> Result<()> {
+        let path = format!("/entry-access-keys/entry/{entry_id}");
+        self.call(Method::DELETE, &path, None::<&()>).await
+    }
+
+    // --- tags ---
+
+    pub async fn list_tags(&self) -> Result<Vec<Tag>> {
+        self.get("/tags").await
+    }
+
+    pub async fn create_tag(&self, tag: &NewTag) -> Result<Tag> {
+        self.data(Method::POST, "/tags", Some(tag)).await
+    }
+
+    pub async fn update_tag(&self, tag_id: &str, update: &TagUpdate) -> Result<Tag> {
+        self.data(Method::PUT, &format!("/tags/{tag_id}"), Some(update))
+            .await
+    }
+
+    pub async fn delete_tag(&self, tag_id: &str) -> Result<()> {
+        self.call(Method::DELETE, &format!("/tags/{tag_id}"), None::<&()>)
+            .await
+    }
+
+    // --- user_tags ---
+
+    /// Tags the user granted, optionally only for `tag_id`.
+    pub async fn list_user_tags(&self, tag_id: Option<&str>) -> Result<Vec<UserTag>> {
+        match tag_id {
+            Some(tag_id) => self.get_query("/user-tags", &[("tag_id", tag_id)]).await,
+            None => self.get("/user-tags").await,
+        }
+    }
+
+    pub async fn create_user_tag(&self, user_tag: &NewUserTag) -> Result<UserTag> {
+        self.data(Method::POST, "/user-tags", Some(user_tag)).await
+    }
+
+    pub async fn delete_user_tag(&self, user_tag_id: &str) -> Result<()> {
+        let path = format!("/user-tags/{user_tag_id}");
+        self.call(Method::DELETE, &path, None::<&()>).await
+    }
+
+    // --- transport ---
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.get_query(path, &[]).await
+    }
+
+    /// `GET path` with `query` parameters, which are percent-encoded.
+    async fn get_query<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        let bytes = self.send(Method::GET, path, query, None::<&()>).await?;
+        unwrap_envelope(path, &bytes)
+    }
+
+    async fn data<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&impl Serialize>,
+    ) -> Result<T> {
+        let bytes = self.send(method, path, &[], body).await?;
+        unwrap_envelope(path, &bytes)
+    }
+
+    async fn call(&self, method: Method, path: &str, body: Option<&impl Serialize>) -> Result<()> {
+        self.send(method, path, &[], body).await.map(drop)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&impl Serialize>,
+    ) -> Result<Vec<u8>> {
+        let body = body
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| Error::Cloud(e.to_string()))?;
+        let url = format!("{}{path}", self.base_url);
+
+        let mut attempt = 1;
+        loop {
+            let mut request = self
+                .client
+                .request(method.clone(), &url)
+                .bearer_auth(&self.credentials.access_token)
+                .header("X-User-ID", &self.credentials.user_id);
+            if !query.is_empty() {
+                request = request.query(query);
+            }
+            if let Some(body) = &body {
+                request = request
+                    .header("Content-Type", "application/json")
+                    .body(body.clone());
+            }
+
+            let (error, retryable) = match request.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    let bytes = response.bytes().await.map_err(transport_error)?;
+                    if status.is_success() {
+                        return Ok(bytes.to_vec());
+                    }
+                    let retryable = status == StatusCode::TOO_MANY_REQUESTS
+                        || (status.is_server_error() && method != Method::POST);
+                    (api_error(status, &bytes), retryable)
+                }
+                Err(e) => {
+                    let retryable = e.is_connect() || (e.is_timeout() && method != Method::POST);
+                    (transport_error(e), retryable)
+                }
+            };
+
+            if !retryable || attempt >= self.retry.max_attempts {
+                return Err(error);
+            }
+            log::debug!("{method} {path} failed (attempt {attempt}), retrying: {error}");
+            tokio::time::sleep(self.retry.delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn unwrap_envelope<T: DeserializeOwned>(path: &str, bytes: &[u8]) -> Result<T> {
+    let envelope: Envelope<T> = serde_json::from_slice(bytes)
+        .map_err(|e| Error::Cloud(format!("unexpected response from {path}: {e}")))?;
+    Ok(envelope.data)
+}
+
+fn api_error(status: StatusCode, body: &[u8]) -> Error {
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|body| {
+            ["message", "error"]
+                .iter()
+                .find_map(|key| body.get(key)?.as_str().map(str::to_string))
+        })
+        .unwrap_or_else(|| {
+            status
+                .canonical_reason()
+                .unwrap_or("request failed")
+                .to_string()
+        });
+    Error::Api {
+        status: status.as_u16(),
+        message,
+    }
+}
+
+fn transport_error(error: impl std::fmt::Display) -> Error {
+    Error::Cloud(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{MockResponse, MockServer};
+    use super::*;
+
+    fn client(server: &MockServer) -> ApiClient {
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token-1".into(),
+        };
+        ApiClient::new(&server.url(), credentials).with_retry(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        })
+    }
+
+    fn entry_row(id: &str) -> Value {
+        json!({
+            "id": id,
+            "author_id": "user-1",
+            "encrypted_title": "t",
+            "encrypted_content": "c",
+            "encrypted_frontmatter": null,
+            "encryption_metadata": "{\"contentNonceB64\":\"n\"}",
+            "title_hash": "h",
+            "content_preview_hash": null,
+            "is_published": null,
+            "file_path": "a.md",
+            "created_at": "2026-01-01T00:00:00Z",
+            "updated_at": "2026-01-02T00:00:00.123+00:00",
+            "owner_encrypted_entry_key": "k",
+            "owner_key_nonce": "kn"
+        })
+    }
+
+    #[tokio::test]
+    async fn sends_auth_headers_and_unwraps_envelope() {
+        let server = MockServer::start().await;
+        server.respond(
+            "GET",
+            "/users/*",
+            [MockResponse::data(json!({
+                "id": "user-1",
+                "public_key": "pk",
+                "discoverable": null,
+                "created_at": "2026-01-01T00:00:00Z",
+                "updated_at": "2026-01-01T00:00:00Z"
+            }))],
+        );
+
+        let profile = client(&server).get_profile("user-1").await.unwrap();
+        assert_eq!(profile.public_key.as_deref(), Some("pk"));
+        assert!(profile.discoverable && profile.sync_enabled);
+
+        let request = &server.requests()[0];
+        assert_eq!(request.path, "/users/user-1");
+        assert_eq!(request.header("Authorization"), Some("Bearer token-1"));
+        assert_eq!(request.header("X-User-ID"), Some("user-1"));
+    }
+
+    #[tokio::test]
+    async fn parses_entry_rows() {
+        let server = MockServer::start().await;
+        server.respond(
+            "GET",
+            "/entries",
+            [MockResponse::data(json!([entry_row("e1")]))],
+        );
+        server.respond(
+            "POST",
+            "/entries",
+            [MockResponse::data(json!({ "entry": entry_row("e2") }))],
+        );
+
+        let api = client(&server);
+        let entries = api.list_entries().await.unwrap();
+        assert_eq!(entries[0].encryption_metadata["contentNonceB64"], "n");
+        assert!(!entries[0].is_published);
+
+        let payload = EntryPayload {
+            encrypted_title: "t".into(),
+            encrypted_content: "c".into(),
+            encrypted_frontmatter: None,
+            encryption_metadata: json!({}),
+            title_hash: "h".into(),
+            content_preview_hash: None,
+            is_published: true,
+            file_path: None,
+            owner_encrypted_entry_key: "k".into(),
+            owner_key_nonce: "kn".into(),
+            tag_ids: None,
+            client_modified_at: None,
+            if_unmodified_since: None,
+        };
+        assert_eq!(api.create_entry(&payload).await.unwrap().id, "e2");
+        let sent = &server.requests_to("POST", "/entries")[0].body;
+        assert_eq!(sent["is_published"], true);
+        assert!(sent.get("tag_ids").is_none());
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures_with_backoff() {
+        let server = MockServer::start().await;
+        server.respond(
+            "GET",
+            "/tags",
+            [
+                MockResponse::status(503),
+                MockResponse::error(429, "slow down"),
+                MockResponse::data(json!([])),
+            ],
+        );
+        assert!(client(&server).list_tags().await.unwrap().is_empty());
+        assert_eq!(server.requests().len(), 3);
+
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(4), Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn maps_errors_without_retrying_posts() {
+        let server = MockServer::start().await;
+        server.respond("POST", "/user-tags", [MockResponse::error(500, "boom")]);
+        server.respond("GET", "/entry-access-keys/*", [MockResponse::status(404)]);
+
+        let api = client(&server);
+        let new = NewUserTag {
+            tag_id: "t".into(),
+            target_id: "u".into(),
+        };
+        match api.create_user_tag(&new).await {
+            Err(Error::Api { status, message }) => {
+                assert_eq!((status, message.as_str()), (500, "boom"))
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(server.requests_to("POST", "/user-tags").len(), 1);
+
+        assert!(api.access_key("e1").await.unwrap().is_none());
+
+        let unreachable =
+            ApiClient::new("http://127.0.0.1:1", api.credentials.clone()).with_retry(api.retry);
+        assert!(matches!(
+            unreachable.list_tags().await,
+            Err(Error::Cloud(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn encodes_query_parameters() {
+        let server = MockServer::start().await;
+        server.respond(
+            "GET",
+            "/sync/changes",
+            [MockResponse::data(json!({ "changes": [], "cursor": "c" }))],
+        );
+
+        let api = client(&server);
+        api.list_changes(Some("2026-01-01T00:00:00+00:00&x=1"), "device 1", 50)
+            .await
+            .unwrap();
+        let query = server.requests()[0].query.clone().unwrap();
+        assert_eq!(
+            query,
+            "device_id=device+1&limit=50&since=2026-01-01T00%3A00%3A00%2B00%3A00%26x%3D1"
+        );
+    }
+
+    #[tokio::test]
+    async fn posts_access_key_batches() {
+        let server = MockServer::start().await;
+        server.respond(
+            "POST",
+            "/entry-access-keys/batch",
+            [MockResponse::data(json!([]))],
+        );
+
+        let api = client(&server);
+        api.put_access_keys("e1", &[]).await.unwrap();
+        assert!(server.requests().is_empty());
+
+        let key = NewAccessKey {
+            user_id: "friend".into(),
+            encrypted_entry_key: "k".into(),
+            key_nonce: "n".into(),
+        };
+        api.put_access_keys("e1", &[key]).await.unwrap();
+        let body = &server.requests()[0].body;
+        assert_eq!(body["entry_id"], "e1");
+        assert_eq!(body["access_keys"][0]["user_id"], "friend");
+    }
+}
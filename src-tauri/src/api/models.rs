@@ -0,0 +1,277 @@
+//! Rows and request bodies of the Diaryx API.
+//!
+//! Row types mirror the tables in `rbac-schema.sql`: `NOT NULL` columns are
+//! required, nullable ones are `Option`. Fields the API adds through joins
+//! or key handling are marked as such.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+/// A row of `user_profiles`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub id: String,
+    #[serde(default)]
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub public_key: Option<String>,
+    /// Password envelope of the secret key; stored by the API alongside the profile.
+    #[serde(default)]
+    pub encrypted_private_key: Option<String>,
+    #[serde(default = "default_true", deserialize_with = "null_as_true")]
+    pub discoverable: bool,
+    #[serde(default = "default_true", deserialize_with = "null_as_true")]
+    pub sync_enabled: bool,
+    #[serde(default)]
+    pub last_sync_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The public part of a profile, as joined onto other rows and search results.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicUser {
+    pub id: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+/// A row of `tags`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: String,
+    pub name: String,
+    pub slug: String,
+    #[serde(default)]
+    pub color: Option<String>,
+    pub author_id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A row of `entries`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    pub author_id: String,
+    pub encrypted_title: String,
+    pub encrypted_content: String,
+    #[serde(default)]
+    pub encrypted_frontmatter: Option<String>,
+    /// Stored as JSONB, but some API versions return it as a JSON string.
+    #[serde(deserialize_with = "json_or_string")]
+    pub encryption_metadata: Value,
+    pub title_hash: String,
+    #[serde(default)]
+    pub content_preview_hash: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub is_published: bool,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The author's own wrapped entry key; only returned to the author.
+    #[serde(default)]
+    pub owner_encrypted_entry_key: Option<String>,
+    #[serde(default)]
+    pub owner_key_nonce: Option<String>,
+    /// The caller's access key, joined from `entry_access_keys`.
+    #[serde(default)]
+    pub access_key: Option<KeyRef>,
+    /// The author's public key, joined on shared entries.
+    #[serde(default)]
+    pub author_public_key: Option<String>,
+}
+
+/// A wrapped entry key without its row metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRef {
+    pub encrypted_entry_key: String,
+    pub key_nonce: String,
+}
+
+/// A row of `entry_tags`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryTag {
+    pub id: String,
+    pub entry_id: String,
+    pub tag_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A row of `user_tags`: `tagger_id` shares `tag_id` with `target_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserTag {
+    pub id: String,
+    pub tagger_id: String,
+    pub target_id: String,
+    pub tag_id: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub tag: Option<Tag>,
+    #[serde(default)]
+    pub target_user: Option<PublicUser>,
+}
+
+/// A row of `entry_access_keys`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryAccessKey {
+    pub id: String,
+    pub entry_id: String,
+    pub user_id: String,
+    pub encrypted_entry_key: String,
+    pub key_nonce: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub user: Option<PublicUser>,
+}
+
+/// Sharing state of one entry, from `/entries/{id}/shared`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedEntryInfo {
+    #[serde(default)]
+    pub entry_id: Option<String>,
+    #[serde(default)]
+    pub shared_with_users: Vec<PublicUser>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub access_keys: Vec<EntryAccessKey>,
+}
+
+/// A row of `entry_changes`: one write to an entry, or its deletion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryChange {
+    pub seq: i64,
+    pub entry_id: String,
+    /// Another user's for entries shared with this one.
+    #[serde(default)]
+    pub author_id: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub deleted: bool,
+    /// Base64 SHA-512 of `encrypted_content`; absent on tombstones.
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// A page of `GET /sync/changes`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangePage {
+    pub changes: Vec<EntryChange>,
+    /// Opaque position after the last change, to pass as `since` next time.
+    pub cursor: String,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub has_more: bool,
+}
+
+/// Body of `POST /entries` and `PUT /entries/{id}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EntryPayload {
+    pub encrypted_title: String,
+    pub encrypted_content: String,
+    pub encrypted_frontmatter: Option<String>,
+    pub encryption_metadata: Value,
+    pub title_hash: String,
+    pub content_preview_hash: Option<String>,
+    pub is_published: bool,
+    pub file_path: Option<String>,
+    pub owner_encrypted_entry_key: String,
+    pub owner_key_nonce: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_modified_at: Option<DateTime<Utc>>,
+    /// Rejects the update with 409 if the entry changed on the server since.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub if_unmodified_since: Option<DateTime<Utc>>,
+}
+
+/// Body of a `PUT /entries/{id}` that only replaces the owner's wrapped key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OwnerKeyUpdate {
+    pub owner_encrypted_entry_key: String,
+    pub owner_key_nonce: String,
+}
+
+/// Body of `POST /tags`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NewTag {
+    pub name: String,
+    pub slug: String,
+    pub color: Option<String>,
+}
+
+/// Body of `PUT /tags/{id}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TagUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+/// Body of `POST /user-tags`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NewUserTag {
+    pub tag_id: String,
+    pub target_id: String,
+}
+
+/// One key of a `POST /entry-access-keys/batch`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NewAccessKey {
+    pub user_id: String,
+    pub encrypted_entry_key: String,
+    pub key_nonce: String,
+}
+
+/// Body of `PUT /users/{id}` publishing the user's keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProfileKeys {
+    pub public_key: String,
+    pub encrypted_private_key: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn null_as_true<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(Option::<bool>::deserialize(deserializer)?.unwrap_or(true))
+}
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+fn json_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(raw) => serde_json::from_str(&raw).map_err(serde::de::Error::custom),
+        value => Ok(value),
+    }
+}
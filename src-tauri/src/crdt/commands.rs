@@ -0,0 +1,25 @@
+//! Tauri commands switching CRDT mode.
+
+use tauri::State;
+
+use super::CrdtStatus;
+use crate::error::Result;
+use crate::vaults::Vaults;
+
+#[tauri::command]
+pub fn crdt_status(vaults: State<'_, Vaults>) -> CrdtStatus {
+    vaults.outbox().docs().status()
+}
+
+/// Turn CRDT mode on. Returns the number of documents created.
+#[tauri::command]
+pub async fn enable_crdt_mode(vaults: State<'_, Vaults>) -> Result<usize> {
+    let (store, outbox) = vaults.store_and_outbox();
+    tokio::task::spawn_blocking(move || outbox.docs().enable(&store)).await?
+}
+
+/// Turn CRDT mode off. Published entries fall back to three-way merges.
+#[tauri::command]
+pub fn disable_crdt_mode(vaults: State<'_, Vaults>) -> Result<()> {
+    vaults.outbox().docs().disable()
+}
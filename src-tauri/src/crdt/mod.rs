@@ -0,0 +1,455 @@
+//! Optional CRDT entry documents.
+//!
+//! With CRDT mode on, every entry also lives in a Yjs text document (via
+//! `yrs`), so edits made on several devices merge without conflicts. The
+//! `.md` file stays the editing surface: changes to it are folded into the
+//! document as line-level edits before each sync, and the merged text is
+//! written back to the file after every remote update. Documents travel to
+//! the cloud inside the encrypted entry object, as a full-state update.
+//!
+//! Document states are kept outside the journal and sealed with the
+//! journal's vault, like the sync bases.
+
+pub mod commands;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crypto_box::aead::rand_core::RngCore;
+use crypto_box::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use yrs::updates::decoder::Decode;
+use yrs::{
+    Doc, GetString, OffsetKind, Options, ReadTxn, StateVector, Text, TextRef, Transact, Update,
+};
+
+use crate::error::{Error, Result};
+use crate::store::vault::Vault;
+use crate::store::{atomic, EntryStore};
+use crate::sync::merge;
+use crate::sync::snapshots::Snapshots;
+
+/// Name of the shared text holding the Markdown, as used by Yjs clients.
+pub const TEXT_NAME: &str = "content";
+
+const CONFIG_FILE: &str = "crdt.json";
+const CONFIG_VERSION: u32 = 1;
+
+/// One entry as a CRDT text document.
+pub struct EntryDoc {
+    doc: Doc,
+    text: TextRef,
+}
+
+impl EntryDoc {
+    /// An empty document editing as `client_id`.
+    pub fn new(client_id: u64) -> Self {
+        let mut options = Options::with_client_id(client_id);
+        options.offset_kind = OffsetKind::Bytes;
+        let doc = Doc::with_options(options);
+        let text = doc.get_or_insert_text(TEXT_NAME);
+        Self { doc, text }
+    }
+
+    /// A document holding the state encoded by [`EntryDoc::encode`].
+    pub fn decode(client_id: u64, state: &[u8]) -> Result<Self> {
+        let doc = Self::new(client_id);
+        doc.apply(state)?;
+        Ok(doc)
+    }
+
+    /// The full state as a Yjs v1 update.
+    pub fn encode(&self) -> Vec<u8> {
+        self.doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default())
+    }
+
+    pub fn state_vector(&self) -> StateVector {
+        self.doc.transact().state_vector()
+    }
+
+    pub fn text(&self) -> String {
+        self.text.get_string(&self.doc.transact())
+    }
+
+    /// Merge a Yjs v1 update from another replica.
+    pub fn apply(&self, update: &[u8]) -> Result<()> {
+        let update = Update::decode_v1(update).map_err(|e| Error::Crdt(e.to_string()))?;
+        self.doc
+            .transact_mut()
+            .apply_update(update)
+            .map_err(|e| Error::Crdt(e.to_string()))
+    }
+
+    /// Edit the text into `new`, touching only the lines that changed.
+    ///
+    /// Returns whether anything changed.
+    pub fn set_text(&self, new: &str) -> bool {
+        let old = self.text();
+        if old == new {
+            return false;
+        }
+
+        let mut txn = self.doc.transact_mut();
+        // Back to front, so earlier offsets stay valid.
+        for hunk in hunks(&old, new).into_iter().rev() {
+            let removed = u32::try_from(hunk.old.len()).unwrap_or(u32::MAX);
+            let at = u32::try_from(hunk.old.start).unwrap_or(u32::MAX);
+            if removed > 0 {
+                self.text.remove_range(&mut txn, at, removed);
+            }
+            if !hunk.new.is_empty() {
+                self.text.insert(&mut txn, at, &new[hunk.new]);
+            }
+        }
+        true
+    }
+}
+
+/// A replaced byte range of the old text and its replacement in the new one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hunk {
+    old: std::ops::Range<usize>,
+    new: std::ops::Range<usize>,
+}
+
+/// Line-level differences between `old` and `new`, trimmed to the changed characters.
+fn hunks(old: &str, new: &str) -> Vec<Hunk> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let matched = merge::line_matches(&old_lines, &new_lines);
+
+    let offsets = |lines: &[&str]| -> Vec<usize> {
+        let mut offsets = vec![0];
+        for line in lines {
+            offsets.push(offsets.last().unwrap_or(&0) + line.len());
+        }
+        offsets
+    };
+    let (old_at, new_at) = (offsets(&old_lines), offsets(&new_lines));
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let anchors = matched
+        .iter()
+        .enumerate()
+        .filter_map(|(i, j)| Some((i, (*j)?)))
+        .chain([(old_lines.len(), new_lines.len())]);
+    for (next_i, next_j) in anchors {
+        if next_i > i || next_j > j {
+            let hunk = Hunk {
+                old: old_at[i]..old_at[next_i],
+                new: new_at[j]..new_at[next_j],
+            };
+            out.push(trim(old, new, hunk));
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+    out
+}
+
+/// Shrink `hunk` by the characters both sides share at its ends.
+fn trim(old: &str, new: &str, mut hunk: Hunk) -> Hunk {
+    let prefix: usize = old[hunk.old.clone()]
+        .chars()
+        .zip(new[hunk.new.clone()].chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    hunk.old.start += prefix;
+    hunk.new.start += prefix;
+
+    let suffix: usize = old[hunk.old.clone()]
+        .chars()
+        .rev()
+        .zip(new[hunk.new.clone()].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    hunk.old.end -= suffix;
+    hunk.new.end -= suffix;
+    hunk
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CrdtConfig {
+    version: u32,
+    /// This device's Yjs client ID. Kept below 2^32 for JavaScript peers.
+    client_id: u64,
+}
+
+/// Whether CRDT mode is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrdtStatus {
+    pub enabled: bool,
+    pub documents: usize,
+}
+
+/// The CRDT documents of a journal. CRDT mode is on while a config file exists.
+#[derive(Debug)]
+pub struct EntryDocs {
+    dir: PathBuf,
+    config: Mutex<Option<CrdtConfig>>,
+    states: Snapshots,
+}
+
+impl EntryDocs {
+    /// Load the documents kept in `dir`.
+    pub fn load(dir: &Path) -> Self {
+        let config = fs::read_to_string(dir.join(CONFIG_FILE))
+            .ok()
+            .and_then(|raw| serde_json::from_str::<CrdtConfig>(&raw).ok())
+            .filter(|config| config.version == CONFIG_VERSION);
+        Self {
+            dir: dir.to_path_buf(),
+            config: Mutex::new(config),
+            states: Snapshots::new(Self::states_dir(dir)),
+        }
+    }
+
+    /// Where the document states of the documents kept in `dir` are.
+    pub fn states_dir(dir: &Path) -> PathBuf {
+        dir.join("docs")
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<CrdtConfig>> {
+        self.config.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn client_id(&self) -> Option<u64> {
+        self.lock().as_ref().map(|config| config.client_id)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.client_id().is_some()
+    }
+
+    pub fn status(&self) -> CrdtStatus {
+        let documents = fs::read_dir(Self::states_dir(&self.dir)).map_or(0, |dir| dir.count());
+        CrdtStatus {
+            enabled: self.is_enabled(),
+            documents,
+        }
+    }
+
+    /// Turn CRDT mode on and create a document for every entry.
+    ///
+    /// Returns the number of documents created.
+    pub fn enable(&self, store: &EntryStore) -> Result<usize> {
+        {
+            let mut config = self.lock();
+            if config.is_none() {
+                let created = CrdtConfig {
+                    version: CONFIG_VERSION,
+                    client_id: u64::from(OsRng.next_u32()),
+                };
+                let json = serde_json::to_vec_pretty(&created).map_err(std::io::Error::from)?;
+                fs::create_dir_all(&self.dir)?;
+                atomic::write_atomic(&self.dir.join(CONFIG_FILE), &json)?;
+                *config = Some(created);
+            }
+        }
+
+        let mut created = 0;
+        for id in store.entry_ids()? {
+            if self.states.read(store.vault(), &id)?.is_none() {
+                if let Some(entry) = store.get_entry(&id)? {
+                    self.capture(store.vault(), &id, &entry.content)?;
+                    created += 1;
+                }
+            }
+        }
+        Ok(created)
+    }
+
+    /// Turn CRDT mode off and drop every document. Entry files are untouched.
+    pub fn disable(&self) -> Result<()> {
+        *self.lock() = None;
+        match fs::remove_dir_all(&self.dir) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn open(&self, vault: &Vault, entry_id: &str, client_id: u64) -> Result<EntryDoc> {
+        match self.states.read(vault, entry_id)? {
+            Some(state) => {
+                let state = STANDARD
+                    .decode(state)
+                    .map_err(|e| Error::Crdt(e.to_string()))?;
+                EntryDoc::decode(client_id, &state)
+            }
+            None => Ok(EntryDoc::new(client_id)),
+        }
+    }
+
+    fn save(&self, vault: &Vault, entry_id: &str, doc: &EntryDoc) -> Result<()> {
+        self.states
+            .set(vault, entry_id, &STANDARD.encode(doc.encode()))
+    }
+
+    /// Fold the file content of `entry_id` into its document and return the
+    /// encoded state, or `None` when CRDT mode is off.
+    pub fn capture(&self, vault: &Vault, entry_id: &str, content: &str) -> Result<Option<Vec<u8>>> {
+        let Some(client_id) = self.client_id() else {
+            return Ok(None);
+        };
+        let doc = self.open(vault, entry_id, client_id)?;
+        if doc.set_text(content) || self.states.get(vault, entry_id).is_none() {
+            self.save(vault, entry_id, &doc)?;
+        }
+        Ok(Some(doc.encode()))
+    }
+
+    /// Merge a remote document state into `entry_id`, after folding in the
+    /// local `content`.
+    ///
+    /// Returns the merged text and whether the local document has changes the
+    /// remote state lacks, or `None` when the histories never met, e.g. CRDT
+    /// mode was enabled on both devices separately. Merging those would
+    /// duplicate the text, so the document is left alone for the caller to
+    /// merge the text itself and then [`EntryDocs::adopt_remote`].
+    pub fn merge_remote(
+        &self,
+        vault: &Vault,
+        entry_id: &str,
+        content: &str,
+        remote_state: &[u8],
+    ) -> Result<Option<(String, bool)>> {
+        let client_id = self
+            .client_id()
+            .ok_or_else(|| Error::Crdt("CRDT mode is off".into()))?;
+        let remote = EntryDoc::decode(client_id, remote_state)?;
+        let local = self.open(vault, entry_id, client_id)?;
+        let remote_clients = remote.state_vector();
+        let shared = local
+            .state_vector()
+            .iter()
+            .any(|(client, _)| remote_clients.contains_client(client));
+        if !shared {
+            return Ok(None);
+        }
+
+        local.set_text(content);
+        local.apply(remote_state)?;
+        self.save(vault, entry_id, &local)?;
+        Ok(Some((local.text(), local.state_vector() != remote_clients)))
+    }
+
+    /// Replace the document of `entry_id` with the remote history, with
+    /// `content` folded in, so later merges with that device share history.
+    pub fn adopt_remote(
+        &self,
+        vault: &Vault,
+        entry_id: &str,
+        content: &str,
+        remote_state: &[u8],
+    ) -> Result<()> {
+        let client_id = self
+            .client_id()
+            .ok_or_else(|| Error::Crdt("CRDT mode is off".into()))?;
+        let doc = EntryDoc::decode(client_id, remote_state)?;
+        doc.set_text(content);
+        self.save(vault, entry_id, &doc)
+    }
+
+    pub fn remove(&self, entry_id: &str) -> Result<()> {
+        self.states.remove(entry_id)
+    }
+
+    /// Move the document of each old entry ID in `renames` to its new one.
+    pub fn rename_entries(&self, renames: &BTreeMap<String, String>) -> Result<()> {
+        self.states.rename_entries(renames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_edits_merge_without_conflicts() {
+        let laptop = EntryDoc::new(1);
+        laptop.set_text("# Day\n\nMorning.\nEvening.\n");
+        let phone = EntryDoc::decode(2, &laptop.encode()).unwrap();
+
+        laptop.set_text("# Day\n\nMorning run.\nEvening.\n");
+        phone.set_text("# Day\n\nMorning.\nEvening, tired.\n");
+        phone.apply(&laptop.encode()).unwrap();
+        laptop.apply(&phone.encode()).unwrap();
+
+        assert_eq!(laptop.text(), "# Day\n\nMorning run.\nEvening, tired.\n");
+        assert_eq!(phone.text(), laptop.text());
+        assert_eq!(phone.state_vector(), laptop.state_vector());
+    }
+
+    #[test]
+    fn documents_follow_vault_rekeys_and_unreadable_ones_fail() {
+        use crate::crypto::keys::UserKeyPair;
+
+        let dir = tempfile::tempdir().unwrap();
+        let crdt = dir.path().join("crdt");
+        let journal = dir.path().join("journal");
+        let store = EntryStore::new(&journal).with_sealed_dirs(vec![EntryDocs::states_dir(&crdt)]);
+        let day = store.create_entry("Day").unwrap();
+        store.save_entry(&day, "Morning.\n").unwrap();
+        let keys = UserKeyPair::generate();
+        store.unlock_vault(keys.clone()).unwrap();
+        store.enable_vault().unwrap();
+
+        let docs = EntryDocs::load(&crdt);
+        docs.enable(&store).unwrap();
+        let before = docs
+            .capture(store.vault(), &day, "Morning.\n")
+            .unwrap()
+            .unwrap();
+
+        let rotated = UserKeyPair::generate();
+        store.rekey_vault(&keys, rotated.clone()).unwrap();
+        let after = docs
+            .capture(store.vault(), &day, "Morning.\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(after, before);
+
+        // A journal whose documents were left behind by a rekey reports them.
+        let unconverted = EntryStore::new(&journal);
+        unconverted.unlock_vault(rotated.clone()).unwrap();
+        unconverted
+            .rekey_vault(&rotated, UserKeyPair::generate())
+            .unwrap();
+        let result = docs.capture(unconverted.vault(), &day, "Morning.\n");
+        assert!(matches!(result, Err(Error::Crypto(_))));
+    }
+
+    #[test]
+    fn set_text_edits_only_changed_characters() {
+        assert_eq!(
+            hunks("a\nbé c\nd\n", "a\nbé x c\nd\ne\n"),
+            [
+                Hunk {
+                    old: 6..6,
+                    new: 6..8
+                },
+                Hunk {
+                    old: 10..10,
+                    new: 12..14
+                },
+            ]
+        );
+
+        let doc = EntryDoc::new(1);
+        for text in ["", "one\n", "zero\none\n", "zero\n", "ünï\ncödé\n", ""] {
+            doc.set_text(text);
+            assert_eq!(doc.text(), text);
+        }
+    }
+}
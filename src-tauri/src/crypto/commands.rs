@@ -0,0 +1,72 @@
+//! Tauri commands exposing entry encryption to the webview.
+//!
+//! Every command uses the key pair held by the unlocked [`Session`]; only
+//! public keys and ciphertext cross the IPC boundary.
+
+use serde_json::Value;
+use tauri::State;
+
+use super::keys::{encode, public_key_from_b64};
+use super::{EncryptedEntryData, RewrappedKey};
+use crate::error::Result;
+use crate::session::Session;
+
+#[tauri::command]
+pub fn encrypt_entry(session: State<'_, Session>, entry: Value) -> Result<EncryptedEntryData> {
+    session.with_keys(|owner| super::encrypt_entry(&entry, owner))
+}
+
+/// Re-encrypt `entry` under its existing entry key, keeping issued access keys valid.
+#[tauri::command]
+pub fn encrypt_entry_with_key(
+    session: State<'_, Session>,
+    entry: Value,
+    encrypted_entry_key_b64: String,
+    key_nonce_b64: String,
+) -> Result<EncryptedEntryData> {
+    session.with_keys(|owner| {
+        super::encrypt_entry_with_key(&entry, &encrypted_entry_key_b64, &key_nonce_b64, owner)
+    })
+}
+
+#[tauri::command]
+pub fn decrypt_entry(
+    session: State<'_, Session>,
+    data: EncryptedEntryData,
+    author_public_key_b64: String,
+) -> Result<Value> {
+    let author = public_key_from_b64(&author_public_key_b64)?;
+    session.with_keys(|reader| super::decrypt_entry(&data, reader.secret(), &author))
+}
+
+#[tauri::command]
+pub fn rewrap_entry_key(
+    session: State<'_, Session>,
+    encrypted_entry_key_b64: String,
+    key_nonce_b64: String,
+    recipient_public_key_b64: String,
+) -> Result<RewrappedKey> {
+    let recipient = public_key_from_b64(&recipient_public_key_b64)?;
+    session.with_keys(|author| {
+        super::rewrap_entry_key(&encrypted_entry_key_b64, &key_nonce_b64, author, &recipient)
+    })
+}
+
+/// The raw key of one of the user's own entries, for share links that carry
+/// it to readers without an account. The user's secret key stays here.
+#[tauri::command]
+pub fn open_entry_key(
+    session: State<'_, Session>,
+    encrypted_entry_key_b64: String,
+    key_nonce_b64: String,
+) -> Result<String> {
+    session.with_keys(|owner| {
+        let key = super::unwrap_key(
+            &encrypted_entry_key_b64,
+            &key_nonce_b64,
+            &owner.public,
+            owner.secret(),
+        )?;
+        Ok(encode(&*key))
+    })
+}
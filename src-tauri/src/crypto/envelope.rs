@@ -0,0 +1,237 @@
+//! Password-protected secret key envelopes.
+//!
+//! Version 2 envelopes derive the wrapping key with Argon2id over a random
+//! salt and carry their parameters in a text header:
+//!
+//! ```text
+//! $dxk$v=2$argon2id$m=19456,t=2,p=1$<salt b64>$<nonce || secretbox b64>
+//! ```
+//!
+//! Legacy envelopes produced by `KeyManager.encryptSecretKey` are a bare
+//! base64 `nonce || secretbox` keyed by `SHA-512(password)[..32]`. They are
+//! still accepted by [`open`], which flags them for upgrade.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use crypto_box::aead::rand_core::RngCore;
+use crypto_box::aead::OsRng;
+use crypto_box::KEY_SIZE;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use zeroize::Zeroizing;
+
+use super::keys::{decode, encode};
+use crate::error::{Error, Result};
+
+const PREFIX: &str = "$dxk$";
+const VERSION: u32 = 2;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 24;
+/// Refuse to open envelopes demanding more than 1 GiB, to bound unlock cost.
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// Argon2id cost parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfParams {
+    /// Memory cost in KiB.
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// OWASP's recommended minimum for Argon2id.
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    fn weaker_than(&self, other: &KdfParams) -> bool {
+        self.memory_kib < other.memory_kib || self.iterations < other.iterations
+    }
+}
+
+/// Result of opening an envelope.
+pub struct Opened {
+    pub secret: Zeroizing<[u8; KEY_SIZE]>,
+    /// True for legacy envelopes or ones sealed with weaker than default parameters.
+    pub needs_upgrade: bool,
+}
+
+/// Seal a secret key under `password`.
+pub fn seal(secret: &[u8; KEY_SIZE], password: &str, params: KdfParams) -> Result<String> {
+    let mut salt = [0u8; SALT_SIZE];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive(password, &salt, &params)?;
+    let (ciphertext, nonce) = super::seal(secret, &key)?;
+
+    let mut payload = nonce;
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!(
+        "{PREFIX}v={VERSION}$argon2id$m={},t={},p={}${}${}",
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        encode(&salt),
+        encode(&payload)
+    ))
+}
+
+/// Open an envelope of either version.
+pub fn open(envelope: &str, password: &str) -> Result<Opened> {
+    match envelope.strip_prefix(PREFIX) {
+        Some(rest) => open_v2(rest, password),
+        None => open_legacy(envelope, password),
+    }
+}
+
+fn open_v2(rest: &str, password: &str) -> Result<Opened> {
+    let parts: Vec<&str> = rest.split('$').collect();
+    let [version, algorithm, params, salt, payload] = parts.as_slice() else {
+        return Err(invalid("malformed header"));
+    };
+    if *version != format!("v={VERSION}") || *algorithm != "argon2id" {
+        return Err(invalid("unsupported version or algorithm"));
+    }
+
+    let params = parse_params(params)?;
+    let key = derive(password, &decode(salt)?, &params)?;
+    let secret = open_payload(&decode(payload)?, &key)?;
+    Ok(Opened {
+        secret,
+        needs_upgrade: params.weaker_than(&KdfParams::default()),
+    })
+}
+
+fn open_legacy(envelope: &str, password: &str) -> Result<Opened> {
+    let digest = Sha512::digest(password.as_bytes());
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    key.copy_from_slice(&digest[..KEY_SIZE]);
+
+    let secret = open_payload(&decode(envelope)?, &key)?;
+    Ok(Opened {
+        secret,
+        needs_upgrade: true,
+    })
+}
+
+/// Split `nonce || ciphertext` and open it.
+fn open_payload(payload: &[u8], key: &[u8; KEY_SIZE]) -> Result<Zeroizing<[u8; KEY_SIZE]>> {
+    if payload.len() <= NONCE_SIZE {
+        return Err(invalid("payload too short"));
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_SIZE);
+    let plaintext = super::open(ciphertext, nonce, key)
+        .map_err(|_| Error::Crypto("incorrect password or corrupted key".into()))?;
+    let secret: [u8; KEY_SIZE] = plaintext
+        .as_slice()
+        .try_into()
+        .map_err(|_| invalid("unexpected secret key length"))?;
+    Ok(Zeroizing::new(secret))
+}
+
+fn derive(password: &str, salt: &[u8], params: &KdfParams) -> Result<Zeroizing<[u8; KEY_SIZE]>> {
+    if params.memory_kib > MAX_MEMORY_KIB {
+        return Err(invalid("memory cost too high"));
+    }
+    let argon_params = Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        Some(KEY_SIZE),
+    )
+    .map_err(|e| Error::Crypto(format!("invalid KDF parameters: {e}")))?;
+
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+        .hash_password_into(password.as_bytes(), salt, &mut *key)
+        .map_err(|e| Error::Crypto(format!("key derivation failed: {e}")))?;
+    Ok(key)
+}
+
+fn parse_params(raw: &str) -> Result<KdfParams> {
+    let mut params = KdfParams {
+        memory_kib: 0,
+        iterations: 0,
+        parallelism: 0,
+    };
+    for pair in raw.split(',') {
+        let (name, value) = pair
+            .split_once('=')
+            .ok_or_else(|| invalid("malformed parameters"))?;
+        let value: u32 = value.parse().map_err(|_| invalid("malformed parameters"))?;
+        match name {
+            "m" => params.memory_kib = value,
+            "t" => params.iterations = value,
+            "p" => params.parallelism = value,
+            _ => return Err(invalid("unknown parameter")),
+        }
+    }
+    Ok(params)
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::Crypto(format!("invalid key envelope: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/key-envelope.json");
+
+    fn fixture() -> Value {
+        serde_json::from_str(FIXTURE).unwrap()
+    }
+
+    fn expected_secret(fixture: &Value) -> Vec<u8> {
+        decode(fixture["secretKey"].as_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn opens_legacy_envelope_and_flags_upgrade() {
+        let fixture = fixture();
+        let opened = open(
+            fixture["legacyEnvelope"].as_str().unwrap(),
+            fixture["password"].as_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(opened.secret.to_vec(), expected_secret(&fixture));
+        assert!(opened.needs_upgrade);
+    }
+
+    #[test]
+    fn opens_v2_fixture() {
+        let fixture = fixture();
+        let opened = open(
+            fixture["v2Envelope"].as_str().unwrap(),
+            fixture["password"].as_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(opened.secret.to_vec(), expected_secret(&fixture));
+        assert!(!opened.needs_upgrade);
+    }
+
+    #[test]
+    fn seal_open_round_trip_rejects_wrong_password() {
+        let secret = [7u8; KEY_SIZE];
+        let params = KdfParams {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let envelope = seal(&secret, "pw", params).unwrap();
+        assert!(envelope.starts_with("$dxk$v=2$argon2id$m=64,t=1,p=1$"));
+
+        let opened = open(&envelope, "pw").unwrap();
+        assert_eq!(*opened.secret, secret);
+        assert!(opened.needs_upgrade);
+        assert!(open(&envelope, "wrong").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! Curve25519 user key pairs and base64 helpers.
+//!
+//! Secret key material is only held in [`SecretKey`] (zeroized on drop) or
+//! [`Zeroizing`] buffers, never in plain `Vec`s or `String`s that outlive a call.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crypto_box::aead::OsRng;
+use crypto_box::{PublicKey, SecretKey, KEY_SIZE};
+use zeroize::Zeroizing;
+
+use crate::error::{Error, Result};
+
+/// A user's box key pair. Mirrors `UserKeyPair` in `KeyManager.ts`.
+#[derive(Clone)]
+pub struct UserKeyPair {
+    pub public: PublicKey,
+    secret: SecretKey,
+}
+
+impl std::fmt::Debug for UserKeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UserKeyPair")
+            .field("public", &encode(self.public.as_bytes()))
+            .finish_non_exhaustive()
+    }
+}
+
+impl UserKeyPair {
+    /// Generate a fresh random key pair.
+    pub fn generate() -> Self {
+        Self::from_secret(SecretKey::generate(&mut OsRng))
+    }
+
+    pub fn from_secret(secret: SecretKey) -> Self {
+        Self {
+            public: secret.public_key(),
+            secret,
+        }
+    }
+
+    /// Build a key pair from raw secret key bytes, deriving the public key.
+    pub fn from_secret_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes: Zeroizing<[u8; KEY_SIZE]> = Zeroizing::new(
+            bytes
+                .try_into()
+                .map_err(|_| Error::Crypto("invalid secret key length".into()))?,
+        );
+        Ok(Self::from_secret(SecretKey::from(*bytes)))
+    }
+
+    /// Decode a base64 key pair as stored by `KeyManager`, checking both halves match.
+    pub fn from_b64(public_b64: &str, secret_b64: &str) -> Result<Self> {
+        let secret = Zeroizing::new(decode(secret_b64)?);
+        let pair = Self::from_secret_bytes(&secret)?;
+        if pair.public != public_key_from_b64(public_b64)? {
+            return Err(Error::Crypto("public key does not match secret key".into()));
+        }
+        Ok(pair)
+    }
+
+    pub fn secret(&self) -> &SecretKey {
+        &self.secret
+    }
+
+    /// Copy of the raw secret key, wiped when dropped.
+    pub fn secret_bytes(&self) -> Zeroizing<[u8; KEY_SIZE]> {
+        Zeroizing::new(self.secret.to_bytes())
+    }
+
+    pub fn public_b64(&self) -> String {
+        encode(self.public.as_bytes())
+    }
+}
+
+pub fn public_key_from_b64(b64: &str) -> Result<PublicKey> {
+    let bytes: [u8; KEY_SIZE] = decode(b64)?
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::Crypto("invalid public key length".into()))?;
+    Ok(PublicKey::from(bytes))
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    STANDARD.encode(bytes)
+}
+
+pub fn decode(b64: &str) -> Result<Vec<u8>> {
+    STANDARD
+        .decode(b64)
+        .map_err(|e| Error::Crypto(format!("invalid base64: {e}")))
+}
@@ -0,0 +1,284 @@
+//! Entry encryption, wire-compatible with `EntryCryptor.ts`.
+//!
+//! Each entry is serialized to JSON and sealed with XSalsa20-Poly1305 under a
+//! random per-entry key. That key is wrapped with a Curve25519 box from the
+//! author to the reader (the author themselves for their own entries), so
+//! sharing only requires re-wrapping the 32-byte key.
+
+pub mod commands;
+pub mod envelope;
+pub mod keys;
+pub mod recovery;
+
+use crypto_box::aead::rand_core::RngCore;
+use crypto_box::aead::{Aead, AeadCore, KeyInit, OsRng};
+use crypto_box::{PublicKey, SalsaBox, SecretKey, KEY_SIZE};
+use crypto_secretbox::{Key, Nonce, XSalsa20Poly1305};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::error::{Error, Result};
+use keys::{decode, encode, UserKeyPair};
+
+const NONCE_SIZE: usize = 24;
+
+/// Encrypted entry payload. Matches `EncryptedEntryData` in `EntryCryptor.ts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedEntryData {
+    pub encrypted_content_b64: String,
+    pub content_nonce_b64: String,
+    pub encrypted_entry_key_b64: String,
+    pub key_nonce_b64: String,
+}
+
+/// An entry key wrapped for another reader. Matches `RewrappedKey` in `EntryCryptor.ts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewrappedKey {
+    pub encrypted_entry_key_b64: String,
+    pub key_nonce_b64: String,
+}
+
+/// Encrypt `entry` for its owner.
+pub fn encrypt_entry(entry: &Value, owner: &UserKeyPair) -> Result<EncryptedEntryData> {
+    let plaintext = Zeroizing::new(serde_json::to_vec(entry).map_err(crypto_error)?);
+    let mut entry_key = Zeroizing::new([0u8; KEY_SIZE]);
+    OsRng.fill_bytes(&mut *entry_key);
+
+    let (encrypted_content, content_nonce) = seal(&plaintext, &entry_key)?;
+    let wrapped = wrap_key(&entry_key, &owner.public, owner.secret())?;
+
+    Ok(EncryptedEntryData {
+        encrypted_content_b64: encode(&encrypted_content),
+        content_nonce_b64: encode(&content_nonce),
+        encrypted_entry_key_b64: wrapped.encrypted_entry_key_b64,
+        key_nonce_b64: wrapped.key_nonce_b64,
+    })
+}
+
+/// Re-encrypt `entry` under the owner's existing entry key, so access keys
+/// already issued to recipients stay valid. Port of `encryptEntryWithExistingKey`.
+pub fn encrypt_entry_with_key(
+    entry: &Value,
+    encrypted_entry_key_b64: &str,
+    key_nonce_b64: &str,
+    owner: &UserKeyPair,
+) -> Result<EncryptedEntryData> {
+    let entry_key = unwrap_key(
+        encrypted_entry_key_b64,
+        key_nonce_b64,
+        &owner.public,
+        owner.secret(),
+    )?;
+    let plaintext = Zeroizing::new(serde_json::to_vec(entry).map_err(crypto_error)?);
+    let (encrypted_content, content_nonce) = seal(&plaintext, &entry_key)?;
+
+    Ok(EncryptedEntryData {
+        encrypted_content_b64: encode(&encrypted_content),
+        content_nonce_b64: encode(&content_nonce),
+        encrypted_entry_key_b64: encrypted_entry_key_b64.to_string(),
+        key_nonce_b64: key_nonce_b64.to_string(),
+    })
+}
+
+/// Decrypt an entry readable by `reader` that was wrapped by `author_public`.
+pub fn decrypt_entry(
+    data: &EncryptedEntryData,
+    reader: &SecretKey,
+    author_public: &PublicKey,
+) -> Result<Value> {
+    let entry_key = unwrap_key(
+        &data.encrypted_entry_key_b64,
+        &data.key_nonce_b64,
+        author_public,
+        reader,
+    )?;
+    let plaintext = open(
+        &decode(&data.encrypted_content_b64)?,
+        &decode(&data.content_nonce_b64)?,
+        &entry_key,
+    )?;
+    serde_json::from_slice(&plaintext).map_err(crypto_error)
+}
+
+/// Re-wrap an entry key held by `author` so `recipient` can read the entry.
+pub fn rewrap_entry_key(
+    encrypted_entry_key_b64: &str,
+    key_nonce_b64: &str,
+    author: &UserKeyPair,
+    recipient: &PublicKey,
+) -> Result<RewrappedKey> {
+    let entry_key = unwrap_key(
+        encrypted_entry_key_b64,
+        key_nonce_b64,
+        &author.public,
+        author.secret(),
+    )?;
+    wrap_key(&entry_key, recipient, author.secret())
+}
+
+/// Box an entry key from `sender` to `recipient`.
+pub fn wrap_key(
+    entry_key: &[u8; KEY_SIZE],
+    recipient: &PublicKey,
+    sender: &SecretKey,
+) -> Result<RewrappedKey> {
+    let salsa_box = SalsaBox::new(recipient, sender);
+    let nonce = SalsaBox::generate_nonce(&mut OsRng);
+    let wrapped = salsa_box
+        .encrypt(&nonce, entry_key.as_slice())
+        .map_err(|_| Error::Crypto("failed to wrap entry key".into()))?;
+    Ok(RewrappedKey {
+        encrypted_entry_key_b64: encode(&wrapped),
+        key_nonce_b64: encode(&nonce),
+    })
+}
+
+/// Open a boxed entry key sent by `sender` to `recipient`.
+pub fn unwrap_key(
+    encrypted_entry_key_b64: &str,
+    key_nonce_b64: &str,
+    sender: &PublicKey,
+    recipient: &SecretKey,
+) -> Result<Zeroizing<[u8; KEY_SIZE]>> {
+    let nonce = decode(key_nonce_b64)?;
+    check_nonce(&nonce)?;
+    let salsa_box = SalsaBox::new(sender, recipient);
+    let key = Zeroizing::new(
+        salsa_box
+            .decrypt(
+                nonce.as_slice().into(),
+                decode(encrypted_entry_key_b64)?.as_slice(),
+            )
+            .map_err(|_| Error::Crypto("failed to unwrap entry key".into()))?,
+    );
+    let key: [u8; KEY_SIZE] = key
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::Crypto("invalid entry key length".into()))?;
+    Ok(Zeroizing::new(key))
+}
+
+/// Secretbox `plaintext` under `key`, returning ciphertext and nonce.
+pub fn seal(plaintext: &[u8], key: &[u8; KEY_SIZE]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let cipher = XSalsa20Poly1305::new(Key::from_slice(key));
+    let nonce = XSalsa20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| Error::Crypto("encryption failed".into()))?;
+    Ok((ciphertext, nonce.to_vec()))
+}
+
+/// Open a secretbox produced by [`seal`] or `nacl.secretbox`.
+pub fn open(ciphertext: &[u8], nonce: &[u8], key: &[u8; KEY_SIZE]) -> Result<Zeroizing<Vec<u8>>> {
+    check_nonce(nonce)?;
+    let cipher = XSalsa20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map(Zeroizing::new)
+        .map_err(|_| Error::Crypto("decryption failed".into()))
+}
+
+fn check_nonce(nonce: &[u8]) -> Result<()> {
+    if nonce.len() != NONCE_SIZE {
+        return Err(Error::Crypto("invalid nonce length".into()));
+    }
+    Ok(())
+}
+
+fn crypto_error(error: impl std::fmt::Display) -> Error {
+    Error::Crypto(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the TS `EntryCryptor` produces for fixed random bytes, which the
+    /// TS suite checks byte for byte.
+    const FIXTURE: &str = include_str!("../../tests/fixtures/entry-cryptor.json");
+
+    fn fixture() -> Value {
+        serde_json::from_str(FIXTURE).unwrap()
+    }
+
+    fn pair(value: &Value) -> UserKeyPair {
+        UserKeyPair::from_b64(
+            value["publicKey"].as_str().unwrap(),
+            value["secretKey"].as_str().unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn decrypts_typescript_fixture() {
+        let fixture = fixture();
+        let owner = pair(&fixture["owner"]);
+        let data: EncryptedEntryData =
+            serde_json::from_value(fixture["encrypted"].clone()).unwrap();
+
+        let entry = decrypt_entry(&data, owner.secret(), &owner.public).unwrap();
+        assert_eq!(entry, fixture["entry"]);
+    }
+
+    #[test]
+    fn decrypts_fixture_shared_with_recipient() {
+        let fixture = fixture();
+        let owner = pair(&fixture["owner"]);
+        let recipient = pair(&fixture["recipient"]);
+        let mut data: EncryptedEntryData =
+            serde_json::from_value(fixture["encrypted"].clone()).unwrap();
+        let shared: RewrappedKey =
+            serde_json::from_value(fixture["sharedWithRecipient"].clone()).unwrap();
+        data.encrypted_entry_key_b64 = shared.encrypted_entry_key_b64;
+        data.key_nonce_b64 = shared.key_nonce_b64;
+
+        let entry = decrypt_entry(&data, recipient.secret(), &owner.public).unwrap();
+        assert_eq!(entry, fixture["entry"]);
+    }
+
+    #[test]
+    fn encrypt_rewrap_decrypt_round_trip() {
+        let fixture = fixture();
+        let owner = pair(&fixture["owner"]);
+        let recipient = UserKeyPair::generate();
+
+        let mut data = encrypt_entry(&fixture["entry"], &owner).unwrap();
+        let rewrapped = rewrap_entry_key(
+            &data.encrypted_entry_key_b64,
+            &data.key_nonce_b64,
+            &owner,
+            &recipient.public,
+        )
+        .unwrap();
+        data.encrypted_entry_key_b64 = rewrapped.encrypted_entry_key_b64;
+        data.key_nonce_b64 = rewrapped.key_nonce_b64;
+
+        let entry = decrypt_entry(&data, recipient.secret(), &owner.public).unwrap();
+        assert_eq!(entry, fixture["entry"]);
+        assert!(decrypt_entry(&data, owner.secret(), &owner.public).is_err());
+
+        // Re-encrypting under the same key keeps the recipient's access key valid.
+        let original = encrypt_entry(&fixture["entry"], &owner).unwrap();
+        let mut updated = encrypt_entry_with_key(
+            &serde_json::json!({ "title": "edited" }),
+            &original.encrypted_entry_key_b64,
+            &original.key_nonce_b64,
+            &owner,
+        )
+        .unwrap();
+        let shared = rewrap_entry_key(
+            &original.encrypted_entry_key_b64,
+            &original.key_nonce_b64,
+            &owner,
+            &recipient.public,
+        )
+        .unwrap();
+        updated.encrypted_entry_key_b64 = shared.encrypted_entry_key_b64;
+        updated.key_nonce_b64 = shared.key_nonce_b64;
+        let entry = decrypt_entry(&updated, recipient.secret(), &owner.public).unwrap();
+        assert_eq!(entry["title"], "edited");
+    }
+}
@@ -0,0 +1,140 @@
+//! Recovery phrases for the user's secret key.
+//!
+//! A phrase is 24 words from the BIP39 English list encoding 256 bits of
+//! entropy. The entropy is hashed into a wrapping key that seals the
+//! Curve25519 secret key into a recovery envelope, kept next to the password
+//! envelope:
+//!
+//! ```text
+//! $dxr$v=1$<nonce || secretbox b64>
+//! ```
+//!
+//! The phrase itself is never persisted.
+
+use bip39::{Language, Mnemonic};
+use crypto_box::aead::rand_core::RngCore;
+use crypto_box::aead::OsRng;
+use crypto_box::KEY_SIZE;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use super::keys::{decode, encode};
+use crate::error::{Error, Result};
+
+const PREFIX: &str = "$dxr$v=1$";
+const ENTROPY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 24;
+/// Domain separation so the wrapping key is never reused for anything else.
+const KEY_CONTEXT: &[u8] = b"diaryx recovery wrapping key v1";
+
+/// Number of words in a recovery phrase.
+pub const WORD_COUNT: usize = 24;
+
+/// Generate a fresh random recovery phrase.
+pub fn generate() -> Zeroizing<String> {
+    let mut entropy = Zeroizing::new([0u8; ENTROPY_SIZE]);
+    OsRng.fill_bytes(&mut *entropy);
+    let mnemonic = Mnemonic::from_entropy(&*entropy).expect("32 bytes is a valid entropy length");
+    Zeroizing::new(mnemonic.to_string())
+}
+
+/// Normalize a phrase as typed by the user: lowercase, single spaces.
+pub fn normalize(phrase: &str) -> Zeroizing<String> {
+    Zeroizing::new(
+        phrase
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+/// Seal `secret` under the wrapping key derived from `phrase`.
+pub fn seal(secret: &[u8; KEY_SIZE], phrase: &str) -> Result<String> {
+    let key = wrapping_key(phrase)?;
+    let (ciphertext, nonce) = super::seal(secret, &key)?;
+    let mut payload = nonce;
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{PREFIX}{}", encode(&payload)))
+}
+
+/// Open a recovery envelope with `phrase`.
+pub fn open(envelope: &str, phrase: &str) -> Result<Zeroizing<[u8; KEY_SIZE]>> {
+    let encoded = envelope
+        .strip_prefix(PREFIX)
+        .ok_or_else(|| Error::Crypto("invalid recovery envelope".into()))?;
+    let payload = decode(encoded)?;
+    if payload.len() <= NONCE_SIZE {
+        return Err(Error::Crypto("invalid recovery envelope".into()));
+    }
+
+    let key = wrapping_key(phrase)?;
+    let (nonce, ciphertext) = payload.split_at(NONCE_SIZE);
+    let plaintext = super::open(ciphertext, nonce, &key)
+        .map_err(|_| Error::Crypto("recovery phrase does not match".into()))?;
+    let secret: [u8; KEY_SIZE] = plaintext
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::Crypto("unexpected secret key length".into()))?;
+    Ok(Zeroizing::new(secret))
+}
+
+/// Validate `phrase` (word list and checksum) and hash its entropy into a key.
+fn wrapping_key(phrase: &str) -> Result<Zeroizing<[u8; KEY_SIZE]>> {
+    let mnemonic = Mnemonic::parse_in_normalized(Language::English, &normalize(phrase))
+        .map_err(|e| Error::Crypto(format!("invalid recovery phrase: {e}")))?;
+    let entropy = Zeroizing::new(mnemonic.to_entropy());
+    if entropy.len() != ENTROPY_SIZE {
+        return Err(Error::Crypto(format!(
+            "invalid recovery phrase: expected {WORD_COUNT} words"
+        )));
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_CONTEXT);
+    hasher.update(&*entropy);
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    key.copy_from_slice(&hasher.finalize());
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BIP39 test vector for 32 zero bytes of entropy.
+    const ZERO_PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon \
+        abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+        abandon abandon abandon abandon art";
+
+    #[test]
+    fn generates_24_word_phrases() {
+        let phrase = generate();
+        assert_eq!(phrase.split(' ').count(), WORD_COUNT);
+        assert!(wrapping_key(&phrase).is_ok());
+    }
+
+    #[test]
+    fn round_trips_and_tolerates_case_and_spacing() {
+        let secret = [9u8; KEY_SIZE];
+        let envelope = seal(&secret, ZERO_PHRASE).unwrap();
+        let typed = format!("  {}\n", ZERO_PHRASE.to_uppercase().replace(' ', "   "));
+        assert_eq!(*open(&envelope, &typed).unwrap(), secret);
+    }
+
+    #[test]
+    fn rejects_wrong_or_mistyped_phrases() {
+        let envelope = seal(&[9u8; KEY_SIZE], ZERO_PHRASE).unwrap();
+        assert!(open(&envelope, &generate()).is_err());
+
+        // A swapped word breaks the checksum before decryption is attempted.
+        let typo = ZERO_PHRASE.replacen("abandon", "ability", 1);
+        let error = open(&envelope, &typo).unwrap_err().to_string();
+        assert!(error.contains("invalid recovery phrase"), "{error}");
+
+        // Valid 12-word phrases are too short.
+        let short = "abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+            abandon abandon about";
+        assert!(open(&envelope, short).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use serde::{Serialize, Serializer};
+
+/// Errors returned by the native Diaryx backend.
+///
+/// Every Tauri command returns `Result<T, Error>`; the error is serialized
+/// as its display string so the frontend receives a plain message.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("entry not found: {0}")]
+    NotFound(String),
+
+    #[error("entry already exists: {0}")]
+    AlreadyExists(String),
+
+    #[error("invalid entry id: {0}")]
+    InvalidId(String),
+
+    #[error("invalid search query: {0}")]
+    InvalidQuery(String),
+
+    #[error("invalid frontmatter: {0}")]
+    Frontmatter(String),
+
+    #[error("crypto error: {0}")]
+    Crypto(String),
+
+    #[error("vault is locked")]
+    Locked,
+
+    #[error("invalid vault: {0}")]
+    InvalidVault(String),
+
+    #[error("CRDT document error: {0}")]
+    Crdt(String),
+
+    #[error("cloud request failed: {0}")]
+    Cloud(String),
+
+    #[error("cloud API error {status}: {message}")]
+    Api { status: u16, message: String },
+
+    #[error("background task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+}
+
+impl Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
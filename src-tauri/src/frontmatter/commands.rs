@@ -0,0 +1,39 @@
+//! Tauri commands for reading and editing entry frontmatter.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use tauri::State;
+
+use super::ParsedEntry;
+use crate::error::{Error, Result};
+use crate::search::SearchEngine;
+use crate::vaults::Vaults;
+
+#[tauri::command]
+pub fn parse_frontmatter(content: String) -> Result<ParsedEntry> {
+    super::parse(&content)
+}
+
+#[tauri::command]
+pub fn update_frontmatter(content: String, updates: BTreeMap<String, Value>) -> Result<String> {
+    super::update(&content, &updates)
+}
+
+/// Update keys in a stored entry and return its new content.
+#[tauri::command]
+pub fn update_entry_frontmatter(
+    vaults: State<'_, Vaults>,
+    search: State<'_, SearchEngine>,
+    id: String,
+    updates: BTreeMap<String, Value>,
+) -> Result<String> {
+    let store = vaults.store();
+    let entry = store
+        .get_entry(&id)?
+        .ok_or_else(|| Error::NotFound(id.clone()))?;
+    let content = super::update(&entry.content, &updates)?;
+    store.save_entry(&id, &content)?;
+    search.refresh_entry(&store, &id)?;
+    Ok(content)
+}
@@ -0,0 +1,311 @@
+//! YAML frontmatter parsing and lossless editing.
+//!
+//! [`split`] separates a leading `---` block from the Markdown body without
+//! touching either. [`parse`] reads the block into a typed [`Frontmatter`].
+//! [`update`] rewrites individual top-level keys in place: every byte outside
+//! the edited keys, including comments, ordering and quoting, is preserved.
+
+pub mod commands;
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+/// Typed view of an entry's frontmatter.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Frontmatter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub tags: Vec<String>,
+    /// Every other key, as JSON-compatible values.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+/// Parsed frontmatter together with the remaining Markdown body.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParsedEntry {
+    pub frontmatter: Frontmatter,
+    pub body: String,
+}
+
+/// Byte ranges of the parts of an entry with frontmatter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Split {
+    /// The YAML between the delimiter lines.
+    pub yaml: Range<usize>,
+    /// Everything after the closing delimiter line.
+    pub body: Range<usize>,
+}
+
+/// Locate the frontmatter block, if `content` starts with one.
+pub fn split(content: &str) -> Option<Split> {
+    let first = content.split_inclusive('\n').next()?;
+    if first.trim_end() != "---" || !first.ends_with('\n') {
+        return None;
+    }
+
+    let yaml_start = first.len();
+    let mut offset = yaml_start;
+    for line in content[yaml_start..].split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return Some(Split {
+                yaml: yaml_start..offset,
+                body: offset + line.len()..content.len(),
+            });
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Parse `content` into typed frontmatter and body.
+pub fn parse(content: &str) -> Result<ParsedEntry> {
+    let Some(split) = split(content) else {
+        return Ok(ParsedEntry {
+            frontmatter: Frontmatter::default(),
+            body: content.to_string(),
+        });
+    };
+
+    let yaml = &content[split.yaml.clone()];
+    let frontmatter = if yaml.trim().is_empty() {
+        Frontmatter::default()
+    } else {
+        serde_yaml::from_str(yaml).map_err(|e| Error::Frontmatter(e.to_string()))?
+    };
+    Ok(ParsedEntry {
+        frontmatter,
+        body: content[split.body].to_string(),
+    })
+}
+
+/// Apply key updates to `content`, returning the new text.
+///
+/// A `null` value removes the key. Existing keys are rewritten where they
+/// stand; new keys are appended to the end of the block, which is created if
+/// the entry has none.
+pub fn update(content: &str, updates: &BTreeMap<String, Value>) -> Result<String> {
+    let mut out = content.to_string();
+    for (key, value) in updates {
+        out = set_key(&out, key, value)?;
+    }
+    // Validate the result so a bad edit never reaches disk.
+    parse(&out)?;
+    Ok(out)
+}
+
+fn set_key(content: &str, key: &str, value: &Value) -> Result<String> {
+    if key.is_empty() || key.contains([':', '\n']) || key.starts_with(['#', '-', ' ']) {
+        return Err(Error::Frontmatter(format!("invalid key: {key:?}")));
+    }
+
+    let Some(split) = split(content) else {
+        if value.is_null() {
+            return Ok(content.to_string());
+        }
+        let line = render(key, value, false)?;
+        return Ok(format!("---\n{line}---\n{content}"));
+    };
+
+    let yaml = &content[split.yaml.clone()];
+    let rendered = match key_span(yaml, key) {
+        Some(span) if value.is_null() => {
+            format!("{}{}", &yaml[..span.start], &yaml[span.end..])
+        }
+        Some(span) => {
+            let was_inline = !yaml[span.clone()].trim_end().contains('\n');
+            let line = render(key, value, was_inline)?;
+            format!("{}{}{}", &yaml[..span.start], line, &yaml[span.end..])
+        }
+        None if value.is_null() => return Ok(content.to_string()),
+        None => {
+            let separator = if yaml.is_empty() || yaml.ends_with('\n') {
+                ""
+            } else {
+                "\n"
+            };
+            format!("{yaml}{separator}{}", render(key, value, false)?)
+        }
+    };
+
+    Ok(format!(
+        "{}{}{}",
+        &content[..split.yaml.start],
+        rendered,
+        &content[split.yaml.end..]
+    ))
+}
+
+/// Byte range of `key` and its value lines within the YAML block.
+///
+/// The range covers the key line plus any indented or `- ` continuation lines,
+/// excluding trailing blank lines and comments that belong to what follows.
+fn key_span(yaml: &str, key: &str) -> Option<Range<usize>> {
+    let mut offset = 0;
+    let mut start = None;
+    let mut end = 0;
+
+    for line in yaml.split_inclusive('\n') {
+        let line_end = offset + line.len();
+        match start {
+            None => {
+                if top_level_key(line) == Some(key) {
+                    start = Some(offset);
+                    end = line_end;
+                }
+            }
+            Some(_) => {
+                if line.trim().is_empty() {
+                    // Blank lines only belong to the value if more of it follows.
+                } else if line.starts_with([' ', '\t']) || line.starts_with("- ") {
+                    end = line_end;
+                } else {
+                    break;
+                }
+            }
+        }
+        offset = line_end;
+    }
+    start.map(|start| start..end)
+}
+
+fn top_level_key(line: &str) -> Option<&str> {
+    if line.starts_with([' ', '\t', '#', '-']) {
+        return None;
+    }
+    let (key, rest) = line.split_once(':')?;
+    if !(rest.is_empty() || rest.starts_with([' ', '\t', '\r', '\n'])) {
+        return None;
+    }
+    Some(key.trim().trim_matches(|c| c == '"' || c == '\''))
+}
+
+/// Render `key: value` as YAML, ending with a newline.
+fn render(key: &str, value: &Value, inline: bool) -> Result<String> {
+    match value {
+        Value::Array(items) if inline && items.iter().all(is_scalar) => {
+            let rendered: Result<Vec<String>> = items.iter().map(scalar).collect();
+            Ok(format!("{key}: [{}]\n", rendered?.join(", ")))
+        }
+        Value::Array(items) if items.is_empty() => Ok(format!("{key}: []\n")),
+        Value::Array(_) | Value::Object(_) => {
+            let yaml =
+                serde_yaml::to_string(value).map_err(|e| Error::Frontmatter(e.to_string()))?;
+            let indented: String = yaml.lines().map(|line| format!("  {line}\n")).collect();
+            Ok(format!("{key}:\n{indented}"))
+        }
+        _ => Ok(format!("{key}: {}\n", scalar(value)?)),
+    }
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !matches!(value, Value::Array(_) | Value::Object(_))
+}
+
+fn scalar(value: &Value) -> Result<String> {
+    let yaml = serde_yaml::to_string(value).map_err(|e| Error::Frontmatter(e.to_string()))?;
+    Ok(yaml.trim_end().to_string())
+}
+
+/// Accept `tags: a, b`, `tags: [a, b]` and block lists alike.
+fn string_or_list<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tags {
+        One(String),
+        Many(Vec<String>),
+        None(()),
+    }
+
+    Ok(match Tags::deserialize(deserializer)? {
+        Tags::One(s) => s
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(String::from)
+            .collect(),
+        Tags::Many(tags) => tags,
+        Tags::None(()) => Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    const ENTRY: &str = "---\n# written on the train\ntitle: \"Morning\"\ntags: [a, b]\nmood: 'calm'\n---\nBody text\n";
+
+    #[test]
+    fn parses_typed_fields_and_extras() {
+        let parsed = parse(ENTRY).unwrap();
+        assert_eq!(parsed.frontmatter.title.as_deref(), Some("Morning"));
+        assert_eq!(parsed.frontmatter.tags, ["a", "b"]);
+        assert_eq!(parsed.frontmatter.extra["mood"], json!("calm"));
+        assert_eq!(parsed.body, "Body text\n");
+    }
+
+    #[test]
+    fn updating_one_key_preserves_the_rest() {
+        let updates = BTreeMap::from([("tags".to_string(), json!(["a", "b", "c"]))]);
+        let updated = update(ENTRY, &updates).unwrap();
+        assert_eq!(updated, ENTRY.replace("tags: [a, b]", "tags: [a, b, c]"));
+    }
+
+    #[test]
+    fn removes_and_appends_keys() {
+        let updates = BTreeMap::from([
+            ("mood".to_string(), Value::Null),
+            ("weather".to_string(), json!("rain")),
+        ]);
+        let updated = update(ENTRY, &updates).unwrap();
+        assert_eq!(updated, ENTRY.replace("mood: 'calm'\n", "weather: rain\n"));
+    }
+
+    #[test]
+    fn creates_block_when_missing() {
+        let updates = BTreeMap::from([("title".to_string(), json!("New"))]);
+        assert_eq!(
+            update("Body", &updates).unwrap(),
+            "---\ntitle: New\n---\nBody"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn split_round_trips(yaml in "([a-z]{1,8}: [a-z0-9 ]{0,12}\n){0,6}", body in "[^\u{0}]{0,64}") {
+            let content = format!("---\n{yaml}---\n{body}");
+            let split = split(&content).unwrap();
+            let rebuilt = format!(
+                "{}{}{}",
+                &content[..split.yaml.start],
+                &content[split.yaml.clone()],
+                &content[split.yaml.end..]
+            );
+            prop_assert_eq!(&rebuilt, &content);
+            prop_assert_eq!(&content[split.body], body.as_str());
+        }
+
+        #[test]
+        fn set_then_parse_round_trips(value in "[A-Za-z0-9 :#'\"-]{0,24}") {
+            let updates = BTreeMap::from([("note".to_string(), json!(value.clone()))]);
+            let updated = update(ENTRY, &updates).unwrap();
+            let parsed = parse(&updated).unwrap();
+            prop_assert_eq!(&parsed.frontmatter.extra["note"], &json!(value));
+            prop_assert_eq!(parsed.frontmatter.title.as_deref(), Some("Morning"));
+            prop_assert!(updated.starts_with(&ENTRY[..ENTRY.find("---\nBody").unwrap()]));
+        }
+    }
+}
@@ -1,3 +1,25 @@
+pub mod api;
+pub mod crdt;
+pub mod crypto;
+mod error;
+pub mod frontmatter;
+pub mod rotation;
+pub mod search;
+pub mod session;
+pub mod store;
+pub mod sync;
+pub mod vaults;
+pub mod watcher;
+
+use search::SearchEngine;
+use session::Session;
+use store::EntryStore;
+use vaults::Vaults;
+use watcher::JournalWatcher;
+
+/// Bundle identifier, also used to namespace files under the platform data directory.
+pub const APP_IDENTIFIER: &str = "net.diaryx.journal";
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -40,16 +62,101 @@ pub fn run() {
         }
     };
 
+    let vaults = Vaults::load(
+        Vaults::default_path(),
+        Vaults::default_data_dir(),
+        EntryStore::default_root(),
+    );
+
     builder
-        .setup(|_app| {
+        .manage(vaults)
+        .manage(JournalWatcher::default())
+        .manage(SearchEngine::default())
+        .manage(Session::load(Session::default_path()))
+        .setup(|app| {
             #[cfg(any(target_os = "linux", target_os = "windows"))]
             {
                 use tauri_plugin_deep_link::DeepLinkExt;
                 app.deep_link().register_all()?;
             }
+
+            vaults::commands::start_vault(app.handle());
+            session::spawn_monitor(app.handle().clone());
+            sync::spawn_drainer(app.handle().clone());
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![greet])
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            store::commands::list_entries,
+            store::commands::get_entry,
+            store::commands::save_entry,
+            store::commands::create_entry,
+            store::commands::delete_entry,
+            store::commands::rename_entry,
+            store::commands::create_notebook,
+            store::commands::list_notebook,
+            store::commands::move_entry,
+            store::commands::legacy_entry_ids,
+            store::commands::list_trash,
+            store::commands::restore_entry,
+            store::commands::empty_trash,
+            store::commands::get_trash_retention,
+            store::commands::set_trash_retention,
+            store::commands::get_entry_timestamps,
+            store::commands::list_revisions,
+            store::commands::diff_revisions,
+            store::commands::restore_revision,
+            store::commands::get_revision_retention,
+            store::commands::set_revision_retention,
+            store::commands::scan_journal,
+            store::commands::vault_status,
+            store::commands::enable_vault,
+            store::commands::disable_vault,
+            search::commands::search_entries,
+            frontmatter::commands::parse_frontmatter,
+            frontmatter::commands::update_frontmatter,
+            frontmatter::commands::update_entry_frontmatter,
+            crypto::commands::encrypt_entry,
+            crypto::commands::encrypt_entry_with_key,
+            crypto::commands::decrypt_entry,
+            crypto::commands::rewrap_entry_key,
+            crypto::commands::open_entry_key,
+            session::commands::session_status,
+            session::commands::create_session_keys,
+            session::commands::import_session_keys,
+            session::commands::unlock_session,
+            session::commands::lock_session,
+            session::commands::forget_session,
+            session::commands::change_session_password,
+            session::commands::set_idle_timeout,
+            session::commands::export_recovery_phrase,
+            session::commands::confirm_recovery_phrase,
+            session::commands::restore_from_recovery_phrase,
+            session::commands::touch_session,
+            rotation::commands::rotate_master_key,
+            rotation::commands::key_rotation_pending,
+            sync::commands::enqueue_sync_operation,
+            sync::commands::sync_queue_status,
+            sync::commands::set_sync_credentials,
+            sync::commands::clear_sync_credentials,
+            sync::commands::set_sync_online,
+            sync::commands::retry_sync_queue,
+            sync::commands::link_cloud_entries,
+            sync::commands::pull_cloud_changes,
+            sync::commands::list_sync_conflicts,
+            sync::commands::get_conflict_sides,
+            sync::commands::resolve_conflict,
+            sync::commands::get_conflict_style,
+            sync::commands::set_conflict_style,
+            crdt::commands::crdt_status,
+            crdt::commands::enable_crdt_mode,
+            crdt::commands::disable_crdt_mode,
+            vaults::commands::list_vaults,
+            vaults::commands::add_vault,
+            vaults::commands::set_vault_settings,
+            vaults::commands::switch_vault,
+            vaults::commands::remove_vault,
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
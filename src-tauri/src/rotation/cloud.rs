@@ -0,0 +1,165 @@
+//! Cloud calls made by a key rotation, on top of [`ApiClient`].
+
+use crate::api::models::{NewAccessKey, OwnerKeyUpdate, ProfileKeys};
+use crate::api::ApiClient;
+use crate::crypto::RewrappedKey;
+use crate::error::Result;
+
+/// A cloud entry owned by the user, with the owner's wrapped entry key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedEntry {
+    pub id: String,
+    pub owner_encrypted_entry_key: String,
+    pub owner_key_nonce: String,
+}
+
+/// An entry key wrapped for one user, as stored in `entry-access-keys`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessKeyGrant {
+    pub user_id: String,
+    pub public_key_b64: String,
+    pub encrypted_entry_key: String,
+    pub key_nonce: String,
+}
+
+/// The rotation's view of the API.
+#[derive(Debug, Clone)]
+pub struct CloudKeys {
+    api: ApiClient,
+}
+
+impl CloudKeys {
+    pub fn new(api: ApiClient) -> Self {
+        Self { api }
+    }
+
+    /// Entries authored by the user. Entries without an owner key cannot be re-wrapped and are skipped.
+    pub async fn owned_entries(&self) -> Result<Vec<OwnedEntry>> {
+        let entries = self.api.list_entries().await?;
+        Ok(entries
+            .into_iter()
+            .filter(|entry| entry.author_id == self.api.user_id())
+            .filter_map(|entry| {
+                Some(OwnedEntry {
+                    owner_encrypted_entry_key: entry.owner_encrypted_entry_key?,
+                    owner_key_nonce: entry.owner_key_nonce?,
+                    id: entry.id,
+                })
+            })
+            .collect())
+    }
+
+    /// Every access key of `entry_id`, with the recipient's public key.
+    ///
+    /// Recipients without a public key on their profile are skipped.
+    pub async fn access_keys(&self, entry_id: &str) -> Result<Vec<AccessKeyGrant>> {
+        let info = self.api.shared_info(entry_id).await?;
+        Ok(info
+            .access_keys
+            .into_iter()
+            .filter_map(|key| {
+                let public_key_b64 = key.user?.public_key.filter(|k| !k.is_empty())?;
+                Some(AccessKeyGrant {
+                    user_id: key.user_id,
+                    public_key_b64,
+                    encrypted_entry_key: key.encrypted_entry_key,
+                    key_nonce: key.key_nonce,
+                })
+            })
+            .collect())
+    }
+
+    pub async fn put_access_keys(&self, entry_id: &str, grants: &[AccessKeyGrant]) -> Result<()> {
+        let keys: Vec<NewAccessKey> = grants
+            .iter()
+            .map(|grant| NewAccessKey {
+                user_id: grant.user_id.clone(),
+                encrypted_entry_key: grant.encrypted_entry_key.clone(),
+                key_nonce: grant.key_nonce.clone(),
+            })
+            .collect();
+        self.api.put_access_keys(entry_id, &keys).await
+    }
+
+    pub async fn replace_owner_key(&self, entry_id: &str, key: &RewrappedKey) -> Result<()> {
+        let update = OwnerKeyUpdate {
+            owner_encrypted_entry_key: key.encrypted_entry_key_b64.clone(),
+            owner_key_nonce: key.key_nonce_b64.clone(),
+        };
+        self.api.update_owner_key(entry_id, &update).await
+    }
+
+    /// Publish the new public key and password envelope to the user's profile.
+    pub async fn update_profile_keys(&self, public_key_b64: &str, envelope: &str) -> Result<()> {
+        let keys = ProfileKeys {
+            public_key: public_key_b64.to_string(),
+            encrypted_private_key: envelope.to_string(),
+        };
+        self.api.update_profile_keys(&keys).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::api::mock::{MockResponse, MockServer};
+    use crate::api::Credentials;
+
+    fn entry_row(id: &str, author_id: &str, owner_key: Option<&str>) -> Value {
+        json!({
+            "id": id,
+            "author_id": author_id,
+            "encrypted_title": "t",
+            "encrypted_content": "c",
+            "encryption_metadata": {},
+            "title_hash": "h",
+            "created_at": "2026-01-01T00:00:00Z",
+            "updated_at": "2026-01-01T00:00:00Z",
+            "owner_encrypted_entry_key": owner_key,
+            "owner_key_nonce": owner_key.map(|_| "n"),
+        })
+    }
+
+    #[tokio::test]
+    async fn lists_owned_entries_and_recipient_keys() {
+        let server = MockServer::start().await;
+        server.respond(
+            "GET",
+            "/entries",
+            [MockResponse::data(json!([
+                entry_row("mine", "user-1", Some("k")),
+                entry_row("keyless", "user-1", None),
+                entry_row("theirs", "user-2", Some("k")),
+            ]))],
+        );
+        server.respond(
+            "GET",
+            "/entries/*/shared",
+            [MockResponse::data(json!({
+                "accessKeys": [
+                    { "id": "a1", "entry_id": "mine", "user_id": "user-2", "encrypted_entry_key": "k2",
+                      "key_nonce": "n2", "created_at": "2026-01-01T00:00:00Z",
+                      "user": { "id": "user-2", "public_key": "pk2" } },
+                    { "id": "a2", "entry_id": "mine", "user_id": "user-3", "encrypted_entry_key": "k3",
+                      "key_nonce": "n3", "created_at": "2026-01-01T00:00:00Z" }
+                ]
+            }))],
+        );
+
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        let cloud = CloudKeys::new(ApiClient::new(&server.url(), credentials));
+
+        let owned = cloud.owned_entries().await.unwrap();
+        assert_eq!(owned.len(), 1);
+        assert_eq!(owned[0].id, "mine");
+
+        let grants = cloud.access_keys("mine").await.unwrap();
+        assert_eq!(grants.len(), 1);
+        assert_eq!(grants[0].public_key_b64, "pk2");
+    }
+}
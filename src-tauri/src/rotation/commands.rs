@@ -0,0 +1,58 @@
+//! Tauri commands for rotating the session's key pair.
+
+use tauri::{AppHandle, Manager};
+use zeroize::Zeroizing;
+
+use super::cloud::CloudKeys;
+use super::{default_journal_path, is_pending, RotationReport};
+use crate::api::{ApiClient, Credentials};
+use crate::error::Result;
+use crate::search::SearchEngine;
+use crate::session::{notify, Session};
+use crate::vaults::Vaults;
+
+/// Whether an interrupted rotation is waiting to be resumed.
+#[tauri::command]
+pub fn key_rotation_pending() -> bool {
+    is_pending(&default_journal_path())
+}
+
+/// Rotate the key pair, or resume an interrupted rotation.
+///
+/// The returned envelope is already on the profile; the recovery phrase
+/// must be exported again.
+#[tauri::command]
+pub async fn rotate_master_key(
+    app: AppHandle,
+    api_base_url: String,
+    access_token: String,
+    password: String,
+) -> Result<RotationReport> {
+    let password = Zeroizing::new(password);
+    let session = app.state::<Session>();
+    let vaults = app.state::<Vaults>();
+    let store = vaults.store();
+    let credentials = Credentials {
+        user_id: session.stored_keys()?.user_id,
+        access_token,
+    };
+    let cloud = CloudKeys::new(ApiClient::new(&api_base_url, credentials));
+
+    let stores = vaults.stores();
+    let report = super::rotate_master_key(
+        &session,
+        &stores,
+        &cloud,
+        &default_journal_path(),
+        &password,
+    )
+    .await?;
+
+    if report.vault_files > 0 {
+        if let Err(error) = app.state::<SearchEngine>().rebuild(&store) {
+            log::warn!("failed to rebuild search index: {error}");
+        }
+    }
+    notify(&app, &session);
+    Ok(report)
+}
@@ -0,0 +1,304 @@
+//! Master key rotation.
+//!
+//! Replaces the user's key pair, e.g. after a device is lost. In order, the
+//! workflow re-encrypts every encrypted local journal, re-wraps the key of every cloud
+//! entry the user owns (their own copy and one per recipient, so shares keep
+//! working), publishes the new public key and password envelope to the
+//! profile, and finally swaps the keys held by the [`Session`].
+//!
+//! Progress is journaled to disk after every step, with the new secret key
+//! sealed under the user's password, so an interrupted rotation resumes where
+//! it stopped. Each step also tolerates having already been applied.
+//!
+//! Entries other users shared with us are wrapped by their authors and must
+//! be re-shared by them; the recovery phrase must be exported again.
+
+pub mod cloud;
+pub mod commands;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::envelope::{self, KdfParams};
+use crate::crypto::keys::{public_key_from_b64, UserKeyPair};
+use crate::crypto::{unwrap_key, wrap_key, RewrappedKey};
+use crate::error::{Error, Result};
+use crate::session::Session;
+use crate::store::{atomic, EntryStore};
+use cloud::{AccessKeyGrant, CloudKeys, OwnedEntry};
+
+const JOURNAL_FILE_NAME: &str = "key-rotation.json";
+
+/// On-disk progress of a rotation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RotationJournal {
+    old_public_key_b64: String,
+    new_public_key_b64: String,
+    /// New secret key sealed by [`envelope::seal`] under the user's password.
+    new_envelope: String,
+    vault_done: bool,
+    cloud_entries_done: BTreeSet<String>,
+    profile_updated: bool,
+}
+
+/// Outcome of a completed rotation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationReport {
+    pub public_key_b64: String,
+    /// Password envelope of the new secret key, already uploaded to the profile.
+    pub envelope: String,
+    pub vault_files: usize,
+    pub cloud_entries: usize,
+    pub recipient_keys: usize,
+    /// Whether this run picked up an interrupted rotation.
+    pub resumed: bool,
+}
+
+/// Default journal location, next to the session keys.
+pub fn default_journal_path() -> PathBuf {
+    Session::default_path().with_file_name(JOURNAL_FILE_NAME)
+}
+
+/// Whether a rotation was interrupted and should be resumed.
+pub fn is_pending(journal_path: &Path) -> bool {
+    journal_path.is_file()
+}
+
+/// Run or resume a rotation of the session's key pair.
+///
+/// Requires the session to be unlocked with the current (old) key pair and
+/// the user's password, which protects the new key in the journal and the
+/// profile. `stores` are the journals of every configured vault.
+pub async fn rotate_master_key(
+    session: &Session,
+    stores: &[EntryStore],
+    cloud: &CloudKeys,
+    journal_path: &Path,
+    password: &str,
+) -> Result<RotationReport> {
+    let old = session.with_keys(|pair| Ok(pair.clone()))?;
+    let (mut journal, resumed) = match load_journal(journal_path)? {
+        Some(journal) if journal.old_public_key_b64 == old.public_b64() => (journal, true),
+        Some(journal) if journal.new_public_key_b64 == old.public_b64() => {
+            // Only the journal cleanup was missing.
+            remove_journal(journal_path)?;
+            return Err(Error::Crypto("key rotation already completed".into()));
+        }
+        Some(_) => {
+            return Err(Error::Crypto(
+                "pending key rotation is for another key".into(),
+            ))
+        }
+        None => (start_journal(journal_path, &old, password)?, false),
+    };
+    let opened = envelope::open(&journal.new_envelope, password)?;
+    let new = UserKeyPair::from_secret_bytes(&*opened.secret)?;
+
+    let mut report = RotationReport {
+        public_key_b64: journal.new_public_key_b64.clone(),
+        envelope: journal.new_envelope.clone(),
+        vault_files: 0,
+        cloud_entries: 0,
+        recipient_keys: 0,
+        resumed,
+    };
+
+    if !journal.vault_done {
+        for store in stores {
+            report.vault_files += store.rekey_vault(&old, new.clone())?;
+        }
+        journal.vault_done = true;
+        save_journal(journal_path, &journal)?;
+    }
+
+    for entry in cloud.owned_entries().await? {
+        if journal.cloud_entries_done.contains(&entry.id) {
+            continue;
+        }
+        let recipients = cloud.access_keys(&entry.id).await?;
+        let (owner_key, grants) = rewrap_entry(&entry, &recipients, &old, &new)?;
+        cloud.put_access_keys(&entry.id, &grants).await?;
+        cloud.replace_owner_key(&entry.id, &owner_key).await?;
+
+        report.cloud_entries += 1;
+        report.recipient_keys += grants.len();
+        journal.cloud_entries_done.insert(entry.id);
+        save_journal(journal_path, &journal)?;
+    }
+
+    if !journal.profile_updated {
+        cloud
+            .update_profile_keys(&journal.new_public_key_b64, &journal.new_envelope)
+            .await?;
+        journal.profile_updated = true;
+        save_journal(journal_path, &journal)?;
+    }
+
+    for store in stores {
+        store.unlock_vault(new.clone())?;
+    }
+    session.replace_keys(new, journal.new_envelope.clone())?;
+    remove_journal(journal_path)?;
+    Ok(report)
+}
+
+/// Re-wrap one owned entry's key from `old` to `new`.
+///
+/// Returns the owner's copy and one grant per recipient with a known public
+/// key. Keys already wrapped by `new` (from an interrupted run) are accepted.
+fn rewrap_entry(
+    entry: &OwnedEntry,
+    recipients: &[AccessKeyGrant],
+    old: &UserKeyPair,
+    new: &UserKeyPair,
+) -> Result<(RewrappedKey, Vec<AccessKeyGrant>)> {
+    let (encrypted, nonce) = (&entry.owner_encrypted_entry_key, &entry.owner_key_nonce);
+    let entry_key = unwrap_key(encrypted, nonce, &old.public, old.secret())
+        .or_else(|_| unwrap_key(encrypted, nonce, &new.public, new.secret()))?;
+
+    let owner_key = wrap_key(&entry_key, &new.public, new.secret())?;
+    let mut grants = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let public_key_b64 = if recipient.public_key_b64 == old.public_b64() {
+            new.public_b64()
+        } else {
+            recipient.public_key_b64.clone()
+        };
+        let public_key = public_key_from_b64(&public_key_b64)?;
+        let wrapped = wrap_key(&entry_key, &public_key, new.secret())?;
+        grants.push(AccessKeyGrant {
+            user_id: recipient.user_id.clone(),
+            public_key_b64,
+            encrypted_entry_key: wrapped.encrypted_entry_key_b64,
+            key_nonce: wrapped.key_nonce_b64,
+        });
+    }
+    Ok((owner_key, grants))
+}
+
+fn start_journal(path: &Path, old: &UserKeyPair, password: &str) -> Result<RotationJournal> {
+    let new = UserKeyPair::generate();
+    let journal = RotationJournal {
+        old_public_key_b64: old.public_b64(),
+        new_public_key_b64: new.public_b64(),
+        new_envelope: envelope::seal(&new.secret_bytes(), password, KdfParams::default())?,
+        vault_done: false,
+        cloud_entries_done: BTreeSet::new(),
+        profile_updated: false,
+    };
+    save_journal(path, &journal)?;
+    Ok(journal)
+}
+
+fn load_journal(path: &Path) -> Result<Option<RotationJournal>> {
+    match fs::read_to_string(path) {
+        Ok(raw) => serde_json::from_str(&raw)
+            .map(Some)
+            .map_err(|e| Error::Crypto(format!("invalid key rotation journal: {e}"))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_journal(path: &Path, journal: &RotationJournal) -> Result<()> {
+    let json = serde_json::to_vec_pretty(journal).map_err(std::io::Error::from)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    atomic::write_atomic(path, &json)?;
+    Ok(())
+}
+
+fn remove_journal(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owned_entry(owner: &UserKeyPair, entry_key: &[u8; 32]) -> OwnedEntry {
+        let wrapped = wrap_key(entry_key, &owner.public, owner.secret()).unwrap();
+        OwnedEntry {
+            id: "cloud-1".into(),
+            owner_encrypted_entry_key: wrapped.encrypted_entry_key_b64,
+            owner_key_nonce: wrapped.key_nonce_b64,
+        }
+    }
+
+    fn grant(user_id: &str, pair: &UserKeyPair) -> AccessKeyGrant {
+        AccessKeyGrant {
+            user_id: user_id.into(),
+            public_key_b64: pair.public_b64(),
+            encrypted_entry_key: String::new(),
+            key_nonce: String::new(),
+        }
+    }
+
+    #[test]
+    fn rewraps_owner_and_recipient_keys_idempotently() {
+        let (old, new, friend) = (
+            UserKeyPair::generate(),
+            UserKeyPair::generate(),
+            UserKeyPair::generate(),
+        );
+        let entry_key = [5u8; 32];
+        let entry = owned_entry(&old, &entry_key);
+        let recipients = [grant("me", &old), grant("friend", &friend)];
+
+        let (owner_key, grants) = rewrap_entry(&entry, &recipients, &old, &new).unwrap();
+        let opened = unwrap_key(
+            &owner_key.encrypted_entry_key_b64,
+            &owner_key.key_nonce_b64,
+            &new.public,
+            new.secret(),
+        )
+        .unwrap();
+        assert_eq!(*opened, entry_key);
+
+        // Our own grant now targets the new key; the friend opens theirs with our new public key.
+        assert_eq!(grants[0].public_key_b64, new.public_b64());
+        let shared = unwrap_key(
+            &grants[1].encrypted_entry_key,
+            &grants[1].key_nonce,
+            &new.public,
+            friend.secret(),
+        )
+        .unwrap();
+        assert_eq!(*shared, entry_key);
+
+        // Resuming after the owner key was already replaced still works.
+        let resumed = OwnedEntry {
+            owner_encrypted_entry_key: owner_key.encrypted_entry_key_b64,
+            owner_key_nonce: owner_key.key_nonce_b64,
+            ..entry
+        };
+        assert!(rewrap_entry(&resumed, &recipients, &old, &new).is_ok());
+    }
+
+    #[test]
+    fn journal_round_trips_and_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(JOURNAL_FILE_NAME);
+        assert!(load_journal(&path).unwrap().is_none());
+
+        let old = UserKeyPair::generate();
+        let mut journal = start_journal(&path, &old, "pw").unwrap();
+        assert!(is_pending(&path));
+        journal.cloud_entries_done.insert("cloud-1".into());
+        save_journal(&path, &journal).unwrap();
+        assert_eq!(load_journal(&path).unwrap(), Some(journal));
+
+        remove_journal(&path).unwrap();
+        assert!(!is_pending(&path));
+    }
+}
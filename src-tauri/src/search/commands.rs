@@ -0,0 +1,15 @@
+//! Tauri commands exposing [`SearchEngine`] to the webview.
+
+use tauri::State;
+
+use super::query::SearchQuery;
+use super::{SearchEngine, SearchHit};
+use crate::error::Result;
+
+#[tauri::command]
+pub fn search_entries(
+    search: State<'_, SearchEngine>,
+    query: SearchQuery,
+) -> Result<Vec<SearchHit>> {
+    search.search(&query)
+}
@@ -0,0 +1,572 @@
+//! Full-text search over journal entries.
+//!
+//! An in-memory inverted index over entry titles, bodies and frontmatter with
+//! BM25 ranking, phrase and prefix queries, highlighted snippets and tag/date
+//! filters. The index is built in the background at startup and updated
+//! incrementally by the store commands and the journal watcher.
+
+pub mod commands;
+pub mod query;
+pub mod tokenizer;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::frontmatter;
+use crate::store::timestamps::parse_date;
+use crate::store::{EntryStore, JournalEntry};
+use crate::watcher::EntryChange;
+use query::{Clause, SearchQuery};
+use tokenizer::{tokenize, Token};
+
+const DEFAULT_LIMIT: usize = 50;
+const TITLE_BOOST: f64 = 3.0;
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+const SNIPPET_BEFORE: usize = 8;
+const SNIPPET_AFTER: usize = 24;
+
+/// A ranked search result.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub id: String,
+    pub title: String,
+    pub score: f64,
+    /// HTML-escaped excerpt with matches wrapped in `<mark>`.
+    pub snippet: String,
+}
+
+#[derive(Debug)]
+struct Document {
+    title: String,
+    content: String,
+    tags: Vec<String>,
+    date: Option<DateTime<Utc>>,
+    /// Positions below this belong to the title.
+    title_len: u32,
+    /// Number of tokens, title included.
+    len: u32,
+    /// Distinct terms, whose postings hold this document.
+    terms: Vec<String>,
+}
+
+/// Inverted index: term -> entry ID -> token positions.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    docs: HashMap<String, Document>,
+    postings: BTreeMap<String, HashMap<String, Vec<u32>>>,
+    total_len: u64,
+}
+
+impl SearchIndex {
+    /// Index `entry`, replacing any previous version.
+    pub fn upsert(&mut self, entry: &JournalEntry) {
+        self.remove(&entry.id);
+
+        let title_tokens = tokenize(&entry.title);
+        let title_len = title_tokens.len() as u32;
+        // Leave a gap after the title so phrases never span title and body.
+        let body_positions = (title_len + 1..).zip(tokenize(&entry.content));
+        let positions = (0..).zip(title_tokens).chain(body_positions);
+
+        let mut len = 0;
+        let mut doc_postings: HashMap<String, Vec<u32>> = HashMap::new();
+        for (position, token) in positions {
+            doc_postings.entry(token.term).or_default().push(position);
+            len += 1;
+        }
+
+        let mut terms = Vec::with_capacity(doc_postings.len());
+        for (term, positions) in doc_postings {
+            terms.push(term.clone());
+            self.postings
+                .entry(term)
+                .or_default()
+                .insert(entry.id.clone(), positions);
+        }
+
+        self.total_len += u64::from(len);
+        self.docs.insert(
+            entry.id.clone(),
+            Document {
+                title: entry.title.clone(),
+                content: entry.content.clone(),
+                tags: frontmatter_tags(&entry.content),
+                date: parse_date(&entry.created_at),
+                title_len,
+                len,
+                terms,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        let Some(doc) = self.docs.remove(id) else {
+            return;
+        };
+        self.total_len -= u64::from(doc.len);
+        for term in &doc.terms {
+            if let Some(docs) = self.postings.get_mut(term) {
+                docs.remove(id);
+                if docs.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        let (from, to) = query.date_range()?;
+        let clauses = query::parse(&query.query);
+        let wanted_tags: Vec<String> = query.tags.iter().map(|t| t.to_lowercase()).collect();
+        let filter_only = clauses.is_empty();
+        if self.docs.is_empty()
+            || (filter_only && wanted_tags.is_empty() && from.is_none() && to.is_none())
+        {
+            return Ok(Vec::new());
+        }
+
+        // Without text every document is a candidate for the filters.
+        let mut scores: Option<HashMap<&str, f64>> =
+            filter_only.then(|| self.docs.keys().map(|id| (id.as_str(), 0.0)).collect());
+        for clause in &clauses {
+            let clause_scores = self.score_clause(clause);
+            scores = Some(match scores {
+                None => clause_scores,
+                Some(acc) => acc
+                    .into_iter()
+                    .filter_map(|(id, s)| clause_scores.get(id).map(|c| (id, s + c)))
+                    .collect(),
+            });
+        }
+
+        let mut hits: Vec<SearchHit> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(id, score)| {
+                let doc = &self.docs[id];
+                let in_range = match doc.date {
+                    Some(date) => from.is_none_or(|f| date >= f) && to.is_none_or(|t| date <= t),
+                    None => from.is_none() && to.is_none(),
+                };
+                let has_tags = wanted_tags.iter().all(|t| doc.tags.contains(t));
+                (in_range && has_tags).then(|| SearchHit {
+                    id: id.to_string(),
+                    title: doc.title.clone(),
+                    score,
+                    snippet: snippet(&doc.content, &clauses),
+                })
+            })
+            .collect();
+
+        if filter_only {
+            // Nothing to rank by, so newest first.
+            hits.sort_by(|a, b| {
+                let date = |hit: &SearchHit| self.docs[&hit.id].date;
+                date(b).cmp(&date(a)).then_with(|| a.id.cmp(&b.id))
+            });
+        } else {
+            hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+        }
+        hits.truncate(query.limit.unwrap_or(DEFAULT_LIMIT));
+        Ok(hits)
+    }
+
+    /// BM25 score of every document matching `clause`.
+    fn score_clause(&self, clause: &Clause) -> HashMap<&str, f64> {
+        let mut frequencies: HashMap<&str, f64> = HashMap::new();
+        match clause {
+            Clause::Term(term) => {
+                if let Some(docs) = self.postings.get(term) {
+                    self.accumulate(docs, &mut frequencies);
+                }
+            }
+            Clause::Prefix(prefix) => {
+                let matching = self
+                    .postings
+                    .range(prefix.clone()..)
+                    .take_while(|(term, _)| term.starts_with(prefix.as_str()));
+                for (_, docs) in matching {
+                    self.accumulate(docs, &mut frequencies);
+                }
+            }
+            Clause::Phrase(words) => {
+                for (id, starts) in self.phrase_matches(words) {
+                    let doc = &self.docs[id];
+                    let weight: f64 = starts
+                        .iter()
+                        .map(|&p| if p < doc.title_len { TITLE_BOOST } else { 1.0 })
+                        .sum();
+                    frequencies.insert(id, weight);
+                }
+            }
+        }
+
+        let n = self.docs.len() as f64;
+        let df = frequencies.len() as f64;
+        let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+        let avg_len = (self.total_len as f64 / n).max(1.0);
+
+        frequencies
+            .into_iter()
+            .map(|(id, tf)| {
+                let len = f64::from(self.docs[id].len);
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len);
+                (id, idf * tf * (BM25_K1 + 1.0) / (tf + norm))
+            })
+            .collect()
+    }
+
+    fn accumulate<'a>(
+        &'a self,
+        docs: &'a HashMap<String, Vec<u32>>,
+        into: &mut HashMap<&'a str, f64>,
+    ) {
+        for (id, positions) in docs {
+            let title_len = self.docs[id].title_len;
+            let weight: f64 = positions
+                .iter()
+                .map(|&p| if p < title_len { TITLE_BOOST } else { 1.0 })
+                .sum();
+            *into.entry(id.as_str()).or_default() += weight;
+        }
+    }
+
+    /// Start positions of `words` appearing consecutively, per document.
+    fn phrase_matches(&self, words: &[String]) -> HashMap<&str, Vec<u32>> {
+        let mut matches = HashMap::new();
+        let Some(first) = self.postings.get(&words[0]) else {
+            return matches;
+        };
+
+        for (id, starts) in first {
+            let found: Vec<u32> = starts
+                .iter()
+                .copied()
+                .filter(|&start| {
+                    words[1..].iter().zip(1..).all(|(word, offset)| {
+                        self.postings
+                            .get(word)
+                            .and_then(|docs| docs.get(id))
+                            .is_some_and(|positions| positions.contains(&(start + offset)))
+                    })
+                })
+                .collect();
+            if !found.is_empty() {
+                matches.insert(id.as_str(), found);
+            }
+        }
+        matches
+    }
+}
+
+/// Managed search state shared by commands and the watcher.
+#[derive(Debug, Default)]
+pub struct SearchEngine {
+    index: RwLock<SearchIndex>,
+    /// Bumped by every [`SearchEngine::clear`], so that a rebuild of a vault
+    /// since switched away from or locked is not swapped in.
+    generation: AtomicU64,
+}
+
+impl SearchEngine {
+    fn read(&self) -> RwLockReadGuard<'_, SearchIndex> {
+        self.index.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, SearchIndex> {
+        self.index.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Rebuild the whole index from the store. Dropped if the index is
+    /// cleared meanwhile, as the store is then no longer the one to search.
+    pub fn rebuild(&self, store: &EntryStore) -> Result<()> {
+        let generation = self.generation.load(Ordering::SeqCst);
+        let mut index = SearchIndex::default();
+        for id in store.entry_ids()? {
+            if let Some(entry) = store.get_entry(&id)? {
+                index.upsert(&entry);
+            }
+        }
+        self.install(generation, index);
+        Ok(())
+    }
+
+    fn install(&self, generation: u64, index: SearchIndex) {
+        let mut current = self.write();
+        if self.generation.load(Ordering::SeqCst) == generation {
+            *current = index;
+        }
+    }
+
+    /// Re-index `id` from disk, dropping it if the entry no longer exists.
+    pub fn refresh_entry(&self, store: &EntryStore, id: &str) -> Result<()> {
+        match store.get_entry(id)? {
+            Some(entry) => self.write().upsert(&entry),
+            None => self.write().remove(id),
+        }
+        Ok(())
+    }
+
+    pub fn remove_entry(&self, id: &str) {
+        self.write().remove(id);
+    }
+
+    /// Drop every indexed entry, e.g. when the vault locks.
+    pub fn clear(&self) {
+        let mut current = self.write();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *current = SearchIndex::default();
+    }
+
+    /// Apply a batch of watcher changes.
+    pub fn apply_changes(&self, store: &EntryStore, changes: &[EntryChange]) {
+        for change in changes {
+            let result = match change {
+                EntryChange::Create { id }
+                | EntryChange::Modify { id }
+                | EntryChange::Rename { id, .. } => self.refresh_entry(store, id),
+                EntryChange::Remove { id } => {
+                    self.remove_entry(id);
+                    Ok(())
+                }
+            };
+            if let Err(error) = result {
+                log::warn!("failed to update search index: {error}");
+            }
+        }
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        self.read().search(query)
+    }
+}
+
+/// Lowercased `tags` from the entry's frontmatter.
+fn frontmatter_tags(content: &str) -> Vec<String> {
+    frontmatter::parse(content)
+        .map(|parsed| parsed.frontmatter.tags)
+        .unwrap_or_default()
+        .iter()
+        .map(|tag| tag.to_lowercase())
+        .collect()
+}
+
+/// Build a highlighted excerpt around the first match in `content`.
+fn snippet(content: &str, clauses: &[Clause]) -> String {
+    let tokens: Vec<Token> = tokenize(content);
+    let Some(first) = tokens
+        .iter()
+        .position(|t| clauses.iter().any(|c| c.matches_term(&t.term)))
+    else {
+        return escape_html(&content.chars().take(150).collect::<String>());
+    };
+
+    let start = first.saturating_sub(SNIPPET_BEFORE);
+    let end = (first + SNIPPET_AFTER).min(tokens.len() - 1);
+    let window = &tokens[start..=end];
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    let mut cursor = window[0].start;
+    for token in window {
+        out.push_str(&escape_html(&content[cursor..token.start]));
+        let text = escape_html(&content[token.start..token.end]);
+        if clauses.iter().any(|c| c.matches_term(&token.term)) {
+            out.push_str("<mark>");
+            out.push_str(&text);
+            out.push_str("</mark>");
+        } else {
+            out.push_str(&text);
+        }
+        cursor = token.end;
+    }
+    if end + 1 < tokens.len() {
+        out.push('…');
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    fn entry(id: &str, created_at: &str, content: &str) -> JournalEntry {
+        JournalEntry {
+            id: id.to_string(),
+            title: crate::store::entry::create_title_from_id(id),
+            content: content.to_string(),
+            created_at: created_at.to_string(),
+            modified_at: created_at.to_string(),
+            file_path: format!("Diaryx/{id}.md"),
+        }
+    }
+
+    fn index() -> SearchIndex {
+        let mut index = SearchIndex::default();
+        index.upsert(&entry(
+            "morning-coffee",
+            "2024-01-10T08:00:00.000Z",
+            "---\ntags: [routine, food]\n---\nA strong morning coffee before the walk.",
+        ));
+        index.upsert(&entry(
+            "evening-walk",
+            "2024-02-01T19:00:00.000Z",
+            "Coffee in the morning, a long walk in the evening.",
+        ));
+        index
+    }
+
+    fn search(index: &SearchIndex, query: &str) -> Vec<String> {
+        let query = SearchQuery {
+            query: query.to_string(),
+            ..Default::default()
+        };
+        index
+            .search(&query)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.id)
+            .collect()
+    }
+
+    #[test]
+    fn ranks_title_matches_first() {
+        assert_eq!(search(&index(), "walk"), ["evening-walk", "morning-coffee"]);
+    }
+
+    #[test]
+    fn phrase_and_prefix_queries() {
+        let index = index();
+        assert_eq!(search(&index, r#""morning coffee""#), ["morning-coffee"]);
+        assert_eq!(search(&index, "even*"), ["evening-walk"]);
+    }
+
+    #[test]
+    fn filters_by_tag_and_date_and_highlights() {
+        let index = index();
+        let query = SearchQuery {
+            query: "coffee".to_string(),
+            tags: vec!["Routine".to_string()],
+            to: Some("2024-01-31".to_string()),
+            ..Default::default()
+        };
+        let hits = index.search(&query).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].snippet.contains("<mark>coffee</mark>"));
+    }
+
+    #[test]
+    fn date_only_bounds_include_the_whole_day() {
+        let index = index();
+        let query = SearchQuery {
+            query: "walk".to_string(),
+            from: Some("2024-02-01".to_string()),
+            to: Some("2024-02-01".to_string()),
+            ..Default::default()
+        };
+        let hits = index.search(&query).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "evening-walk");
+
+        let query = SearchQuery {
+            query: "walk".to_string(),
+            to: Some("last week".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(index.search(&query), Err(Error::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn rebuild_is_dropped_after_a_clear() {
+        let engine = SearchEngine::default();
+        let generation = engine.generation.load(Ordering::SeqCst);
+        engine.clear();
+        engine.install(generation, index());
+        assert!(search(&engine.read(), "coffee").is_empty());
+
+        engine.install(engine.generation.load(Ordering::SeqCst), index());
+        assert_eq!(search(&engine.read(), "coffee").len(), 2);
+    }
+
+    #[test]
+    fn remove_drops_postings() {
+        let mut index = index();
+        index.remove("evening-walk");
+        assert_eq!(search(&index, "evening"), Vec::<String>::new());
+        assert!(!index.postings.contains_key("evening"));
+        assert!(index.postings.contains_key("coffee"));
+        assert_eq!(index.total_len, u64::from(index.docs["morning-coffee"].len));
+    }
+
+    #[test]
+    fn document_length_counts_tokens() {
+        let mut index = SearchIndex::default();
+        // "Morning Coffee" + "coffee and toast"
+        index.upsert(&entry(
+            "morning-coffee",
+            "2024-01-10T08:00:00.000Z",
+            "coffee and toast",
+        ));
+        assert_eq!(index.docs["morning-coffee"].len, 5);
+        assert_eq!(index.total_len, 5);
+
+        index.upsert(&entry("morning-coffee", "2024-01-10T08:00:00.000Z", ""));
+        assert_eq!(index.docs["morning-coffee"].len, 2);
+        assert_eq!(index.total_len, 2);
+    }
+
+    #[test]
+    fn filter_only_queries_return_every_match() {
+        let index = index();
+        let by_tag = SearchQuery {
+            tags: vec!["Routine".to_string()],
+            ..Default::default()
+        };
+        let ids: Vec<String> = index
+            .search(&by_tag)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.id)
+            .collect();
+        assert_eq!(ids, ["morning-coffee"]);
+
+        let by_date = SearchQuery {
+            from: Some("2024-01-01".to_string()),
+            ..Default::default()
+        };
+        let ids: Vec<String> = index
+            .search(&by_date)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.id)
+            .collect();
+        assert_eq!(ids, ["evening-walk", "morning-coffee"]);
+
+        assert!(index.search(&SearchQuery::default()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! Search query parsing.
+//!
+//! Supported syntax: bare words (`coffee`), prefixes (`caff*`) and quoted
+//! phrases (`"morning coffee"`). All clauses must match.
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::Deserialize;
+
+use super::tokenizer::tokenize;
+use crate::error::{Error, Result};
+use crate::store::timestamps::parse_date;
+
+/// Parameters of a `search_entries` call.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    pub query: String,
+    /// Only return entries carrying every one of these tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Inclusive lower bound on the entry date (RFC 3339 or `YYYY-MM-DD`).
+    pub from: Option<String>,
+    /// Inclusive upper bound on the entry date (RFC 3339 or `YYYY-MM-DD`).
+    pub to: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl SearchQuery {
+    /// Inclusive bounds on the entry date from `from` and `to`. A date-only
+    /// `to` covers the whole day.
+    pub fn date_range(&self) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)> {
+        let from = self.from.as_deref().map(parse_bound).transpose()?;
+        let to = match self.to.as_deref() {
+            Some(to) => Some(match NaiveDate::parse_from_str(to, "%Y-%m-%d") {
+                Ok(day) => {
+                    let end = day
+                        .and_hms_nano_opt(23, 59, 59, 999_999_999)
+                        .ok_or_else(|| invalid_date(to))?;
+                    Utc.from_utc_datetime(&end)
+                }
+                Err(_) => parse_bound(to)?,
+            }),
+            None => None,
+        };
+        Ok((from, to))
+    }
+}
+
+fn parse_bound(value: &str) -> Result<DateTime<Utc>> {
+    parse_date(value).ok_or_else(|| invalid_date(value))
+}
+
+fn invalid_date(value: &str) -> Error {
+    Error::InvalidQuery(format!("unrecognized date {value:?}"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clause {
+    Term(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+impl Clause {
+    /// Whether a single indexed term satisfies this clause, for highlighting.
+    pub fn matches_term(&self, term: &str) -> bool {
+        match self {
+            Clause::Term(t) => t == term,
+            Clause::Prefix(p) => term.starts_with(p.as_str()),
+            Clause::Phrase(words) => words.iter().any(|w| w == term),
+        }
+    }
+}
+
+/// Parse a raw query string into clauses.
+pub fn parse(query: &str) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    let mut rest = query;
+
+    while let Some(open) = rest.find('"') {
+        parse_words(&rest[..open], &mut clauses);
+        let after = &rest[open + 1..];
+        let close = after.find('"').unwrap_or(after.len());
+        let words: Vec<String> = tokenize(&after[..close])
+            .into_iter()
+            .map(|t| t.term)
+            .collect();
+        match words.len() {
+            0 => {}
+            1 => clauses.extend(words.into_iter().map(Clause::Term)),
+            _ => clauses.push(Clause::Phrase(words)),
+        }
+        rest = after.get(close + 1..).unwrap_or("");
+    }
+    parse_words(rest, &mut clauses);
+    clauses
+}
+
+fn parse_words(text: &str, clauses: &mut Vec<Clause>) {
+    for word in text.split_whitespace() {
+        let prefix = word.ends_with('*');
+        let tokens = tokenize(word);
+        // Only the last token of `foo-bar*` is a prefix; the rest are exact terms.
+        let last = tokens.len().saturating_sub(1);
+        for (i, token) in tokens.into_iter().enumerate() {
+            clauses.push(if prefix && i == last {
+                Clause::Prefix(token.term)
+            } else {
+                Clause::Term(token.term)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_terms_prefixes_and_phrases() {
+        assert_eq!(
+            parse(r#"walk caff* "Morning Coffee""#),
+            vec![
+                Clause::Term("walk".into()),
+                Clause::Prefix("caff".into()),
+                Clause::Phrase(vec!["morning".into(), "coffee".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn date_only_upper_bound_covers_the_day() {
+        let query = SearchQuery {
+            from: Some("2024-01-31".to_string()),
+            to: Some("2024-01-31".to_string()),
+            ..Default::default()
+        };
+        let (from, to) = query.date_range().unwrap();
+        assert_eq!(from.unwrap().to_rfc3339(), "2024-01-31T00:00:00+00:00");
+        assert!(to.unwrap() > parse_date("2024-01-31T23:59:59Z").unwrap());
+        assert!(to.unwrap() < parse_date("2024-02-01").unwrap());
+    }
+
+    #[test]
+    fn rejects_unparseable_dates() {
+        for (from, to) in [(Some("yesterday"), None), (None, Some("2024-13-01"))] {
+            let query = SearchQuery {
+                from: from.map(str::to_string),
+                to: to.map(str::to_string),
+                ..Default::default()
+            };
+            assert!(matches!(query.date_range(), Err(Error::InvalidQuery(_))));
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Unicode-aware tokenizer shared by indexing and querying.
+
+/// A lowercased term and its byte span in the source text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub term: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Split `text` into lowercased alphanumeric runs.
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                tokens.push(token(text, s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(token(text, s, text.len()));
+    }
+    tokens
+}
+
+fn token(text: &str, start: usize, end: usize) -> Token {
+    Token {
+        term: text[start..end].to_lowercase(),
+        start,
+        end,
+    }
+}
@@ -0,0 +1,176 @@
+//! Tauri commands driving the [`Session`] state machine.
+//!
+//! Unlocking and locking also unlock and lock the journal vault. Passwords
+//! are wiped before returning; the secret key never crosses the IPC boundary.
+
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager, State};
+use zeroize::Zeroizing;
+
+use super::{lock_app, notify, LockReason, RecoveryExport, Session, SessionStatus, StoredKeys};
+use crate::crypto::keys::UserKeyPair;
+use crate::error::Result;
+use crate::search::SearchEngine;
+use crate::vaults::Vaults;
+
+#[tauri::command]
+pub fn session_status(session: State<'_, Session>) -> SessionStatus {
+    session.status()
+}
+
+/// Generate and persist a key pair for a new user. Returns the keys to back up.
+#[tauri::command]
+pub async fn create_session_keys(
+    app: AppHandle,
+    user_id: String,
+    password: String,
+) -> Result<StoredKeys> {
+    let password = Zeroizing::new(password);
+    tokio::task::spawn_blocking(move || {
+        let session = app.state::<Session>();
+        let stored = session.create_keys(&user_id, &password)?;
+        unlock_vault(&app, session.with_keys(|pair| Ok(pair.clone()))?);
+        notify(&app, &session);
+        Ok(stored)
+    })
+    .await?
+}
+
+/// Adopt keys restored from the cloud. The session is left locked.
+#[tauri::command]
+pub fn import_session_keys(
+    app: AppHandle,
+    session: State<'_, Session>,
+    user_id: String,
+    public_key_b64: String,
+    envelope: String,
+    recovery_envelope: Option<String>,
+) -> Result<SessionStatus> {
+    lock_app(&app, LockReason::User);
+    session.import_keys(StoredKeys {
+        user_id,
+        public_key_b64,
+        envelope,
+        recovery_envelope,
+    })?;
+    notify(&app, &session);
+    Ok(session.status())
+}
+
+#[tauri::command]
+pub async fn unlock_session(app: AppHandle, password: String) -> Result<SessionStatus> {
+    let password = Zeroizing::new(password);
+    tokio::task::spawn_blocking(move || {
+        let session = app.state::<Session>();
+        let pair = session.unlock(&password)?;
+        unlock_vault(&app, pair);
+        notify(&app, &session);
+        Ok(session.status())
+    })
+    .await?
+}
+
+#[tauri::command]
+pub fn lock_session(app: AppHandle, session: State<'_, Session>) -> SessionStatus {
+    lock_app(&app, LockReason::User);
+    session.status()
+}
+
+/// Lock and delete the device's stored keys, e.g. on logout.
+#[tauri::command]
+pub fn forget_session(app: AppHandle, session: State<'_, Session>) -> Result<SessionStatus> {
+    lock_app(&app, LockReason::User);
+    session.forget()?;
+    notify(&app, &session);
+    Ok(session.status())
+}
+
+/// Re-seal the stored key under a new password. Returns the keys to back up.
+#[tauri::command]
+pub async fn change_session_password(
+    app: AppHandle,
+    old_password: String,
+    new_password: String,
+) -> Result<StoredKeys> {
+    let old_password = Zeroizing::new(old_password);
+    let new_password = Zeroizing::new(new_password);
+    tokio::task::spawn_blocking(move || {
+        app.state::<Session>()
+            .change_password(&old_password, &new_password)
+    })
+    .await?
+}
+
+/// Set the idle auto-lock timeout in seconds; `None` disables it.
+#[tauri::command]
+pub fn set_idle_timeout(
+    session: State<'_, Session>,
+    seconds: Option<u64>,
+) -> Result<SessionStatus> {
+    session.set_idle_timeout(seconds.map(Duration::from_secs))?;
+    Ok(session.status())
+}
+
+/// Generate a recovery phrase to show the user, with the word positions to confirm.
+#[tauri::command]
+pub fn export_recovery_phrase(session: State<'_, Session>) -> Result<RecoveryExport> {
+    session.export_recovery()
+}
+
+/// Confirm the challenged words of the exported phrase. Returns the keys to back up.
+#[tauri::command]
+pub fn confirm_recovery_phrase(
+    app: AppHandle,
+    session: State<'_, Session>,
+    words: Vec<String>,
+) -> Result<StoredKeys> {
+    let words = Zeroizing::new(words);
+    let stored = session.confirm_recovery(&words)?;
+    notify(&app, &session);
+    Ok(stored)
+}
+
+/// Restore the key pair from a recovery phrase, without network access.
+///
+/// `backup` supplies keys fetched elsewhere when this device has none stored.
+#[tauri::command]
+pub async fn restore_from_recovery_phrase(
+    app: AppHandle,
+    phrase: String,
+    new_password: String,
+    backup: Option<StoredKeys>,
+) -> Result<StoredKeys> {
+    let phrase = Zeroizing::new(phrase);
+    let new_password = Zeroizing::new(new_password);
+    tokio::task::spawn_blocking(move || {
+        let session = app.state::<Session>();
+        let pair = session.restore_from_phrase(&phrase, &new_password, backup)?;
+        unlock_vault(&app, pair);
+        notify(&app, &session);
+        session.stored_keys()
+    })
+    .await?
+}
+
+/// Report user activity from the webview.
+#[tauri::command]
+pub fn touch_session(session: State<'_, Session>) {
+    session.touch();
+}
+
+/// Hand the key pair to the vault and index its now-readable entries.
+fn unlock_vault(app: &AppHandle, pair: UserKeyPair) {
+    let store = app.state::<Vaults>().store();
+    if let Err(error) = store.unlock_vault(pair) {
+        log::warn!("session unlocked but vault did not: {error}");
+        return;
+    }
+    if store.vault_status().enabled {
+        if let Err(error) = app.state::<SearchEngine>().rebuild(&store) {
+            log::warn!("failed to build search index: {error}");
+        }
+    }
+    // Queued uploads need the keys.
+    app.state::<Vaults>().outbox().wake();
+}
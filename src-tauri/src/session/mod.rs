@@ -0,0 +1,622 @@
+//! Rust-owned E2E session.
+//!
+//! The user's key pair is persisted only as a password-protected envelope
+//! and held in memory while the session is unlocked. The webview sees the
+//! session status and public key, never the secret key; crypto commands use
+//! the key held here. A monitor thread locks the session after an idle
+//! timeout, kept next to the keys, and when the machine wakes from sleep.
+
+pub mod commands;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crypto_box::aead::rand_core::RngCore;
+use crypto_box::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use zeroize::Zeroizing;
+
+use crate::crypto::envelope::{self, KdfParams};
+use crate::crypto::keys::{public_key_from_b64, UserKeyPair};
+use crate::crypto::recovery;
+use crate::error::{Error, Result};
+use crate::search::SearchEngine;
+use crate::store::atomic;
+use crate::vaults::Vaults;
+
+/// Name of the Tauri event carrying the new [`SessionStatus`] after a change.
+pub const SESSION_EVENT: &str = "session-changed";
+
+const KEYS_FILE_NAME: &str = "session-keys.json";
+const SETTINGS_FILE_NAME: &str = "session-settings.json";
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const MONITOR_INTERVAL: Duration = Duration::from_secs(5);
+/// Wall-clock time outrunning the monotonic clock by this much means the machine slept.
+const SUSPEND_GAP: Duration = Duration::from_secs(30);
+/// How many words of a new recovery phrase the user must type back.
+const RECOVERY_CHALLENGE_WORDS: usize = 4;
+
+/// The user's keys as persisted on disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredKeys {
+    pub user_id: String,
+    pub public_key_b64: String,
+    /// Secret key sealed by [`envelope::seal`].
+    pub envelope: String,
+    /// Secret key sealed by [`recovery::seal`], once a recovery phrase is confirmed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_envelope: Option<String>,
+}
+
+/// Session preferences of this device. They outlive the keys, so logging
+/// out keeps them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SessionSettings {
+    /// `None` disables the idle lock.
+    idle_timeout_secs: Option<u64>,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            idle_timeout_secs: Some(DEFAULT_IDLE_TIMEOUT.as_secs()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SessionState {
+    NoKeys,
+    Locked,
+    Unlocked,
+}
+
+/// Session state as reported to the webview.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionStatus {
+    pub state: SessionState,
+    pub user_id: Option<String>,
+    pub public_key_b64: Option<String>,
+    pub idle_timeout_secs: Option<u64>,
+    pub has_recovery_phrase: bool,
+}
+
+/// A freshly generated recovery phrase and the word positions to confirm.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryExport {
+    pub phrase: Zeroizing<String>,
+    /// 1-based positions of the words the user must type back.
+    pub challenge: Vec<usize>,
+}
+
+/// Why the session was locked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockReason {
+    User,
+    Idle,
+    Suspend,
+}
+
+#[derive(Debug)]
+struct Inner {
+    stored: Option<StoredKeys>,
+    keys: Option<UserKeyPair>,
+    last_activity: Instant,
+    idle_timeout: Option<Duration>,
+    /// Phrase shown to the user but not yet confirmed.
+    pending_recovery: Option<RecoveryExport>,
+}
+
+/// Managed session state.
+#[derive(Debug)]
+pub struct Session {
+    path: PathBuf,
+    inner: Mutex<Inner>,
+}
+
+impl Session {
+    /// Default location of the persisted keys inside the platform data directory.
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(crate::APP_IDENTIFIER)
+            .join(KEYS_FILE_NAME)
+    }
+
+    /// Load persisted keys from `path`, and the settings next to them. The
+    /// session always starts locked.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let stored = read_json(&path, "session keys");
+        let settings: SessionSettings =
+            read_json(&path.with_file_name(SETTINGS_FILE_NAME), "session settings")
+                .unwrap_or_default();
+        Self {
+            path,
+            inner: Mutex::new(Inner {
+                stored,
+                keys: None,
+                last_activity: Instant::now(),
+                idle_timeout: settings.idle_timeout_secs.map(Duration::from_secs),
+                pending_recovery: None,
+            }),
+        }
+    }
+
+    fn lock_inner(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        let inner = self.lock_inner();
+        let state = match (&inner.stored, &inner.keys) {
+            (None, _) => SessionState::NoKeys,
+            (Some(_), None) => SessionState::Locked,
+            (Some(_), Some(_)) => SessionState::Unlocked,
+        };
+        SessionStatus {
+            state,
+            user_id: inner.stored.as_ref().map(|s| s.user_id.clone()),
+            public_key_b64: inner.stored.as_ref().map(|s| s.public_key_b64.clone()),
+            idle_timeout_secs: inner.idle_timeout.map(|t| t.as_secs()),
+            has_recovery_phrase: inner
+                .stored
+                .as_ref()
+                .is_some_and(|s| s.recovery_envelope.is_some()),
+        }
+    }
+
+    /// Keys persisted for this device.
+    pub fn stored_keys(&self) -> Result<StoredKeys> {
+        self.lock_inner()
+            .stored
+            .clone()
+            .ok_or_else(|| Error::Crypto("no keys stored for this device".into()))
+    }
+
+    /// Generate a key pair for `user_id`, persist it under `password` and unlock.
+    pub fn create_keys(&self, user_id: &str, password: &str) -> Result<StoredKeys> {
+        let pair = UserKeyPair::generate();
+        let stored = StoredKeys {
+            user_id: user_id.to_string(),
+            public_key_b64: pair.public_b64(),
+            envelope: envelope::seal(&pair.secret_bytes(), password, KdfParams::default())?,
+            recovery_envelope: None,
+        };
+        self.persist(&stored)?;
+
+        let mut inner = self.lock_inner();
+        inner.stored = Some(stored.clone());
+        inner.keys = Some(pair);
+        inner.last_activity = Instant::now();
+        Ok(stored)
+    }
+
+    /// Adopt keys restored elsewhere, e.g. from the cloud. The session is locked.
+    pub fn import_keys(&self, stored: StoredKeys) -> Result<()> {
+        public_key_from_b64(&stored.public_key_b64)?;
+        self.persist(&stored)?;
+
+        let mut inner = self.lock_inner();
+        inner.stored = Some(stored);
+        inner.keys = None;
+        Ok(())
+    }
+
+    /// Open the stored envelope with `password` and hold the key pair.
+    ///
+    /// Legacy or weak envelopes are re-sealed and persisted on success.
+    pub fn unlock(&self, password: &str) -> Result<UserKeyPair> {
+        let stored = self.stored_keys()?;
+
+        let opened = envelope::open(&stored.envelope, password)?;
+        let pair = UserKeyPair::from_secret_bytes(&*opened.secret)?;
+        if pair.public_b64() != stored.public_key_b64 {
+            return Err(Error::Crypto("public key does not match secret key".into()));
+        }
+
+        let stored = if opened.needs_upgrade {
+            let upgraded = StoredKeys {
+                envelope: envelope::seal(&opened.secret, password, KdfParams::default())?,
+                ..stored
+            };
+            self.persist(&upgraded)?;
+            upgraded
+        } else {
+            stored
+        };
+
+        let mut inner = self.lock_inner();
+        inner.stored = Some(stored);
+        inner.keys = Some(pair.clone());
+        inner.last_activity = Instant::now();
+        Ok(pair)
+    }
+
+    /// Drop the held key pair. Returns whether the session was unlocked.
+    pub fn lock(&self) -> bool {
+        let mut inner = self.lock_inner();
+        inner.pending_recovery = None;
+        inner.keys.take().is_some()
+    }
+
+    /// Forget the persisted keys, e.g. on logout.
+    pub fn forget(&self) -> Result<()> {
+        let mut inner = self.lock_inner();
+        inner.keys = None;
+        inner.stored = None;
+        inner.pending_recovery = None;
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Re-seal the stored key under `new_password`. Returns the updated keys.
+    pub fn change_password(&self, old_password: &str, new_password: &str) -> Result<StoredKeys> {
+        let stored = self.stored_keys()?;
+        let opened = envelope::open(&stored.envelope, old_password)?;
+        let updated = StoredKeys {
+            envelope: envelope::seal(&opened.secret, new_password, KdfParams::default())?,
+            ..stored
+        };
+        self.persist(&updated)?;
+        self.lock_inner().stored = Some(updated.clone());
+        Ok(updated)
+    }
+
+    /// Generate a recovery phrase for the unlocked key pair.
+    ///
+    /// Nothing is persisted until [`Session::confirm_recovery`] succeeds.
+    pub fn export_recovery(&self) -> Result<RecoveryExport> {
+        let mut inner = self.lock_inner();
+        if inner.keys.is_none() {
+            return Err(Error::Locked);
+        }
+
+        let mut positions: Vec<usize> = (1..=recovery::WORD_COUNT).collect();
+        let mut challenge = Vec::with_capacity(RECOVERY_CHALLENGE_WORDS);
+        for _ in 0..RECOVERY_CHALLENGE_WORDS {
+            let pick = OsRng.next_u32() as usize % positions.len();
+            challenge.push(positions.swap_remove(pick));
+        }
+        challenge.sort_unstable();
+
+        let export = RecoveryExport {
+            phrase: recovery::generate(),
+            challenge,
+        };
+        inner.pending_recovery = Some(export.clone());
+        inner.last_activity = Instant::now();
+        Ok(export)
+    }
+
+    /// Check the challenged `words` and, if they match, persist the recovery envelope.
+    pub fn confirm_recovery(&self, words: &[String]) -> Result<StoredKeys> {
+        let mut inner = self.lock_inner();
+        let pending = inner
+            .pending_recovery
+            .as_ref()
+            .ok_or_else(|| Error::Crypto("no recovery phrase awaiting confirmation".into()))?;
+        let phrase: Vec<&str> = pending.phrase.split(' ').collect();
+        let matches = words.len() == pending.challenge.len()
+            && pending
+                .challenge
+                .iter()
+                .zip(words)
+                .all(|(&position, word)| *recovery::normalize(word) == phrase[position - 1]);
+        if !matches {
+            return Err(Error::Crypto("recovery words do not match".into()));
+        }
+
+        let keys = inner.keys.as_ref().ok_or(Error::Locked)?;
+        let recovery_envelope = recovery::seal(&keys.secret_bytes(), &pending.phrase)?;
+        let stored = StoredKeys {
+            recovery_envelope: Some(recovery_envelope),
+            ..inner.stored.clone().ok_or(Error::Locked)?
+        };
+        self.persist(&stored)?;
+        inner.stored = Some(stored.clone());
+        inner.pending_recovery = None;
+        Ok(stored)
+    }
+
+    /// Restore the key pair from a recovery phrase and protect it with `new_password`.
+    ///
+    /// Works offline from the locally stored keys, or from `backup` (e.g. keys
+    /// fetched from the cloud) when given. Unlocks the session on success.
+    pub fn restore_from_phrase(
+        &self,
+        phrase: &str,
+        new_password: &str,
+        backup: Option<StoredKeys>,
+    ) -> Result<UserKeyPair> {
+        let stored = match backup {
+            Some(backup) => backup,
+            None => self.stored_keys()?,
+        };
+        let recovery_envelope = stored
+            .recovery_envelope
+            .as_deref()
+            .ok_or_else(|| Error::Crypto("no recovery phrase was set up for these keys".into()))?;
+
+        let secret = recovery::open(recovery_envelope, phrase)?;
+        let pair = UserKeyPair::from_secret_bytes(&*secret)?;
+        if pair.public_b64() != stored.public_key_b64 {
+            return Err(Error::Crypto("public key does not match secret key".into()));
+        }
+        let restored = StoredKeys {
+            envelope: envelope::seal(&secret, new_password, KdfParams::default())?,
+            ..stored
+        };
+        self.persist(&restored)?;
+
+        let mut inner = self.lock_inner();
+        inner.stored = Some(restored);
+        inner.keys = Some(pair.clone());
+        inner.last_activity = Instant::now();
+        Ok(pair)
+    }
+
+    /// Swap in a rotated key pair and its password envelope, keeping the session unlocked.
+    ///
+    /// The recovery envelope sealed the old key, so it is dropped.
+    pub fn replace_keys(&self, keys: UserKeyPair, envelope: String) -> Result<()> {
+        let stored = StoredKeys {
+            public_key_b64: keys.public_b64(),
+            envelope,
+            recovery_envelope: None,
+            ..self.stored_keys()?
+        };
+        self.persist(&stored)?;
+
+        let mut inner = self.lock_inner();
+        inner.stored = Some(stored);
+        inner.keys = Some(keys);
+        inner.pending_recovery = None;
+        inner.last_activity = Instant::now();
+        Ok(())
+    }
+
+    /// Record user activity, postponing the idle lock.
+    pub fn touch(&self) {
+        self.lock_inner().last_activity = Instant::now();
+    }
+
+    /// Set and persist the idle timeout; `None` disables auto-lock.
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        let settings = SessionSettings {
+            idle_timeout_secs: timeout.map(|t| t.as_secs()),
+        };
+        write_json(&self.path.with_file_name(SETTINGS_FILE_NAME), &settings)?;
+
+        let mut inner = self.lock_inner();
+        inner.idle_timeout = timeout;
+        inner.last_activity = Instant::now();
+        Ok(())
+    }
+
+    /// Run `f` with the unlocked key pair, counting it as activity.
+    pub fn with_keys<T>(&self, f: impl FnOnce(&UserKeyPair) -> Result<T>) -> Result<T> {
+        let mut inner = self.lock_inner();
+        inner.last_activity = Instant::now();
+        let keys = inner.keys.as_ref().ok_or(Error::Locked)?;
+        f(keys)
+    }
+
+    /// A copy of the unlocked key pair for background work, which does not
+    /// count as activity and so never holds off the idle lock.
+    pub fn keys_snapshot(&self) -> Result<UserKeyPair> {
+        self.lock_inner().keys.clone().ok_or(Error::Locked)
+    }
+
+    /// Whether an unlocked session has been idle longer than its timeout.
+    pub(crate) fn is_idle(&self, now: Instant) -> bool {
+        let inner = self.lock_inner();
+        inner.keys.is_some()
+            && inner
+                .idle_timeout
+                .is_some_and(|timeout| now.duration_since(inner.last_activity) >= timeout)
+    }
+
+    fn persist(&self, stored: &StoredKeys) -> Result<()> {
+        write_json(&self.path, stored)
+    }
+}
+
+/// The JSON file at `path`, if it exists and is valid.
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path, what: &str) -> Option<T> {
+    let raw = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&raw)
+        .map_err(|e| log::warn!("ignoring invalid {what}: {e}"))
+        .ok()
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let json = serde_json::to_vec_pretty(value).map_err(std::io::Error::from)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    atomic::write_atomic(path, &json)?;
+    Ok(())
+}
+
+/// Lock the session and vault, drop decrypted search data and notify the webview.
+pub fn lock_app(app: &AppHandle, reason: LockReason) {
+    let session = app.state::<Session>();
+    if !session.lock() {
+        return;
+    }
+    log::info!("session locked ({reason:?})");
+
+    let store = app.state::<Vaults>().store();
+    store.lock_vault();
+    if store.vault_status().enabled {
+        app.state::<SearchEngine>().clear();
+    }
+    notify(app, &session);
+}
+
+/// Emit the current [`SessionStatus`] as the [`SESSION_EVENT`] event.
+pub fn notify(app: &AppHandle, session: &Session) {
+    if let Err(error) = app.emit(SESSION_EVENT, session.status()) {
+        log::warn!("failed to emit session status: {error}");
+    }
+}
+
+/// Start the thread that enforces the idle timeout and locks after suspend.
+///
+/// Suspend is detected by comparing the wall clock with the monotonic clock,
+/// which does not advance while the machine sleeps on most platforms.
+pub fn spawn_monitor(app: AppHandle) {
+    thread::spawn(move || {
+        let mut last_tick = (Instant::now(), SystemTime::now());
+        loop {
+            thread::sleep(MONITOR_INTERVAL);
+            let now = (Instant::now(), SystemTime::now());
+            let monotonic = now.0.duration_since(last_tick.0);
+            let wall = now.1.duration_since(last_tick.1).unwrap_or_default();
+            last_tick = now;
+
+            if wall.saturating_sub(monotonic) >= SUSPEND_GAP {
+                lock_app(&app, LockReason::Suspend);
+            } else if app.state::<Session>().is_idle(now.0) {
+                lock_app(&app, LockReason::Idle);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> (tempfile::TempDir, Session) {
+        let dir = tempfile::tempdir().unwrap();
+        let session = Session::load(dir.path().join(KEYS_FILE_NAME));
+        (dir, session)
+    }
+
+    #[test]
+    fn walks_through_no_keys_locked_and_unlocked() {
+        let (dir, session) = session();
+        assert_eq!(session.status().state, SessionState::NoKeys);
+
+        let stored = session.create_keys("user-1", "pw").unwrap();
+        assert_eq!(session.status().state, SessionState::Unlocked);
+        let public = session.with_keys(|pair| Ok(pair.public_b64())).unwrap();
+        assert_eq!(public, stored.public_key_b64);
+
+        assert!(session.lock());
+        assert!(matches!(session.with_keys(|_| Ok(())), Err(Error::Locked)));
+
+        // A restarted app finds the keys and starts locked.
+        let reloaded = Session::load(dir.path().join(KEYS_FILE_NAME));
+        let status = reloaded.status();
+        assert_eq!(status.state, SessionState::Locked);
+        assert_eq!(status.user_id.as_deref(), Some("user-1"));
+        assert!(reloaded.unlock("wrong").is_err());
+        reloaded.unlock("pw").unwrap();
+        assert_eq!(reloaded.status().state, SessionState::Unlocked);
+    }
+
+    #[test]
+    fn idle_timeout_applies_only_while_unlocked() {
+        let (_dir, session) = session();
+        let minute = Some(Duration::from_secs(60));
+        session.create_keys("user-1", "pw").unwrap();
+        session.set_idle_timeout(minute).unwrap();
+
+        let later = Instant::now() + Duration::from_secs(61);
+        assert!(session.is_idle(later));
+        session.set_idle_timeout(None).unwrap();
+        assert!(!session.is_idle(later));
+
+        session.set_idle_timeout(minute).unwrap();
+        session.lock();
+        assert!(!session.is_idle(later));
+    }
+
+    #[test]
+    fn idle_timeout_survives_restarts_and_logouts() {
+        let (dir, session) = session();
+        assert_eq!(session.status().idle_timeout_secs, Some(15 * 60));
+        session.create_keys("user-1", "pw").unwrap();
+        let five_minutes = Some(Duration::from_secs(5 * 60));
+        session.set_idle_timeout(five_minutes).unwrap();
+
+        let reloaded = Session::load(dir.path().join(KEYS_FILE_NAME));
+        assert_eq!(reloaded.status().idle_timeout_secs, Some(5 * 60));
+        reloaded.set_idle_timeout(None).unwrap();
+        reloaded.forget().unwrap();
+
+        let reloaded = Session::load(dir.path().join(KEYS_FILE_NAME));
+        assert_eq!(reloaded.status().state, SessionState::NoKeys);
+        assert_eq!(reloaded.status().idle_timeout_secs, None);
+    }
+
+    #[test]
+    fn forget_drops_an_unconfirmed_recovery_phrase() {
+        let (_dir, session) = session();
+        session.create_keys("user-1", "pw").unwrap();
+        let export = session.export_recovery().unwrap();
+        session.forget().unwrap();
+
+        session.create_keys("user-2", "pw").unwrap();
+        let words: Vec<String> = export.phrase.split(' ').map(str::to_string).collect();
+        let answers: Vec<String> = export
+            .challenge
+            .iter()
+            .map(|&position| words[position - 1].clone())
+            .collect();
+        assert!(session.confirm_recovery(&answers).is_err());
+        assert!(!session.status().has_recovery_phrase);
+    }
+
+    #[test]
+    fn change_password_reseals_the_stored_key() {
+        let (_dir, session) = session();
+        session.create_keys("user-1", "old").unwrap();
+        session.change_password("old", "new").unwrap();
+        session.lock();
+
+        assert!(session.unlock("old").is_err());
+        session.unlock("new").unwrap();
+    }
+
+    #[test]
+    fn confirmed_recovery_phrase_restores_offline() {
+        let (_dir, session) = session();
+        let stored = session.create_keys("user-1", "forgotten").unwrap();
+        let export = session.export_recovery().unwrap();
+        let words: Vec<&str> = export.phrase.split(' ').collect();
+
+        let wrong = vec!["zoo".to_string(); export.challenge.len()];
+        assert!(session.confirm_recovery(&wrong).is_err());
+        let answers: Vec<String> = export
+            .challenge
+            .iter()
+            .map(|&position| words[position - 1].to_uppercase())
+            .collect();
+        session.confirm_recovery(&answers).unwrap();
+        assert!(session.status().has_recovery_phrase);
+
+        session.lock();
+        let pair = session
+            .restore_from_phrase(&export.phrase, "fresh", None)
+            .unwrap();
+        assert_eq!(pair.public_b64(), stored.public_key_b64);
+        session.lock();
+        session.unlock("fresh").unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+//! Crash-safe file writes.
+//!
+//! Content is written to a temp file in the destination directory, fsynced,
+//! renamed over the target and the directory is fsynced. A crash at any point
+//! leaves either the old file or the new one, never a truncated mix.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Prefix of temp files, so scanners and the watcher can skip them.
+pub const TEMP_PREFIX: &str = ".diaryx-tmp-";
+
+/// Age after which a temp file is taken to belong to a write that died. Any
+/// write still in flight is far younger.
+const STALE_TEMP_AGE: Duration = Duration::from_secs(10 * 60);
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Atomically replace `path` with `contents`.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = parent_dir(path);
+    let temp_path = temp_path_in(dir);
+
+    let result = write_and_sync(&temp_path, contents)
+        .and_then(|()| fs::rename(&temp_path, path))
+        .and_then(|()| sync_dir(dir));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Rename `from` to `to` in a single `rename(2)`, refusing to overwrite `to`.
+///
+/// The existence check and rename are not one syscall, so callers must
+/// serialize writes to the journal folder.
+pub fn rename_no_clobber(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        ));
+    }
+    fs::rename(from, to)?;
+    let to_dir = parent_dir(to);
+    sync_dir(to_dir)?;
+    let from_dir = parent_dir(from);
+    if from_dir != to_dir {
+        sync_dir(from_dir)?;
+    }
+    Ok(())
+}
+
+/// Remove temp files in `dir` left behind by writes that never reached the
+/// rename. Recent ones are kept, as they may belong to a write in progress.
+pub fn remove_stale_temp_files(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with(TEMP_PREFIX) {
+            continue;
+        }
+        let age = entry.metadata()?.modified()?.elapsed().unwrap_or_default();
+        if age >= STALE_TEMP_AGE {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn write_and_sync(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn temp_path_in(dir: &Path) -> PathBuf {
+    let n = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    dir.join(format!("{TEMP_PREFIX}{}-{n}", std::process::id()))
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Flush directory entries so a completed rename survives power loss.
+/// Windows does not support opening directories for sync, so this is a no-op there.
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        fs::File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::FileTimes;
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[test]
+    fn interrupted_write_leaves_original_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("entry.md");
+        fs::write(&target, "original").unwrap();
+
+        // Simulate a crash after the temp file was partially written but before the rename.
+        let temp = temp_path_in(dir.path());
+        fs::write(&temp, "partial").unwrap();
+        let crashed_at = SystemTime::now() - STALE_TEMP_AGE;
+        fs::File::options()
+            .write(true)
+            .open(&temp)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(crashed_at))
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "original");
+        remove_stale_temp_files(dir.path()).unwrap();
+        assert!(!temp.exists());
+
+        write_atomic(&target, b"updated").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "updated");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn recent_temp_files_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = temp_path_in(dir.path());
+        fs::write(&temp, "in flight").unwrap();
+
+        remove_stale_temp_files(dir.path()).unwrap();
+        assert!(temp.exists());
+    }
+
+    #[test]
+    fn failed_write_cleans_up_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // Renaming a file over a directory fails, exercising the cleanup path.
+        let target = dir.path().join("occupied");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("child"), "x").unwrap();
+
+        assert!(write_atomic(&target, b"data").is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn rename_refuses_to_clobber() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        let b = dir.path().join("b.md");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+
+        let err = rename_no_clobber(&a, &b).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b");
+    }
+}
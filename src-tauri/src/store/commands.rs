@@ -0,0 +1,236 @@
+//! Tauri commands exposing [`EntryStore`] to the webview.
+
+use std::collections::BTreeMap;
+
+use tauri::State;
+
+use super::history::{RetentionPolicy, Revision};
+use super::notebooks::Notebook;
+use super::trash::TrashedEntry;
+use super::vault::VaultStatus;
+use super::{EntryStore, EntryTimestamps, JournalEntry, JournalEntryMetadata};
+use crate::error::Result;
+use crate::search::SearchEngine;
+use crate::sync::Outbox;
+use crate::vaults::Vaults;
+
+#[tauri::command]
+pub fn list_entries(vaults: State<'_, Vaults>) -> Result<Vec<JournalEntryMetadata>> {
+    vaults.store().list_entries()
+}
+
+#[tauri::command]
+pub fn get_entry(vaults: State<'_, Vaults>, id: String) -> Result<Option<JournalEntry>> {
+    vaults.store().get_entry(&id)
+}
+
+#[tauri::command]
+pub fn save_entry(
+    vaults: State<'_, Vaults>,
+    search: State<'_, SearchEngine>,
+    id: String,
+    content: String,
+) -> Result<()> {
+    let store = vaults.store();
+    store.save_entry(&id, &content)?;
+    search.refresh_entry(&store, &id)
+}
+
+/// Create an entry for `title` in `notebook`, the journal root by default.
+#[tauri::command]
+pub fn create_entry(
+    vaults: State<'_, Vaults>,
+    search: State<'_, SearchEngine>,
+    title: String,
+    notebook: Option<String>,
+) -> Result<String> {
+    let store = vaults.store();
+    let id = store.create_entry_in(notebook.as_deref().unwrap_or_default(), &title)?;
+    search.refresh_entry(&store, &id)?;
+    Ok(id)
+}
+
+/// Move `id` to the trash and return its trash record, if it existed.
+#[tauri::command]
+pub fn delete_entry(
+    vaults: State<'_, Vaults>,
+    search: State<'_, SearchEngine>,
+    id: String,
+) -> Result<Option<TrashedEntry>> {
+    let store = vaults.store();
+    let trashed = store.delete_entry(&id)?;
+    search.remove_entry(&id);
+    Ok(trashed)
+}
+
+/// Trashed entries, most recently deleted first. Expired ones are purged first.
+#[tauri::command]
+pub fn list_trash(vaults: State<'_, Vaults>) -> Result<Vec<TrashedEntry>> {
+    let (store, outbox) = vaults.store_and_outbox();
+    purge_trash(&store, &outbox)?;
+    Ok(store.list_trash())
+}
+
+/// Move a trashed entry back into the journal and return its ID.
+#[tauri::command]
+pub fn restore_entry(
+    vaults: State<'_, Vaults>,
+    search: State<'_, SearchEngine>,
+    trash_id: String,
+) -> Result<String> {
+    let store = vaults.store();
+    let id = store.restore_entry(&trash_id)?;
+    search.refresh_entry(&store, &id)?;
+    Ok(id)
+}
+
+/// Delete every trashed entry for good, unpublishing their cloud copies.
+#[tauri::command]
+pub fn empty_trash(vaults: State<'_, Vaults>) -> Result<Vec<TrashedEntry>> {
+    let (store, outbox) = vaults.store_and_outbox();
+    let purged = store.empty_trash()?;
+    outbox.unpublish_purged(&purged)?;
+    Ok(purged)
+}
+
+#[tauri::command]
+pub fn get_trash_retention(vaults: State<'_, Vaults>) -> u32 {
+    vaults.store().trash_retention_days()
+}
+
+#[tauri::command]
+pub fn set_trash_retention(vaults: State<'_, Vaults>, days: u32) -> Result<()> {
+    let (store, outbox) = vaults.store_and_outbox();
+    store.set_trash_retention_days(days)?;
+    purge_trash(&store, &outbox)
+}
+
+/// Delete trashed entries past the retention period, unpublishing their cloud copies.
+pub fn purge_trash(store: &EntryStore, outbox: &Outbox) -> Result<()> {
+    let purged = store.purge_trash()?;
+    if !purged.is_empty() {
+        log::info!("purged {} entries from the trash", purged.len());
+    }
+    outbox.unpublish_purged(&purged)
+}
+
+/// Rename the file of `old_id` after `new_title`. The returned ID is unchanged.
+#[tauri::command]
+pub fn rename_entry(
+    vaults: State<'_, Vaults>,
+    search: State<'_, SearchEngine>,
+    old_id: String,
+    new_title: String,
+) -> Result<String> {
+    let store = vaults.store();
+    let id = store.rename_entry(&old_id, &new_title)?;
+    search.refresh_entry(&store, &id)?;
+    Ok(id)
+}
+
+/// Create a notebook folder and return its normalized path.
+#[tauri::command]
+pub fn create_notebook(vaults: State<'_, Vaults>, path: String) -> Result<String> {
+    vaults.store().create_notebook(&path)
+}
+
+/// Notebooks and entries directly inside the notebook `path`; empty for the journal root.
+#[tauri::command]
+pub fn list_notebook(vaults: State<'_, Vaults>, path: String) -> Result<Notebook> {
+    vaults.store().list_notebook(&path)
+}
+
+/// Move `id` into the notebook `notebook` and return its new path. The ID is unchanged.
+#[tauri::command]
+pub fn move_entry(
+    vaults: State<'_, Vaults>,
+    search: State<'_, SearchEngine>,
+    id: String,
+    notebook: String,
+) -> Result<String> {
+    let store = vaults.store();
+    let file_path = store.move_entry(&id, &notebook)?;
+    search.refresh_entry(&store, &id)?;
+    Ok(file_path)
+}
+
+/// New stable IDs of entries that were known by their filename, by old ID,
+/// so the frontend can migrate what it keeps per entry.
+#[tauri::command]
+pub fn legacy_entry_ids(vaults: State<'_, Vaults>) -> BTreeMap<String, String> {
+    vaults.store().ids().legacy()
+}
+
+#[tauri::command]
+pub fn get_entry_timestamps(vaults: State<'_, Vaults>, id: String) -> Result<EntryTimestamps> {
+    vaults.store().get_entry_timestamps(&id)
+}
+
+/// Recorded revisions of `id`, newest first.
+#[tauri::command]
+pub fn list_revisions(vaults: State<'_, Vaults>, id: String) -> Vec<Revision> {
+    vaults.store().history().list(&id)
+}
+
+/// Unified diff of `id` from revision `from` to `to`, or to the current content.
+#[tauri::command]
+pub fn diff_revisions(
+    vaults: State<'_, Vaults>,
+    id: String,
+    from: u64,
+    to: Option<u64>,
+) -> Result<String> {
+    vaults.store().diff_revisions(&id, from, to)
+}
+
+/// Make revision `revision` the content of `id` and return that content.
+#[tauri::command]
+pub fn restore_revision(
+    vaults: State<'_, Vaults>,
+    search: State<'_, SearchEngine>,
+    id: String,
+    revision: u64,
+) -> Result<String> {
+    let store = vaults.store();
+    let content = store.restore_revision(&id, revision)?;
+    search.refresh_entry(&store, &id)?;
+    Ok(content)
+}
+
+#[tauri::command]
+pub fn get_revision_retention(vaults: State<'_, Vaults>) -> RetentionPolicy {
+    vaults.store().history().retention()
+}
+
+#[tauri::command]
+pub fn set_revision_retention(vaults: State<'_, Vaults>, retention: RetentionPolicy) -> Result<()> {
+    vaults.store().history().set_retention(retention)
+}
+
+#[tauri::command]
+pub async fn scan_journal(vaults: State<'_, Vaults>) -> Result<Vec<JournalEntryMetadata>> {
+    super::scanner::scan_journal(vaults.store(), vaults.index()).await
+}
+
+#[tauri::command]
+pub fn vault_status(vaults: State<'_, Vaults>) -> VaultStatus {
+    vaults.store().vault_status()
+}
+
+/// Encrypt the content of every entry in place, leaving file names as they
+/// are. Returns the number of files converted.
+#[tauri::command]
+pub async fn enable_vault(vaults: State<'_, Vaults>) -> Result<usize> {
+    let store = vaults.store();
+    let index = vaults.index();
+    let converted = tokio::task::spawn_blocking(move || store.enable_vault()).await??;
+    index.discard()?;
+    Ok(converted)
+}
+
+/// Decrypt the journal back to plaintext. Returns the number of files converted.
+#[tauri::command]
+pub async fn disable_vault(vaults: State<'_, Vaults>) -> Result<usize> {
+    let store = vaults.store();
+    tokio::task::spawn_blocking(move || store.disable_vault()).await?
+}
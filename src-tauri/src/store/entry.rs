@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
+
+/// File extension used for journal entries on disk.
+pub const FILE_EXTENSION: &str = "md";
+
+/// Default length of an entry preview, mirroring `PreviewService`.
+pub const DEFAULT_PREVIEW_LENGTH: usize = 150;
+
+/// A full journal entry. Matches the `JournalEntry` interface in `storage/types.ts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: String,
+    pub title: String,
+    pub content: String,
+    pub created_at: String,
+    pub modified_at: String,
+    pub file_path: String,
+}
+
+/// Lightweight entry metadata used by lists and cards.
+/// Matches the `JournalEntryMetadata` interface in `storage/types.ts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntryMetadata {
+    pub id: String,
+    pub title: String,
+    pub created_at: String,
+    pub modified_at: String,
+    pub file_path: String,
+    pub preview: String,
+    #[serde(rename = "isPublished", skip_serializing_if = "Option::is_none")]
+    pub is_published: Option<bool>,
+    #[serde(rename = "isShared", skip_serializing_if = "Option::is_none")]
+    pub is_shared: Option<bool>,
+    #[serde(rename = "cloudId", skip_serializing_if = "Option::is_none")]
+    pub cloud_id: Option<String>,
+}
+
+impl JournalEntryMetadata {
+    /// Build metadata for an entry, leaving cloud fields to the frontend.
+    pub fn from_entry(entry: &JournalEntry) -> Self {
+        Self {
+            id: entry.id.clone(),
+            title: entry.title.clone(),
+            created_at: entry.created_at.clone(),
+            modified_at: entry.modified_at.clone(),
+            file_path: entry.file_path.clone(),
+            preview: create_preview(&entry.content, DEFAULT_PREVIEW_LENGTH),
+            is_published: None,
+            is_shared: None,
+            cloud_id: None,
+        }
+    }
+}
+
+/// Create a display title from a filename ID. Port of `createTitleFromId`.
+pub fn create_title_from_id(id: &str) -> String {
+    let spaced = id.replace(['-', '_'], " ");
+    let mut title = String::with_capacity(spaced.len());
+    let mut at_word_start = true;
+    for c in spaced.chars() {
+        if at_word_start && c.is_alphanumeric() {
+            title.extend(c.to_uppercase());
+        } else {
+            title.push(c);
+        }
+        at_word_start = !(c.is_alphanumeric() || c == '_' || is_combining_mark(c));
+    }
+    title
+}
+
+/// Create a text preview from entry content. Port of `PreviewService.createPreview`.
+pub fn create_preview(content: &str, max_length: usize) -> String {
+    let cleaned: String = content
+        .chars()
+        .filter(|c| !matches!(c, '#' | '*' | '_' | '`'))
+        .collect();
+    let cleaned = cleaned.trim();
+
+    let mut preview: String = cleaned.chars().take(max_length).collect();
+    if cleaned.chars().count() > max_length {
+        preview.push_str("...");
+    }
+    preview
+}
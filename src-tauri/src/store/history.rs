@@ -0,0 +1,606 @@
+//! Revision history of entries.
+//!
+//! Every save through the [`EntryStore`] records the new content as a
+//! revision. Each distinct content is stored once, named by its SHA-256,
+//! sealed through the journal's [`Vault`] and deflate-compressed, so history
+//! is encrypted whenever entries are. Objects and the revision index live in
+//! `.diaryx-history/` inside the journal and are thinned out by a
+//! [`RetentionPolicy`].
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Duration, Utc};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::vault::Vault;
+use super::{atomic, EntryStore};
+use crate::error::{Error, Result};
+use crate::sync::merge::line_matches;
+
+/// Directory inside the journal root holding the history.
+pub const HISTORY_DIR: &str = ".diaryx-history";
+const INDEX_FILE: &str = "index.json";
+const OBJECTS_DIR: &str = "objects";
+const INDEX_VERSION: u32 = 1;
+/// Unchanged lines shown around each hunk of a [`unified_diff`].
+const DIFF_CONTEXT: usize = 3;
+
+/// One recorded version of an entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision {
+    /// Increasing per entry, starting at 1.
+    pub id: u64,
+    /// SHA-256 of the content, naming its object.
+    pub hash: String,
+    pub saved_at: DateTime<Utc>,
+    /// Length of the content in bytes.
+    pub size: usize,
+}
+
+/// Which revisions survive pruning. The newest revision of an entry is always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// Every revision younger than this is kept.
+    pub keep_all_minutes: u32,
+    /// Beyond that, the last revision of each hour up to this age.
+    pub keep_hourly_hours: u32,
+    /// Beyond that, the last revision of each day up to this age.
+    pub keep_daily_days: u32,
+    /// At most this many revisions per entry; the oldest go first.
+    pub max_revisions: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_all_minutes: 60,
+            keep_hourly_hours: 48,
+            keep_daily_days: 90,
+            max_revisions: 200,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// For each of `revisions`, oldest first, whether to keep it at `now`.
+    fn retain(&self, revisions: &[Revision], now: DateTime<Utc>) -> Vec<bool> {
+        let mut keep = vec![false; revisions.len()];
+        let mut buckets = HashSet::new();
+        let mut kept = 0;
+        for (i, revision) in revisions.iter().enumerate().rev() {
+            if kept >= self.max_revisions.max(1) {
+                break;
+            }
+            let age = now - revision.saved_at;
+            let stamp = revision.saved_at.timestamp();
+            let bucket = if i + 1 == revisions.len()
+                || age < Duration::minutes(self.keep_all_minutes.into())
+            {
+                None
+            } else if age < Duration::hours(self.keep_hourly_hours.into()) {
+                Some(('h', stamp.div_euclid(3600)))
+            } else if age < Duration::days(self.keep_daily_days.into()) {
+                Some(('d', stamp.div_euclid(86_400)))
+            } else {
+                continue;
+            };
+            // Newest first, so the first revision seen in a bucket is its last.
+            if bucket.is_none_or(|bucket| buckets.insert(bucket)) {
+                keep[i] = true;
+                kept += 1;
+            }
+        }
+        keep
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryIndex {
+    version: u32,
+    #[serde(default)]
+    retention: RetentionPolicy,
+    /// Revisions per entry ID, oldest first.
+    entries: BTreeMap<String, Vec<Revision>>,
+}
+
+/// Revisions of every entry of one journal, shared by every clone of its [`EntryStore`].
+#[derive(Debug)]
+pub struct History {
+    dir: PathBuf,
+    index: Mutex<HistoryIndex>,
+}
+
+impl History {
+    /// Load the history of the journal at `root`, if any.
+    pub fn load(root: &Path) -> Self {
+        let dir = root.join(HISTORY_DIR);
+        let index = match fs::read_to_string(dir.join(INDEX_FILE)) {
+            Ok(raw) => serde_json::from_str::<HistoryIndex>(&raw)
+                .ok()
+                .filter(|index| index.version == INDEX_VERSION)
+                .unwrap_or_else(|| {
+                    log::warn!("ignoring invalid revision index in {}", dir.display());
+                    HistoryIndex::default()
+                }),
+            Err(_) => HistoryIndex::default(),
+        };
+        Self {
+            dir,
+            index: Mutex::new(index),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HistoryIndex> {
+        self.index.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn retention(&self) -> RetentionPolicy {
+        self.lock().retention
+    }
+
+    /// Switch to `retention` and prune every entry accordingly.
+    pub fn set_retention(&self, retention: RetentionPolicy) -> Result<()> {
+        let mut index = self.lock();
+        index.retention = retention;
+        self.prune(&mut index, Utc::now())
+    }
+
+    /// Whether any revision of `entry_id` has been recorded.
+    pub fn contains(&self, entry_id: &str) -> bool {
+        self.lock().entries.contains_key(entry_id)
+    }
+
+    /// Revisions of `entry_id`, newest first.
+    pub fn list(&self, entry_id: &str) -> Vec<Revision> {
+        self.lock()
+            .entries
+            .get(entry_id)
+            .map(|revisions| revisions.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Record `content`, saved at `saved_at`, as the newest revision of
+    /// `entry_id`, unless it already is.
+    pub fn record(
+        &self,
+        vault: &Vault,
+        entry_id: &str,
+        content: &str,
+        saved_at: DateTime<Utc>,
+    ) -> Result<Option<Revision>> {
+        let hash = content_hash(content);
+        let mut index = self.lock();
+        let last = index
+            .entries
+            .get(entry_id)
+            .and_then(|revisions| revisions.last());
+        if last.is_some_and(|last| last.hash == hash) {
+            return Ok(None);
+        }
+        let id = last.map_or(1, |last| last.id + 1);
+
+        let path = self.object_path(&hash);
+        if !path.is_file() {
+            fs::create_dir_all(self.dir.join(OBJECTS_DIR))?;
+            atomic::write_atomic(&path, &compress(&vault.seal(content)?)?)?;
+        }
+        let revision = Revision {
+            id,
+            hash,
+            saved_at,
+            size: content.len(),
+        };
+        index
+            .entries
+            .entry(entry_id.to_string())
+            .or_default()
+            .push(revision.clone());
+        self.prune(&mut index, Utc::now())?;
+        Ok(Some(revision))
+    }
+
+    /// Content of revision `revision_id` of `entry_id`.
+    pub fn content(&self, vault: &Vault, entry_id: &str, revision_id: u64) -> Result<String> {
+        let revision = self
+            .revision(entry_id, revision_id)
+            .ok_or_else(|| Error::NotFound(format!("{entry_id} revision {revision_id}")))?;
+        vault.open(decompress(&fs::read(self.object_path(&revision.hash))?)?)
+    }
+
+    fn revision(&self, entry_id: &str, revision_id: u64) -> Option<Revision> {
+        self.lock()
+            .entries
+            .get(entry_id)?
+            .iter()
+            .find(|revision| revision.id == revision_id)
+            .cloned()
+    }
+
+    /// Move the revisions of each old ID in `renames` to its new ID, replacing
+    /// any the new ID had.
+    pub fn rename_entries(&self, renames: &BTreeMap<String, String>) -> Result<()> {
+        let mut index = self.lock();
+        let moved: Vec<_> = renames
+            .iter()
+            .filter_map(|(old_id, new_id)| Some((new_id.clone(), index.entries.remove(old_id)?)))
+            .collect();
+        if moved.is_empty() {
+            return Ok(());
+        }
+        let mut dropped = Vec::new();
+        for (new_id, revisions) in moved {
+            let replaced = index.entries.insert(new_id, revisions);
+            dropped.extend(replaced.into_iter().flatten().map(|r| r.hash));
+        }
+        self.save(&index)?;
+        self.collect_garbage(&index, dropped)
+    }
+
+    /// Forget every revision of `entry_id`, e.g. once it is deleted for good.
+    pub fn remove(&self, entry_id: &str) -> Result<()> {
+        let mut index = self.lock();
+        let Some(revisions) = index.entries.remove(entry_id) else {
+            return Ok(());
+        };
+        self.save(&index)?;
+        let dropped = revisions.into_iter().map(|r| r.hash).collect();
+        self.collect_garbage(&index, dropped)
+    }
+
+    /// Apply the retention policy to every entry and delete unused objects.
+    fn prune(&self, index: &mut HistoryIndex, now: DateTime<Utc>) -> Result<()> {
+        let retention = index.retention;
+        let mut dropped = Vec::new();
+        for revisions in index.entries.values_mut() {
+            let mut keep = retention.retain(revisions, now).into_iter();
+            revisions.retain(|revision| {
+                let kept = keep.next().unwrap_or(true);
+                if !kept {
+                    dropped.push(revision.hash.clone());
+                }
+                kept
+            });
+        }
+        self.save(index)?;
+        self.collect_garbage(index, dropped)
+    }
+
+    /// Delete the objects of `dropped` hashes no revision refers to any more.
+    fn collect_garbage(&self, index: &HistoryIndex, mut dropped: Vec<String>) -> Result<()> {
+        let used: HashSet<&str> = index
+            .entries
+            .values()
+            .flatten()
+            .map(|revision| revision.hash.as_str())
+            .collect();
+        dropped.retain(|hash| !used.contains(hash.as_str()));
+        dropped.sort();
+        dropped.dedup();
+        for hash in dropped {
+            match fs::remove_file(self.object_path(&hash)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self, index: &HistoryIndex) -> Result<()> {
+        let json = serde_json::to_vec_pretty(index).map_err(std::io::Error::from)?;
+        fs::create_dir_all(&self.dir)?;
+        atomic::write_atomic(&self.dir.join(INDEX_FILE), &json)?;
+        Ok(())
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(OBJECTS_DIR).join(hash)
+    }
+
+    /// Rewrite each object for which `convert` returns new bytes, as entries
+    /// are rewritten when the vault is enabled, disabled or rekeyed.
+    pub(super) fn convert_objects(
+        &self,
+        convert: impl Fn(Vec<u8>) -> Result<Option<Vec<u8>>>,
+    ) -> Result<usize> {
+        let _index = self.lock();
+        let dir = self.dir.join(OBJECTS_DIR);
+        if !dir.is_dir() {
+            return Ok(0);
+        }
+        atomic::remove_stale_temp_files(&dir)?;
+
+        let mut paths = Vec::new();
+        for dir_entry in fs::read_dir(&dir)? {
+            paths.push(dir_entry?.path());
+        }
+        let mut converted = 0;
+        for path in paths {
+            if let Some(bytes) = convert(decompress(&fs::read(&path)?)?)? {
+                atomic::write_atomic(&path, &compress(&bytes)?)?;
+                converted += 1;
+            }
+        }
+        Ok(converted)
+    }
+}
+
+impl EntryStore {
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Unified diff of `entry_id` from revision `from` to revision `to`, or
+    /// to the current content when `to` is `None`.
+    pub fn diff_revisions(&self, entry_id: &str, from: u64, to: Option<u64>) -> Result<String> {
+        let old = self.history.content(&self.vault, entry_id, from)?;
+        let (new, new_label) = match to {
+            Some(to) => (
+                self.history.content(&self.vault, entry_id, to)?,
+                self.revision_label(entry_id, to),
+            ),
+            None => {
+                // A trashed entry has no current content.
+                let content = match self.stem_of(entry_id)? {
+                    Some(stem) => self.read_content(&self.stem_path(&stem))?,
+                    None => None,
+                };
+                (content.unwrap_or_default(), format!("{entry_id} (current)"))
+            }
+        };
+        let old_label = self.revision_label(entry_id, from);
+        Ok(unified_diff(&old, &new, &old_label, &new_label))
+    }
+
+    fn revision_label(&self, entry_id: &str, revision_id: u64) -> String {
+        match self.history.revision(entry_id, revision_id) {
+            Some(revision) => format!(
+                "{entry_id} @{revision_id}\t{}",
+                revision.saved_at.to_rfc3339()
+            ),
+            None => format!("{entry_id} @{revision_id}"),
+        }
+    }
+
+    /// Save revision `revision_id` as the content of `entry_id` and return it.
+    ///
+    /// The content being replaced stays in the history, so a restore can itself be undone.
+    pub fn restore_revision(&self, entry_id: &str, revision_id: u64) -> Result<String> {
+        let content = self.history.content(&self.vault, entry_id, revision_id)?;
+        self.save_entry(entry_id, &content)?;
+        Ok(content)
+    }
+}
+
+/// Unified diff from `old` to `new` with [`DIFF_CONTEXT`] lines of context.
+///
+/// Empty when both are equal.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = diff_ops(&old_lines, &new_lines);
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| op.tag != Tag::Equal)
+        .map(|(k, _)| k)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- {old_label}\n+++ {new_label}\n");
+    let mut k = 0;
+    while k < changes.len() {
+        // Changes closer than twice the context share a hunk.
+        let start = changes[k].saturating_sub(DIFF_CONTEXT);
+        let mut last = changes[k];
+        k += 1;
+        while k < changes.len() && changes[k] - last <= 2 * DIFF_CONTEXT + 1 {
+            last = changes[k];
+            k += 1;
+        }
+        let hunk = &ops[start..(last + DIFF_CONTEXT + 1).min(ops.len())];
+
+        let old_count = hunk.iter().filter(|op| op.tag != Tag::Insert).count();
+        let new_count = hunk.iter().filter(|op| op.tag != Tag::Delete).count();
+        // An empty side is numbered by the line before it.
+        let old_start = hunk[0].old + usize::from(old_count > 0);
+        let new_start = hunk[0].new + usize::from(new_count > 0);
+        let _ = writeln!(
+            out,
+            "@@ -{old_start},{old_count} +{new_start},{new_count} @@"
+        );
+        for op in hunk {
+            let (prefix, line) = match op.tag {
+                Tag::Equal => (' ', old_lines[op.old]),
+                Tag::Delete => ('-', old_lines[op.old]),
+                Tag::Insert => ('+', new_lines[op.new]),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// One line of an edit script, with the positions in `old` and `new` before it.
+#[derive(Debug, Clone, Copy)]
+struct Op {
+    tag: Tag,
+    old: usize,
+    new: usize,
+}
+
+/// Edit script from `old` to `new`, deletions before insertions.
+fn diff_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let matches = line_matches(old, new);
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        let tag = match matches.get(i) {
+            Some(Some(k)) if *k == j => Tag::Equal,
+            Some(None) => Tag::Delete,
+            _ => Tag::Insert,
+        };
+        ops.push(Op {
+            tag,
+            old: i,
+            new: j,
+        });
+        match tag {
+            Tag::Equal => (i, j) = (i + 1, j + 1),
+            Tag::Delete => i += 1,
+            Tag::Insert => j += 1,
+        }
+    }
+    ops
+}
+
+fn content_hash(content: &str) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in Sha256::digest(content.as_bytes()) {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+fn compress(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    DeflateDecoder::new(bytes).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::JOURNAL_FOLDER;
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, EntryStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join(JOURNAL_FOLDER));
+        (dir, store)
+    }
+
+    #[test]
+    fn records_saves_and_restores_revisions() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Day").unwrap();
+        store.save_entry(&id, "one\ntwo\nthree\n").unwrap();
+        store.save_entry(&id, "one\ntwo\nthree\n").unwrap();
+        store.save_entry(&id, "one\n2\nthree\n").unwrap();
+
+        // The empty file from create_entry is not worth keeping; repeats are skipped.
+        let revisions = store.history().list(&id);
+        assert_eq!(revisions.iter().map(|r| r.id).collect::<Vec<_>>(), [2, 1]);
+        let diff = store.diff_revisions(&id, 1, Some(2)).unwrap();
+        assert_eq!(
+            diff.lines().skip(2).collect::<Vec<_>>(),
+            ["@@ -1,3 +1,3 @@", " one", "-two", "+2", " three"]
+        );
+        assert_eq!(store.diff_revisions(&id, 2, None).unwrap(), "");
+
+        assert_eq!(store.restore_revision(&id, 1).unwrap(), "one\ntwo\nthree\n");
+        assert_eq!(
+            store.get_entry(&id).unwrap().unwrap().content,
+            "one\ntwo\nthree\n"
+        );
+        assert_eq!(store.history().list(&id)[0].id, 3);
+
+        // History is kept across renames and survives reopening the journal.
+        let renamed = store.rename_entry(&id, "Night").unwrap();
+        assert_eq!(renamed, id);
+        let reopened = EntryStore::new(store.root());
+        assert_eq!(reopened.history().list(&renamed).len(), 3);
+        assert_eq!(
+            reopened
+                .history()
+                .content(&reopened.vault, &renamed, 2)
+                .unwrap(),
+            "one\n2\nthree\n"
+        );
+        assert!(matches!(
+            reopened.restore_revision(&renamed, 9),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn prunes_revisions_by_age() {
+        let (_dir, store) = store();
+        let history = store.history();
+        let now = Utc::now();
+        let start_of = |ago: Duration, unit: i64| {
+            let at = now - ago;
+            at - Duration::seconds(at.timestamp().rem_euclid(unit))
+        };
+        let day = start_of(Duration::days(5), 86_400);
+        let hour = start_of(Duration::hours(3), 3600);
+        let times = [
+            now - Duration::days(100), // past the daily window
+            day + Duration::hours(1),  // same day as the next one: dropped
+            day + Duration::hours(20),
+            hour + Duration::minutes(5), // same hour as the next one: dropped
+            hour + Duration::minutes(50),
+            now - Duration::minutes(20),
+            now - Duration::minutes(10),
+        ];
+        for (k, at) in times.into_iter().enumerate() {
+            history
+                .record(&store.vault, "day", &format!("v{k}"), at)
+                .unwrap();
+        }
+        let kept: Vec<u64> = history.list("day").iter().map(|r| r.id).collect();
+        assert_eq!(kept, [7, 6, 5, 3]);
+
+        let objects = fs::read_dir(store.root().join(HISTORY_DIR).join(OBJECTS_DIR));
+        assert_eq!(objects.unwrap().count(), 4);
+
+        history
+            .set_retention(RetentionPolicy {
+                max_revisions: 1,
+                ..RetentionPolicy::default()
+            })
+            .unwrap();
+        assert_eq!(history.list("day")[0].id, 7);
+        assert_eq!(history.list("day").len(), 1);
+    }
+
+    #[test]
+    fn diff_marks_missing_final_newline() {
+        let diff = unified_diff("a\nb", "a\nb\nc\n", "old", "new");
+        assert_eq!(
+            diff,
+            "--- old\n+++ new\n@@ -1,2 +1,3 @@\n a\n-b\n\\ No newline at end of file\n+b\n+c\n"
+        );
+        assert_eq!(unified_diff("same\n", "same\n", "old", "new"), "");
+    }
+}
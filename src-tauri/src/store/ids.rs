@@ -0,0 +1,314 @@
+//! Stable entry IDs.
+//!
+//! Entries are addressed by a random UUID that survives renames. The
+//! registry in `.diaryx-ids.json` in the journal root maps each ID to the
+//! file stem it currently names, so entry content is left untouched. Files
+//! that appear without an ID, created outside the app, get one the first
+//! time they are seen.
+//!
+//! Journals from before stable IDs used the file stem as ID.
+//! [`EntryStore::migrate_ids`] assigns every existing entry an ID, moves
+//! everything keyed by the old ones and keeps a legacy map from old to new
+//! IDs for the frontend to migrate its own state with.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crypto_box::aead::rand_core::RngCore;
+use crypto_box::aead::OsRng;
+use serde::{Deserialize, Serialize};
+
+use super::{atomic, EntryStore};
+use crate::error::Result;
+
+/// Registry file in the journal root.
+pub const IDS_FILE: &str = ".diaryx-ids.json";
+const IDS_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IdsFile {
+    version: u32,
+    /// File stem of every entry by ID.
+    entries: BTreeMap<String, String>,
+    /// IDs assigned to entries known by their file stem before, by stem.
+    #[serde(default)]
+    legacy: BTreeMap<String, String>,
+    /// Whether everything keyed by legacy IDs has been moved over.
+    migrated: bool,
+}
+
+#[derive(Debug)]
+struct Registry {
+    file: IdsFile,
+    /// Reverse of `file.entries`.
+    by_stem: HashMap<String, String>,
+}
+
+impl Registry {
+    fn new(file: IdsFile) -> Self {
+        let by_stem = file
+            .entries
+            .iter()
+            .map(|(id, stem)| (stem.clone(), id.clone()))
+            .collect();
+        Self { file, by_stem }
+    }
+
+    fn set(&mut self, id: &str, stem: &str) {
+        if let Some(old_stem) = self.file.entries.insert(id.to_string(), stem.to_string()) {
+            self.by_stem.remove(&old_stem);
+        }
+        // A stem names one entry; whichever ID held it before is forgotten.
+        if let Some(previous) = self.by_stem.insert(stem.to_string(), id.to_string()) {
+            if previous != id {
+                self.file.entries.remove(&previous);
+            }
+        }
+    }
+
+    fn ensure(&mut self, stem: &str) -> (String, bool) {
+        match self.by_stem.get(stem) {
+            Some(id) => (id.clone(), false),
+            None => {
+                let id = new_id();
+                self.set(&id, stem);
+                (id, true)
+            }
+        }
+    }
+}
+
+/// ID registry of one journal, shared by every clone of its [`EntryStore`].
+#[derive(Debug)]
+pub struct EntryIds {
+    path: PathBuf,
+    registry: Mutex<Registry>,
+}
+
+impl EntryIds {
+    /// Load the registry of the journal at `root`.
+    ///
+    /// Without one, the journal still needs [`EntryStore::migrate_ids`].
+    pub fn load(root: &Path) -> Self {
+        let path = root.join(IDS_FILE);
+        let file = match fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str::<IdsFile>(&raw)
+                .ok()
+                .filter(|file| file.version == IDS_VERSION)
+                .unwrap_or_else(|| {
+                    log::warn!("ignoring invalid entry IDs at {}", path.display());
+                    IdsFile::default()
+                }),
+            Err(_) => IdsFile::default(),
+        };
+        Self {
+            path,
+            registry: Mutex::new(Registry::new(file)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// File stem of `id`, if it names an entry.
+    pub fn stem(&self, id: &str) -> Option<String> {
+        self.lock().file.entries.get(id).cloned()
+    }
+
+    /// ID of the entry stored as `stem`, if it has one.
+    pub fn id_of(&self, stem: &str) -> Option<String> {
+        self.lock().by_stem.get(stem).cloned()
+    }
+
+    /// IDs of `stems`, assigning new ones to stems seen for the first time.
+    pub fn ensure(&self, stems: &[String]) -> Result<Vec<String>> {
+        let mut registry = self.lock();
+        let mut assigned = false;
+        let ids: Vec<String> = stems
+            .iter()
+            .map(|stem| {
+                let (id, new) = registry.ensure(stem);
+                assigned |= new;
+                id
+            })
+            .collect();
+        if assigned {
+            self.save(&mut registry)?;
+        }
+        Ok(ids)
+    }
+
+    /// Give `stem` a fresh ID, as for a newly created entry.
+    pub fn assign(&self, stem: &str) -> Result<String> {
+        let id = new_id();
+        self.set(&id, stem)?;
+        Ok(id)
+    }
+
+    /// Point `id` at `stem`, after a rename or restore.
+    pub fn set(&self, id: &str, stem: &str) -> Result<()> {
+        let mut registry = self.lock();
+        if registry
+            .file
+            .entries
+            .get(id)
+            .is_some_and(|current| current == stem)
+        {
+            return Ok(());
+        }
+        registry.set(id, stem);
+        self.save(&mut registry)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let mut registry = self.lock();
+        if let Some(stem) = registry.file.entries.remove(id) {
+            registry.by_stem.remove(&stem);
+            self.save(&mut registry)?;
+        }
+        Ok(())
+    }
+
+    /// New IDs of entries known by their file stem before stable IDs, by stem.
+    pub fn legacy(&self) -> BTreeMap<String, String> {
+        self.lock().file.legacy.clone()
+    }
+
+    /// The legacy map of a migration that has not finished, assigning IDs to
+    /// `stems` in the journal and `trashed` stems on its first run.
+    fn pending_migration(
+        &self,
+        stems: &[String],
+        trashed: &[String],
+    ) -> Result<Option<BTreeMap<String, String>>> {
+        let mut registry = self.lock();
+        if registry.file.migrated {
+            return Ok(None);
+        }
+        if registry.file.legacy.is_empty() {
+            for stem in stems {
+                let (id, _) = registry.ensure(stem);
+                registry.file.legacy.insert(stem.clone(), id);
+            }
+            for stem in trashed {
+                registry
+                    .file
+                    .legacy
+                    .entry(stem.clone())
+                    .or_insert_with(new_id);
+            }
+            self.save(&mut registry)?;
+        }
+        Ok(Some(registry.file.legacy.clone()))
+    }
+
+    fn finish_migration(&self) -> Result<()> {
+        let mut registry = self.lock();
+        registry.file.migrated = true;
+        self.save(&mut registry)
+    }
+
+    fn save(&self, registry: &mut Registry) -> Result<()> {
+        registry.file.version = IDS_VERSION;
+        let json = serde_json::to_vec_pretty(&registry.file).map_err(std::io::Error::from)?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write_atomic(&self.path, &json)?;
+        Ok(())
+    }
+}
+
+impl EntryStore {
+    /// Give entries from before stable IDs their IDs and move what is keyed
+    /// by the old, filename-based IDs: revision history, the trash and,
+    /// through `rekey`, state kept outside the journal.
+    ///
+    /// Does nothing once done; an interrupted migration resumes with the
+    /// same IDs.
+    pub fn migrate_ids(
+        &self,
+        rekey: impl FnOnce(&BTreeMap<String, String>) -> Result<()>,
+    ) -> Result<()> {
+        let _guard = self.lock();
+        let trashed = self.legacy_trash_stems();
+        let stems = super::entry_stems(&self.root)?;
+        let Some(renames) = self.ids.pending_migration(&stems, &trashed)? else {
+            return Ok(());
+        };
+        if !renames.is_empty() {
+            log::info!("assigning stable IDs to {} entries", renames.len());
+        }
+        self.history.rename_entries(&renames)?;
+        self.rekey_trash(&renames)?;
+        rekey(&renames)?;
+        self.ids.finish_migration()
+    }
+}
+
+/// A random (version 4) UUID.
+pub(crate) fn new_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let mut id = String::with_capacity(36);
+    for (k, byte) in bytes.iter().enumerate() {
+        if matches!(k, 4 | 6 | 8 | 10) {
+            id.push('-');
+        }
+        let _ = write!(id, "{byte:02x}");
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::JOURNAL_FOLDER;
+    use super::*;
+
+    #[test]
+    fn migrates_filename_ids_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(JOURNAL_FOLDER);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("day.md"), "old").unwrap();
+
+        let store = EntryStore::new(&root);
+        let mut rekeyed = None;
+        store
+            .migrate_ids(|renames| {
+                rekeyed = Some(renames.clone());
+                Ok(())
+            })
+            .unwrap();
+        let renames = rekeyed.unwrap();
+        let id = renames["day"].clone();
+        assert_eq!(id.len(), 36);
+        assert_eq!(store.entry_ids().unwrap(), [id.clone()]);
+        assert_eq!(store.get_entry(&id).unwrap().unwrap().content, "old");
+        assert!(store.get_entry("day").unwrap().is_none());
+
+        // A reopened journal keeps its IDs and does not migrate again.
+        let reopened = EntryStore::new(&root);
+        reopened
+            .migrate_ids(|_| panic!("already migrated"))
+            .unwrap();
+        assert_eq!(reopened.ids().legacy(), renames);
+
+        // Renames keep the ID; files created elsewhere get one on sight.
+        assert_eq!(reopened.rename_entry(&id, "Night").unwrap(), id);
+        assert!(root.join("night.md").is_file());
+        fs::write(root.join("external.md"), "").unwrap();
+        let ids = reopened.entry_ids().unwrap();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(EntryStore::new(&root).entry_ids().unwrap().len(), 2);
+        assert!(ids.contains(&id));
+    }
+}
@@ -0,0 +1,217 @@
+//! Persistent on-disk metadata index.
+//!
+//! Caches every entry's metadata keyed by ID together with the file's mtime
+//! and size, so a startup scan only re-reads files that changed since the
+//! last run. The file starts with a header line carrying a format version and
+//! a CRC32 of the JSON body; a mismatch or parse failure is treated as
+//! corruption and the index is rebuilt from scratch.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use super::{atomic, JournalEntryMetadata};
+use crate::error::Result;
+
+/// Bumped whenever the serialized layout changes; older files are rebuilt.
+const INDEX_VERSION: u32 = 1;
+const HEADER_PREFIX: &str = "diaryx-index";
+pub const INDEX_FILE_NAME: &str = "metadata-index.json";
+
+/// Size and modification time of an entry file, used to detect changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub mtime_ms: i64,
+    pub size: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mtime_ms = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+        Ok(Self {
+            mtime_ms,
+            size: metadata.len(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexRecord {
+    pub stamp: FileStamp,
+    pub metadata: JournalEntryMetadata,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IndexData {
+    /// Journal root the records belong to. A different root invalidates the index.
+    pub root: PathBuf,
+    pub records: HashMap<String, IndexRecord>,
+}
+
+impl IndexData {
+    /// Return the cached metadata for `id` if its file has neither changed
+    /// nor moved to `file_path`, which renames keep the mtime of.
+    pub fn fresh(
+        &self,
+        id: &str,
+        file_path: &str,
+        stamp: &FileStamp,
+    ) -> Option<&JournalEntryMetadata> {
+        self.records
+            .get(id)
+            .filter(|record| record.stamp == *stamp && record.metadata.file_path == file_path)
+            .map(|record| &record.metadata)
+    }
+
+    pub fn insert(&mut self, stamp: FileStamp, metadata: JournalEntryMetadata) {
+        self.records
+            .insert(metadata.id.clone(), IndexRecord { stamp, metadata });
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.records.remove(id);
+    }
+
+    /// Drop records whose files no longer exist.
+    pub fn retain_ids(&mut self, ids: &[String]) {
+        let ids: HashSet<&str> = ids.iter().map(String::as_str).collect();
+        self.records.retain(|id, _| ids.contains(id.as_str()));
+    }
+}
+
+/// Metadata index persisted under the app data directory.
+#[derive(Debug)]
+pub struct MetadataIndex {
+    path: PathBuf,
+    data: Mutex<IndexData>,
+}
+
+impl MetadataIndex {
+    /// Default index location inside the platform data directory.
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(crate::APP_IDENTIFIER)
+            .join(INDEX_FILE_NAME)
+    }
+
+    /// Load the index at `path` for the journal at `root`.
+    ///
+    /// Missing, corrupt, outdated or foreign indexes yield an empty index.
+    pub fn load(path: impl Into<PathBuf>, root: &Path) -> Self {
+        let path = path.into();
+        let data = match fs::read_to_string(&path).ok().and_then(|raw| decode(&raw)) {
+            Some(data) if data.root == root => data,
+            Some(_) | None => {
+                if path.exists() {
+                    log::warn!(
+                        "metadata index at {} is invalid, rebuilding",
+                        path.display()
+                    );
+                }
+                IndexData {
+                    root: root.to_path_buf(),
+                    records: HashMap::new(),
+                }
+            }
+        };
+        Self {
+            path,
+            data: Mutex::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, IndexData> {
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Atomically persist the current index.
+    pub fn save(&self) -> Result<()> {
+        let encoded = encode(&self.lock())?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write_atomic(&self.path, encoded.as_bytes())?;
+        Ok(())
+    }
+
+    /// Delete the persisted index, keeping the in-memory records.
+    pub fn discard(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn encode(data: &IndexData) -> Result<String> {
+    let body = serde_json::to_string(data).map_err(std::io::Error::from)?;
+    let checksum = crc32fast::hash(body.as_bytes());
+    Ok(format!(
+        "{HEADER_PREFIX} v{INDEX_VERSION} {checksum:08x}\n{body}"
+    ))
+}
+
+fn decode(raw: &str) -> Option<IndexData> {
+    let (header, body) = raw.split_once('\n')?;
+    let mut parts = header.split(' ');
+    if parts.next()? != HEADER_PREFIX || parts.next()? != format!("v{INDEX_VERSION}") {
+        return None;
+    }
+    let checksum = u32::from_str_radix(parts.next()?, 16).ok()?;
+    if crc32fast::hash(body.as_bytes()) != checksum {
+        return None;
+    }
+    serde_json::from_str(body).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(id: &str) -> JournalEntryMetadata {
+        JournalEntryMetadata {
+            id: id.to_string(),
+            title: id.to_string(),
+            created_at: String::new(),
+            modified_at: String::new(),
+            file_path: format!("Diaryx/{id}.md"),
+            preview: String::new(),
+            is_published: None,
+            is_shared: None,
+            cloud_id: None,
+        }
+    }
+
+    #[test]
+    fn round_trips_and_detects_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILE_NAME);
+        let root = dir.path().join("Diaryx");
+        let stamp = FileStamp {
+            mtime_ms: 1,
+            size: 2,
+        };
+
+        let index = MetadataIndex::load(&path, &root);
+        index.lock().insert(stamp, metadata("a"));
+        index.save().unwrap();
+
+        let reloaded = MetadataIndex::load(&path, &root);
+        assert!(reloaded.lock().fresh("a", "Diaryx/a.md", &stamp).is_some());
+        assert!(reloaded.lock().fresh("a", "Diaryx/b.md", &stamp).is_none());
+
+        let raw = fs::read_to_string(&path).unwrap();
+        fs::write(&path, raw.replace("\"a\"", "\"b\"")).unwrap();
+        assert!(MetadataIndex::load(&path, &root).lock().records.is_empty());
+    }
+}
@@ -0,0 +1,495 @@
+//! Native entry store.
+//!
+//! Owns all CRUD on the journal folder so the frontend no longer needs a
+//! `tauri-plugin-fs` round-trip per file. Entries are addressed by a stable
+//! ID (see [`ids`]); the file is named after the entry's title and may sit
+//! in a notebook folder (see [`notebooks`]).
+
+pub mod atomic;
+pub mod commands;
+pub mod entry;
+pub mod history;
+pub mod ids;
+pub mod index;
+pub mod notebooks;
+pub mod scanner;
+pub mod slug;
+pub mod timestamps;
+pub mod trash;
+pub mod vault;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+
+use crate::error::{Error, Result};
+use entry::{create_title_from_id, FILE_EXTENSION};
+pub use entry::{JournalEntry, JournalEntryMetadata};
+use history::History;
+use ids::EntryIds;
+use index::FileStamp;
+use notebooks::{entry_stems, join_stem, notebook_of, stem_name};
+pub use timestamps::EntryTimestamps;
+use trash::TrashedEntry;
+use vault::Vault;
+
+/// Name of the journal folder inside the user's documents directory.
+pub const JOURNAL_FOLDER: &str = "Diaryx";
+
+/// Filesystem-backed store for journal entries.
+#[derive(Debug, Clone)]
+pub struct EntryStore {
+    root: PathBuf,
+    /// Serializes mutations so collision checks and renames cannot interleave.
+    write_lock: Arc<Mutex<()>>,
+    /// Entries last written by the app with the stamp their file had
+    /// afterwards (`None` once gone), so the watcher can ignore our writes.
+    own_writes: Arc<Mutex<HashMap<String, Option<FileStamp>>>>,
+    vault: Vault,
+    history: Arc<History>,
+    ids: Arc<EntryIds>,
+    /// Folders outside the journal whose files are sealed with its vault.
+    sealed_dirs: Arc<Vec<PathBuf>>,
+}
+
+impl EntryStore {
+    /// Create a store rooted at `root`. The directory is created lazily.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        Self {
+            vault: Vault::load(&root),
+            history: Arc::new(History::load(&root)),
+            ids: Arc::new(EntryIds::load(&root)),
+            root,
+            write_lock: Arc::default(),
+            own_writes: Arc::default(),
+            sealed_dirs: Arc::default(),
+        }
+    }
+
+    /// Default journal location: `Documents/Diaryx`, falling back to the home directory.
+    pub fn default_root() -> PathBuf {
+        dirs::document_dir()
+            .or_else(dirs::home_dir)
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(JOURNAL_FOLDER)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.write_lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Remember the file of `id` as just written by the app. Called after the write.
+    fn mark_own_write(&self, id: &str) {
+        let stamp = self.current_stamp(id);
+        self.own_writes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.to_string(), stamp);
+    }
+
+    /// Whether the file of `id` is as the app's last write left it, so a
+    /// change to it must have been our own.
+    pub fn is_own_write(&self, id: &str) -> bool {
+        let stamp = self.current_stamp(id);
+        self.own_writes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .is_some_and(|written| *written == stamp)
+    }
+
+    fn current_stamp(&self, id: &str) -> Option<FileStamp> {
+        FileStamp::of(&self.entry_path(id).ok()?).ok()
+    }
+
+    /// Ensure the journal directory exists and clear temp files from
+    /// interrupted writes, in notebook folders too.
+    pub fn ensure_dir(&self) -> Result<()> {
+        fs::create_dir_all(&self.root)?;
+        for dir in notebooks::folders(&self.root)? {
+            atomic::remove_stale_temp_files(&dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn ids(&self) -> &EntryIds {
+        &self.ids
+    }
+
+    /// File stem of `id`, or `None` if no entry has that ID.
+    fn stem_of(&self, id: &str) -> Result<Option<String>> {
+        validate_id(id)?;
+        Ok(self.ids.stem(id))
+    }
+
+    fn stem_path(&self, stem: &str) -> PathBuf {
+        self.root.join(format!("{stem}.{FILE_EXTENSION}"))
+    }
+
+    /// Absolute path of the file backing `id`.
+    pub fn entry_path(&self, id: &str) -> Result<PathBuf> {
+        let stem = self
+            .stem_of(id)?
+            .ok_or_else(|| Error::NotFound(id.to_string()))?;
+        Ok(self.stem_path(&stem))
+    }
+
+    /// Path of the file named `stem` as reported to the frontend, relative to
+    /// the journal root.
+    pub(crate) fn display_path(stem: &str) -> String {
+        format!("{stem}.{FILE_EXTENSION}")
+    }
+
+    /// Path of `id` as reported to the frontend, if it names an entry.
+    pub fn display_path_of(&self, id: &str) -> Result<Option<String>> {
+        Ok(self.stem_of(id)?.map(|stem| Self::display_path(&stem)))
+    }
+
+    pub fn entry_exists(&self, id: &str) -> Result<bool> {
+        Ok(self
+            .stem_of(id)?
+            .is_some_and(|stem| self.stem_path(&stem).is_file()))
+    }
+
+    /// IDs of every entry in the journal folder and its notebooks, in
+    /// directory order.
+    ///
+    /// Files seen for the first time are given an ID.
+    pub fn entry_ids(&self) -> Result<Vec<String>> {
+        self.ids.ensure(&entry_stems(&self.root)?)
+    }
+
+    /// List metadata for every entry in the journal folder, newest first.
+    pub fn list_entries(&self) -> Result<Vec<JournalEntryMetadata>> {
+        let mut list = Vec::new();
+        for id in self.entry_ids()? {
+            if let Some(entry) = self.read_listed(&id)? {
+                list.push(JournalEntryMetadata::from_entry(&entry));
+            }
+        }
+
+        list.sort_by(|a, b| b.modified_at.cmp(&a.modified_at));
+        Ok(list)
+    }
+
+    /// Read `id` for a listing. A file that cannot be read or decrypted is
+    /// logged and left out rather than failing the whole list; a locked vault
+    /// still fails it.
+    pub(super) fn read_listed(&self, id: &str) -> Result<Option<JournalEntry>> {
+        match self.get_entry(id) {
+            Err(Error::Locked) => Err(Error::Locked),
+            Err(e) => {
+                log::warn!("skipping unreadable entry {id}: {e}");
+                Ok(None)
+            }
+            result => result,
+        }
+    }
+
+    /// Read and, in vault mode, decrypt the content at `path`.
+    fn read_content(&self, path: &Path) -> Result<Option<String>> {
+        match fs::read(path) {
+            Ok(raw) => self.vault.open(raw).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Read a single entry, returning `None` if it does not exist.
+    pub fn get_entry(&self, id: &str) -> Result<Option<JournalEntry>> {
+        let Some(stem) = self.stem_of(id)? else {
+            return Ok(None);
+        };
+        let path = self.stem_path(&stem);
+        let Some(content) = self.read_content(&path)? else {
+            return Ok(None);
+        };
+
+        let timestamps = EntryTimestamps::resolve(&fs::metadata(&path)?, &content);
+        Ok(Some(JournalEntry {
+            id: id.to_string(),
+            title: create_title_from_id(stem_name(&stem)),
+            content,
+            created_at: timestamps.created_iso(),
+            modified_at: timestamps.modified_iso(),
+            file_path: Self::display_path(&stem),
+        }))
+    }
+
+    /// Resolve the real created/modified timestamps of `id`.
+    pub fn get_entry_timestamps(&self, id: &str) -> Result<EntryTimestamps> {
+        let path = self.entry_path(id)?;
+        let content = self
+            .read_content(&path)?
+            .ok_or_else(|| Error::NotFound(id.to_string()))?;
+        Ok(EntryTimestamps::resolve(&fs::metadata(&path)?, &content))
+    }
+
+    /// Atomically overwrite the content of the existing entry `id`. Fails with
+    /// [`Error::NotFound`] if no entry has that ID.
+    pub fn save_entry(&self, id: &str, content: &str) -> Result<()> {
+        let _guard = self.lock();
+        // Sealed under the lock so a concurrent vault migration cannot leave
+        // it in plaintext, and resolved there so a rename cannot be undone.
+        let bytes = self.vault.seal(content)?;
+        let path = self.entry_path(id)?;
+        let unrecorded = self.unrecorded_content(id, &path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write_atomic(&path, &bytes)?;
+        self.mark_own_write(id);
+
+        if let Some((previous, modified)) = unrecorded {
+            self.record_revision(id, &previous, modified);
+        }
+        self.record_revision(id, content, Utc::now());
+        Ok(())
+    }
+
+    /// Current content of `id` and its mtime, if it was never recorded in the
+    /// history, so the first save keeps what it overwrites.
+    fn unrecorded_content(&self, id: &str, path: &Path) -> Option<(String, DateTime<Utc>)> {
+        if self.history.contains(id) {
+            return None;
+        }
+        let modified = fs::metadata(path).ok()?.modified().ok()?;
+        let content = self.read_content(path).ok()??;
+        (!content.is_empty()).then(|| (content, modified.into()))
+    }
+
+    /// Add a revision of `id`. Failures are only logged: the save itself succeeded.
+    fn record_revision(&self, id: &str, content: &str, saved_at: DateTime<Utc>) {
+        if let Err(e) = self.history.record(&self.vault, id, content, saved_at) {
+            log::warn!("failed to record a revision of {id}: {e}");
+        }
+    }
+
+    /// Create an empty entry for `title` and return its ID.
+    pub fn create_entry(&self, title: &str) -> Result<String> {
+        self.create_entry_in("", title)
+    }
+
+    /// Create an empty entry for `title` in the existing notebook `notebook`
+    /// and return its ID.
+    pub fn create_entry_in(&self, notebook: &str, title: &str) -> Result<String> {
+        let notebook = notebooks::normalize_notebook(notebook)?;
+        let _guard = self.lock();
+        let bytes = self.vault.seal("")?;
+        self.ensure_dir()?;
+        if !self.notebook_dir(&notebook).is_dir() {
+            return Err(Error::NotFound(notebook));
+        }
+        let stem = self.unique_stem(&notebook, title)?;
+        let id = self.ids.assign(&stem)?;
+        if let Err(e) = atomic::write_atomic(&self.stem_path(&stem), &bytes) {
+            let _ = self.ids.remove(&id);
+            return Err(e.into());
+        }
+        self.mark_own_write(&id);
+        Ok(id)
+    }
+
+    /// Move `id` to the trash. Deleting a missing entry is not an error.
+    pub fn delete_entry(&self, id: &str) -> Result<Option<TrashedEntry>> {
+        let _guard = self.lock();
+        let trashed = self.move_to_trash(id)?;
+        self.mark_own_write(id);
+        Ok(trashed)
+    }
+
+    /// Move `id` to a filename derived from `new_title` in the same
+    /// notebook. The ID stays the same and is returned.
+    pub fn rename_entry(&self, id: &str, new_title: &str) -> Result<String> {
+        let _guard = self.lock();
+        let stem = self
+            .stem_of(id)?
+            .ok_or_else(|| Error::NotFound(id.to_string()))?;
+        let old_path = self.stem_path(&stem);
+        if !old_path.is_file() {
+            return Err(Error::NotFound(id.to_string()));
+        }
+
+        let new_stem = self.unique_stem(notebook_of(&stem), new_title)?;
+        match atomic::rename_no_clobber(&old_path, &self.stem_path(&new_stem)) {
+            Ok(()) => {
+                self.ids.set(id, &new_stem)?;
+                self.mark_own_write(id);
+                Ok(id.to_string())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                Err(Error::AlreadyExists(new_stem))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Generate an unused stem for `title` in `notebook`, appending `-1`,
+    /// `-2`, ... on collision.
+    fn unique_stem(&self, notebook: &str, title: &str) -> Result<String> {
+        let taken = self.taken_stems(notebook)?;
+        let name = slug::unique(&slug::slugify(title), |candidate| {
+            taken.contains(&slug::fold(candidate))
+        });
+        Ok(join_stem(notebook, &name))
+    }
+
+    /// Folded stems of every markdown name in `notebook`. Folding makes
+    /// `Day.md` block `day` on case-insensitive filesystems too.
+    fn taken_stems(&self, notebook: &str) -> Result<HashSet<String>> {
+        let mut taken = HashSet::new();
+        let dir = self.notebook_dir(notebook);
+        if !dir.is_dir() {
+            return Ok(taken);
+        }
+        let extension = format!(".{FILE_EXTENSION}");
+        for dir_entry in fs::read_dir(&dir)? {
+            let name = dir_entry?.file_name();
+            let Some(name) = name.to_str() else { continue };
+            if let Some(stem) = slug::fold(name).strip_suffix(&extension) {
+                taken.insert(stem.to_string());
+            }
+        }
+        Ok(taken)
+    }
+}
+
+/// Reject IDs that could escape the journal folder.
+fn validate_id(id: &str) -> Result<()> {
+    if id.is_empty() || id.contains(['/', '\\']) || id == "." || id == ".." || id.starts_with('.') {
+        return Err(Error::InvalidId(id.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, EntryStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join(JOURNAL_FOLDER));
+        (dir, store)
+    }
+
+    #[test]
+    fn create_save_and_get_round_trip() {
+        let (_dir, store) = store();
+        let id = store.create_entry("My First Entry").unwrap();
+        assert!(store
+            .entry_path(&id)
+            .unwrap()
+            .ends_with("my-first-entry.md"));
+
+        store.save_entry(&id, "# Hello\n\nWorld").unwrap();
+        let entry = store.get_entry(&id).unwrap().unwrap();
+        assert_eq!(entry.title, "My First Entry");
+        assert_eq!(entry.content, "# Hello\n\nWorld");
+        assert_eq!(entry.file_path, "my-first-entry.md");
+    }
+
+    #[test]
+    fn create_appends_counter_on_collision() {
+        let (_dir, store) = store();
+        let stems: Vec<_> = (0..3)
+            .map(|_| {
+                let id = store.create_entry("Day").unwrap();
+                store.get_entry(&id).unwrap().unwrap().file_path
+            })
+            .collect();
+        assert_eq!(stems, ["day.md", "day-1.md", "day-2.md"]);
+    }
+
+    #[test]
+    fn unicode_titles_collide_case_insensitively() {
+        let (_dir, store) = store();
+        store.ensure_dir().unwrap();
+        fs::write(store.root.join("Дневник.md"), "").unwrap();
+
+        let id = store.create_entry("дневник").unwrap();
+        let entry = store.get_entry(&id).unwrap().unwrap();
+        assert_eq!(entry.file_path, "дневник-1.md");
+        assert_eq!(entry.title, "Дневник 1");
+    }
+
+    #[test]
+    fn rename_moves_content_and_delete_removes_file() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Old").unwrap();
+        store.save_entry(&id, "body").unwrap();
+
+        assert_eq!(store.rename_entry(&id, "New Title").unwrap(), id);
+        let entry = store.get_entry(&id).unwrap().unwrap();
+        assert_eq!(entry.content, "body");
+        assert_eq!(entry.file_path, "new-title.md");
+        assert!(!store.root().join("old.md").exists());
+
+        store.delete_entry(&id).unwrap();
+        assert!(store.list_entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn own_writes_stop_matching_after_an_external_edit() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Day").unwrap();
+        store.save_entry(&id, "mine").unwrap();
+        assert!(store.is_own_write(&id));
+
+        fs::write(store.entry_path(&id).unwrap(), "theirs, a bit longer").unwrap();
+        assert!(!store.is_own_write(&id));
+
+        let deleted = store.create_entry("Gone").unwrap();
+        store.delete_entry(&deleted).unwrap();
+        assert!(store.is_own_write(&deleted));
+        assert!(!store.is_own_write("never-written"));
+    }
+
+    #[test]
+    fn listing_skips_unreadable_files() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Day").unwrap();
+        fs::write(store.root.join("binary.md"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let list = store.list_entries().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].id, id);
+    }
+
+    #[test]
+    fn frontmatter_date_overrides_file_birthtime() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Dated").unwrap();
+        store
+            .save_entry(&id, "---\ndate: 2024-03-05\n---\nbody")
+            .unwrap();
+
+        let entry = store.get_entry(&id).unwrap().unwrap();
+        assert_eq!(entry.created_at, "2024-03-05T00:00:00.000Z");
+        assert!(entry.modified_at > entry.created_at);
+    }
+
+    #[test]
+    fn rejects_ids_outside_the_journal() {
+        let (_dir, store) = store();
+        assert!(matches!(
+            store.get_entry("../secret"),
+            Err(Error::InvalidId(_))
+        ));
+        assert!(matches!(
+            store.save_entry(".hidden", ""),
+            Err(Error::InvalidId(_))
+        ));
+        assert!(matches!(
+            store.save_entry("unknown", ""),
+            Err(Error::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,413 @@
+//! Notebooks.
+//!
+//! Folders inside the journal are notebooks, so entries can be organized by
+//! year or project. An entry's stem is its path relative to the journal root
+//! without the extension, separated by `/` on every platform: `2024/day` is
+//! `day.md` in the notebook `2024`. Hidden folders, which hold the app's own
+//! data such as the trash, and system folders are never scanned.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use serde::Serialize;
+
+use super::entry::FILE_EXTENSION;
+use super::{atomic, slug, validate_id, EntryStore, JournalEntryMetadata};
+use crate::error::{Error, Result};
+
+/// Folders that operating systems and sync tools leave in user folders.
+const SYSTEM_DIRS: &[&str] = &[
+    "$RECYCLE.BIN",
+    "System Volume Information",
+    "__MACOSX",
+    "@eaDir",
+    "lost+found",
+];
+
+/// A notebook and what it directly contains.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notebook {
+    /// Path relative to the journal root, empty for the root itself.
+    pub path: String,
+    /// Paths of the notebooks inside, sorted.
+    pub notebooks: Vec<String>,
+    /// Entries directly inside, newest first.
+    pub entries: Vec<JournalEntryMetadata>,
+}
+
+impl EntryStore {
+    pub(super) fn notebook_dir(&self, notebook: &str) -> PathBuf {
+        if notebook.is_empty() {
+            self.root.clone()
+        } else {
+            self.root.join(notebook)
+        }
+    }
+
+    /// Create the notebook `path` and any missing parents. Returns its
+    /// normalized path.
+    pub fn create_notebook(&self, path: &str) -> Result<String> {
+        let notebook = normalize_notebook(path)?;
+        if notebook.is_empty() {
+            return Err(Error::InvalidId(path.to_string()));
+        }
+        let _guard = self.lock();
+        let dir = self.notebook_dir(&notebook);
+        if dir.exists() {
+            return Err(Error::AlreadyExists(notebook));
+        }
+        fs::create_dir_all(dir)?;
+        Ok(notebook)
+    }
+
+    /// The notebooks and entries directly inside the notebook `path`.
+    ///
+    /// Files seen for the first time are given an ID.
+    pub fn list_notebook(&self, path: &str) -> Result<Notebook> {
+        let notebook = normalize_notebook(path)?;
+        let dir = self.notebook_dir(&notebook);
+        let mut listing = Notebook {
+            path: notebook,
+            notebooks: Vec::new(),
+            entries: Vec::new(),
+        };
+        if !dir.is_dir() {
+            // The journal root itself is created lazily.
+            if listing.path.is_empty() {
+                return Ok(listing);
+            }
+            return Err(Error::NotFound(listing.path));
+        }
+
+        let mut stems = Vec::new();
+        for dir_entry in fs::read_dir(&dir)? {
+            let dir_entry = dir_entry?;
+            if dir_entry.file_type()?.is_dir() {
+                if let Some(name) = dir_entry.file_name().to_str() {
+                    if !is_ignored_dir(name) {
+                        listing.notebooks.push(join_stem(&listing.path, name));
+                    }
+                }
+            } else if let Some(stem) = file_stem(&listing.path, &dir_entry.path()) {
+                stems.push(stem);
+            }
+        }
+        listing.notebooks.sort();
+
+        for id in self.ids.ensure(&stems)? {
+            if let Some(entry) = self.get_entry(&id)? {
+                listing
+                    .entries
+                    .push(JournalEntryMetadata::from_entry(&entry));
+            }
+        }
+        listing
+            .entries
+            .sort_by(|a, b| b.modified_at.cmp(&a.modified_at));
+        Ok(listing)
+    }
+
+    /// Move `id` into the notebook `path`, keeping its ID and, unless another
+    /// entry there has it, its file name. Returns the entry's new path.
+    pub fn move_entry(&self, id: &str, path: &str) -> Result<String> {
+        let notebook = normalize_notebook(path)?;
+        let _guard = self.lock();
+        let stem = self
+            .stem_of(id)?
+            .ok_or_else(|| Error::NotFound(id.to_string()))?;
+        let old_path = self.stem_path(&stem);
+        if !old_path.is_file() {
+            return Err(Error::NotFound(id.to_string()));
+        }
+        if notebook_of(&stem) == notebook {
+            return Ok(Self::display_path(&stem));
+        }
+        if !self.notebook_dir(&notebook).is_dir() {
+            return Err(Error::NotFound(notebook));
+        }
+
+        let taken = self.taken_stems(&notebook)?;
+        let name = slug::unique(stem_name(&stem), |candidate| {
+            taken.contains(&slug::fold(candidate))
+        });
+        let new_stem = join_stem(&notebook, &name);
+        match atomic::rename_no_clobber(&old_path, &self.stem_path(&new_stem)) {
+            Ok(()) => {
+                self.ids.set(id, &new_stem)?;
+                self.mark_own_write(id);
+                Ok(Self::display_path(&new_stem))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                Err(Error::AlreadyExists(new_stem))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Stems of every entry in the notebook `notebook` and the notebooks
+    /// inside it.
+    pub fn notebook_stems(&self, notebook: &str) -> Result<Vec<String>> {
+        let mut stems = Vec::new();
+        let dir = self.notebook_dir(notebook);
+        if dir.is_dir() {
+            collect_stems(&dir, notebook, &mut stems)?;
+        }
+        Ok(stems)
+    }
+}
+
+/// Whether the folder `name` is skipped when looking for entries.
+pub fn is_ignored_dir(name: &str) -> bool {
+    name.starts_with('.') || SYSTEM_DIRS.contains(&name)
+}
+
+/// Normalize a notebook path from the frontend, separated by `/` or `\`.
+/// Every folder name has to be valid on every filesystem and not hidden; the
+/// empty path is the journal root.
+pub fn normalize_notebook(path: &str) -> Result<String> {
+    let trimmed = path.trim().trim_matches(['/', '\\']);
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    let folders: Vec<&str> = trimmed.split(['/', '\\']).collect();
+    let valid = folders.iter().all(|folder| {
+        validate_id(folder).is_ok() && !is_ignored_dir(folder) && slug::is_portable(folder)
+    });
+    if !valid {
+        return Err(Error::InvalidId(path.to_string()));
+    }
+    Ok(folders.join("/"))
+}
+
+/// Stem of the entry file at `relative`, a path inside the journal root,
+/// whether or not the file exists. `None` for other files and anything in
+/// hidden or system folders.
+pub fn relative_stem(relative: &Path) -> Option<String> {
+    if relative.extension()? != FILE_EXTENSION {
+        return None;
+    }
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        let Component::Normal(part) = component else {
+            return None;
+        };
+        parts.push(part.to_str()?);
+    }
+    let (file, folders) = parts.split_last()?;
+    if folders.iter().any(|folder| is_ignored_dir(folder)) {
+        return None;
+    }
+    let name = Path::new(file).file_stem()?.to_str()?;
+    validate_id(name).ok()?;
+    Some(join_stem(&folders.join("/"), name))
+}
+
+/// Notebook path of the folder at `relative` inside the journal root, unless
+/// it is ignored.
+pub fn relative_notebook(relative: &Path) -> Option<String> {
+    let mut folders = Vec::new();
+    for component in relative.components() {
+        let Component::Normal(part) = component else {
+            return None;
+        };
+        let folder = part.to_str()?;
+        if is_ignored_dir(folder) {
+            return None;
+        }
+        folders.push(folder);
+    }
+    (!folders.is_empty()).then(|| folders.join("/"))
+}
+
+/// Stem of `name` in `notebook`.
+pub(super) fn join_stem(notebook: &str, name: &str) -> String {
+    if notebook.is_empty() {
+        name.to_string()
+    } else {
+        format!("{notebook}/{name}")
+    }
+}
+
+/// Notebook path of the entry at `stem`, empty for the journal root.
+pub(super) fn notebook_of(stem: &str) -> &str {
+    stem.rsplit_once('/').map_or("", |(notebook, _)| notebook)
+}
+
+/// File stem of the entry at `stem`, without its notebook.
+pub(super) fn stem_name(stem: &str) -> &str {
+    stem.rsplit_once('/').map_or(stem, |(_, name)| name)
+}
+
+/// Stems of every entry in the journal at `root`, notebooks included, in
+/// directory order.
+pub(super) fn entry_stems(root: &Path) -> Result<Vec<String>> {
+    let mut stems = Vec::new();
+    if root.is_dir() {
+        collect_stems(root, "", &mut stems)?;
+    }
+    Ok(stems)
+}
+
+/// The journal root and every notebook folder below it.
+pub(super) fn folders(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut folders = vec![root.to_path_buf()];
+    let mut next = 0;
+    while let Some(dir) = folders.get(next).cloned() {
+        next += 1;
+        for dir_entry in fs::read_dir(&dir)? {
+            let dir_entry = dir_entry?;
+            let name = dir_entry.file_name();
+            if dir_entry.file_type()?.is_dir()
+                && name.to_str().is_some_and(|name| !is_ignored_dir(name))
+            {
+                folders.push(dir_entry.path());
+            }
+        }
+    }
+    Ok(folders)
+}
+
+fn collect_stems(dir: &Path, notebook: &str, stems: &mut Vec<String>) -> Result<()> {
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+        // Symlinked folders are not followed, so links cannot loop.
+        if dir_entry.file_type()?.is_dir() {
+            let Some(name) = dir_entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if is_ignored_dir(&name) {
+                continue;
+            }
+            let inner = join_stem(notebook, &name);
+            // One unreadable notebook should not hide the rest of the journal.
+            if let Err(e) = collect_stems(&path, &inner, stems) {
+                log::warn!("skipping notebook {inner}: {e}");
+            }
+        } else if let Some(stem) = file_stem(notebook, &path) {
+            stems.push(stem);
+        }
+    }
+    Ok(())
+}
+
+/// Stem of the file at `path` in `notebook` if it is an entry.
+fn file_stem(notebook: &str, path: &Path) -> Option<String> {
+    if !path.is_file() || path.extension()? != FILE_EXTENSION {
+        return None;
+    }
+    let name = path.file_stem()?.to_str()?;
+    validate_id(name).ok()?;
+    Some(join_stem(notebook, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::JOURNAL_FOLDER;
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, EntryStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join(JOURNAL_FOLDER));
+        (dir, store)
+    }
+
+    #[test]
+    fn scans_notebooks_but_not_hidden_or_system_folders() {
+        let (_dir, store) = store();
+        for folder in ["2024/march", ".hidden", "__MACOSX"] {
+            fs::create_dir_all(store.root.join(folder)).unwrap();
+        }
+        fs::write(store.root.join("top.md"), "").unwrap();
+        fs::write(store.root.join("2024/march/day.md"), "").unwrap();
+        fs::write(store.root.join(".hidden/secret.md"), "").unwrap();
+        fs::write(store.root.join("__MACOSX/day.md"), "").unwrap();
+
+        let mut paths: Vec<_> = store
+            .list_entries()
+            .unwrap()
+            .into_iter()
+            .map(|metadata| metadata.file_path)
+            .collect();
+        paths.sort();
+        assert_eq!(paths, ["2024/march/day.md", "top.md"]);
+        let mut folders = folders(&store.root).unwrap();
+        folders.sort();
+        assert_eq!(
+            folders[1..],
+            [store.root.join("2024"), store.root.join("2024/march")]
+        );
+
+        let year = store.list_notebook("2024").unwrap();
+        assert_eq!(year.notebooks, ["2024/march"]);
+        assert!(year.entries.is_empty());
+        let march = store.list_notebook("/2024\\march/").unwrap();
+        assert_eq!(march.entries[0].title, "Day");
+        assert!(matches!(
+            store.list_notebook("missing"),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn moves_entries_between_notebooks() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Day").unwrap();
+        assert_eq!(
+            store.create_notebook(" Work/Project X ").unwrap(),
+            "Work/Project X"
+        );
+        assert!(matches!(
+            store.create_notebook("Work"),
+            Err(Error::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            store.create_notebook("a/../b"),
+            Err(Error::InvalidId(_))
+        ));
+        assert!(matches!(
+            store.create_notebook(".trash"),
+            Err(Error::InvalidId(_))
+        ));
+
+        assert_eq!(
+            store.move_entry(&id, "Work/Project X").unwrap(),
+            "Work/Project X/day.md"
+        );
+        let entry = store.get_entry(&id).unwrap().unwrap();
+        assert_eq!(entry.title, "Day");
+        assert!(store.root.join("Work/Project X/day.md").is_file());
+
+        // Names are unique per notebook.
+        let other = store.create_entry("Day").unwrap();
+        assert_eq!(
+            store.get_entry(&other).unwrap().unwrap().file_path,
+            "day.md"
+        );
+        assert_eq!(
+            store.move_entry(&other, "Work/Project X").unwrap(),
+            "Work/Project X/day-1.md"
+        );
+        assert_eq!(store.move_entry(&id, "").unwrap(), "day.md");
+        assert!(matches!(
+            store.move_entry(&id, "missing"),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn maps_relative_paths_to_stems() {
+        assert_eq!(
+            relative_stem(Path::new("2024/day.md")).as_deref(),
+            Some("2024/day")
+        );
+        assert_eq!(relative_stem(Path::new(".diaryx-trash/day.md")), None);
+        assert_eq!(relative_stem(Path::new("2024/notes.txt")), None);
+        assert_eq!(
+            relative_notebook(Path::new("2024/march")).as_deref(),
+            Some("2024/march")
+        );
+        assert_eq!(relative_notebook(Path::new("__MACOSX")), None);
+    }
+}
@@ -0,0 +1,108 @@
+//! Parallel journal scanner.
+//!
+//! Walks the journal folder once, consults the [`MetadataIndex`] and reads
+//! only new or changed entry files, concurrently on tokio's blocking pool.
+//! Every entry's metadata is returned in one payload; files that cannot be
+//! read are logged and left out.
+
+use std::sync::Arc;
+use std::thread;
+
+use tokio::task::JoinSet;
+
+use super::index::{FileStamp, MetadataIndex};
+use super::{EntryStore, JournalEntryMetadata};
+use crate::error::Result;
+
+/// Scan the journal and return metadata for every entry, newest first.
+pub async fn scan_journal(
+    store: EntryStore,
+    index: Arc<MetadataIndex>,
+) -> Result<Vec<JournalEntryMetadata>> {
+    let lister = store.clone();
+    let stamped =
+        tokio::task::spawn_blocking(move || -> Result<Vec<(String, String, FileStamp)>> {
+            let mut stamped = Vec::new();
+            for id in lister.entry_ids()? {
+                // An entry removed between listing and stat is simply skipped.
+                let (Ok(path), Ok(Some(file_path))) =
+                    (lister.entry_path(&id), lister.display_path_of(&id))
+                else {
+                    continue;
+                };
+                if let Ok(stamp) = FileStamp::of(&path) {
+                    stamped.push((id, file_path, stamp));
+                }
+            }
+            Ok(stamped)
+        })
+        .await??;
+
+    let mut list = Vec::with_capacity(stamped.len());
+    let mut stale = Vec::new();
+    {
+        let data = index.lock();
+        for (id, file_path, stamp) in &stamped {
+            match data.fresh(id, file_path, stamp) {
+                Some(metadata) => list.push(metadata.clone()),
+                None => stale.push((id.clone(), *stamp)),
+            }
+        }
+    }
+
+    let changed = !stale.is_empty() || index.lock().records.len() != list.len();
+    let read = read_concurrently(&store, stale).await?;
+    {
+        let mut data = index.lock();
+        for (stamp, metadata) in read {
+            list.push(metadata.clone());
+            data.insert(stamp, metadata);
+        }
+        let ids: Vec<String> = stamped.into_iter().map(|(id, _, _)| id).collect();
+        data.retain_ids(&ids);
+    }
+
+    // Previews are plaintext, so vault journals are only indexed in memory.
+    if changed && !store.vault_status().enabled {
+        let index = Arc::clone(&index);
+        tokio::task::spawn_blocking(move || index.save()).await??;
+    }
+
+    list.sort_by(|a, b| b.modified_at.cmp(&a.modified_at));
+    Ok(list)
+}
+
+/// Read the given entries on the blocking pool, one chunk per worker.
+async fn read_concurrently(
+    store: &EntryStore,
+    ids: Vec<(String, FileStamp)>,
+) -> Result<Vec<(FileStamp, JournalEntryMetadata)>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // One chunk per worker keeps the blocking pool from spawning a thread per file.
+    let workers = thread::available_parallelism().map_or(4, |n| n.get());
+    let chunk_size = ids.len().div_ceil(workers);
+
+    let mut tasks = JoinSet::new();
+    for chunk in ids.chunks(chunk_size) {
+        let store = store.clone();
+        let chunk = chunk.to_vec();
+        tasks.spawn_blocking(move || -> Result<Vec<(FileStamp, JournalEntryMetadata)>> {
+            let mut metadata = Vec::with_capacity(chunk.len());
+            for (id, stamp) in chunk {
+                if let Some(entry) = store.read_listed(&id)? {
+                    metadata.push((stamp, JournalEntryMetadata::from_entry(&entry)));
+                }
+            }
+            Ok(metadata)
+        });
+    }
+
+    let mut read = Vec::with_capacity(ids.len());
+    while let Some(result) = tasks.join_next().await {
+        read.extend(result??);
+    }
+    Ok(read)
+}
@@ -0,0 +1,213 @@
+//! Entry filenames from titles.
+//!
+//! Letters and digits of every script are kept, lowercased and in NFC, so
+//! `日記` or `Дневник` name a file as written. Symbols such as emoji are
+//! transliterated to ASCII words, whitespace and dashes separate words and
+//! ASCII punctuation is dropped.
+//!
+//! A slug is valid on every filesystem a journal is likely to be synced
+//! through: none of the characters Windows forbids, no leading dot, no
+//! reserved device name, and short enough in bytes that a collision
+//! counter, the `.md` extension and a trash suffix stay well within the
+//! 255-byte limit of ext4 and APFS (and the 255 UTF-16 units of NTFS).
+
+use deunicode::deunicode_char;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Maximum length of a slug in bytes, collision counter included.
+pub const MAX_SLUG_BYTES: usize = 100;
+
+/// Slug of a title without a single letter, digit or symbol.
+pub const FALLBACK_SLUG: &str = "untitled";
+
+/// Device names Windows reserves whatever the extension.
+const RESERVED_NAMES: &[&str] = &["con", "prn", "aux", "nul"];
+/// Device names Windows reserves when followed by a digit.
+const RESERVED_PREFIXES: &[&str] = &["com", "lpt"];
+
+/// Slug of `title`, never empty.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for c in title.nfc() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if is_combining_mark(c) {
+            // Marks left over by NFC belong to the letter before them.
+            if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push(c);
+            }
+        } else if c.is_whitespace() || c == '-' {
+            separate(&mut slug);
+        } else if !c.is_ascii() {
+            push_transliterated(&mut slug, c);
+        }
+    }
+
+    let slug: String = slug.nfc().collect();
+    let slug = truncate(&slug, MAX_SLUG_BYTES).trim_end_matches('-');
+    if slug.is_empty() {
+        FALLBACK_SLUG.to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// `slug`, or `slug-1`, `slug-2`, ... shortened to fit: the first that is
+/// not a reserved name and that `taken` does not claim.
+///
+/// Every name on disk is known up front, so collisions are resolved in a
+/// single call instead of one filesystem probe per counter.
+pub fn unique(slug: &str, taken: impl Fn(&str) -> bool) -> String {
+    let free = |candidate: &str| !is_reserved(candidate) && !taken(candidate);
+    if free(slug) {
+        return slug.to_string();
+    }
+    (1u64..)
+        .map(|counter| {
+            let suffix = format!("-{counter}");
+            let base = truncate(slug, MAX_SLUG_BYTES - suffix.len()).trim_end_matches('-');
+            format!("{base}{suffix}")
+        })
+        .find(|candidate| free(candidate))
+        .expect("counters are unbounded")
+}
+
+/// The form under which case- and normalization-insensitive filesystems
+/// (APFS, NTFS) consider two names equal.
+pub fn fold(name: &str) -> String {
+    name.nfc().flat_map(char::to_lowercase).nfc().collect()
+}
+
+/// Whether Windows reserves `name` for a device.
+pub fn is_reserved(name: &str) -> bool {
+    let base = name.split('.').next().unwrap_or(name).to_lowercase();
+    RESERVED_NAMES.contains(&base.as_str())
+        || RESERVED_PREFIXES.iter().any(|prefix| {
+            base.strip_prefix(prefix).is_some_and(|rest| {
+                let mut chars = rest.chars();
+                matches!(
+                    (chars.next(), chars.next()),
+                    (Some('0'..='9' | '¹' | '²' | '³'), None)
+                )
+            })
+        })
+}
+
+/// Whether `name`, as typed by a user, is valid as is on every filesystem.
+pub fn is_portable(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_SLUG_BYTES
+        && !name.contains(|c: char| c.is_control() || "<>:\"/\\|?*".contains(c))
+        && !name.ends_with(['.', ' '])
+        && !is_reserved(name)
+}
+
+fn separate(slug: &mut String) {
+    if !slug.is_empty() && !slug.ends_with('-') {
+        slug.push('-');
+    }
+}
+
+/// Spell out a symbol as its own word; invisible ones such as joiners and
+/// variation selectors are dropped.
+fn push_transliterated(slug: &mut String, c: char) {
+    let Some(ascii) = deunicode_char(c).filter(|ascii| !ascii.is_empty()) else {
+        return;
+    };
+    separate(slug);
+    for a in ascii.chars() {
+        if a.is_ascii_alphanumeric() {
+            slug.push(a.to_ascii_lowercase());
+        } else {
+            separate(slug);
+        }
+    }
+    separate(slug);
+}
+
+/// The longest prefix of `s` within `max_bytes`, cut at a character boundary.
+fn truncate(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::validate_id;
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn keeps_letters_of_every_script() {
+        assert_eq!(slugify("Hello  World!! ** DiaryX"), "hello-world-diaryx");
+        assert_eq!(slugify("Don't stop"), "dont-stop");
+        assert_eq!(slugify("日記 2024"), "日記-2024");
+        assert_eq!(slugify("Дневник"), "дневник");
+        assert_eq!(slugify("Cafe\u{301} au lait"), "café-au-lait");
+    }
+
+    #[test]
+    fn transliterates_symbols_and_falls_back() {
+        assert_eq!(slugify("🦄 Day"), "unicorn-day");
+        assert_eq!(slugify("!!! ..."), FALLBACK_SLUG);
+        assert_eq!(slugify(""), FALLBACK_SLUG);
+    }
+
+    #[test]
+    fn limits_length_in_bytes() {
+        let slug = slugify(&"日".repeat(60));
+        assert_eq!(slug, "日".repeat(MAX_SLUG_BYTES / 3));
+
+        let taken = |candidate: &str| candidate == slug;
+        let next = unique(&slug, taken);
+        assert!(next.len() <= MAX_SLUG_BYTES);
+        assert!(next.ends_with("日-1"));
+    }
+
+    #[test]
+    fn skips_reserved_and_taken_names() {
+        assert!(is_reserved("CON"));
+        assert!(is_reserved("com1"));
+        assert!(is_reserved("lpt²"));
+        assert!(!is_reserved("console"));
+        assert!(is_portable("Project X"));
+        assert!(!is_portable("a:b"));
+        assert!(!is_portable("nul"));
+        assert!(!is_portable("trailing."));
+        assert_eq!(unique("con", |_| false), "con-1");
+        assert_eq!(unique("day", |c| c == "day" || c == "day-1"), "day-2");
+        assert_eq!(fold("Cafe\u{301}"), fold("CAFÉ"));
+    }
+
+    proptest! {
+        #[test]
+        fn slugs_are_portable_filenames(title in any::<String>()) {
+            let slug = slugify(&title);
+            prop_assert!(validate_id(&slug).is_ok());
+            prop_assert!(slug.len() <= MAX_SLUG_BYTES);
+            prop_assert!(!slug.contains(|c: char| c.is_control() || "<>:\"/\\|?*. ".contains(c)));
+            prop_assert!(!slug.starts_with('-') && !slug.ends_with('-'));
+            prop_assert_eq!(slug.nfc().collect::<String>(), slug.clone());
+            prop_assert_eq!(slugify(&slug), slug);
+        }
+
+        #[test]
+        fn unique_avoids_every_taken_name(title in "\\PC{0,80}", taken in 0usize..20) {
+            let slug = slugify(&title);
+            let mut names: HashSet<String> = HashSet::from([slug.clone()]);
+            names.extend((1..taken).map(|counter| format!("{slug}-{counter}")));
+            let name = unique(&slug, |candidate| names.contains(candidate));
+            prop_assert!(!names.contains(&name));
+            prop_assert!(!is_reserved(&name));
+            prop_assert!(name.len() <= MAX_SLUG_BYTES);
+        }
+    }
+}
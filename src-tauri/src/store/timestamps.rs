@@ -0,0 +1,81 @@
+//! Real created/modified timestamps for entry files.
+//!
+//! Timestamps come from filesystem metadata. A `date:` or `created:` key in
+//! the entry's frontmatter takes precedence for `created_at`, so the timeline
+//! survives copies between devices that reset birthtime.
+
+use std::fs::Metadata;
+use std::time::SystemTime;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::frontmatter;
+
+/// Creation and modification time of an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EntryTimestamps {
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
+
+impl EntryTimestamps {
+    /// Resolve timestamps from file metadata and, if present, the entry frontmatter.
+    ///
+    /// Platforms without birthtime fall back to mtime for `created_at`.
+    pub fn resolve(metadata: &Metadata, content: &str) -> Self {
+        let modified_at = metadata
+            .modified()
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| DateTime::<Utc>::from(SystemTime::UNIX_EPOCH));
+        let created_at = frontmatter_created(content)
+            .or_else(|| metadata.created().ok().map(DateTime::<Utc>::from))
+            .unwrap_or(modified_at);
+
+        Self {
+            created_at,
+            modified_at,
+        }
+    }
+
+    pub fn created_iso(&self) -> String {
+        to_iso(&self.created_at)
+    }
+
+    pub fn modified_iso(&self) -> String {
+        to_iso(&self.modified_at)
+    }
+}
+
+/// Format a timestamp like JavaScript's `Date.toISOString()`.
+pub fn to_iso(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Read a `date:` or `created:` value from the entry's frontmatter.
+fn frontmatter_created(content: &str) -> Option<DateTime<Utc>> {
+    let frontmatter = frontmatter::parse(content).ok()?.frontmatter;
+    let created = frontmatter.extra.get("created").and_then(Value::as_str);
+    frontmatter
+        .date
+        .as_deref()
+        .and_then(parse_date)
+        .or_else(|| created.and_then(parse_date))
+}
+
+/// Parse the date formats commonly found in frontmatter.
+pub fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(Utc.from_utc_datetime(&naive));
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|naive| Utc.from_utc_datetime(&naive))
+}
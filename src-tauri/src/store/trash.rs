@@ -0,0 +1,363 @@
+//! Soft deletion.
+//!
+//! Deleting an entry moves its file, still sealed as it was, into
+//! `.diaryx-trash/` inside the journal and records where it came from in a
+//! manifest next to it. Trashed entries can be restored until the trash is
+//! emptied or they are purged after the retention period. Their revision
+//! history is kept until then as well.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::entry::{create_title_from_id, FILE_EXTENSION};
+use super::notebooks::{notebook_of, stem_name};
+use super::{atomic, EntryStore, EntryTimestamps};
+use crate::error::{Error, Result};
+
+/// Directory inside the journal root holding trashed entries.
+pub const TRASH_DIR: &str = ".diaryx-trash";
+const MANIFEST_FILE: &str = "trash.json";
+const MANIFEST_VERSION: u32 = 1;
+/// Days a trashed entry is kept unless configured otherwise.
+pub const DEFAULT_RETENTION_DAYS: u32 = 30;
+
+/// An entry in the trash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedEntry {
+    /// Name of the file in the trash, unique across deletions.
+    pub trash_id: String,
+    /// ID the entry had, kept on restore.
+    pub entry_id: String,
+    /// File stem the entry had, reused on restore while it is free.
+    #[serde(default)]
+    pub stem: String,
+    pub title: String,
+    /// Path the entry had, as reported to the frontend.
+    pub file_path: String,
+    pub created_at: String,
+    pub modified_at: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrashManifest {
+    version: u32,
+    retention_days: u32,
+    /// Oldest deletion first.
+    entries: Vec<TrashedEntry>,
+}
+
+impl Default for TrashManifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            retention_days: DEFAULT_RETENTION_DAYS,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl EntryStore {
+    fn trash_dir(&self) -> PathBuf {
+        self.root.join(TRASH_DIR)
+    }
+
+    fn trash_path(&self, trash_id: &str) -> PathBuf {
+        self.trash_dir()
+            .join(format!("{trash_id}.{FILE_EXTENSION}"))
+    }
+
+    fn read_manifest(&self) -> TrashManifest {
+        let path = self.trash_dir().join(MANIFEST_FILE);
+        match fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str::<TrashManifest>(&raw)
+                .ok()
+                .filter(|manifest| manifest.version == MANIFEST_VERSION)
+                .unwrap_or_else(|| {
+                    log::warn!("ignoring invalid trash manifest at {}", path.display());
+                    TrashManifest::default()
+                }),
+            Err(_) => TrashManifest::default(),
+        }
+    }
+
+    fn write_manifest(&self, manifest: &TrashManifest) -> Result<()> {
+        let json = serde_json::to_vec_pretty(manifest).map_err(std::io::Error::from)?;
+        fs::create_dir_all(self.trash_dir())?;
+        atomic::write_atomic(&self.trash_dir().join(MANIFEST_FILE), &json)?;
+        Ok(())
+    }
+
+    /// Stems of entries trashed before stable IDs, whose stem is their ID.
+    pub(super) fn legacy_trash_stems(&self) -> Vec<String> {
+        self.read_manifest()
+            .entries
+            .into_iter()
+            .filter(|trashed| trashed.stem.is_empty())
+            .map(|trashed| trashed.entry_id)
+            .collect()
+    }
+
+    /// Give entries trashed before stable IDs their new IDs from `renames`.
+    pub(super) fn rekey_trash(&self, renames: &BTreeMap<String, String>) -> Result<()> {
+        let mut manifest = self.read_manifest();
+        let mut changed = false;
+        for trashed in &mut manifest.entries {
+            if !trashed.stem.is_empty() {
+                continue;
+            }
+            if let Some(id) = renames.get(&trashed.entry_id) {
+                trashed.stem = std::mem::replace(&mut trashed.entry_id, id.clone());
+                changed = true;
+            }
+        }
+        if changed {
+            self.write_manifest(&manifest)?;
+        }
+        Ok(())
+    }
+
+    /// Move `id` into the trash. The caller holds the write lock.
+    pub(super) fn move_to_trash(&self, id: &str) -> Result<Option<TrashedEntry>> {
+        let Some(stem) = self.stem_of(id)? else {
+            return Ok(None);
+        };
+        let path = self.stem_path(&stem);
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                self.ids.remove(id)?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let content = self.read_content(&path)?.unwrap_or_default();
+        let timestamps = EntryTimestamps::resolve(&metadata, &content);
+
+        let mut manifest = self.read_manifest();
+        let deleted_at = Utc::now();
+        let base = format!("{}-{}", stem_name(&stem), deleted_at.timestamp_millis());
+        let mut trash_id = base.clone();
+        let mut counter = 1;
+        while self.trash_path(&trash_id).exists() {
+            trash_id = format!("{base}-{counter}");
+            counter += 1;
+        }
+
+        fs::create_dir_all(self.trash_dir())?;
+        let trash_path = self.trash_path(&trash_id);
+        atomic::rename_no_clobber(&path, &trash_path)?;
+        let trashed = TrashedEntry {
+            trash_id,
+            entry_id: id.to_string(),
+            title: create_title_from_id(stem_name(&stem)),
+            file_path: Self::display_path(&stem),
+            stem,
+            created_at: timestamps.created_iso(),
+            modified_at: timestamps.modified_iso(),
+            deleted_at,
+        };
+        manifest.entries.push(trashed.clone());
+        if let Err(e) = self.write_manifest(&manifest) {
+            // Without a manifest record the file could never be restored.
+            let _ = fs::rename(&trash_path, &path);
+            return Err(e);
+        }
+        self.ids.remove(id)?;
+        Ok(Some(trashed))
+    }
+
+    /// Trashed entries, most recently deleted first.
+    pub fn list_trash(&self) -> Vec<TrashedEntry> {
+        let mut entries = self.read_manifest().entries;
+        entries.reverse();
+        entries
+    }
+
+    /// Move `trash_id` back into the journal and return its ID.
+    ///
+    /// The entry gets its old file name back, in its old notebook, unless
+    /// another entry took it meanwhile; then it is named after its title like
+    /// a new entry. A notebook removed since is created again.
+    pub fn restore_entry(&self, trash_id: &str) -> Result<String> {
+        let _guard = self.lock();
+        let mut manifest = self.read_manifest();
+        let position = manifest
+            .entries
+            .iter()
+            .position(|trashed| trashed.trash_id == trash_id)
+            .ok_or_else(|| Error::NotFound(trash_id.to_string()))?;
+        let trashed = manifest.entries.remove(position);
+
+        let stem = if trashed.stem.is_empty() || self.stem_path(&trashed.stem).exists() {
+            self.unique_stem(notebook_of(&trashed.stem), &trashed.title)?
+        } else {
+            trashed.stem.clone()
+        };
+        fs::create_dir_all(self.notebook_dir(notebook_of(&stem)))?;
+        let restored =
+            atomic::rename_no_clobber(&self.trash_path(trash_id), &self.stem_path(&stem));
+        match restored {
+            Ok(()) => self.write_manifest(&manifest)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                // The file is gone; drop the record that cannot be restored.
+                self.write_manifest(&manifest)?;
+                return Err(Error::NotFound(trash_id.to_string()));
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        // Only an ID shared with a live entry, from files added outside the
+        // app, has to be replaced.
+        let id = if self.ids.stem(&trashed.entry_id).is_some() {
+            let id = self.ids.assign(&stem)?;
+            let renames = BTreeMap::from([(trashed.entry_id.clone(), id.clone())]);
+            if let Err(e) = self.history.rename_entries(&renames) {
+                log::warn!("failed to move the history of {}: {e}", trashed.entry_id);
+            }
+            id
+        } else {
+            self.ids.set(&trashed.entry_id, &stem)?;
+            trashed.entry_id
+        };
+        self.mark_own_write(&id);
+        Ok(id)
+    }
+
+    /// Delete every trashed entry for good and return them.
+    pub fn empty_trash(&self) -> Result<Vec<TrashedEntry>> {
+        self.remove_from_trash(|_| true)
+    }
+
+    /// Delete trashed entries older than the retention period and return them.
+    pub fn purge_trash(&self) -> Result<Vec<TrashedEntry>> {
+        let days = self.trash_retention_days();
+        let cutoff = Utc::now() - Duration::days(days.into());
+        self.remove_from_trash(|trashed| trashed.deleted_at <= cutoff)
+    }
+
+    pub fn trash_retention_days(&self) -> u32 {
+        self.read_manifest().retention_days
+    }
+
+    pub fn set_trash_retention_days(&self, days: u32) -> Result<()> {
+        let _guard = self.lock();
+        let mut manifest = self.read_manifest();
+        manifest.retention_days = days;
+        self.write_manifest(&manifest)
+    }
+
+    fn remove_from_trash(
+        &self,
+        remove: impl Fn(&TrashedEntry) -> bool,
+    ) -> Result<Vec<TrashedEntry>> {
+        let _guard = self.lock();
+        let mut manifest = self.read_manifest();
+        let (removed, kept): (Vec<_>, Vec<_>) = manifest.entries.into_iter().partition(remove);
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+        manifest.entries = kept;
+        self.write_manifest(&manifest)?;
+
+        for trashed in &removed {
+            match fs::remove_file(self.trash_path(&trashed.trash_id)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            let still_used = self.entry_exists(&trashed.entry_id)?
+                || manifest
+                    .entries
+                    .iter()
+                    .any(|other| other.entry_id == trashed.entry_id);
+            if !still_used {
+                if let Err(e) = self.history.remove(&trashed.entry_id) {
+                    log::warn!("failed to drop the history of {}: {e}", trashed.entry_id);
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Rewrite each trashed file for which `convert` returns new bytes; see
+    /// `convert_entries`.
+    pub(super) fn convert_trash(
+        &self,
+        convert: impl Fn(Vec<u8>) -> Result<Option<Vec<u8>>>,
+    ) -> Result<usize> {
+        let mut converted = 0;
+        for trashed in self.read_manifest().entries {
+            let path = self.trash_path(&trashed.trash_id);
+            let raw = match fs::read(&path) {
+                Ok(raw) => raw,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(bytes) = convert(raw)? {
+                atomic::write_atomic(&path, &bytes)?;
+                converted += 1;
+            }
+        }
+        Ok(converted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::JOURNAL_FOLDER;
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, EntryStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join(JOURNAL_FOLDER));
+        (dir, store)
+    }
+
+    #[test]
+    fn deleted_entries_can_be_restored_until_purged() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Day").unwrap();
+        store.save_entry(&id, "kept").unwrap();
+
+        let trashed = store.delete_entry(&id).unwrap().unwrap();
+        assert_eq!(trashed.entry_id, id);
+        assert_eq!(trashed.file_path, "day.md");
+        assert!(store.entry_ids().unwrap().is_empty());
+        assert!(store.get_entry(&id).unwrap().is_none());
+        assert_eq!(store.list_trash(), [trashed.clone()]);
+
+        // A new entry took the file name, so the restored one keeps its ID
+        // under a new name.
+        let other = store.create_entry("Day").unwrap();
+        assert_eq!(store.restore_entry(&trashed.trash_id).unwrap(), id);
+        let restored = store.get_entry(&id).unwrap().unwrap();
+        assert_eq!(restored.content, "kept");
+        assert_eq!(restored.file_path, "day-1.md");
+        assert!(store.list_trash().is_empty());
+        assert!(matches!(
+            store.restore_entry(&trashed.trash_id),
+            Err(Error::NotFound(_))
+        ));
+
+        // Nothing is old enough for the default retention period.
+        store.delete_entry(&id).unwrap();
+        store.delete_entry(&other).unwrap();
+        assert!(store.purge_trash().unwrap().is_empty());
+        store.set_trash_retention_days(0).unwrap();
+        let purged = store.purge_trash().unwrap();
+        assert_eq!(purged.len(), 2);
+        assert!(store.list_trash().is_empty());
+        assert!(!store.history().contains(&id));
+        assert_eq!(
+            fs::read_dir(store.root().join(TRASH_DIR)).unwrap().count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,444 @@
+//! Encrypted-at-rest vault mode.
+//!
+//! When the vault is enabled, each entry file holds a header line followed by
+//! the JSON [`EncryptedEntryData`] of its markdown, sealed to the user's own
+//! key pair with the `EntryCryptor` scheme. A marker file in the journal root
+//! records that the vault is on and which public key it belongs to. Entry
+//! content and history are only decrypted in memory while the vault is
+//! unlocked; file and notebook folder names, which follow entry titles, stay
+//! readable on disk, as do file sizes and timestamps.
+//!
+//! Files are recognised by their header rather than by the marker, so a
+//! migration interrupted halfway leaves a readable journal and can simply be
+//! run again.
+
+use std::fs::{self, File, FileTimes};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{atomic, EntryStore};
+use crate::crypto::keys::UserKeyPair;
+use crate::crypto::{self, EncryptedEntryData};
+use crate::error::{Error, Result};
+
+/// Marker file in the journal root; its presence turns vault mode on.
+const MARKER_FILE: &str = ".diaryx-vault.json";
+/// First line of every encrypted entry file.
+const FILE_HEADER: &str = "diaryx-vault v1\n";
+const MARKER_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultMarker {
+    version: u32,
+    public_key: String,
+}
+
+#[derive(Debug, Default)]
+struct VaultState {
+    marker: Option<VaultMarker>,
+    keys: Option<UserKeyPair>,
+}
+
+/// Whether the vault is enabled and unlocked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+}
+
+/// Vault state of one journal, shared by every clone of its [`EntryStore`].
+#[derive(Debug, Clone, Default)]
+pub struct Vault {
+    state: Arc<RwLock<VaultState>>,
+    /// Held shared by [`Vault::write_sealed`] and exclusively by migrations,
+    /// so files outside the journal are never sealed with a stale state.
+    sealing: Arc<RwLock<()>>,
+}
+
+impl Vault {
+    /// Load the vault marker from the journal at `root`, if any.
+    pub fn load(root: &Path) -> Self {
+        let marker = match fs::read_to_string(root.join(MARKER_FILE)) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .map_err(|e| log::warn!("ignoring invalid vault marker: {e}"))
+                .ok(),
+            Err(_) => None,
+        };
+        Self {
+            state: Arc::new(RwLock::new(VaultState { marker, keys: None })),
+            sealing: Arc::default(),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, VaultState> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, VaultState> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn status(&self) -> VaultStatus {
+        let state = self.read();
+        VaultStatus {
+            enabled: state.marker.is_some(),
+            unlocked: state.keys.is_some(),
+        }
+    }
+
+    /// Hold `keys` in memory. Fails if the vault belongs to a different key pair.
+    pub fn unlock(&self, keys: UserKeyPair) -> Result<()> {
+        let mut state = self.write();
+        if let Some(marker) = &state.marker {
+            if marker.public_key != keys.public_b64() {
+                return Err(Error::Crypto("key pair does not match this vault".into()));
+            }
+        }
+        state.keys = Some(keys);
+        Ok(())
+    }
+
+    /// Drop the held key pair; it is zeroized on drop.
+    pub fn lock(&self) {
+        self.write().keys = None;
+    }
+
+    /// Decode raw file bytes into markdown, decrypting vault files.
+    pub fn open(&self, raw: Vec<u8>) -> Result<String> {
+        let Some(sealed) = raw.strip_prefix(FILE_HEADER.as_bytes()) else {
+            return String::from_utf8(raw)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into());
+        };
+
+        let state = self.read();
+        decrypt_file(sealed, state.keys.as_ref().ok_or(Error::Locked)?)
+    }
+
+    /// Encode markdown for disk, encrypting it when the vault is enabled.
+    pub fn seal(&self, content: &str) -> Result<Vec<u8>> {
+        if self.read().marker.is_none() {
+            return Ok(content.as_bytes().to_vec());
+        }
+        self.encrypt(content)
+    }
+
+    /// Seal `content` and write it to `path` outside the journal. Waits for a
+    /// running migration, which would otherwise miss the file.
+    pub fn write_sealed(&self, path: &Path, content: &str) -> Result<()> {
+        let _sealing = self.sealing.read().unwrap_or_else(|e| e.into_inner());
+        atomic::write_atomic(path, &self.seal(content)?)?;
+        Ok(())
+    }
+
+    /// Block [`Vault::write_sealed`] until the guard is dropped.
+    fn block_sealing(&self) -> RwLockWriteGuard<'_, ()> {
+        self.sealing.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn encrypt(&self, content: &str) -> Result<Vec<u8>> {
+        let state = self.read();
+        encrypt_file(content, state.keys.as_ref().ok_or(Error::Locked)?)
+    }
+}
+
+fn decrypt_file(sealed: &[u8], keys: &UserKeyPair) -> Result<String> {
+    let data: EncryptedEntryData =
+        serde_json::from_slice(sealed).map_err(|e| Error::Crypto(e.to_string()))?;
+    match crypto::decrypt_entry(&data, keys.secret(), &keys.public)? {
+        Value::String(content) => Ok(content),
+        _ => Err(Error::Crypto("vault file does not contain markdown".into())),
+    }
+}
+
+fn encrypt_file(content: &str, keys: &UserKeyPair) -> Result<Vec<u8>> {
+    let data = crypto::encrypt_entry(&Value::String(content.to_string()), keys)?;
+    let json = serde_json::to_vec(&data).map_err(|e| Error::Crypto(e.to_string()))?;
+    Ok([FILE_HEADER.as_bytes(), &json].concat())
+}
+
+fn write_marker(root: &Path, marker: &VaultMarker) -> Result<()> {
+    let json = serde_json::to_vec_pretty(marker).map_err(std::io::Error::from)?;
+    atomic::write_atomic(&root.join(MARKER_FILE), &json)?;
+    Ok(())
+}
+
+impl EntryStore {
+    pub fn vault_status(&self) -> VaultStatus {
+        self.vault.status()
+    }
+
+    pub fn unlock_vault(&self, keys: UserKeyPair) -> Result<()> {
+        self.vault.unlock(keys)
+    }
+
+    pub fn lock_vault(&self) {
+        self.vault.lock();
+    }
+
+    /// The vault, for data kept outside the journal that must follow vault mode.
+    pub fn vault(&self) -> &Vault {
+        &self.vault
+    }
+
+    /// Also convert the files in `dirs`, written with [`Vault::seal`] outside
+    /// the journal, whenever vault mode is turned on or off or rekeyed.
+    pub fn with_sealed_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.sealed_dirs = Arc::new(dirs);
+        self
+    }
+
+    /// Encrypt every plaintext entry in place. Requires the vault to be unlocked.
+    ///
+    /// Saves wait for the store lock held throughout, and so are encrypted
+    /// once it is released. Trashed entries, revision history and the sealed
+    /// folders are converted along with the entries; returns the number of
+    /// entry files converted.
+    pub fn enable_vault(&self) -> Result<usize> {
+        let _guard = self.lock();
+        let _sealing = self.vault.block_sealing();
+        let public_key = {
+            let state = self.vault.read();
+            state.keys.as_ref().ok_or(Error::Locked)?.public_b64()
+        };
+
+        self.ensure_dir()?;
+        let marker = VaultMarker {
+            version: MARKER_VERSION,
+            public_key,
+        };
+        write_marker(&self.root, &marker)?;
+        self.vault.write().marker = Some(marker);
+
+        let convert = |raw: Vec<u8>| -> Result<Option<Vec<u8>>> {
+            if raw.starts_with(FILE_HEADER.as_bytes()) {
+                return Ok(None);
+            }
+            let content = self.vault.open(raw)?;
+            self.vault.encrypt(&content).map(Some)
+        };
+        let converted = self.convert_entries(convert)?;
+        self.convert_trash(convert)?;
+        self.history.convert_objects(convert)?;
+        self.convert_sealed_dirs(convert)?;
+        Ok(converted)
+    }
+
+    /// Decrypt every vault file back to plaintext and remove the marker.
+    ///
+    /// Requires the vault to be unlocked; returns the number of files converted.
+    pub fn disable_vault(&self) -> Result<usize> {
+        let _guard = self.lock();
+        let _sealing = self.vault.block_sealing();
+        if !self.vault.status().unlocked {
+            return Err(Error::Locked);
+        }
+
+        let convert = |raw: Vec<u8>| -> Result<Option<Vec<u8>>> {
+            if !raw.starts_with(FILE_HEADER.as_bytes()) {
+                return Ok(None);
+            }
+            self.vault
+                .open(raw)
+                .map(|content| Some(content.into_bytes()))
+        };
+        let converted = self.convert_entries(convert)?;
+        self.convert_trash(convert)?;
+        self.history.convert_objects(convert)?;
+        self.convert_sealed_dirs(convert)?;
+
+        match fs::remove_file(self.root.join(MARKER_FILE)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.vault.write().marker = None;
+        Ok(converted)
+    }
+
+    /// Re-encrypt every vault file from `old` to `new` keys and hold `new`.
+    ///
+    /// Files already under `new` are skipped, so an interrupted run can be
+    /// repeated. The marker switches to `new` only once every file has.
+    pub fn rekey_vault(&self, old: &UserKeyPair, new: UserKeyPair) -> Result<usize> {
+        let _guard = self.lock();
+        let _sealing = self.vault.block_sealing();
+        if self.vault.read().marker.is_none() {
+            return Ok(0);
+        }
+
+        let convert = |raw: Vec<u8>| -> Result<Option<Vec<u8>>> {
+            let Some(sealed) = raw.strip_prefix(FILE_HEADER.as_bytes()) else {
+                return Ok(None);
+            };
+            if decrypt_file(sealed, &new).is_ok() {
+                return Ok(None);
+            }
+            encrypt_file(&decrypt_file(sealed, old)?, &new).map(Some)
+        };
+        let converted = self.convert_entries(convert)?;
+        self.convert_trash(convert)?;
+        self.history.convert_objects(convert)?;
+        self.convert_sealed_dirs(convert)?;
+
+        let marker = VaultMarker {
+            version: MARKER_VERSION,
+            public_key: new.public_b64(),
+        };
+        write_marker(&self.root, &marker)?;
+        let mut state = self.vault.write();
+        state.marker = Some(marker);
+        state.keys = Some(new);
+        Ok(converted)
+    }
+
+    /// Rewrite each entry for which `convert` returns new bytes, keeping its mtime.
+    fn convert_entries(
+        &self,
+        convert: impl Fn(Vec<u8>) -> Result<Option<Vec<u8>>>,
+    ) -> Result<usize> {
+        let mut converted = 0;
+        for id in self.entry_ids()? {
+            let path = self.entry_path(&id)?;
+            let modified = fs::metadata(&path)?.modified()?;
+            let Some(bytes) = convert(fs::read(&path)?)? else {
+                continue;
+            };
+
+            atomic::write_atomic(&path, &bytes)?;
+            File::options()
+                .write(true)
+                .open(&path)?
+                .set_times(FileTimes::new().set_modified(modified))?;
+            self.mark_own_write(&id);
+            converted += 1;
+        }
+        Ok(converted)
+    }
+
+    /// Rewrite each file in the sealed folders for which `convert` returns new bytes.
+    fn convert_sealed_dirs(
+        &self,
+        convert: impl Fn(Vec<u8>) -> Result<Option<Vec<u8>>>,
+    ) -> Result<usize> {
+        let mut converted = 0;
+        for dir in self.sealed_dirs.iter().filter(|dir| dir.is_dir()) {
+            atomic::remove_stale_temp_files(dir)?;
+            for dir_entry in fs::read_dir(dir)? {
+                let dir_entry = dir_entry?;
+                let name = dir_entry.file_name();
+                if name.to_string_lossy().starts_with(atomic::TEMP_PREFIX)
+                    || !dir_entry.file_type()?.is_file()
+                {
+                    continue;
+                }
+                let path = dir_entry.path();
+                if let Some(bytes) = convert(fs::read(&path)?)? {
+                    atomic::write_atomic(&path, &bytes)?;
+                    converted += 1;
+                }
+            }
+        }
+        Ok(converted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::JOURNAL_FOLDER;
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, EntryStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join(JOURNAL_FOLDER));
+        (dir, store)
+    }
+
+    #[test]
+    fn migrates_journal_in_place_and_back() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Secret").unwrap();
+        store.save_entry(&id, "# Secret\n\ndiary").unwrap();
+        let path = store.entry_path(&id).unwrap();
+
+        assert!(matches!(store.enable_vault(), Err(Error::Locked)));
+        store.unlock_vault(UserKeyPair::generate()).unwrap();
+        assert_eq!(store.enable_vault().unwrap(), 1);
+        assert!(fs::read(&path).unwrap().starts_with(FILE_HEADER.as_bytes()));
+        assert_eq!(
+            store.get_entry(&id).unwrap().unwrap().content,
+            "# Secret\n\ndiary"
+        );
+
+        store.save_entry(&id, "updated").unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("updated"));
+
+        // A fresh store sees the marker and cannot read or write while locked.
+        let reopened = EntryStore::new(store.root());
+        assert!(reopened.vault_status().enabled);
+        assert!(matches!(reopened.get_entry(&id), Err(Error::Locked)));
+        assert!(matches!(reopened.save_entry(&id, "x"), Err(Error::Locked)));
+        assert!(reopened.unlock_vault(UserKeyPair::generate()).is_err());
+
+        let rotated = UserKeyPair::generate();
+        let old = store.vault.read().keys.clone().unwrap();
+        assert_eq!(store.rekey_vault(&old, rotated.clone()).unwrap(), 1);
+        assert_eq!(store.rekey_vault(&old, rotated.clone()).unwrap(), 0);
+        assert!(EntryStore::new(store.root()).unlock_vault(rotated).is_ok());
+        assert_eq!(store.get_entry(&id).unwrap().unwrap().content, "updated");
+
+        assert_eq!(store.disable_vault().unwrap(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "updated");
+        assert!(!store.vault_status().enabled);
+    }
+
+    #[test]
+    fn converts_sealed_folders_outside_the_journal() {
+        let (dir, store) = store();
+        let sealed = dir.path().join("sync-base");
+        let store = store.with_sealed_dirs(vec![sealed.clone()]);
+        fs::create_dir_all(&sealed).unwrap();
+        let base = sealed.join("entry");
+        store.vault().write_sealed(&base, "synced").unwrap();
+
+        let keys = UserKeyPair::generate();
+        store.unlock_vault(keys.clone()).unwrap();
+        store.enable_vault().unwrap();
+        assert!(fs::read(&base).unwrap().starts_with(FILE_HEADER.as_bytes()));
+
+        let rotated = UserKeyPair::generate();
+        store.rekey_vault(&keys, rotated.clone()).unwrap();
+        let sealed_raw = fs::read(&base).unwrap();
+        let sealed_part = sealed_raw.strip_prefix(FILE_HEADER.as_bytes()).unwrap();
+        assert_eq!(decrypt_file(sealed_part, &rotated).unwrap(), "synced");
+
+        store.disable_vault().unwrap();
+        assert_eq!(fs::read_to_string(&base).unwrap(), "synced");
+    }
+
+    #[test]
+    fn saves_racing_the_migration_end_up_encrypted() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Racing").unwrap();
+        store.unlock_vault(UserKeyPair::generate()).unwrap();
+
+        let saver = {
+            let (store, id) = (store.clone(), id.clone());
+            std::thread::spawn(move || {
+                for n in 0..200 {
+                    store.save_entry(&id, &format!("draft {n}")).unwrap();
+                }
+            })
+        };
+        store.enable_vault().unwrap();
+        saver.join().unwrap();
+
+        let path = store.entry_path(&id).unwrap();
+        assert!(fs::read(&path).unwrap().starts_with(FILE_HEADER.as_bytes()));
+        assert_eq!(store.get_entry(&id).unwrap().unwrap().content, "draft 199");
+    }
+}
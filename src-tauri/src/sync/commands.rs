@@ -0,0 +1,160 @@
+//! Tauri commands feeding and steering the outbound [`Outbox`](super::Outbox) and settling sync conflicts.
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use super::conflicts::{ConflictRecord, ConflictStyle, Resolution};
+use super::queue::Operation;
+use super::{emit_event, PullReport, SyncQueueStatus};
+use crate::api::{ApiClient, Credentials};
+use crate::error::{Error, Result};
+use crate::search::SearchEngine;
+use crate::session::Session;
+use crate::vaults::Vaults;
+
+/// A local entry and its cloud copy, as known to the webview.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudLink {
+    pub entry_id: String,
+    pub cloud_id: String,
+}
+
+/// Both sides of a conflict.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictSides {
+    pub local: String,
+    pub remote: String,
+}
+
+/// Queue `operation` for `entry_id`.
+///
+/// `cloud_id` links entries published before the native queue existed; it
+/// is only used when no mapping is known yet.
+#[tauri::command]
+pub fn enqueue_sync_operation(
+    vaults: State<'_, Vaults>,
+    entry_id: String,
+    operation: Operation,
+    cloud_id: Option<String>,
+) -> Result<SyncQueueStatus> {
+    let outbox = vaults.outbox();
+    if let Some(cloud_id) = cloud_id {
+        outbox.link(&entry_id, &cloud_id)?;
+    }
+    outbox.enqueue(&entry_id, operation)?;
+    Ok(outbox.status())
+}
+
+#[tauri::command]
+pub fn sync_queue_status(vaults: State<'_, Vaults>) -> SyncQueueStatus {
+    vaults.outbox().status()
+}
+
+/// Hand the API credentials of the signed-in user to the queue.
+#[tauri::command]
+pub fn set_sync_credentials(
+    vaults: State<'_, Vaults>,
+    api_base_url: String,
+    user_id: String,
+    access_token: String,
+) -> SyncQueueStatus {
+    let outbox = vaults.outbox();
+    let credentials = Credentials {
+        user_id,
+        access_token,
+    };
+    outbox.connect(Some(ApiClient::new(&api_base_url, credentials)));
+    outbox.status()
+}
+
+/// Forget the credentials, e.g. on logout. Queued operations are kept.
+#[tauri::command]
+pub fn clear_sync_credentials(vaults: State<'_, Vaults>) -> SyncQueueStatus {
+    let outbox = vaults.outbox();
+    outbox.connect(None);
+    outbox.status()
+}
+
+/// Report connectivity from the webview's `online`/`offline` events.
+#[tauri::command]
+pub fn set_sync_online(vaults: State<'_, Vaults>, online: bool) -> Result<SyncQueueStatus> {
+    let outbox = vaults.outbox();
+    outbox.set_online(online)?;
+    Ok(outbox.status())
+}
+
+/// Retry every queued entry now, ignoring backoff.
+#[tauri::command]
+pub fn retry_sync_queue(vaults: State<'_, Vaults>) -> Result<SyncQueueStatus> {
+    let outbox = vaults.outbox();
+    outbox.retry_now()?;
+    Ok(outbox.status())
+}
+
+/// Link entries published before the native queue existed, so pulling
+/// does not import them a second time. Known entries keep their mapping.
+#[tauri::command]
+pub fn link_cloud_entries(vaults: State<'_, Vaults>, links: Vec<CloudLink>) -> Result<()> {
+    let outbox = vaults.outbox();
+    for link in links {
+        outbox.link(&link.entry_id, &link.cloud_id)?;
+    }
+    Ok(())
+}
+
+/// Merge remote changes into the journal, import entries published
+/// elsewhere and unlink deleted ones.
+#[tauri::command]
+pub async fn pull_cloud_changes(app: AppHandle) -> Result<PullReport> {
+    let (store, outbox) = app.state::<Vaults>().store_and_outbox();
+    outbox
+        .pull(&store, &app.state::<Session>(), |event| {
+            emit_event(&app, &event)
+        })
+        .await
+}
+
+#[tauri::command]
+pub fn list_sync_conflicts(vaults: State<'_, Vaults>) -> Vec<ConflictRecord> {
+    vaults.outbox().conflicts().list()
+}
+
+/// The local and remote side of a conflict, for a side-by-side view.
+#[tauri::command]
+pub fn get_conflict_sides(vaults: State<'_, Vaults>, entry_id: String) -> Result<ConflictSides> {
+    let (store, outbox) = vaults.store_and_outbox();
+    let (local, remote) = outbox
+        .conflicts()
+        .sides(store.vault(), &entry_id)
+        .ok_or(Error::NotFound(entry_id))?;
+    Ok(ConflictSides { local, remote })
+}
+
+/// Settle the conflict of `entry_id` and queue the result for upload.
+#[tauri::command]
+pub fn resolve_conflict(
+    vaults: State<'_, Vaults>,
+    search: State<'_, SearchEngine>,
+    entry_id: String,
+    resolution: Resolution,
+) -> Result<()> {
+    let (store, outbox) = vaults.store_and_outbox();
+    let copy_id = outbox.conflicts().get(&entry_id).and_then(|r| r.copy_id);
+    outbox.resolve_conflict(&store, &entry_id, resolution)?;
+    for id in std::iter::once(&entry_id).chain(&copy_id) {
+        search.refresh_entry(&store, id)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_conflict_style(vaults: State<'_, Vaults>) -> ConflictStyle {
+    vaults.outbox().conflicts().style()
+}
+
+#[tauri::command]
+pub fn set_conflict_style(vaults: State<'_, Vaults>, style: ConflictStyle) -> Result<()> {
+    vaults.outbox().conflicts().set_style(style)
+}
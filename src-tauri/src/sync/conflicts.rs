@@ -0,0 +1,237 @@
+//! Unresolved sync conflicts.
+//!
+//! When a three-way merge leaves conflicts, the entry is written either with
+//! conflict markers or with the local side plus a separate conflict copy of
+//! the remote version, and a [`ConflictRecord`] is kept until
+//! `resolve_conflict` is called. Uploads of a conflicted entry wait for the
+//! resolution. Both sides are kept as vault-sealed [`Snapshots`] so either
+//! can be chosen later.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::merge::Conflict;
+use super::snapshots::Snapshots;
+use crate::error::Result;
+use crate::store::atomic;
+use crate::store::vault::Vault;
+
+const CONFLICTS_VERSION: u32 = 1;
+
+/// How conflicting body edits are presented.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictStyle {
+    /// Git-style conflict markers inside the entry.
+    #[default]
+    Markers,
+    /// Keep the local side in the entry and write the remote version to a new entry.
+    Copy,
+}
+
+/// An entry whose merge needs a decision.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictRecord {
+    pub entry_id: String,
+    pub style: ConflictStyle,
+    /// The entry holding the remote version, with [`ConflictStyle::Copy`].
+    pub copy_id: Option<String>,
+    /// Frontmatter keys changed differently on both sides.
+    pub frontmatter_keys: Vec<String>,
+    /// Number of conflicting body regions.
+    pub body_regions: usize,
+    /// The server's `updated_at` of the remote version.
+    pub remote_updated_at: DateTime<Utc>,
+    pub detected_at: DateTime<Utc>,
+}
+
+impl ConflictRecord {
+    pub fn new(
+        entry_id: &str,
+        style: ConflictStyle,
+        copy_id: Option<String>,
+        conflicts: &[Conflict],
+        remote_updated_at: DateTime<Utc>,
+    ) -> Self {
+        let frontmatter_keys = conflicts
+            .iter()
+            .filter_map(|c| match c {
+                Conflict::Frontmatter { key, .. } => Some(key.clone()),
+                Conflict::Body { .. } => None,
+            })
+            .collect();
+        Self {
+            entry_id: entry_id.to_string(),
+            style,
+            copy_id,
+            frontmatter_keys,
+            body_regions: conflicts
+                .iter()
+                .filter(|c| matches!(c, Conflict::Body { .. }))
+                .count(),
+            remote_updated_at,
+            detected_at: Utc::now(),
+        }
+    }
+}
+
+/// How to settle a conflict.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Resolution {
+    /// The local version from before the merge.
+    Local,
+    /// The remote version.
+    Remote,
+    /// Content edited by the user, e.g. with the markers removed.
+    Merged { content: String },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ConflictsFile {
+    version: u32,
+    style: ConflictStyle,
+    records: BTreeMap<String, ConflictRecord>,
+}
+
+/// Persistent conflict records and the preferred [`ConflictStyle`].
+#[derive(Debug)]
+pub struct Conflicts {
+    path: PathBuf,
+    file: Mutex<ConflictsFile>,
+    local: Snapshots,
+    remote: Snapshots,
+}
+
+impl Conflicts {
+    /// Load the conflicts kept in `dir`.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join("conflicts.json");
+        let file = match fs::read_to_string(&path) {
+            Ok(raw) => match serde_json::from_str::<ConflictsFile>(&raw) {
+                Ok(file) if file.version == CONFLICTS_VERSION => file,
+                _ => {
+                    log::warn!(
+                        "conflicts at {} are invalid, starting empty",
+                        path.display()
+                    );
+                    ConflictsFile::default()
+                }
+            },
+            Err(_) => ConflictsFile::default(),
+        };
+        let [local, remote] = Self::sides_dirs(dir);
+        Self {
+            path,
+            file: Mutex::new(file),
+            local: Snapshots::new(local),
+            remote: Snapshots::new(remote),
+        }
+    }
+
+    /// Where the local and remote sides of the conflicts kept in `dir` are.
+    pub fn sides_dirs(dir: &Path) -> [PathBuf; 2] {
+        let conflicts = dir.join("conflicts");
+        [conflicts.join("local"), conflicts.join("remote")]
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ConflictsFile> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn style(&self) -> ConflictStyle {
+        self.lock().style
+    }
+
+    pub fn set_style(&self, style: ConflictStyle) -> Result<()> {
+        let mut file = self.lock();
+        file.style = style;
+        self.save(&mut file)
+    }
+
+    pub fn get(&self, entry_id: &str) -> Option<ConflictRecord> {
+        self.lock().records.get(entry_id).cloned()
+    }
+
+    pub fn contains(&self, entry_id: &str) -> bool {
+        self.lock().records.contains_key(entry_id)
+    }
+
+    pub fn list(&self) -> Vec<ConflictRecord> {
+        self.lock().records.values().cloned().collect()
+    }
+
+    /// Record a conflict together with both sides of it.
+    pub fn insert(
+        &self,
+        vault: &Vault,
+        record: ConflictRecord,
+        local: &str,
+        remote: &str,
+    ) -> Result<()> {
+        self.local.set(vault, &record.entry_id, local)?;
+        self.remote.set(vault, &record.entry_id, remote)?;
+        let mut file = self.lock();
+        file.records.insert(record.entry_id.clone(), record);
+        self.save(&mut file)
+    }
+
+    /// The local and remote side of a recorded conflict.
+    pub fn sides(&self, vault: &Vault, entry_id: &str) -> Option<(String, String)> {
+        Some((
+            self.local.get(vault, entry_id)?,
+            self.remote.get(vault, entry_id)?,
+        ))
+    }
+
+    pub fn remove(&self, entry_id: &str) -> Result<Option<ConflictRecord>> {
+        let removed = {
+            let mut file = self.lock();
+            let removed = file.records.remove(entry_id);
+            self.save(&mut file)?;
+            removed
+        };
+        self.local.remove(entry_id)?;
+        self.remote.remove(entry_id)?;
+        Ok(removed)
+    }
+
+    /// Move the conflicts of each old entry ID in `renames` to its new one.
+    pub fn rename_entries(&self, renames: &BTreeMap<String, String>) -> Result<()> {
+        self.local.rename_entries(renames)?;
+        self.remote.rename_entries(renames)?;
+        let mut file = self.lock();
+        if file.records.is_empty() {
+            return Ok(());
+        }
+        let records = std::mem::take(&mut file.records);
+        for (_, mut record) in records {
+            if let Some(new_id) = renames.get(&record.entry_id) {
+                record.entry_id = new_id.clone();
+            }
+            if let Some(copy_id) = &mut record.copy_id {
+                if let Some(new_id) = renames.get(copy_id) {
+                    *copy_id = new_id.clone();
+                }
+            }
+            file.records.insert(record.entry_id.clone(), record);
+        }
+        self.save(&mut file)
+    }
+
+    fn save(&self, file: &mut ConflictsFile) -> Result<()> {
+        file.version = CONFLICTS_VERSION;
+        let json = serde_json::to_vec_pretty(&*file).map_err(std::io::Error::from)?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write_atomic(&self.path, &json)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! This device's position in the server's change log.
+//!
+//! The cursor belongs to one user; signing in as someone else starts over
+//! from the beginning of their log.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use crypto_box::aead::rand_core::RngCore;
+use crypto_box::aead::OsRng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::store::atomic;
+
+const CURSOR_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CursorFile {
+    version: u32,
+    /// Random ID identifying this installation to the server.
+    device_id: String,
+    user_id: Option<String>,
+    cursor: Option<String>,
+}
+
+/// Persistent change cursor and device ID.
+#[derive(Debug)]
+pub struct ChangeCursor {
+    path: PathBuf,
+    file: Mutex<CursorFile>,
+}
+
+impl ChangeCursor {
+    /// Load the cursor at `path`, creating a device ID if there is none yet.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let file = fs::read_to_string(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<CursorFile>(&raw).ok())
+            .filter(|file| file.version == CURSOR_VERSION)
+            .unwrap_or_else(|| {
+                let mut id = [0u8; 16];
+                OsRng.fill_bytes(&mut id);
+                CursorFile {
+                    version: CURSOR_VERSION,
+                    device_id: URL_SAFE_NO_PAD.encode(id),
+                    user_id: None,
+                    cursor: None,
+                }
+            });
+        Self {
+            path,
+            file: Mutex::new(file),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CursorFile> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn device_id(&self) -> String {
+        self.lock().device_id.clone()
+    }
+
+    /// Where to continue reading `user_id`'s changes; `None` reads from the start.
+    pub fn get(&self, user_id: &str) -> Option<String> {
+        let file = self.lock();
+        file.cursor
+            .clone()
+            .filter(|_| file.user_id.as_deref() == Some(user_id))
+    }
+
+    pub fn set(&self, user_id: &str, cursor: &str) -> Result<()> {
+        let mut file = self.lock();
+        file.user_id = Some(user_id.to_string());
+        file.cursor = Some(cursor.to_string());
+        self.save(&file)
+    }
+
+    /// Forget the position, so the next pull reads the whole log.
+    pub fn reset(&self) -> Result<()> {
+        let mut file = self.lock();
+        file.cursor = None;
+        self.save(&file)
+    }
+
+    fn save(&self, file: &CursorFile) -> Result<()> {
+        let json = serde_json::to_vec_pretty(file).map_err(std::io::Error::from)?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write_atomic(&self.path, &json)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+//! Links between local entries and their cloud copies.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::store::atomic;
+
+/// Where a local entry lives in the cloud. Mirrors `CloudEntryMapping` in `storage/types.ts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudMapping {
+    pub cloud_id: String,
+    pub published_at: DateTime<Utc>,
+    /// The server's `updated_at` after our last upload.
+    pub last_server_timestamp: Option<DateTime<Utc>>,
+    /// Base64 SHA-512 of the cloud copy's encrypted content as last synced.
+    #[serde(default)]
+    pub content_hash: Option<String>,
+}
+
+/// Persistent map from local entry ID to [`CloudMapping`].
+#[derive(Debug)]
+pub struct CloudMappings {
+    path: PathBuf,
+    mappings: Mutex<BTreeMap<String, CloudMapping>>,
+}
+
+impl CloudMappings {
+    /// Load the mappings at `path`. A missing or unreadable file yields no mappings.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mappings = fs::read_to_string(&path)
+            .ok()
+            .and_then(|raw| {
+                serde_json::from_str(&raw)
+                    .map_err(|e| {
+                        log::warn!("cloud mappings at {} are invalid: {e}", path.display())
+                    })
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            mappings: Mutex::new(mappings),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, CloudMapping>> {
+        self.mappings.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get(&self, entry_id: &str) -> Option<CloudMapping> {
+        self.lock().get(entry_id).cloned()
+    }
+
+    /// IDs of every mapped entry.
+    pub fn entry_ids(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    /// Cloud IDs of every mapped entry.
+    pub fn cloud_ids(&self) -> HashSet<String> {
+        self.lock()
+            .values()
+            .map(|mapping| mapping.cloud_id.clone())
+            .collect()
+    }
+
+    /// The local entry linked to `cloud_id`.
+    pub fn entry_for(&self, cloud_id: &str) -> Option<String> {
+        self.lock()
+            .iter()
+            .find(|(_, mapping)| mapping.cloud_id == cloud_id)
+            .map(|(entry_id, _)| entry_id.clone())
+    }
+
+    pub fn insert(&self, entry_id: &str, mapping: CloudMapping) -> Result<()> {
+        let mut mappings = self.lock();
+        mappings.insert(entry_id.to_string(), mapping);
+        self.save(&mappings)
+    }
+
+    /// Record the server timestamp after an upload.
+    pub fn touch(&self, entry_id: &str, server_timestamp: DateTime<Utc>) -> Result<()> {
+        let mut mappings = self.lock();
+        if let Some(mapping) = mappings.get_mut(entry_id) {
+            mapping.last_server_timestamp = Some(server_timestamp);
+        }
+        self.save(&mappings)
+    }
+
+    /// Record the content hash of the cloud copy we are in sync with.
+    pub fn set_content_hash(&self, entry_id: &str, content_hash: &str) -> Result<()> {
+        let mut mappings = self.lock();
+        if let Some(mapping) = mappings.get_mut(entry_id) {
+            mapping.content_hash = Some(content_hash.to_string());
+        }
+        self.save(&mappings)
+    }
+
+    pub fn remove(&self, entry_id: &str) -> Result<Option<CloudMapping>> {
+        let mut mappings = self.lock();
+        let removed = mappings.remove(entry_id);
+        self.save(&mappings)?;
+        Ok(removed)
+    }
+
+    /// Move each mapping from the old entry ID in `renames` to its new one.
+    pub fn rename_entries(&self, renames: &BTreeMap<String, String>) -> Result<()> {
+        let mut mappings = self.lock();
+        let moved: Vec<_> = renames
+            .iter()
+            .filter_map(|(old_id, new_id)| Some((new_id.clone(), mappings.remove(old_id)?)))
+            .collect();
+        if moved.is_empty() {
+            return Ok(());
+        }
+        mappings.extend(moved);
+        self.save(&mappings)
+    }
+
+    fn save(&self, mappings: &BTreeMap<String, CloudMapping>) -> Result<()> {
+        let json = serde_json::to_vec_pretty(mappings).map_err(std::io::Error::from)?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write_atomic(&self.path, &json)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+//! Three-way merge of entry content.
+//!
+//! The Markdown body is merged line by line against the last-synced base
+//! (diff3); frontmatter is merged key by key, so two devices editing
+//! different keys never conflict. List values such as `tags` are merged as
+//! sets. Everything the merge cannot decide is reported as a [`Conflict`].
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::frontmatter;
+
+/// Opening marker of a body conflict; the local side follows.
+pub const MARKER_LOCAL: &str = "<<<<<<< local";
+/// Separator between the local and remote side.
+pub const MARKER_SEPARATOR: &str = "=======";
+/// Closing marker of a body conflict, after the remote side.
+pub const MARKER_REMOTE: &str = ">>>>>>> remote";
+
+/// Upper bound for the LCS table; larger changed regions are treated as rewritten.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Something both sides changed differently.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Conflict {
+    /// A run of body lines.
+    Body { local: String, remote: String },
+    /// A frontmatter key. The local value is kept in the merged content.
+    Frontmatter {
+        key: String,
+        local: Option<Value>,
+        remote: Option<Value>,
+    },
+}
+
+/// How unresolved body regions are written into the merged content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyConflicts {
+    /// Both sides between conflict markers.
+    Markers,
+    /// The local side only.
+    KeepLocal,
+}
+
+/// Result of [`merge`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merged {
+    pub content: String,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merged {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Merge the changes from `base` to `local` and from `base` to `remote`.
+///
+/// The result keeps local formatting of the frontmatter block; keys taken
+/// from the remote side are rewritten in place.
+pub fn merge(
+    base: &str,
+    local: &str,
+    remote: &str,
+    body_conflicts: BodyConflicts,
+) -> Result<Merged> {
+    let (base_keys, base_body) = parts(base)?;
+    let (local_keys, local_body) = parts(local)?;
+    let (remote_keys, remote_body) = parts(remote)?;
+
+    let mut conflicts = Vec::new();
+    let body = merge_lines(
+        base_body,
+        local_body,
+        remote_body,
+        body_conflicts,
+        &mut conflicts,
+    );
+
+    let mut updates = BTreeMap::new();
+    let keys = local_keys
+        .keys()
+        .chain(remote_keys.keys())
+        .chain(base_keys.keys());
+    for key in keys.collect::<std::collections::BTreeSet<_>>() {
+        let (b, l, r) = (
+            base_keys.get(key),
+            local_keys.get(key),
+            remote_keys.get(key),
+        );
+        match merge_value(b, l, r) {
+            Some(merged) if merged.as_ref() != l => {
+                updates.insert(key.clone(), merged.unwrap_or(Value::Null));
+            }
+            Some(_) => {}
+            None => conflicts.push(Conflict::Frontmatter {
+                key: key.clone(),
+                local: l.cloned(),
+                remote: r.cloned(),
+            }),
+        }
+    }
+
+    let header = &local[..local.len() - local_body.len()];
+    let content = frontmatter::update(&format!("{header}{body}"), &updates)?;
+    Ok(Merged { content, conflicts })
+}
+
+/// Frontmatter keys and body of `content`.
+fn parts(content: &str) -> Result<(BTreeMap<String, Value>, &str)> {
+    let Some(split) = frontmatter::split(content) else {
+        return Ok((BTreeMap::new(), content));
+    };
+    let yaml = &content[split.yaml];
+    let keys = if yaml.trim().is_empty() {
+        BTreeMap::new()
+    } else {
+        serde_yaml::from_str(yaml).map_err(|e| Error::Frontmatter(e.to_string()))?
+    };
+    Ok((keys, &content[split.body]))
+}
+
+/// Merge one key. `Some(None)` removes it; `None` is a conflict.
+fn merge_value(
+    base: Option<&Value>,
+    local: Option<&Value>,
+    remote: Option<&Value>,
+) -> Option<Option<Value>> {
+    if local == remote || remote == base {
+        return Some(local.cloned());
+    }
+    if local == base {
+        return Some(remote.cloned());
+    }
+    match (local, remote) {
+        (Some(Value::Array(local)), Some(Value::Array(remote))) => {
+            let base = match base {
+                Some(Value::Array(base)) => base.as_slice(),
+                _ => &[],
+            };
+            let mut merged: Vec<Value> = local
+                .iter()
+                .filter(|item| !(base.contains(item) && !remote.contains(item)))
+                .cloned()
+                .collect();
+            for item in remote {
+                if !base.contains(item) && !merged.contains(item) {
+                    merged.push(item.clone());
+                }
+            }
+            Some(Some(Value::Array(merged)))
+        }
+        _ => None,
+    }
+}
+
+/// diff3 over lines, appending unresolved regions to `conflicts`.
+fn merge_lines(
+    base: &str,
+    local: &str,
+    remote: &str,
+    body_conflicts: BodyConflicts,
+    conflicts: &mut Vec<Conflict>,
+) -> String {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let local: Vec<&str> = local.split_inclusive('\n').collect();
+    let remote: Vec<&str> = remote.split_inclusive('\n').collect();
+    let to_local = line_matches(&base, &local);
+    let to_remote = line_matches(&base, &remote);
+
+    let mut out = String::new();
+    let (mut i, mut a, mut b) = (0, 0, 0);
+    loop {
+        if i < base.len() && to_local[i] == Some(a) && to_remote[i] == Some(b) {
+            out.push_str(base[i]);
+            (i, a, b) = (i + 1, a + 1, b + 1);
+            continue;
+        }
+
+        // The next base line kept by both sides ends the unstable region.
+        let next = (i..base.len()).find_map(|k| Some((k, to_local[k]?, to_remote[k]?)));
+        let (i2, a2, b2) = next.unwrap_or((base.len(), local.len(), remote.len()));
+        let (o, l, r) = (&base[i..i2], &local[a..a2], &remote[b..b2]);
+        if o.is_empty() && l.is_empty() && r.is_empty() {
+            break;
+        }
+
+        if l == o || l == r {
+            out.extend(r.iter().copied());
+        } else if r == o {
+            out.extend(l.iter().copied());
+        } else {
+            let (l, r) = (l.concat(), r.concat());
+            match body_conflicts {
+                BodyConflicts::Markers => {
+                    for (marker, side) in [(MARKER_LOCAL, &l), (MARKER_SEPARATOR, &r)] {
+                        push_line(&mut out, marker);
+                        out.push_str(side);
+                    }
+                    push_line(&mut out, MARKER_REMOTE);
+                }
+                BodyConflicts::KeepLocal => out.push_str(&l),
+            }
+            conflicts.push(Conflict::Body {
+                local: l,
+                remote: r,
+            });
+        }
+        (i, a, b) = (i2, a2, b2);
+    }
+    out
+}
+
+/// Append `line` on a line of its own.
+fn push_line(out: &mut String, line: &str) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(line);
+    out.push('\n');
+}
+
+/// For every line of `old`, its index in `new` along a longest common subsequence.
+pub(crate) fn line_matches(old: &[&str], new: &[&str]) -> Vec<Option<usize>> {
+    let mut out = vec![None; old.len()];
+    let prefix = old.iter().zip(new).take_while(|(x, y)| x == y).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    for (k, slot) in out.iter_mut().enumerate().take(prefix) {
+        *slot = Some(k);
+    }
+    for k in 0..suffix {
+        out[old.len() - 1 - k] = Some(new.len() - 1 - k);
+    }
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    let (n, m) = (old_mid.len(), new_mid.len());
+    if n == 0 || m == 0 || n * m > MAX_DIFF_CELLS {
+        return out;
+    }
+
+    // lengths[x][y]: LCS length of old_mid[x..] and new_mid[y..].
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    let at = |x: usize, y: usize| x * (m + 1) + y;
+    for x in (0..n).rev() {
+        for y in (0..m).rev() {
+            lengths[at(x, y)] = if old_mid[x] == new_mid[y] {
+                lengths[at(x + 1, y + 1)] + 1
+            } else {
+                lengths[at(x + 1, y)].max(lengths[at(x, y + 1)])
+            };
+        }
+    }
+    let (mut x, mut y) = (0, 0);
+    while x < n && y < m {
+        if old_mid[x] == new_mid[y] {
+            out[prefix + x] = Some(prefix + y);
+            (x, y) = (x + 1, y + 1);
+        } else if lengths[at(x + 1, y)] >= lengths[at(x, y + 1)] {
+            x += 1;
+        } else {
+            y += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_edits_to_different_lines() {
+        let base = "one\ntwo\nthree\n";
+        let local = "ONE\ntwo\nthree\n";
+        let remote = "one\ntwo\nthree\nfour\n";
+        let merged = merge(base, local, remote, BodyConflicts::Markers).unwrap();
+        assert!(merged.is_clean());
+        assert_eq!(merged.content, "ONE\ntwo\nthree\nfour\n");
+    }
+
+    #[test]
+    fn marks_overlapping_edits() {
+        let base = "a\nb\nc";
+        let local = "a\nlocal\nc";
+        let remote = "a\nremote\nc";
+        let merged = merge(base, local, remote, BodyConflicts::Markers).unwrap();
+        assert_eq!(
+            merged.content,
+            "a\n<<<<<<< local\nlocal\n=======\nremote\n>>>>>>> remote\nc"
+        );
+        assert_eq!(
+            merged.conflicts,
+            vec![Conflict::Body {
+                local: "local\n".into(),
+                remote: "remote\n".into()
+            }]
+        );
+
+        let kept = merge(base, local, remote, BodyConflicts::KeepLocal).unwrap();
+        assert_eq!(kept.content, local);
+        assert_eq!(kept.conflicts.len(), 1);
+    }
+
+    #[test]
+    fn merges_frontmatter_by_key() {
+        let base = "---\ntitle: Day\nmood: ok\ntags: [a, b]\n---\nbody\n";
+        let local = "---\ntitle: Day # mine\nmood: great\ntags: [a, b, c]\n---\nbody\n";
+        let remote = "---\ntitle: Day\nmood: ok\ntags: [b, d]\nweather: rain\n---\nbody\n";
+        let merged = merge(base, local, remote, BodyConflicts::Markers).unwrap();
+        assert!(merged.is_clean());
+        let parsed = frontmatter::parse(&merged.content).unwrap();
+        assert_eq!(parsed.frontmatter.tags, ["b", "c", "d"]);
+        assert_eq!(parsed.frontmatter.extra["mood"], "great");
+        assert_eq!(parsed.frontmatter.extra["weather"], "rain");
+        assert!(merged.content.contains("title: Day # mine"));
+
+        let remote = "---\ntitle: Day\nmood: bad\n---\nbody\n";
+        let merged = merge(base, local, remote, BodyConflicts::Markers).unwrap();
+        assert!(merged.conflicts.contains(&Conflict::Frontmatter {
+            key: "mood".into(),
+            local: Some("great".into()),
+            remote: Some("bad".into()),
+        }));
+        assert!(merged.content.contains("mood: great"));
+    }
+}
@@ -0,0 +1,1368 @@
+//! Outbound cloud sync.
+//!
+//! Publish, update, unpublish, share and revoke requests go into a durable
+//! per-entry [`OutboundQueue`] instead of straight to the network. A
+//! background task drains it whenever the webview reports connectivity, API
+//! credentials are set and the session is unlocked. Failed entries are
+//! retried with exponential backoff; progress and failures are emitted as
+//! the [`SYNC_EVENT`] event.
+//!
+//! Remote changes are three-way merged into local entries (see [`merge`]);
+//! merges that need a decision are kept in [`Conflicts`] until resolved.
+//! They are found through the server's change log, read from a per-device
+//! [`ChangeCursor`] that is saved after every page, so an interrupted pull
+//! resumes where it stopped.
+//!
+//! Each vault has an outbox of its own, kept with the vault's app data, so
+//! its queue, links and cursor wait for it while another vault is active.
+//! Credentials and connectivity are shared by all of them.
+
+pub mod commands;
+pub mod conflicts;
+pub mod cursor;
+pub mod mappings;
+pub mod merge;
+pub mod outbound;
+pub mod queue;
+pub mod snapshots;
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+
+use crate::api::models::EntryChange;
+use crate::api::ApiClient;
+use crate::crdt::EntryDocs;
+use crate::error::{Error, Result};
+use crate::search::SearchEngine;
+use crate::session::Session;
+use crate::store::trash::TrashedEntry;
+use crate::store::EntryStore;
+use crate::vaults::Vaults;
+use conflicts::{ConflictRecord, Conflicts, Resolution};
+use cursor::ChangeCursor;
+use mappings::{CloudMapping, CloudMappings};
+use outbound::{Changed, Outbound, Pulled};
+use queue::{Operation, OutboundQueue, PendingEntry};
+use snapshots::Snapshots;
+
+/// Name of the event carrying [`SyncEvent`]s.
+pub const SYNC_EVENT: &str = "sync-queue";
+
+const QUEUE_FILE_NAME: &str = "sync-queue.json";
+const MAPPINGS_FILE_NAME: &str = "cloud-mappings.json";
+const CURSOR_FILE_NAME: &str = "sync-cursor.json";
+const BASES_DIR_NAME: &str = "sync-base";
+const CRDT_DIR_NAME: &str = "crdt";
+
+/// How long the drainer sleeps when nothing is scheduled, in case it missed a wake-up.
+const IDLE_POLL: Duration = Duration::from_secs(30);
+
+/// Changes requested per page of the change log.
+const CHANGE_PAGE_SIZE: usize = 200;
+
+/// Progress of the outbound queue, for the webview.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum SyncEvent {
+    /// `operation` reached the cloud.
+    Progress {
+        entry_id: String,
+        operation: Operation,
+        cloud_id: Option<String>,
+        remaining: usize,
+    },
+    /// `operation` failed; it is retried at `next_attempt_at` unless dropped.
+    Failed {
+        entry_id: String,
+        operation: Operation,
+        error: String,
+        will_retry: bool,
+        next_attempt_at: Option<DateTime<Utc>>,
+    },
+    /// Merging a remote change left conflicts; see `list_sync_conflicts`.
+    Conflict {
+        entry_id: String,
+        copy_id: Option<String>,
+    },
+    /// An entry published from another device was added to the journal.
+    Imported { entry_id: String, cloud_id: String },
+    /// The cloud copy of an entry was deleted elsewhere; it is no longer published.
+    Removed { entry_id: String },
+    /// An entry another user shared changed, or was deleted or unshared.
+    Shared { cloud_id: String, deleted: bool },
+    /// The queue is empty.
+    Drained,
+}
+
+/// Result of [`Outbox::pull`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullReport {
+    /// Entries that took in remote changes.
+    pub merged: Vec<String>,
+    /// Entries left with conflicts.
+    pub conflicts: Vec<String>,
+    /// Entries created from cloud entries published elsewhere.
+    pub imported: Vec<String>,
+    /// Entries whose cloud copy was deleted.
+    pub removed: Vec<String>,
+    /// Cloud IDs of entries shared with the user that changed.
+    pub shared: Vec<String>,
+}
+
+/// Snapshot of the outbound queue.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncQueueStatus {
+    pub online: bool,
+    pub connected: bool,
+    pub pending: Vec<PendingEntry>,
+}
+
+/// API client and connectivity, shared by the outboxes of every vault.
+#[derive(Debug, Default)]
+struct Connection {
+    api: Mutex<Option<ApiClient>>,
+    online: AtomicBool,
+    wake: Notify,
+}
+
+/// Outbound queue, cloud mappings and sync state of one vault.
+#[derive(Debug)]
+pub struct Outbox {
+    queue: OutboundQueue,
+    mappings: CloudMappings,
+    cursor: ChangeCursor,
+    bases: Snapshots,
+    conflicts: Conflicts,
+    docs: EntryDocs,
+    /// Cloud entries linked to other vaults, which pulls leave alone.
+    foreign: Mutex<HashSet<String>>,
+    connection: Arc<Connection>,
+}
+
+impl Outbox {
+    /// Load the queue and mappings from `dir`. Starts offline and without credentials.
+    pub fn load(dir: &Path) -> Self {
+        Self::with_connection(dir, Arc::default())
+    }
+
+    /// Load the sync state of another vault from `dir`, keeping the
+    /// credentials and connectivity of this one.
+    pub fn reopen(&self, dir: &Path) -> Self {
+        Self::with_connection(dir, self.connection.clone())
+    }
+
+    fn with_connection(dir: &Path, connection: Arc<Connection>) -> Self {
+        Self {
+            queue: OutboundQueue::load(dir.join(QUEUE_FILE_NAME)),
+            mappings: CloudMappings::load(dir.join(MAPPINGS_FILE_NAME)),
+            cursor: ChangeCursor::load(dir.join(CURSOR_FILE_NAME)),
+            bases: Snapshots::new(dir.join(BASES_DIR_NAME)),
+            conflicts: Conflicts::load(dir),
+            docs: EntryDocs::load(&dir.join(CRDT_DIR_NAME)),
+            foreign: Mutex::new(HashSet::new()),
+            connection,
+        }
+    }
+
+    /// Cloud entries linked by the outbox whose state is kept in `dir`.
+    pub fn linked_cloud_ids(dir: &Path) -> HashSet<String> {
+        CloudMappings::load(dir.join(MAPPINGS_FILE_NAME)).cloud_ids()
+    }
+
+    /// Folders of the outbox kept in `dir` whose files are sealed with the
+    /// journal's vault, and so must be converted along with it.
+    pub fn sealed_dirs(dir: &Path) -> Vec<PathBuf> {
+        let mut dirs = vec![dir.join(BASES_DIR_NAME)];
+        dirs.extend(Conflicts::sides_dirs(dir));
+        dirs.push(EntryDocs::states_dir(&dir.join(CRDT_DIR_NAME)));
+        dirs
+    }
+
+    /// Leave the cloud entries `cloud_ids`, linked in other vaults, out of pulls
+    /// instead of importing them here.
+    pub fn set_foreign(&self, cloud_ids: HashSet<String>) {
+        *self.foreign.lock().unwrap_or_else(|e| e.into_inner()) = cloud_ids;
+    }
+
+    fn is_foreign(&self, cloud_id: &str) -> bool {
+        self.foreign
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(cloud_id)
+    }
+
+    pub fn mappings(&self) -> &CloudMappings {
+        &self.mappings
+    }
+
+    /// Link `entry_id` to `cloud_id` unless it already has a mapping.
+    ///
+    /// For entries published before the native queue existed.
+    pub fn link(&self, entry_id: &str, cloud_id: &str) -> Result<()> {
+        if self.mappings.get(entry_id).is_some() {
+            return Ok(());
+        }
+        self.mappings.insert(
+            entry_id,
+            CloudMapping {
+                cloud_id: cloud_id.to_string(),
+                published_at: Utc::now(),
+                last_server_timestamp: None,
+                content_hash: None,
+            },
+        )
+    }
+
+    pub fn conflicts(&self) -> &Conflicts {
+        &self.conflicts
+    }
+
+    pub fn docs(&self) -> &EntryDocs {
+        &self.docs
+    }
+
+    pub fn status(&self) -> SyncQueueStatus {
+        SyncQueueStatus {
+            online: self.connection.online.load(Ordering::SeqCst),
+            connected: self.api().is_some(),
+            pending: self.queue.snapshot(),
+        }
+    }
+
+    pub fn enqueue(&self, entry_id: &str, operation: Operation) -> Result<()> {
+        self.queue.enqueue(entry_id, operation)?;
+        self.wake();
+        Ok(())
+    }
+
+    /// Queue unpublishing every entry of `purged` that has a cloud copy.
+    ///
+    /// Trashed entries stay published until they are deleted for good.
+    pub fn unpublish_purged(&self, purged: &[TrashedEntry]) -> Result<()> {
+        for trashed in purged {
+            if self.mappings.get(&trashed.entry_id).is_some() {
+                self.enqueue(&trashed.entry_id, Operation::Unpublish)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move everything kept for each old entry ID in `renames` to its new one,
+    /// when entries get stable IDs.
+    pub fn rename_entries(&self, renames: &BTreeMap<String, String>) -> Result<()> {
+        if renames.is_empty() {
+            return Ok(());
+        }
+        self.mappings.rename_entries(renames)?;
+        self.queue.rename_entries(renames)?;
+        self.bases.rename_entries(renames)?;
+        self.conflicts.rename_entries(renames)?;
+        self.docs.rename_entries(renames)
+    }
+
+    /// Set or clear the API client used to drain the queue.
+    pub fn connect(&self, api: Option<ApiClient>) {
+        *self
+            .connection
+            .api
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = api;
+        self.wake();
+    }
+
+    /// Record connectivity. Coming back online retries every entry immediately.
+    pub fn set_online(&self, online: bool) -> Result<()> {
+        let was_online = self.connection.online.swap(online, Ordering::SeqCst);
+        if online && !was_online {
+            self.queue.retry_now()?;
+            self.wake();
+        }
+        Ok(())
+    }
+
+    /// Retry every entry now, ignoring backoff.
+    pub fn retry_now(&self) -> Result<()> {
+        self.queue.retry_now()?;
+        self.wake();
+        Ok(())
+    }
+
+    /// Ask the drainer to look at the queue.
+    pub fn wake(&self) {
+        self.connection.wake.notify_one();
+    }
+
+    fn api(&self) -> Option<ApiClient> {
+        self.connection
+            .api
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Apply every due operation, oldest entry first.
+    ///
+    /// Stops when the queue has nothing due, the session is locked, or the
+    /// server is unreachable.
+    pub async fn drain(&self, store: &EntryStore, session: &Session, emit: impl Fn(SyncEvent)) {
+        if !self.connection.online.load(Ordering::SeqCst) {
+            return;
+        }
+        let Some(api) = self.api() else {
+            return;
+        };
+        let Ok(keys) = session.keys_snapshot() else {
+            return;
+        };
+        let outbound = Outbound {
+            api: &api,
+            store,
+            keys: &keys,
+            mappings: &self.mappings,
+            bases: &self.bases,
+            conflicts: &self.conflicts,
+            docs: &self.docs,
+        };
+
+        let mut applied = false;
+        'entries: while let Some(pending) = self.queue.next_due(Utc::now()) {
+            for operation in pending.operations {
+                if !self.connection.online.load(Ordering::SeqCst) {
+                    break 'entries;
+                }
+                let entry_id = pending.entry_id.clone();
+                let had_conflict = self.conflicts.contains(&entry_id);
+                match outbound.apply(&entry_id, &operation).await {
+                    Ok(cloud_id) => {
+                        self.log_error(self.queue.finish(&entry_id, &operation));
+                        applied = true;
+                        if !had_conflict {
+                            self.emit_conflict(&entry_id, &emit);
+                        }
+                        emit(SyncEvent::Progress {
+                            entry_id,
+                            operation,
+                            cloud_id,
+                            remaining: self.queue.len(),
+                        });
+                    }
+                    Err(error) if outbound::is_permanent(&error) => {
+                        log::warn!("dropping {operation:?} for {entry_id}: {error}");
+                        self.log_error(self.queue.finish(&entry_id, &operation));
+                        emit(SyncEvent::Failed {
+                            entry_id,
+                            operation,
+                            error: error.to_string(),
+                            will_retry: false,
+                            next_attempt_at: None,
+                        });
+                    }
+                    Err(error) => {
+                        let failed = self.queue.fail(&entry_id, &error.to_string(), Utc::now());
+                        let next_attempt_at = failed.ok().flatten().and_then(|e| e.next_attempt_at);
+                        emit(SyncEvent::Failed {
+                            entry_id,
+                            operation,
+                            error: error.to_string(),
+                            will_retry: true,
+                            next_attempt_at,
+                        });
+                        if outbound::is_offline(&error) {
+                            break 'entries;
+                        }
+                        continue 'entries;
+                    }
+                }
+            }
+        }
+
+        if applied && self.queue.is_empty() {
+            emit(SyncEvent::Drained);
+        }
+    }
+
+    /// Bring in entries changed in the cloud since the last pull.
+    ///
+    /// Remote edits are merged into the journal, entries published from other
+    /// devices are imported and deleted cloud copies unlinked. Entries whose
+    /// merge differs from the cloud copy are queued for upload. Servers
+    /// without a change log get every published entry compared instead.
+    pub async fn pull(
+        &self,
+        store: &EntryStore,
+        session: &Session,
+        emit: impl Fn(SyncEvent),
+    ) -> Result<PullReport> {
+        let api = self
+            .api()
+            .ok_or_else(|| Error::Cloud("not signed in".into()))?;
+        let keys = session.keys_snapshot()?;
+        let outbound = Outbound {
+            api: &api,
+            store,
+            keys: &keys,
+            mappings: &self.mappings,
+            bases: &self.bases,
+            conflicts: &self.conflicts,
+            docs: &self.docs,
+        };
+
+        let mut report = PullReport::default();
+        let pulled = self.pull_changes(&outbound, &mut report, &emit).await;
+        match pulled {
+            Err(Error::Api { status: 404, .. }) => {
+                log::info!("server has no change log, comparing every published entry");
+            }
+            result => return result.map(|()| report),
+        }
+
+        for entry_id in self.mappings.entry_ids() {
+            if self.conflicts.contains(&entry_id) {
+                continue;
+            }
+            match outbound.pull(&entry_id).await {
+                Ok(pulled) => self.record_pull(entry_id, pulled, &mut report, &emit)?,
+                Err(error) if outbound::is_offline(&error) => return Err(error),
+                Err(error) => log::warn!("failed to pull {entry_id}: {error}"),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Apply the change log from the saved cursor on, saving it after every page.
+    ///
+    /// A change that fails for a reason retrying could fix stops the pull
+    /// before the cursor moves past it.
+    async fn pull_changes(
+        &self,
+        outbound: &Outbound<'_>,
+        report: &mut PullReport,
+        emit: &impl Fn(SyncEvent),
+    ) -> Result<()> {
+        let user_id = outbound.api.user_id();
+        let device_id = self.cursor.device_id();
+        loop {
+            let since = self.cursor.get(user_id);
+            let page = match outbound
+                .api
+                .list_changes(since.as_deref(), &device_id, CHANGE_PAGE_SIZE)
+                .await
+            {
+                // The log was compacted past our position; read it from the start.
+                Err(Error::Api { status: 410, .. }) if since.is_some() => {
+                    self.cursor.reset()?;
+                    continue;
+                }
+                result => result?,
+            };
+
+            for change in latest_changes(&page.changes) {
+                // Shared entries are read from the cloud and never join the journal.
+                let shared = change.author_id.as_deref().is_some_and(|a| a != user_id);
+                if shared {
+                    emit(SyncEvent::Shared {
+                        cloud_id: change.entry_id.clone(),
+                        deleted: change.deleted,
+                    });
+                    report.shared.push(change.entry_id.clone());
+                    continue;
+                }
+                // Linked to another vault, which pulls it when active.
+                if self.is_foreign(&change.entry_id) {
+                    continue;
+                }
+                match outbound.apply_change(change).await {
+                    Ok(Changed::Skipped) => {}
+                    Ok(Changed::Pulled { entry_id, pulled }) => {
+                        self.record_pull(entry_id, pulled, report, emit)?
+                    }
+                    Ok(Changed::Imported { entry_id, cloud_id }) => {
+                        emit(SyncEvent::Imported {
+                            entry_id: entry_id.clone(),
+                            cloud_id,
+                        });
+                        report.imported.push(entry_id);
+                    }
+                    Ok(Changed::Removed { entry_id }) => {
+                        emit(SyncEvent::Removed {
+                            entry_id: entry_id.clone(),
+                        });
+                        report.removed.push(entry_id);
+                    }
+                    Err(error) if outbound::is_permanent(&error) => {
+                        log::warn!(
+                            "skipping change {} of {}: {error}",
+                            change.seq,
+                            change.entry_id
+                        );
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+
+            self.cursor.set(user_id, &page.cursor)?;
+            if !page.has_more || page.changes.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn record_pull(
+        &self,
+        entry_id: String,
+        pulled: Pulled,
+        report: &mut PullReport,
+        emit: &impl Fn(SyncEvent),
+    ) -> Result<()> {
+        match pulled {
+            Pulled::Unchanged => {}
+            Pulled::Merged { needs_upload, .. } => {
+                if needs_upload {
+                    self.enqueue(&entry_id, Operation::Update)?;
+                }
+                report.merged.push(entry_id);
+            }
+            Pulled::Conflicted => {
+                self.emit_conflict(&entry_id, emit);
+                report.conflicts.push(entry_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Settle the conflict of `entry_id` and queue the result for upload.
+    ///
+    /// The conflict copy, if any, is deleted unless it was edited since.
+    pub fn resolve_conflict(
+        &self,
+        store: &EntryStore,
+        entry_id: &str,
+        resolution: Resolution,
+    ) -> Result<()> {
+        let vault = store.vault();
+        let record = self
+            .conflicts
+            .get(entry_id)
+            .ok_or_else(|| Error::NotFound(entry_id.to_string()))?;
+        let (local, remote) = self
+            .conflicts
+            .sides(vault, entry_id)
+            .ok_or_else(|| Error::NotFound(entry_id.to_string()))?;
+        let content = match resolution {
+            Resolution::Local => local,
+            Resolution::Remote => remote.clone(),
+            Resolution::Merged { content } => content,
+        };
+
+        store.save_entry(entry_id, &content)?;
+        if let Some(copy_id) = &record.copy_id {
+            if store
+                .get_entry(copy_id)?
+                .is_some_and(|copy| copy.content == remote)
+            {
+                store.delete_entry(copy_id)?;
+            }
+        }
+        // The cloud holds the remote side, which the resolution builds on.
+        self.bases.set(vault, entry_id, &remote)?;
+        self.mappings.touch(entry_id, record.remote_updated_at)?;
+        self.conflicts.remove(entry_id)?;
+        if content != remote {
+            self.enqueue(entry_id, Operation::Update)?;
+        }
+        Ok(())
+    }
+
+    fn emit_conflict(&self, entry_id: &str, emit: &impl Fn(SyncEvent)) {
+        if let Some(ConflictRecord { copy_id, .. }) = self.conflicts.get(entry_id) {
+            emit(SyncEvent::Conflict {
+                entry_id: entry_id.to_string(),
+                copy_id,
+            });
+        }
+    }
+
+    /// How long to wait before the next drain.
+    fn next_wait(&self) -> Duration {
+        self.queue
+            .next_attempt_at()
+            .and_then(|at| (at - Utc::now()).to_std().ok())
+            .map_or(IDLE_POLL, |wait| wait.min(IDLE_POLL))
+    }
+
+    fn log_error(&self, result: Result<()>) {
+        if let Err(error) = result {
+            log::warn!("failed to update sync queue: {error}");
+        }
+    }
+}
+
+/// The last change of every entry in `changes`, in log order.
+fn latest_changes(changes: &[EntryChange]) -> Vec<&EntryChange> {
+    let mut seen = std::collections::HashSet::new();
+    let mut latest: Vec<_> = changes
+        .iter()
+        .rev()
+        .filter(|change| seen.insert(change.entry_id.as_str()))
+        .collect();
+    latest.reverse();
+    latest
+}
+
+/// Start the task that drains the [`Outbox`] whenever it is woken or a retry is due.
+pub fn spawn_drainer(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            // A switch wakes the drainer, which then drains the new vault.
+            let (store, outbox) = app.state::<Vaults>().store_and_outbox();
+            outbox
+                .drain(&store, &app.state::<Session>(), |event| {
+                    emit_event(&app, &event)
+                })
+                .await;
+
+            let wait = outbox.next_wait();
+            tokio::select! {
+                _ = outbox.connection.wake.notified() => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    });
+}
+
+/// Emit `event` to the webview, first re-indexing entries the sync may have rewritten.
+pub(crate) fn emit_event(app: &AppHandle, event: &SyncEvent) {
+    let rewritten = match event {
+        SyncEvent::Progress { entry_id, .. } => vec![entry_id],
+        SyncEvent::Conflict { entry_id, copy_id } => {
+            std::iter::once(entry_id).chain(copy_id).collect()
+        }
+        SyncEvent::Imported { entry_id, .. } => vec![entry_id],
+        SyncEvent::Failed { .. } | SyncEvent::Removed { .. } | SyncEvent::Drained => Vec::new(),
+    };
+    let store = app.state::<Vaults>().store();
+    for entry_id in rewritten {
+        if let Err(error) = app.state::<SearchEngine>().refresh_entry(&store, entry_id) {
+            log::warn!("failed to re-index {entry_id}: {error}");
+        }
+    }
+
+    if let Err(error) = app.emit(SYNC_EVENT, event) {
+        log::warn!("failed to emit sync event: {error}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::api::mock::{MockResponse, MockServer};
+    use crate::api::{Credentials, RetryPolicy};
+    use crate::crypto::keys::UserKeyPair;
+
+    fn entry_row(id: &str, owner: &UserKeyPair) -> serde_json::Value {
+        let wrapped = crate::crypto::wrap_key(&[7u8; 32], &owner.public, owner.secret()).unwrap();
+        json!({
+            "id": id,
+            "author_id": "user-1",
+            "encrypted_title": "t",
+            "encrypted_content": "c",
+            "encryption_metadata": {},
+            "title_hash": "h",
+            "created_at": "2026-01-01T00:00:00Z",
+            "updated_at": "2026-01-01T00:00:00Z",
+            "owner_encrypted_entry_key": wrapped.encrypted_entry_key_b64,
+            "owner_key_nonce": wrapped.key_nonce_b64,
+        })
+    }
+
+    #[tokio::test]
+    async fn drains_after_reconnecting_and_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = dir.path().join("journal");
+        fs::create_dir_all(&journal).unwrap();
+        fs::write(journal.join("hello.md"), "---\ntags: [a]\n---\nHi").unwrap();
+        let store = EntryStore::new(&journal);
+        let hello = store.entry_ids().unwrap().remove(0);
+
+        let session = Session::load(dir.path().join("keys.json"));
+        session.create_keys("user-1", "pw").unwrap();
+        let keys = session.with_keys(|pair| Ok(pair.clone())).unwrap();
+
+        let server = MockServer::start().await;
+        server.respond(
+            "POST",
+            "/entries",
+            [MockResponse::data(
+                json!({ "entry": entry_row("cloud-1", &keys) }),
+            )],
+        );
+        server.respond(
+            "GET",
+            "/entries/*",
+            [MockResponse::data(entry_row("cloud-1", &keys))],
+        );
+        server.respond(
+            "PUT",
+            "/entries/*",
+            [MockResponse::data(entry_row("cloud-1", &keys))],
+        );
+        server.respond("GET", "/user-tags", [MockResponse::data(json!([]))]);
+        server.respond(
+            "POST",
+            "/entry-access-keys/batch",
+            [MockResponse::data(json!([]))],
+        );
+
+        let state = dir.path().join("state");
+        let outbox = Outbox::load(&state);
+        outbox
+            .enqueue(
+                &hello,
+                Operation::Publish {
+                    tag_ids: vec!["tag-1".into()],
+                },
+            )
+            .unwrap();
+        outbox.enqueue(&hello, Operation::Update).unwrap();
+
+        // Offline: nothing is sent and the queue survives a restart.
+        let events = Mutex::new(Vec::new());
+        outbox
+            .drain(&store, &session, |e| events.lock().unwrap().push(e))
+            .await;
+        assert!(server.requests().is_empty());
+        let outbox = Outbox::load(&state);
+        assert_eq!(outbox.status().pending.len(), 1);
+
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        let retry = RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        };
+        outbox.connect(Some(
+            ApiClient::new("http://127.0.0.1:1", credentials.clone()).with_retry(retry),
+        ));
+        outbox.set_online(true).unwrap();
+        outbox
+            .drain(&store, &session, |e| events.lock().unwrap().push(e))
+            .await;
+        let pending = &outbox.status().pending[0];
+        assert_eq!(pending.attempts, 1);
+        assert!(pending.next_attempt_at.is_some());
+        assert!(matches!(
+            &events.lock().unwrap()[0],
+            SyncEvent::Failed {
+                will_retry: true,
+                ..
+            }
+        ));
+
+        outbox.connect(Some(
+            ApiClient::new(&server.url(), credentials).with_retry(retry),
+        ));
+        outbox.retry_now().unwrap();
+        events.lock().unwrap().clear();
+        outbox
+            .drain(&store, &session, |e| events.lock().unwrap().push(e))
+            .await;
+
+        assert!(outbox.status().pending.is_empty());
+        assert_eq!(outbox.mappings().get(&hello).unwrap().cloud_id, "cloud-1");
+        let published = &server.requests_to("POST", "/entries")[0].body;
+        assert_eq!(published["tag_ids"], json!(["tag-1"]));
+        // The author always receives an access key.
+        let batch = &server.requests_to("POST", "/entry-access-keys/batch")[0].body;
+        assert_eq!(batch["access_keys"][0]["user_id"], "user-1");
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                SyncEvent::Progress {
+                    entry_id: hello.clone(),
+                    operation: Operation::Publish {
+                        tag_ids: vec!["tag-1".into()]
+                    },
+                    cloud_id: Some("cloud-1".into()),
+                    remaining: 0,
+                },
+                SyncEvent::Drained,
+            ]
+        );
+
+        // Unpublishing removes the mapping; a missing cloud copy is not an error.
+        server.respond(
+            "DELETE",
+            "/entry-access-keys/entry/*",
+            [MockResponse::status(404)],
+        );
+        server.respond("DELETE", "/entries/*", [MockResponse::data(json!({}))]);
+        outbox.enqueue(&hello, Operation::Unpublish).unwrap();
+        outbox.drain(&store, &session, |_| {}).await;
+        assert!(outbox.mappings().get(&hello).is_none());
+        assert!(Outbox::load(&state).status().pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn background_sync_does_not_hold_off_the_idle_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join("journal"));
+        let session = Session::load(dir.path().join("keys.json"));
+        session.create_keys("user-1", "pw").unwrap();
+        session
+            .set_idle_timeout(Some(Duration::from_secs(60)))
+            .unwrap();
+        let idle_at = std::time::Instant::now() + Duration::from_secs(60);
+
+        let outbox = Outbox::load(&dir.path().join("state"));
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        let retry = RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        };
+        outbox.connect(Some(
+            ApiClient::new("http://127.0.0.1:1", credentials).with_retry(retry),
+        ));
+        outbox.set_online(true).unwrap();
+        outbox.enqueue("entry", Operation::Update).unwrap();
+        outbox.drain(&store, &session, |_| {}).await;
+        assert!(outbox.pull(&store, &session, |_| {}).await.is_err());
+
+        assert!(session.is_idle(idle_at));
+    }
+
+    fn encrypted_row(owner: &UserKeyPair, content: &str, updated_at: &str) -> serde_json::Value {
+        object_row(owner, &json!({ "content": content }), updated_at)
+    }
+
+    fn object_row(
+        owner: &UserKeyPair,
+        object: &serde_json::Value,
+        updated_at: &str,
+    ) -> serde_json::Value {
+        let data = crate::crypto::encrypt_entry(object, owner).unwrap();
+        json!({
+            "id": "cloud-1",
+            "author_id": "user-1",
+            "encrypted_title": "t",
+            "encrypted_content": data.encrypted_content_b64,
+            "encryption_metadata": { "contentNonceB64": data.content_nonce_b64 },
+            "title_hash": "h",
+            "created_at": "2026-01-01T00:00:00Z",
+            "updated_at": updated_at,
+            "owner_encrypted_entry_key": data.encrypted_entry_key_b64,
+            "owner_key_nonce": data.key_nonce_b64,
+        })
+    }
+
+    #[tokio::test]
+    async fn pull_merges_remote_edits_and_resolves_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join("journal"));
+        let session = Session::load(dir.path().join("keys.json"));
+        session.create_keys("user-1", "pw").unwrap();
+        let keys = session.with_keys(|pair| Ok(pair.clone())).unwrap();
+
+        let base = "one\ntwo\nthree\n";
+        let day = store.create_entry("Day").unwrap();
+        store.save_entry(&day, base).unwrap();
+        let outbox = Outbox::load(&dir.path().join("state"));
+        let synced_at = "2026-01-01T00:00:00Z".parse().unwrap();
+        outbox
+            .mappings()
+            .insert(
+                &day,
+                mappings::CloudMapping {
+                    cloud_id: "cloud-1".into(),
+                    published_at: synced_at,
+                    last_server_timestamp: Some(synced_at),
+                    content_hash: None,
+                },
+            )
+            .unwrap();
+        outbox.bases.set(store.vault(), &day, base).unwrap();
+
+        let server = MockServer::start().await;
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        outbox.connect(Some(ApiClient::new(&server.url(), credentials)));
+
+        // Unchanged in the cloud.
+        server.respond(
+            "GET",
+            "/entries/*",
+            [MockResponse::data(encrypted_row(
+                &keys,
+                base,
+                "2026-01-01T00:00:00Z",
+            ))],
+        );
+        let report = outbox.pull(&store, &session, |_| {}).await.unwrap();
+        assert_eq!(report, PullReport::default());
+
+        // Edits to different lines merge, and the result is queued for upload.
+        store.save_entry(&day, "ONE\ntwo\nthree\n").unwrap();
+        let remote = "one\ntwo\nthree\nfour\n";
+        server.respond(
+            "GET",
+            "/entries/*",
+            [MockResponse::data(encrypted_row(
+                &keys,
+                remote,
+                "2026-01-02T00:00:00Z",
+            ))],
+        );
+        let report = outbox.pull(&store, &session, |_| {}).await.unwrap();
+        assert_eq!(report.merged, [day.clone()]);
+        assert_eq!(
+            store.get_entry(&day).unwrap().unwrap().content,
+            "ONE\ntwo\nthree\nfour\n"
+        );
+        assert_eq!(outbox.status().pending[0].operations, [Operation::Update]);
+
+        // Overlapping edits are marked and wait for a resolution.
+        store.save_entry(&day, "ONE\nmine\nthree\nfour\n").unwrap();
+        let remote = "one\ntheirs\nthree\nfour\n";
+        server.respond(
+            "GET",
+            "/entries/*",
+            [MockResponse::data(encrypted_row(
+                &keys,
+                remote,
+                "2026-01-03T00:00:00Z",
+            ))],
+        );
+        let events = Mutex::new(Vec::new());
+        let report = outbox
+            .pull(&store, &session, |e| events.lock().unwrap().push(e))
+            .await
+            .unwrap();
+        assert_eq!(report.conflicts, [day.clone()]);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [SyncEvent::Conflict {
+                entry_id: day.clone(),
+                copy_id: None
+            }]
+        );
+        let marked = store.get_entry(&day).unwrap().unwrap().content;
+        assert!(marked.contains(merge::MARKER_LOCAL) && marked.contains("theirs"));
+
+        let resolved = "ONE\ntheirs\nmine\nthree\nfour\n";
+        let resolution = Resolution::Merged {
+            content: resolved.into(),
+        };
+        outbox.resolve_conflict(&store, &day, resolution).unwrap();
+        assert!(!outbox.conflicts().contains(&day));
+        assert_eq!(store.get_entry(&day).unwrap().unwrap().content, resolved);
+        assert_eq!(outbox.bases.get(store.vault(), &day).unwrap(), remote);
+        // The resolved version has the latest remote change as its base.
+        let report = outbox.pull(&store, &session, |_| {}).await.unwrap();
+        assert_eq!(report, PullReport::default());
+    }
+
+    /// A change log served two rows per page, like `GET /sync/changes`.
+    fn serve_change_log(server: &MockServer, log: std::sync::Arc<Mutex<Vec<serde_json::Value>>>) {
+        server.handle("GET", "/sync/changes", move |request| {
+            let query = request.query.clone().unwrap_or_default();
+            assert!(query.contains("device_id="));
+            let since: i64 = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("since="))
+                .map_or(0, |since| since.parse().unwrap());
+            let log = log.lock().unwrap();
+            let rest: Vec<_> = log
+                .iter()
+                .filter(|c| c["seq"].as_i64() > Some(since))
+                .collect();
+            let page: Vec<_> = rest.iter().take(2).cloned().cloned().collect();
+            let cursor = page.last().map_or(since, |c| c["seq"].as_i64().unwrap());
+            MockResponse::data(json!({
+                "changes": page,
+                "cursor": cursor.to_string(),
+                "has_more": rest.len() > 2,
+            }))
+        });
+    }
+
+    fn change(seq: i64, entry_id: &str, row: Option<&serde_json::Value>) -> serde_json::Value {
+        let content_hash =
+            row.map(|row| outbound::hash(row["encrypted_content"].as_str().unwrap().as_bytes()));
+        json!({
+            "seq": seq,
+            "entry_id": entry_id,
+            "deleted": row.is_none(),
+            "content_hash": content_hash,
+            "changed_at": "2026-01-02T00:00:00Z",
+        })
+    }
+
+    #[tokio::test]
+    async fn pull_resumes_from_the_change_cursor_and_applies_tombstones() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join("journal"));
+        let session = Session::load(dir.path().join("keys.json"));
+        session.create_keys("user-1", "pw").unwrap();
+        let keys = session.with_keys(|pair| Ok(pair.clone())).unwrap();
+
+        let day = store.create_entry("Day").unwrap();
+        store.save_entry(&day, "one\n").unwrap();
+        let state = dir.path().join("state");
+        let outbox = Outbox::load(&state);
+        outbox.link(&day, "cloud-1").unwrap();
+        outbox.bases.set(store.vault(), &day, "one\n").unwrap();
+
+        let edited = encrypted_row(&keys, "one\ntwo\n", "2026-01-02T00:00:00Z");
+        let mut phone = object_row(
+            &keys,
+            &json!({ "title": "From phone", "content": "Hello\n" }),
+            "2026-01-02T00:00:00Z",
+        );
+        phone["id"] = json!("cloud-2");
+        let mut tablet = object_row(
+            &keys,
+            &json!({ "title": "From tablet", "content": "Hi\n" }),
+            "2026-01-02T00:00:00Z",
+        );
+        tablet["id"] = json!("cloud-3");
+
+        let server = MockServer::start().await;
+        server.respond(
+            "GET",
+            "/entries/cloud-1",
+            [MockResponse::data(edited.clone())],
+        );
+        server.respond(
+            "GET",
+            "/entries/cloud-2",
+            [MockResponse::data(phone.clone())],
+        );
+        server.respond(
+            "GET",
+            "/entries/cloud-3",
+            [
+                MockResponse::status(500),
+                MockResponse::data(tablet.clone()),
+            ],
+        );
+        let log = std::sync::Arc::new(Mutex::new(vec![
+            change(1, "cloud-1", Some(&edited)),
+            change(2, "cloud-2", Some(&phone)),
+            change(3, "cloud-3", Some(&tablet)),
+        ]));
+        serve_change_log(&server, log.clone());
+
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        let retry = RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        };
+        let api = ApiClient::new(&server.url(), credentials).with_retry(retry);
+        outbox.connect(Some(api.clone()));
+
+        // The second page fails; the first one is kept.
+        assert!(outbox.pull(&store, &session, |_| {}).await.is_err());
+        assert_eq!(
+            store.get_entry(&day).unwrap().unwrap().content,
+            "one\ntwo\n"
+        );
+        let phone_id = outbox.mappings().entry_for("cloud-2").unwrap();
+        assert_eq!(
+            store.get_entry(&phone_id).unwrap().unwrap().content,
+            "Hello\n"
+        );
+
+        // After a restart only the failed page is requested again.
+        let outbox = Outbox::load(&state);
+        outbox.connect(Some(api));
+        let events = Mutex::new(Vec::new());
+        let report = outbox
+            .pull(&store, &session, |e| events.lock().unwrap().push(e))
+            .await
+            .unwrap();
+        let last = server.requests_to("GET", "/sync/changes").pop().unwrap();
+        assert!(last.query.unwrap().contains("since=2"));
+        let tablet_id = outbox.mappings().entry_for("cloud-3").unwrap();
+        assert_eq!(report.imported, [tablet_id.clone()]);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [SyncEvent::Imported {
+                entry_id: tablet_id,
+                cloud_id: "cloud-3".into()
+            }]
+        );
+
+        // Our own upload echoes back with a known hash and is not downloaded;
+        // the deleted cloud copy unlinks the entry but keeps it locally.
+        log.lock().unwrap().extend([
+            change(4, "cloud-2", Some(&phone)),
+            change(5, "cloud-1", None),
+        ]);
+        let report = outbox.pull(&store, &session, |_| {}).await.unwrap();
+        assert_eq!(report.removed, [day.clone()]);
+        assert!(report.imported.is_empty() && report.merged.is_empty());
+        assert!(outbox.mappings().get(&day).is_none());
+        assert!(store.get_entry(&day).unwrap().is_some());
+        assert_eq!(server.requests_to("GET", "/entries/cloud-1").len(), 1);
+        assert_eq!(server.requests_to("GET", "/entries/cloud-2").len(), 1);
+
+        // Nothing new: one request, and the cursor stays put.
+        let report = outbox.pull(&store, &session, |_| {}).await.unwrap();
+        assert_eq!(report, PullReport::default());
+        let last = server.requests_to("GET", "/sync/changes").pop().unwrap();
+        assert!(last.query.unwrap().contains("since=5"));
+    }
+
+    #[tokio::test]
+    async fn pull_leaves_entries_of_other_vaults_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join("journal"));
+        let session = Session::load(dir.path().join("keys.json"));
+        session.create_keys("user-1", "pw").unwrap();
+        let keys = session.with_keys(|pair| Ok(pair.clone())).unwrap();
+
+        let mut work = object_row(
+            &keys,
+            &json!({ "title": "Work", "content": "Hi\n" }),
+            "2026-01-02T00:00:00Z",
+        );
+        work["id"] = json!("cloud-1");
+        let server = MockServer::start().await;
+        server.respond(
+            "GET",
+            "/entries/cloud-1",
+            [MockResponse::data(work.clone())],
+        );
+        let log = vec![change(1, "cloud-1", Some(&work))];
+        serve_change_log(&server, std::sync::Arc::new(Mutex::new(log)));
+
+        let outbox = Outbox::load(&dir.path().join("state"));
+        outbox.set_foreign(HashSet::from(["cloud-1".to_string()]));
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        outbox.connect(Some(ApiClient::new(&server.url(), credentials)));
+
+        let report = outbox.pull(&store, &session, |_| {}).await.unwrap();
+        assert_eq!(report, PullReport::default());
+        assert!(server.requests_to("GET", "/entries/cloud-1").is_empty());
+        assert!(outbox.mappings().entry_for("cloud-1").is_none());
+    }
+
+    #[tokio::test]
+    async fn pull_reports_shared_entries_without_importing_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join("journal"));
+        let session = Session::load(dir.path().join("keys.json"));
+        session.create_keys("user-1", "pw").unwrap();
+
+        let mut edited = change(1, "cloud-9", None);
+        edited["deleted"] = json!(false);
+        edited["author_id"] = json!("friend");
+        let mut revoked = change(2, "cloud-8", None);
+        revoked["author_id"] = json!("friend");
+        let server = MockServer::start().await;
+        let log = vec![edited, revoked];
+        serve_change_log(&server, std::sync::Arc::new(Mutex::new(log)));
+
+        let outbox = Outbox::load(&dir.path().join("state"));
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        outbox.connect(Some(ApiClient::new(&server.url(), credentials)));
+
+        let events = Mutex::new(Vec::new());
+        let report = outbox
+            .pull(&store, &session, |event| events.lock().unwrap().push(event))
+            .await
+            .unwrap();
+        assert_eq!(report.shared, ["cloud-9", "cloud-8"]);
+        assert!(report.imported.is_empty() && report.removed.is_empty());
+        assert_eq!(
+            events.into_inner().unwrap(),
+            [
+                SyncEvent::Shared {
+                    cloud_id: "cloud-9".into(),
+                    deleted: false
+                },
+                SyncEvent::Shared {
+                    cloud_id: "cloud-8".into(),
+                    deleted: true
+                },
+            ]
+        );
+        assert!(server.requests_to("GET", "/entries/*").is_empty());
+        assert!(store.list_entries().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn crdt_mode_merges_concurrent_edits_of_the_same_lines() {
+        use base64::Engine;
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join("journal"));
+        let session = Session::load(dir.path().join("keys.json"));
+        session.create_keys("user-1", "pw").unwrap();
+        let keys = session.with_keys(|pair| Ok(pair.clone())).unwrap();
+
+        let day = store.create_entry("Day").unwrap();
+        store.save_entry(&day, "Morning.\nEvening.\n").unwrap();
+        let outbox = Outbox::load(&dir.path().join("state"));
+        assert_eq!(outbox.docs().enable(&store).unwrap(), 1);
+        let synced_at = "2026-01-01T00:00:00Z".parse().unwrap();
+        outbox
+            .mappings()
+            .insert(
+                &day,
+                mappings::CloudMapping {
+                    cloud_id: "cloud-1".into(),
+                    published_at: synced_at,
+                    last_server_timestamp: Some(synced_at),
+                    content_hash: None,
+                },
+            )
+            .unwrap();
+
+        // The phone edits the same line as the laptop, starting from the shared document.
+        let state = outbox
+            .docs()
+            .capture(store.vault(), &day, "Morning.\nEvening.\n")
+            .unwrap()
+            .unwrap();
+        let phone = crate::crdt::EntryDoc::decode(99, &state).unwrap();
+        phone.set_text("Morning.\nEvening, tired.\n");
+        let object = json!({
+            "content": phone.text(),
+            "crdt": base64::engine::general_purpose::STANDARD.encode(phone.encode()),
+        });
+        store.save_entry(&day, "Morning run.\nEvening!\n").unwrap();
+
+        let server = MockServer::start().await;
+        server.respond(
+            "GET",
+            "/entries/*",
+            [MockResponse::data(object_row(
+                &keys,
+                &object,
+                "2026-01-02T00:00:00Z",
+            ))],
+        );
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        outbox.connect(Some(ApiClient::new(&server.url(), credentials)));
+
+        let report = outbox.pull(&store, &session, |_| {}).await.unwrap();
+        assert_eq!(report.merged, [day.clone()]);
+        assert!(outbox.conflicts().list().is_empty());
+        let merged = store.get_entry(&day).unwrap().unwrap().content;
+        assert!(merged.starts_with("Morning run.\nEvening"));
+        assert!(merged.contains(", tired") && merged.contains('!'));
+        assert_eq!(outbox.status().pending[0].operations, [Operation::Update]);
+    }
+
+    #[tokio::test]
+    async fn crdt_mode_merges_documents_without_shared_history_three_way() {
+        use base64::Engine;
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join("journal"));
+        let session = Session::load(dir.path().join("keys.json"));
+        session.create_keys("user-1", "pw").unwrap();
+        let keys = session.with_keys(|pair| Ok(pair.clone())).unwrap();
+
+        let day = store.create_entry("Day").unwrap();
+        store.save_entry(&day, "Morning.\nEvening.\n").unwrap();
+        let outbox = Outbox::load(&dir.path().join("state"));
+        outbox
+            .bases
+            .set(store.vault(), &day, "Morning.\nEvening.\n")
+            .unwrap();
+        outbox.docs().enable(&store).unwrap();
+        let synced_at = "2026-01-01T00:00:00Z".parse().unwrap();
+        outbox
+            .mappings()
+            .insert(
+                &day,
+                mappings::CloudMapping {
+                    cloud_id: "cloud-1".into(),
+                    published_at: synced_at,
+                    last_server_timestamp: Some(synced_at),
+                    content_hash: None,
+                },
+            )
+            .unwrap();
+
+        // The phone turned CRDT mode on by itself, so its document starts afresh.
+        let phone = crate::crdt::EntryDoc::new(99);
+        phone.set_text("Morning.\nEvening, tired.\n");
+        let object = json!({
+            "content": phone.text(),
+            "crdt": base64::engine::general_purpose::STANDARD.encode(phone.encode()),
+        });
+        store.save_entry(&day, "Morning run.\nEvening.\n").unwrap();
+
+        let server = MockServer::start().await;
+        server.respond(
+            "GET",
+            "/entries/*",
+            [MockResponse::data(object_row(
+                &keys,
+                &object,
+                "2026-01-02T00:00:00Z",
+            ))],
+        );
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        outbox.connect(Some(ApiClient::new(&server.url(), credentials)));
+
+        let report = outbox.pull(&store, &session, |_| {}).await.unwrap();
+        assert_eq!(report.merged, [day.clone()]);
+        assert!(outbox.conflicts().list().is_empty());
+        let merged = store.get_entry(&day).unwrap().unwrap().content;
+        assert_eq!(merged, "Morning run.\nEvening, tired.\n");
+        assert_eq!(outbox.status().pending[0].operations, [Operation::Update]);
+
+        // The phone's history was adopted, so later merges go through the document.
+        let later = outbox
+            .docs()
+            .merge_remote(store.vault(), &day, &merged, &phone.encode())
+            .unwrap();
+        assert_eq!(
+            later.map(|(text, _)| text).as_deref(),
+            Some(merged.as_str())
+        );
+    }
+}
@@ -0,0 +1,576 @@
+//! Applying queued [`Operation`]s to the cloud.
+//!
+//! Every operation is idempotent, so a retry after a partial failure or a
+//! crash between the request and the queue update is harmless.
+//!
+//! Before overwriting a cloud copy that changed since our last sync, the
+//! remote version is three-way merged into the local entry against the
+//! last-synced base; conflicted entries are not uploaded until resolved.
+//! In CRDT mode the entry's document travels along and remote changes are
+//! merged through it instead, which never conflicts.
+//!
+//! Rows of the server's change log are applied with [`Outbound::apply_change`],
+//! which only downloads an entry when its content hash differs from the
+//! version we last synced.
+
+use std::collections::BTreeMap;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha512};
+
+use super::conflicts::{ConflictRecord, ConflictStyle, Conflicts};
+use super::mappings::{CloudMapping, CloudMappings};
+use super::merge::{self, BodyConflicts};
+use super::queue::Operation;
+use super::snapshots::Snapshots;
+use crate::api::models::{Entry, EntryChange, EntryPayload, NewAccessKey};
+use crate::api::ApiClient;
+use crate::crdt::EntryDocs;
+use crate::crypto::keys::{public_key_from_b64, UserKeyPair};
+use crate::crypto::{self, EncryptedEntryData};
+use crate::error::{Error, Result};
+use crate::frontmatter;
+use crate::store::{EntryStore, JournalEntry};
+
+/// Length of the content prefix hashed into `content_preview_hash`, in UTF-16 units.
+const PREVIEW_HASH_LENGTH: usize = 100;
+
+/// Title of imported entries whose cloud object has none.
+const UNTITLED: &str = "Untitled Entry";
+
+/// What an operation needs to run.
+pub struct Outbound<'a> {
+    pub api: &'a ApiClient,
+    pub store: &'a EntryStore,
+    pub keys: &'a UserKeyPair,
+    pub mappings: &'a CloudMappings,
+    /// Content as of the last sync, the base of three-way merges.
+    pub bases: &'a Snapshots,
+    pub conflicts: &'a Conflicts,
+    pub docs: &'a EntryDocs,
+}
+
+/// Outcome of bringing in the cloud version of an entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pulled {
+    /// The cloud copy has not changed since the last sync.
+    Unchanged,
+    /// The remote changes were merged into `content`, which now differs from
+    /// the cloud copy if `needs_upload`.
+    Merged { content: String, needs_upload: bool },
+    /// The merge left conflicts; see [`Conflicts`].
+    Conflicted,
+}
+
+/// Outcome of applying one row of the change log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Changed {
+    /// Nothing to do: the content hash matches, or the entry is not ours.
+    Skipped,
+    /// A published entry was reconciled with its cloud copy.
+    Pulled { entry_id: String, pulled: Pulled },
+    /// A cloud entry this device did not know became a new local entry.
+    Imported { entry_id: String, cloud_id: String },
+    /// The cloud copy was deleted; the local entry is kept, unpublished.
+    Removed { entry_id: String },
+}
+
+impl Outbound<'_> {
+    /// Apply `operation` to the cloud copy of `entry_id`.
+    ///
+    /// Returns the entry's cloud ID, if it has one afterwards.
+    pub async fn apply(&self, entry_id: &str, operation: &Operation) -> Result<Option<String>> {
+        match operation {
+            Operation::Publish { tag_ids } => self.publish(entry_id, tag_ids).await,
+            Operation::Update => self.update(entry_id).await,
+            Operation::Unpublish => self.unpublish(entry_id).await.map(|()| None),
+            Operation::Share { tag_ids } => self.share(entry_id, tag_ids).await,
+            Operation::Revoke { user_ids } => self.revoke(entry_id, user_ids).await,
+        }
+    }
+
+    async fn publish(&self, entry_id: &str, tag_ids: &[String]) -> Result<Option<String>> {
+        if self.mappings.get(entry_id).is_some() {
+            match self.update(entry_id).await {
+                Ok(Some(_)) => {}
+                Ok(None) => return Ok(None),
+                // The cloud copy was deleted elsewhere; publish a new one.
+                Err(Error::Api { status: 404, .. }) => {
+                    self.mappings.remove(entry_id)?;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        if self.mappings.get(entry_id).is_none() {
+            let Some(entry) = self.store.get_entry(entry_id)? else {
+                return Ok(None);
+            };
+            let object = self.upload_object(&entry)?;
+            let encrypted = crypto::encrypt_entry(&object, self.keys)?;
+            let payload = entry_payload(&entry, encrypted, Some(tag_ids.to_vec()));
+            let created = self.api.create_entry(&payload).await?;
+            self.bases
+                .set(self.store.vault(), entry_id, &entry.content)?;
+            self.mappings.insert(
+                entry_id,
+                CloudMapping {
+                    cloud_id: created.id,
+                    published_at: Utc::now(),
+                    last_server_timestamp: Some(created.updated_at),
+                    content_hash: Some(hash(payload.encrypted_content.as_bytes())),
+                },
+            )?;
+        }
+
+        if !tag_ids.is_empty() {
+            self.share(entry_id, tag_ids).await?;
+        }
+        Ok(self.mappings.get(entry_id).map(|m| m.cloud_id))
+    }
+
+    async fn update(&self, entry_id: &str) -> Result<Option<String>> {
+        let Some(mapping) = self.mappings.get(entry_id) else {
+            return Ok(None);
+        };
+        let Some(mut entry) = self.store.get_entry(entry_id)? else {
+            return Ok(None);
+        };
+        if self.conflicts.contains(entry_id) {
+            // Uploaded once the conflict is resolved.
+            return Ok(Some(mapping.cloud_id));
+        }
+
+        let cloud = self.api.get_entry(&mapping.cloud_id).await?;
+        match self.reconcile(entry_id, &entry, &mapping, &cloud)? {
+            Pulled::Unchanged => {}
+            Pulled::Merged {
+                content,
+                needs_upload: true,
+            } => entry.content = content,
+            Pulled::Merged { .. } | Pulled::Conflicted => return Ok(Some(mapping.cloud_id)),
+        }
+
+        let (wrapped_key, key_nonce) = owner_key(&cloud)?;
+        let object = self.upload_object(&entry)?;
+        let encrypted =
+            crypto::encrypt_entry_with_key(&object, &wrapped_key, &key_nonce, self.keys)?;
+        let payload = entry_payload(&entry, encrypted, None);
+        let updated = self.api.update_entry(&mapping.cloud_id, &payload).await?;
+        self.bases
+            .set(self.store.vault(), entry_id, &entry.content)?;
+        self.mappings.touch(entry_id, updated.updated_at)?;
+        self.mappings
+            .set_content_hash(entry_id, &hash(payload.encrypted_content.as_bytes()))?;
+        Ok(Some(mapping.cloud_id))
+    }
+
+    /// Merge the cloud version of `entry_id` into the local entry if it changed.
+    pub async fn pull(&self, entry_id: &str) -> Result<Pulled> {
+        let Some(mapping) = self.mappings.get(entry_id) else {
+            return Ok(Pulled::Unchanged);
+        };
+        if self.conflicts.contains(entry_id) {
+            return Ok(Pulled::Conflicted);
+        }
+        let Some(entry) = self.store.get_entry(entry_id)? else {
+            return Ok(Pulled::Unchanged);
+        };
+        let cloud = self.api.get_entry(&mapping.cloud_id).await?;
+        self.reconcile(entry_id, &entry, &mapping, &cloud)
+    }
+
+    /// Apply one row of the change log.
+    pub async fn apply_change(&self, change: &EntryChange) -> Result<Changed> {
+        let Some(entry_id) = self.mappings.entry_for(&change.entry_id) else {
+            if change.deleted {
+                return Ok(Changed::Skipped);
+            }
+            return self.import(&change.entry_id).await;
+        };
+        if change.deleted {
+            self.mappings.remove(&entry_id)?;
+            self.bases.remove(&entry_id)?;
+            self.conflicts.remove(&entry_id)?;
+            return Ok(Changed::Removed { entry_id });
+        }
+
+        let synced = self.mappings.get(&entry_id).and_then(|m| m.content_hash);
+        if change.content_hash.is_some() && change.content_hash == synced {
+            return Ok(Changed::Skipped);
+        }
+        let pulled = self.pull(&entry_id).await?;
+        Ok(Changed::Pulled { entry_id, pulled })
+    }
+
+    /// Write the cloud entry `cloud_id` to a new local entry and link the two.
+    async fn import(&self, cloud_id: &str) -> Result<Changed> {
+        let cloud = match self.api.get_entry(cloud_id).await {
+            // Deleted since; its tombstone follows.
+            Err(Error::Api { status: 404, .. }) => return Ok(Changed::Skipped),
+            result => result?,
+        };
+        let object = self.decrypt_object(&cloud)?;
+        let content = object_content(&object, &cloud)?;
+        let title = object
+            .get("title")
+            .and_then(Value::as_str)
+            .filter(|title| !title.trim().is_empty())
+            .unwrap_or(UNTITLED);
+
+        let entry_id = self.store.create_entry(title)?;
+        self.store.save_entry(&entry_id, &content)?;
+        self.bases.set(self.store.vault(), &entry_id, &content)?;
+        self.mappings.insert(
+            &entry_id,
+            CloudMapping {
+                cloud_id: cloud.id.clone(),
+                published_at: cloud.created_at,
+                last_server_timestamp: Some(cloud.updated_at),
+                content_hash: Some(hash(cloud.encrypted_content.as_bytes())),
+            },
+        )?;
+        Ok(Changed::Imported {
+            entry_id,
+            cloud_id: cloud.id,
+        })
+    }
+
+    /// Three-way merge `cloud` into `entry` if the cloud copy changed since the last sync.
+    ///
+    /// Entries synced before bases were kept have none; for them the newer
+    /// side wins, as it did before.
+    fn reconcile(
+        &self,
+        entry_id: &str,
+        entry: &JournalEntry,
+        mapping: &CloudMapping,
+        cloud: &Entry,
+    ) -> Result<Pulled> {
+        if mapping
+            .last_server_timestamp
+            .is_some_and(|synced| cloud.updated_at <= synced)
+        {
+            if mapping.content_hash.is_none() {
+                let content_hash = hash(cloud.encrypted_content.as_bytes());
+                self.mappings.set_content_hash(entry_id, &content_hash)?;
+            }
+            return Ok(Pulled::Unchanged);
+        }
+
+        let vault = self.store.vault();
+        let object = self.decrypt_object(cloud)?;
+        let remote = object_content(&object, cloud)?;
+
+        // A remote document without shared history is merged three-way
+        // below, and its history adopted afterwards.
+        let mut unrelated_doc = None;
+        let remote_doc = object.get("crdt").and_then(Value::as_str);
+        if let Some(state) = remote_doc.filter(|_| self.docs.is_enabled()) {
+            let state = STANDARD
+                .decode(state)
+                .map_err(|e| Error::Crdt(e.to_string()))?;
+            let merged = self
+                .docs
+                .merge_remote(vault, entry_id, &entry.content, &state)?;
+            if let Some((content, needs_upload)) = merged {
+                if content != entry.content {
+                    self.store.save_entry(entry_id, &content)?;
+                }
+                self.bases.set(vault, entry_id, &remote)?;
+                self.mark_synced(entry_id, cloud)?;
+                return Ok(Pulled::Merged {
+                    content,
+                    needs_upload,
+                });
+            }
+            unrelated_doc = Some(state);
+        }
+
+        let base = self.bases.get(vault, entry_id).unwrap_or_else(|| {
+            let local_is_newer = chrono::DateTime::parse_from_rfc3339(&entry.modified_at)
+                .is_ok_and(|modified| modified > cloud.updated_at);
+            if local_is_newer {
+                remote.clone()
+            } else {
+                entry.content.clone()
+            }
+        });
+
+        let style = self.conflicts.style();
+        let body_conflicts = match style {
+            ConflictStyle::Markers => BodyConflicts::Markers,
+            ConflictStyle::Copy => BodyConflicts::KeepLocal,
+        };
+        let merged = merge::merge(&base, &entry.content, &remote, body_conflicts)?;
+        if merged.content != entry.content {
+            self.store.save_entry(entry_id, &merged.content)?;
+        }
+        if let Some(state) = &unrelated_doc {
+            self.docs
+                .adopt_remote(vault, entry_id, &merged.content, state)?;
+        }
+
+        if merged.is_clean() {
+            self.bases.set(vault, entry_id, &remote)?;
+            self.mark_synced(entry_id, cloud)?;
+            return Ok(Pulled::Merged {
+                needs_upload: merged.content != remote,
+                content: merged.content,
+            });
+        }
+
+        let copy_id = match style {
+            ConflictStyle::Copy => Some(self.write_conflict_copy(entry, &remote)?),
+            ConflictStyle::Markers => None,
+        };
+        let record = ConflictRecord::new(
+            entry_id,
+            style,
+            copy_id,
+            &merged.conflicts,
+            cloud.updated_at,
+        );
+        log::info!("sync conflict in {entry_id}: {record:?}");
+        self.conflicts
+            .insert(vault, record, &entry.content, &remote)?;
+        Ok(Pulled::Conflicted)
+    }
+
+    /// Record that `entry_id` took in every change of `cloud`.
+    fn mark_synced(&self, entry_id: &str, cloud: &Entry) -> Result<()> {
+        self.mappings.touch(entry_id, cloud.updated_at)?;
+        self.mappings
+            .set_content_hash(entry_id, &hash(cloud.encrypted_content.as_bytes()))
+    }
+
+    /// Write `remote` to a new entry next to `entry` and return its ID.
+    fn write_conflict_copy(&self, entry: &JournalEntry, remote: &str) -> Result<String> {
+        let title = format!(
+            "{} (conflict {})",
+            entry.title,
+            Utc::now().format("%Y-%m-%d")
+        );
+        let copy_id = self.store.create_entry(&title)?;
+        self.store.save_entry(&copy_id, remote)?;
+        Ok(copy_id)
+    }
+
+    /// The decrypted entry object of one of our own cloud entries.
+    fn decrypt_object(&self, cloud: &Entry) -> Result<Value> {
+        let (encrypted_entry_key_b64, key_nonce_b64) = owner_key(cloud)?;
+        let content_nonce_b64 = cloud
+            .encryption_metadata
+            .get("contentNonceB64")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                Error::Crypto(format!("cloud entry {} has no content nonce", cloud.id))
+            })?;
+        let data = EncryptedEntryData {
+            encrypted_content_b64: cloud.encrypted_content.clone(),
+            content_nonce_b64: content_nonce_b64.to_string(),
+            encrypted_entry_key_b64,
+            key_nonce_b64,
+        };
+        crypto::decrypt_entry(&data, self.keys.secret(), &self.keys.public)
+    }
+
+    /// The object to encrypt, with the CRDT document in CRDT mode.
+    fn upload_object(&self, entry: &JournalEntry) -> Result<Value> {
+        let mut object = entry_object(entry)?;
+        let vault = self.store.vault();
+        if let Some(state) = self.docs.capture(vault, &entry.id, &entry.content)? {
+            object["crdt"] = Value::String(STANDARD.encode(state));
+        }
+        Ok(object)
+    }
+
+    async fn unpublish(&self, entry_id: &str) -> Result<()> {
+        let Some(mapping) = self.mappings.get(entry_id) else {
+            return Ok(());
+        };
+        ignore_not_found(self.api.revoke_all_access(&mapping.cloud_id).await)?;
+        ignore_not_found(self.api.delete_entry(&mapping.cloud_id).await)?;
+        self.mappings.remove(entry_id)?;
+        self.bases.remove(entry_id)?;
+        self.conflicts.remove(entry_id)?;
+        Ok(())
+    }
+
+    /// Issue the entry key to every user assigned one of `tag_ids`, and to the author.
+    async fn share(&self, entry_id: &str, tag_ids: &[String]) -> Result<Option<String>> {
+        let Some(mapping) = self.mappings.get(entry_id) else {
+            return Ok(None);
+        };
+
+        let mut recipients = BTreeMap::new();
+        recipients.insert(self.api.user_id().to_string(), self.keys.public_b64());
+        for tag_id in tag_ids {
+            for assignment in self.api.list_user_tags(Some(tag_id)).await? {
+                let public_key = match assignment.target_user.and_then(|u| u.public_key) {
+                    Some(key) => Some(key),
+                    None => {
+                        self.api
+                            .get_profile(&assignment.target_id)
+                            .await?
+                            .public_key
+                    }
+                };
+                match public_key.filter(|key| !key.is_empty()) {
+                    Some(key) => {
+                        recipients.insert(assignment.target_id, key);
+                    }
+                    None => log::warn!(
+                        "user {} has no public key, not sharing",
+                        assignment.target_id
+                    ),
+                }
+            }
+        }
+
+        let (wrapped_key, key_nonce) = owner_key(&self.api.get_entry(&mapping.cloud_id).await?)?;
+        let mut keys = Vec::with_capacity(recipients.len());
+        for (user_id, public_key) in recipients {
+            let rewrapped = crypto::rewrap_entry_key(
+                &wrapped_key,
+                &key_nonce,
+                self.keys,
+                &public_key_from_b64(&public_key)?,
+            )?;
+            keys.push(NewAccessKey {
+                user_id,
+                encrypted_entry_key: rewrapped.encrypted_entry_key_b64,
+                key_nonce: rewrapped.key_nonce_b64,
+            });
+        }
+        self.api.put_access_keys(&mapping.cloud_id, &keys).await?;
+        Ok(Some(mapping.cloud_id))
+    }
+
+    async fn revoke(&self, entry_id: &str, user_ids: &[String]) -> Result<Option<String>> {
+        let Some(mapping) = self.mappings.get(entry_id) else {
+            return Ok(None);
+        };
+        for user_id in user_ids {
+            ignore_not_found(self.api.revoke_access(&mapping.cloud_id, user_id).await)?;
+        }
+        Ok(Some(mapping.cloud_id))
+    }
+}
+
+/// The owner's wrapped entry key of a cloud entry.
+fn owner_key(entry: &Entry) -> Result<(String, String)> {
+    let key = match (&entry.owner_encrypted_entry_key, &entry.owner_key_nonce) {
+        (Some(key), Some(nonce)) => Some((key.clone(), nonce.clone())),
+        _ => entry
+            .access_key
+            .as_ref()
+            .map(|k| (k.encrypted_entry_key.clone(), k.key_nonce.clone())),
+    };
+    key.ok_or_else(|| Error::Crypto(format!("cloud entry {} has no owner key", entry.id)))
+}
+
+/// The Markdown content of a decrypted entry object.
+fn object_content(object: &Value, cloud: &Entry) -> Result<String> {
+    object
+        .get("content")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| Error::Crypto(format!("cloud entry {} has no content", cloud.id)))
+}
+
+/// Whether retrying `error` cannot help, so the operation should be dropped.
+pub fn is_permanent(error: &Error) -> bool {
+    match error {
+        Error::Api { status, .. } => (400..500).contains(status) && !matches!(status, 408 | 429),
+        Error::Crypto(_) | Error::Frontmatter(_) | Error::InvalidId(_) => true,
+        _ => false,
+    }
+}
+
+/// Whether `error` means the server could not be reached.
+pub fn is_offline(error: &Error) -> bool {
+    matches!(error, Error::Cloud(_))
+}
+
+fn ignore_not_found(result: Result<()>) -> Result<()> {
+    match result {
+        Err(Error::Api { status: 404, .. }) => Ok(()),
+        other => other,
+    }
+}
+
+/// The object that gets encrypted. Matches `EntryObject` in `EntryCryptor.ts`.
+fn entry_object(entry: &JournalEntry) -> Result<Value> {
+    let parsed = frontmatter::parse(&entry.content)?;
+    Ok(json!({
+        "title": entry.title,
+        "content": entry.content,
+        "frontmatter": parsed.frontmatter,
+        "tags": parsed.frontmatter.tags,
+    }))
+}
+
+fn entry_payload(
+    entry: &JournalEntry,
+    encrypted: EncryptedEntryData,
+    tag_ids: Option<Vec<String>>,
+) -> EntryPayload {
+    let metadata = json!({
+        "algorithm": "nacl.box + nacl.secretbox",
+        "version": "1.0",
+        "keyDerivation": "nacl.hash",
+        "createdAt": Utc::now().to_rfc3339(),
+        "contentNonceB64": encrypted.content_nonce_b64,
+    });
+    EntryPayload {
+        encrypted_title: encrypted.encrypted_content_b64.clone(),
+        encrypted_content: encrypted.encrypted_content_b64,
+        // Frontmatter travels inside the encrypted object only.
+        encrypted_frontmatter: None,
+        encryption_metadata: metadata,
+        title_hash: hash(entry.title.as_bytes()),
+        content_preview_hash: Some(preview_hash(&entry.content)),
+        is_published: true,
+        file_path: Some(entry.file_path.clone()),
+        owner_encrypted_entry_key: encrypted.encrypted_entry_key_b64,
+        owner_key_nonce: encrypted.key_nonce_b64,
+        tag_ids,
+        client_modified_at: chrono::DateTime::parse_from_rfc3339(&entry.modified_at)
+            .ok()
+            .map(|t| t.with_timezone(&Utc)),
+        if_unmodified_since: None,
+    }
+}
+
+/// `nacl.hash` (SHA-512) in base64, as `EntryCryptor.generateTitleHash`.
+///
+/// Also the `content_hash` of the server's change log.
+pub(crate) fn hash(bytes: &[u8]) -> String {
+    STANDARD.encode(Sha512::digest(bytes))
+}
+
+/// Port of `EntryCryptor.generatePreviewHash`, which slices by UTF-16 unit.
+fn preview_hash(content: &str) -> String {
+    let units: Vec<u16> = content.encode_utf16().take(PREVIEW_HASH_LENGTH).collect();
+    hash(String::from_utf16_lossy(&units).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_match_entry_cryptor() {
+        // nacl.hash(new TextEncoder().encode("")) in base64.
+        assert_eq!(
+            hash(b""),
+            "z4PhNX7vuL3xVChQ1m2AB9Yg5AULVxXcg/SpIdNs6c5H0NE8XYXysP+DGNKHfuwvY7kxvUdBeoGlODJ6+SfaPg=="
+        );
+        let long = "é".repeat(150);
+        assert_eq!(preview_hash(&long), hash("é".repeat(100).as_bytes()));
+    }
+}
@@ -0,0 +1,421 @@
+//! Durable queue of outbound cloud operations.
+//!
+//! Operations are grouped per entry and coalesced as they arrive, so an
+//! entry edited fifty times while offline is uploaded once. The queue is
+//! rewritten atomically after every change and survives restarts.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::store::atomic;
+
+const QUEUE_VERSION: u32 = 1;
+
+/// First retry delay after a failed attempt; doubled per further failure.
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Upper bound for the retry delay.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(15 * 60);
+
+/// An outbound change to an entry's cloud copy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Operation {
+    /// Upload the entry, creating its cloud copy if needed, and share it with `tag_ids`.
+    Publish {
+        #[serde(default)]
+        tag_ids: Vec<String>,
+    },
+    /// Upload the current content of an already published entry.
+    Update,
+    /// Revoke all access and delete the cloud copy.
+    Unpublish,
+    /// Issue access keys to every user assigned one of `tag_ids`.
+    Share { tag_ids: Vec<String> },
+    /// Delete the access keys of `user_ids`.
+    Revoke { user_ids: Vec<String> },
+}
+
+/// Pending operations of one entry, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingEntry {
+    pub entry_id: String,
+    pub operations: Vec<Operation>,
+    /// Failed attempts since the last success or new operation.
+    pub attempts: u32,
+    /// When the entry may be retried; `None` means now.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+impl PendingEntry {
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.next_attempt_at.map_or(true, |at| at <= now)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct QueueFile {
+    version: u32,
+    entries: Vec<PendingEntry>,
+}
+
+/// The persistent queue.
+#[derive(Debug)]
+pub struct OutboundQueue {
+    path: PathBuf,
+    entries: Mutex<Vec<PendingEntry>>,
+}
+
+impl OutboundQueue {
+    /// Load the queue at `path`. A missing or unreadable file yields an empty queue.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = match fs::read_to_string(&path) {
+            Ok(raw) => match serde_json::from_str::<QueueFile>(&raw) {
+                Ok(file) if file.version == QUEUE_VERSION => file.entries,
+                _ => {
+                    log::warn!(
+                        "sync queue at {} is invalid, starting empty",
+                        path.display()
+                    );
+                    Vec::new()
+                }
+            },
+            Err(_) => Vec::new(),
+        };
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<PendingEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add `operation` for `entry_id`, coalescing it with what is already queued.
+    pub fn enqueue(&self, entry_id: &str, operation: Operation) -> Result<()> {
+        let mut entries = self.lock();
+        match entries.iter().position(|e| e.entry_id == entry_id) {
+            Some(index) => {
+                let entry = &mut entries[index];
+                coalesce(&mut entry.operations, operation);
+                // New work deserves a prompt attempt.
+                entry.attempts = 0;
+                entry.next_attempt_at = None;
+                if entry.operations.is_empty() {
+                    entries.remove(index);
+                }
+            }
+            None => {
+                let mut operations = Vec::new();
+                coalesce(&mut operations, operation);
+                if !operations.is_empty() {
+                    entries.push(PendingEntry {
+                        entry_id: entry_id.to_string(),
+                        operations,
+                        attempts: 0,
+                        next_attempt_at: None,
+                        last_error: None,
+                    });
+                }
+            }
+        }
+        self.save(&entries)
+    }
+
+    /// The oldest entry that is due at `now`.
+    pub fn next_due(&self, now: DateTime<Utc>) -> Option<PendingEntry> {
+        self.lock().iter().find(|e| e.is_due(now)).cloned()
+    }
+
+    /// When the earliest entry not yet due becomes due.
+    pub fn next_attempt_at(&self) -> Option<DateTime<Utc>> {
+        self.lock().iter().filter_map(|e| e.next_attempt_at).min()
+    }
+
+    /// Remove `operation` from the front of `entry_id`'s queue once it was applied.
+    ///
+    /// If the queue was coalesced meanwhile the operation is no longer at the
+    /// front and the rewritten operations are left to run.
+    pub fn finish(&self, entry_id: &str, operation: &Operation) -> Result<()> {
+        let mut entries = self.lock();
+        let Some(index) = entries.iter().position(|e| e.entry_id == entry_id) else {
+            return Ok(());
+        };
+        let entry = &mut entries[index];
+        if entry.operations.first() == Some(operation) {
+            entry.operations.remove(0);
+            entry.attempts = 0;
+            entry.last_error = None;
+        }
+        if entry.operations.is_empty() {
+            entries.remove(index);
+        }
+        self.save(&entries)
+    }
+
+    /// Record a failed attempt and schedule the next one with exponential backoff.
+    pub fn fail(
+        &self,
+        entry_id: &str,
+        error: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<PendingEntry>> {
+        let mut entries = self.lock();
+        let Some(entry) = entries.iter_mut().find(|e| e.entry_id == entry_id) else {
+            return Ok(None);
+        };
+        entry.attempts += 1;
+        let delay = chrono::Duration::from_std(retry_delay(entry.attempts))
+            .unwrap_or(chrono::Duration::zero());
+        entry.next_attempt_at = Some(now + delay);
+        entry.last_error = Some(error.to_string());
+        let failed = entry.clone();
+        self.save(&entries)?;
+        Ok(Some(failed))
+    }
+
+    /// Make every entry due now, e.g. when connectivity returns.
+    pub fn retry_now(&self) -> Result<()> {
+        let mut entries = self.lock();
+        for entry in entries.iter_mut() {
+            entry.next_attempt_at = None;
+        }
+        self.save(&entries)
+    }
+
+    pub fn snapshot(&self) -> Vec<PendingEntry> {
+        self.lock().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Point pending work of each old entry ID in `renames` at its new one.
+    pub fn rename_entries(&self, renames: &BTreeMap<String, String>) -> Result<()> {
+        let mut entries = self.lock();
+        let mut renamed = false;
+        for entry in entries.iter_mut() {
+            if let Some(new_id) = renames.get(&entry.entry_id) {
+                entry.entry_id = new_id.clone();
+                renamed = true;
+            }
+        }
+        if renamed {
+            self.save(&entries)?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, entries: &[PendingEntry]) -> Result<()> {
+        let file = QueueFile {
+            version: QUEUE_VERSION,
+            entries: entries.to_vec(),
+        };
+        let json = serde_json::to_vec_pretty(&file).map_err(std::io::Error::from)?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write_atomic(&self.path, &json)?;
+        Ok(())
+    }
+}
+
+/// Delay before the next attempt after `attempts` consecutive failures.
+pub fn retry_delay(attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    BASE_RETRY_DELAY.saturating_mul(factor).min(MAX_RETRY_DELAY)
+}
+
+/// Fold `operation` into an entry's pending `operations`.
+///
+/// Uploads read the entry when they run, so one pending upload covers any
+/// number of edits. Operations queued before an unpublish are moot, and
+/// nothing but a new publish is worth sending after one.
+fn coalesce(operations: &mut Vec<Operation>, operation: Operation) {
+    let live = operations
+        .iter()
+        .rposition(|op| *op == Operation::Unpublish)
+        .map_or(0, |index| index + 1);
+    let republished = operations[live..]
+        .iter()
+        .any(|op| matches!(op, Operation::Publish { .. }));
+    let unpublished = live > 0 && !republished;
+
+    match operation {
+        Operation::Unpublish => {
+            operations.clear();
+            operations.push(Operation::Unpublish);
+        }
+        Operation::Publish { tag_ids } => {
+            remove_after(operations, live, |op| {
+                matches!(op, Operation::Publish { .. } | Operation::Update)
+            });
+            operations.push(Operation::Publish { tag_ids });
+        }
+        Operation::Update => {
+            let uploading = operations[live..]
+                .iter()
+                .any(|op| matches!(op, Operation::Publish { .. } | Operation::Update));
+            if !unpublished && !uploading {
+                operations.push(Operation::Update);
+            }
+        }
+        Operation::Share { tag_ids } => {
+            if !unpublished {
+                remove_after(operations, live, |op| matches!(op, Operation::Share { .. }));
+                operations.push(Operation::Share { tag_ids });
+            }
+        }
+        Operation::Revoke { user_ids } => {
+            if unpublished {
+                return;
+            }
+            if let Some(Operation::Revoke { user_ids: pending }) = operations[live..].last_mut() {
+                for user_id in user_ids {
+                    if !pending.contains(&user_id) {
+                        pending.push(user_id);
+                    }
+                }
+            } else {
+                operations.push(Operation::Revoke { user_ids });
+            }
+        }
+    }
+}
+
+fn remove_after(
+    operations: &mut Vec<Operation>,
+    start: usize,
+    remove: impl Fn(&Operation) -> bool,
+) {
+    let mut index = start;
+    while index < operations.len() {
+        if remove(&operations[index]) {
+            operations.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publish(tags: &[&str]) -> Operation {
+        Operation::Publish {
+            tag_ids: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn coalesced(operations: Vec<Operation>) -> Vec<Operation> {
+        let mut out = Vec::new();
+        for operation in operations {
+            coalesce(&mut out, operation);
+        }
+        out
+    }
+
+    #[test]
+    fn coalesces_operations_per_entry() {
+        assert_eq!(
+            coalesced(vec![publish(&[]), Operation::Update, Operation::Update]),
+            vec![publish(&[])]
+        );
+        assert_eq!(
+            coalesced(vec![Operation::Update, publish(&["t"])]),
+            vec![publish(&["t"])]
+        );
+        assert_eq!(
+            coalesced(vec![publish(&[]), Operation::Update, Operation::Unpublish]),
+            vec![Operation::Unpublish]
+        );
+        assert_eq!(
+            coalesced(vec![
+                Operation::Unpublish,
+                Operation::Update,
+                publish(&[]),
+                Operation::Update
+            ]),
+            vec![Operation::Unpublish, publish(&[])]
+        );
+        assert_eq!(
+            coalesced(vec![
+                Operation::Share {
+                    tag_ids: vec!["a".into()]
+                },
+                Operation::Revoke {
+                    user_ids: vec!["u1".into()]
+                },
+                Operation::Revoke {
+                    user_ids: vec!["u2".into(), "u1".into()]
+                },
+                Operation::Share {
+                    tag_ids: vec!["b".into()]
+                },
+            ]),
+            vec![
+                Operation::Revoke {
+                    user_ids: vec!["u1".into(), "u2".into()]
+                },
+                Operation::Share {
+                    tag_ids: vec!["b".into()]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn persists_and_backs_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sync-queue.json");
+        let queue = OutboundQueue::load(&path);
+        queue.enqueue("a", publish(&[])).unwrap();
+        queue.enqueue("b", Operation::Update).unwrap();
+        queue.enqueue("a", Operation::Update).unwrap();
+
+        let now = Utc::now();
+        let failed = queue.fail("a", "offline", now).unwrap().unwrap();
+        assert_eq!(
+            failed.next_attempt_at,
+            Some(now + chrono::Duration::seconds(5))
+        );
+        let failed = queue.fail("a", "offline", now).unwrap().unwrap();
+        assert_eq!(
+            failed.next_attempt_at,
+            Some(now + chrono::Duration::seconds(10))
+        );
+        assert_eq!(queue.next_due(now).unwrap().entry_id, "b");
+        assert_eq!(retry_delay(30), MAX_RETRY_DELAY);
+
+        let reloaded = OutboundQueue::load(&path);
+        assert_eq!(reloaded.snapshot(), queue.snapshot());
+
+        reloaded.finish("b", &Operation::Update).unwrap();
+        assert!(reloaded.next_due(now).is_none());
+        reloaded.retry_now().unwrap();
+        assert_eq!(reloaded.next_due(now).unwrap().entry_id, "a");
+        reloaded.finish("a", &publish(&[])).unwrap();
+        assert!(OutboundQueue::load(&path).is_empty());
+    }
+}
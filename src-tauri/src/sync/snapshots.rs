@@ -0,0 +1,74 @@
+//! Per-entry copies of content kept for sync, such as the last-synced base.
+//!
+//! Copies are written through the journal's [`Vault`], so they are encrypted
+//! whenever the entries themselves are.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+use crate::error::Result;
+use crate::store::vault::Vault;
+
+/// A directory of content snapshots keyed by entry ID.
+#[derive(Debug)]
+pub struct Snapshots {
+    dir: PathBuf,
+}
+
+impl Snapshots {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, entry_id: &str) -> PathBuf {
+        // Encoded so any entry ID is a safe filename.
+        self.dir.join(URL_SAFE_NO_PAD.encode(entry_id))
+    }
+
+    /// The snapshot of `entry_id`. Missing or unreadable snapshots yield `None`.
+    pub fn get(&self, vault: &Vault, entry_id: &str) -> Option<String> {
+        self.read(vault, entry_id)
+            .map_err(|e| log::warn!("ignoring unreadable snapshot of {entry_id}: {e}"))
+            .ok()
+            .flatten()
+    }
+
+    /// The snapshot of `entry_id`, or `None` if there is none. Fails if it
+    /// exists but cannot be read.
+    pub fn read(&self, vault: &Vault, entry_id: &str) -> Result<Option<String>> {
+        match fs::read(self.path(entry_id)) {
+            Ok(raw) => vault.open(raw).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set(&self, vault: &Vault, entry_id: &str, content: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        vault.write_sealed(&self.path(entry_id), content)
+    }
+
+    pub fn remove(&self, entry_id: &str) -> Result<()> {
+        match fs::remove_file(self.path(entry_id)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Move the snapshot of each old entry ID in `renames` to its new one.
+    pub fn rename_entries(&self, renames: &BTreeMap<String, String>) -> Result<()> {
+        for (old_id, new_id) in renames {
+            match fs::rename(self.path(old_id), self.path(new_id)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
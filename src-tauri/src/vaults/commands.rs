@@ -0,0 +1,143 @@
+//! Tauri commands managing [`Vaults`], and bringing up the active journal.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::{VaultConfig, VaultInfo, Vaults};
+use crate::error::{Error, Result};
+use crate::search::SearchEngine;
+use crate::session::Session;
+use crate::store::EntryStore;
+use crate::watcher::JournalWatcher;
+
+/// Name of the Tauri event carrying the [`VaultInfo`] of a newly active vault.
+pub const VAULT_EVENT: &str = "vault-changed";
+
+#[tauri::command]
+pub fn list_vaults(vaults: State<'_, Vaults>) -> Vec<VaultInfo> {
+    vaults.list()
+}
+
+/// Add a vault for the folder at `root`. With `encrypt`, its journal is
+/// encrypted at rest from the start, which needs an unlocked session.
+#[tauri::command]
+pub async fn add_vault(
+    vaults: State<'_, Vaults>,
+    session: State<'_, Session>,
+    name: String,
+    root: String,
+    settings: Option<BTreeMap<String, Value>>,
+    encrypt: Option<bool>,
+) -> Result<VaultConfig> {
+    let keys = if encrypt.unwrap_or(false) {
+        Some(session.with_keys(|pair| Ok(pair.clone()))?)
+    } else {
+        None
+    };
+    let vault = vaults.add(&name, &PathBuf::from(root), settings.unwrap_or_default())?;
+    if let Some(keys) = keys {
+        let store = EntryStore::new(&vault.root);
+        let encrypted = tokio::task::spawn_blocking(move || {
+            store.unlock_vault(keys)?;
+            store.enable_vault()
+        })
+        .await
+        .map_err(Error::from)
+        .and_then(|result| result);
+        // A vault that was to be encrypted is not left behind in plaintext.
+        if let Err(error) = encrypted {
+            if let Err(remove_error) = vaults.remove(&vault.id) {
+                log::warn!("failed to remove the vault {}: {remove_error}", vault.id);
+            }
+            return Err(error);
+        }
+    }
+    Ok(vault)
+}
+
+#[tauri::command]
+pub fn set_vault_settings(
+    vaults: State<'_, Vaults>,
+    id: String,
+    settings: BTreeMap<String, Value>,
+) -> Result<VaultConfig> {
+    vaults.set_settings(&id, settings)
+}
+
+/// Make `id` the active vault, restarting the watcher, search index and
+/// sync queue on its journal, and announce it as the [`VAULT_EVENT`] event.
+#[tauri::command]
+pub async fn switch_vault(app: AppHandle, id: String) -> Result<VaultInfo> {
+    let handle = app.clone();
+    let target = id.clone();
+    let switched = tokio::task::spawn_blocking(move || -> Result<bool> {
+        let vaults = handle.state::<Vaults>();
+        let previous = vaults.store();
+        if !vaults.switch(&target)? {
+            return Ok(false);
+        }
+        handle.state::<JournalWatcher>().stop();
+        handle.state::<SearchEngine>().clear();
+        previous.lock_vault();
+        start_vault(&handle);
+        Ok(true)
+    })
+    .await??;
+
+    let info = app.state::<Vaults>().info(&id)?;
+    if switched {
+        if let Err(error) = app.emit(VAULT_EVENT, &info) {
+            log::warn!("failed to emit vault change: {error}");
+        }
+    }
+    Ok(info)
+}
+
+/// Forget the vault `id`, leaving its journal folder on disk.
+#[tauri::command]
+pub fn remove_vault(vaults: State<'_, Vaults>, id: String) -> Result<VaultConfig> {
+    vaults.remove(&id)
+}
+
+/// Bring up the journal of the active vault: give its entries stable IDs,
+/// unlock it with the session keys, watch it, purge its expired trash and
+/// build its search index in the background once its entries are readable.
+pub fn start_vault(app: &AppHandle) {
+    let (store, outbox) = app.state::<Vaults>().store_and_outbox();
+
+    // Entries get stable IDs before anything addresses them.
+    if let Err(error) = store.migrate_ids(|renames| outbox.rename_entries(renames)) {
+        log::warn!("failed to assign stable entry IDs: {error}");
+    }
+
+    if let Ok(pair) = app.state::<Session>().with_keys(|pair| Ok(pair.clone())) {
+        if let Err(error) = store.unlock_vault(pair) {
+            log::warn!("vault did not unlock with the session keys: {error}");
+        }
+    }
+
+    if let Err(error) = app
+        .state::<JournalWatcher>()
+        .start(app.clone(), store.clone())
+    {
+        log::warn!("failed to start journal watcher: {error}");
+    }
+
+    if let Err(error) = crate::store::commands::purge_trash(&store, &outbox) {
+        log::warn!("failed to purge the trash: {error}");
+    }
+
+    // Locked vault journals are indexed once unlocked.
+    let status = store.vault_status();
+    if !status.enabled || status.unlocked {
+        let handle = app.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(error) = handle.state::<SearchEngine>().rebuild(&store) {
+                log::warn!("failed to build search index: {error}");
+            }
+        });
+    }
+}
@@ -0,0 +1,581 @@
+//! Vaults: the journals the app knows about.
+//!
+//! Each vault is a journal folder with its own entries, IDs, history, trash
+//! and notebooks, its own settings and, optionally, encryption at rest (see
+//! [`crate::store::vault`]). The list of vaults and which one is active are
+//! kept in `vaults.json` under the platform config directory. Without that
+//! file the journal in `Documents/Diaryx` is the only vault.
+//!
+//! Commands reach the journal through [`Vaults::store`] and its sync state
+//! through [`Vaults::outbox`], so once a switch has happened they all
+//! address the new vault. Queued uploads, cloud links and the change cursor
+//! of the other vaults wait on disk until they are active again; cloud
+//! entries linked to one vault are never imported into another.
+
+pub mod commands;
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::store::index::{MetadataIndex, INDEX_FILE_NAME};
+use crate::store::vault::Vault;
+use crate::store::{atomic, ids, EntryStore, JOURNAL_FOLDER};
+use crate::sync::Outbox;
+
+/// ID of the vault at the default journal location.
+pub const DEFAULT_VAULT_ID: &str = "default";
+
+const VAULTS_FILE_NAME: &str = "vaults.json";
+/// Directory of per-vault app data, for every vault but the default one.
+const VAULTS_DIR_NAME: &str = "vaults";
+const VAULTS_VERSION: u32 = 1;
+
+/// A configured vault.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultConfig {
+    pub id: String,
+    pub name: String,
+    /// Absolute path of the journal folder.
+    pub root: PathBuf,
+    /// Preferences the frontend keeps for this vault.
+    #[serde(default)]
+    pub settings: BTreeMap<String, Value>,
+}
+
+/// A vault as listed to the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultInfo {
+    #[serde(flatten)]
+    pub config: VaultConfig,
+    pub active: bool,
+    /// Whether entry content is encrypted at rest. File names are not.
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultsFile {
+    version: u32,
+    active: String,
+    vaults: Vec<VaultConfig>,
+}
+
+impl VaultsFile {
+    fn new(default_root: PathBuf) -> Self {
+        Self {
+            version: VAULTS_VERSION,
+            active: DEFAULT_VAULT_ID.to_string(),
+            vaults: vec![VaultConfig {
+                id: DEFAULT_VAULT_ID.to_string(),
+                name: JOURNAL_FOLDER.to_string(),
+                root: default_root,
+                settings: BTreeMap::new(),
+            }],
+        }
+    }
+
+    fn get(&self, id: &str) -> Result<&VaultConfig> {
+        self.vaults
+            .iter()
+            .find(|vault| vault.id == id)
+            .ok_or_else(|| unknown(id))
+    }
+
+    fn get_mut(&mut self, id: &str) -> Result<&mut VaultConfig> {
+        self.vaults
+            .iter_mut()
+            .find(|vault| vault.id == id)
+            .ok_or_else(|| unknown(id))
+    }
+
+    fn active(&self) -> &VaultConfig {
+        self.get(&self.active).unwrap_or_else(|_| &self.vaults[0])
+    }
+}
+
+/// The journal of the active vault, its metadata index and its outbox.
+struct Active {
+    id: String,
+    store: EntryStore,
+    index: Arc<MetadataIndex>,
+    outbox: Arc<Outbox>,
+}
+
+impl Active {
+    /// Open `vault`, whose app data is in `dir` and whose sync state `outbox` holds.
+    fn open(vault: &VaultConfig, dir: &Path, outbox: Outbox) -> Self {
+        let store = open_store(vault, dir);
+        let index = MetadataIndex::load(dir.join(INDEX_FILE_NAME), store.root());
+        Self {
+            id: vault.id.clone(),
+            store,
+            index: Arc::new(index),
+            outbox: Arc::new(outbox),
+        }
+    }
+}
+
+/// Managed state holding the configured vaults and the open journal.
+pub struct Vaults {
+    path: PathBuf,
+    /// Where per-vault app data such as metadata indexes is kept.
+    data_dir: PathBuf,
+    file: Mutex<VaultsFile>,
+    active: RwLock<Active>,
+}
+
+impl Vaults {
+    /// Default location of the vault list inside the platform config directory.
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(crate::APP_IDENTIFIER)
+            .join(VAULTS_FILE_NAME)
+    }
+
+    /// Default location of per-vault app data inside the platform data directory.
+    pub fn default_data_dir() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(crate::APP_IDENTIFIER)
+    }
+
+    /// Load the vault list at `path` and open the active vault.
+    ///
+    /// A missing or invalid list yields a single vault at `default_root`.
+    pub fn load(
+        path: impl Into<PathBuf>,
+        data_dir: impl Into<PathBuf>,
+        default_root: PathBuf,
+    ) -> Self {
+        let path = path.into();
+        let data_dir = data_dir.into();
+        let file = match fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str::<VaultsFile>(&raw)
+                .ok()
+                .filter(|file| file.version == VAULTS_VERSION && !file.vaults.is_empty())
+                .unwrap_or_else(|| {
+                    log::warn!("ignoring invalid vault list at {}", path.display());
+                    VaultsFile::new(default_root)
+                }),
+            Err(_) => VaultsFile::new(default_root),
+        };
+        let vault = file.active();
+        let dir = vault_dir(&data_dir, &vault.id);
+        let active = Active::open(vault, &dir, Outbox::load(&dir));
+        active
+            .outbox
+            .set_foreign(foreign_cloud_ids(&file, &data_dir, &vault.id));
+        Self {
+            path,
+            data_dir,
+            file: Mutex::new(file),
+            active: RwLock::new(active),
+        }
+    }
+
+    fn lock_file(&self) -> MutexGuard<'_, VaultsFile> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The journal of the active vault.
+    pub fn store(&self) -> EntryStore {
+        self.active
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .store
+            .clone()
+    }
+
+    /// The metadata index of the active vault.
+    pub fn index(&self) -> Arc<MetadataIndex> {
+        self.active
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .index
+            .clone()
+    }
+
+    /// The sync state of the active vault.
+    pub fn outbox(&self) -> Arc<Outbox> {
+        self.active
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .outbox
+            .clone()
+    }
+
+    /// The journal and sync state of the active vault, read together so a
+    /// concurrent switch cannot pair one vault's queue with another's entries.
+    pub fn store_and_outbox(&self) -> (EntryStore, Arc<Outbox>) {
+        let active = self.active.read().unwrap_or_else(|e| e.into_inner());
+        (active.store.clone(), active.outbox.clone())
+    }
+
+    pub fn active_id(&self) -> String {
+        self.active
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .id
+            .clone()
+    }
+
+    /// The journal of every vault, the active one sharing its open state.
+    pub fn stores(&self) -> Vec<EntryStore> {
+        let file = self.lock_file();
+        let active = self.active.read().unwrap_or_else(|e| e.into_inner());
+        file.vaults
+            .iter()
+            .map(|vault| {
+                if vault.id == active.id {
+                    active.store.clone()
+                } else {
+                    open_store(vault, &vault_dir(&self.data_dir, &vault.id))
+                }
+            })
+            .collect()
+    }
+
+    pub fn list(&self) -> Vec<VaultInfo> {
+        let file = self.lock_file();
+        let active = self.active.read().unwrap_or_else(|e| e.into_inner());
+        file.vaults
+            .iter()
+            .map(|vault| {
+                let is_active = vault.id == active.id;
+                let encrypted = if is_active {
+                    active.store.vault_status().enabled
+                } else {
+                    Vault::load(&vault.root).status().enabled
+                };
+                VaultInfo {
+                    config: vault.clone(),
+                    active: is_active,
+                    encrypted,
+                }
+            })
+            .collect()
+    }
+
+    pub fn info(&self, id: &str) -> Result<VaultInfo> {
+        self.list()
+            .into_iter()
+            .find(|info| info.config.id == id)
+            .ok_or_else(|| unknown(id))
+    }
+
+    /// Add a vault for the journal folder at `root`, creating the folder.
+    ///
+    /// Folders inside or around another vault's are refused, as each journal
+    /// would show the other's entries as a notebook.
+    pub fn add(
+        &self,
+        name: &str,
+        root: &Path,
+        settings: BTreeMap<String, Value>,
+    ) -> Result<VaultConfig> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::InvalidVault("name is empty".into()));
+        }
+        if !root.is_absolute() {
+            return Err(Error::InvalidVault(format!(
+                "{} is not an absolute path",
+                root.display()
+            )));
+        }
+
+        let mut file = self.lock_file();
+        let canonical = canonical_path(root);
+        if let Some(other) = file.vaults.iter().find(|vault| {
+            let other = canonical_path(&vault.root);
+            canonical.starts_with(&other) || other.starts_with(&canonical)
+        }) {
+            return Err(Error::InvalidVault(format!(
+                "{} overlaps the vault {}",
+                root.display(),
+                other.name
+            )));
+        }
+        fs::create_dir_all(root)?;
+
+        let vault = VaultConfig {
+            id: ids::new_id(),
+            name: name.to_string(),
+            root: root.to_path_buf(),
+            settings,
+        };
+        file.vaults.push(vault.clone());
+        self.save(&mut file)?;
+        Ok(vault)
+    }
+
+    /// Replace the settings of the vault `id`.
+    pub fn set_settings(&self, id: &str, settings: BTreeMap<String, Value>) -> Result<VaultConfig> {
+        let mut file = self.lock_file();
+        let vault = file.get_mut(id)?;
+        vault.settings = settings;
+        let vault = vault.clone();
+        self.save(&mut file)?;
+        Ok(vault)
+    }
+
+    /// Open the vault `id` and make it the active one.
+    ///
+    /// The new vault's outbox keeps the connection of the old one. Returns
+    /// whether the active vault changed; watching and indexing the new
+    /// journal is up to the caller.
+    pub fn switch(&self, id: &str) -> Result<bool> {
+        let mut file = self.lock_file();
+        let vault = file.get(id)?.clone();
+        let mut active = self.active.write().unwrap_or_else(|e| e.into_inner());
+        if active.id == id {
+            return Ok(false);
+        }
+        // Keep whatever the old journal's index learned for next time.
+        if let Err(error) = active.index.save() {
+            log::warn!("failed to save metadata index: {error}");
+        }
+
+        file.active = vault.id.clone();
+        self.save(&mut file)?;
+        let dir = vault_dir(&self.data_dir, &vault.id);
+        let outbox = active.outbox.reopen(&dir);
+        outbox.set_foreign(foreign_cloud_ids(&file, &self.data_dir, &vault.id));
+        *active = Active::open(&vault, &dir, outbox);
+        // The drainer waits on the shared connection and picks up the new outbox.
+        active.outbox.wake();
+        Ok(true)
+    }
+
+    /// Forget the vault `id`. Its journal folder is left on disk, while the
+    /// app's index and sync state of it are deleted, uploads still queued
+    /// included. The default vault shares the app data directory, so only
+    /// its index goes. The active vault cannot be removed.
+    pub fn remove(&self, id: &str) -> Result<VaultConfig> {
+        let mut file = self.lock_file();
+        let vault = file.get(id)?.clone();
+        let active = self.active.read().unwrap_or_else(|e| e.into_inner());
+        if active.id == id {
+            return Err(Error::InvalidVault(
+                "the active vault cannot be removed".into(),
+            ));
+        }
+        file.vaults.retain(|other| other.id != id);
+        self.save(&mut file)?;
+        // Its cloud entries may be imported here from now on.
+        active
+            .outbox
+            .set_foreign(foreign_cloud_ids(&file, &self.data_dir, &active.id));
+
+        let dir = vault_dir(&self.data_dir, id);
+        let removed = if id == DEFAULT_VAULT_ID {
+            fs::remove_file(dir.join(INDEX_FILE_NAME))
+        } else {
+            fs::remove_dir_all(&dir)
+        };
+        match removed {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("failed to delete the app data of vault {id}: {e}"),
+        }
+        Ok(vault)
+    }
+
+    fn save(&self, file: &mut VaultsFile) -> Result<()> {
+        file.version = VAULTS_VERSION;
+        let json = serde_json::to_vec_pretty(file).map_err(std::io::Error::from)?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write_atomic(&self.path, &json)?;
+        Ok(())
+    }
+}
+
+/// `path` with symlinks and `..` resolved, so two spellings of one folder
+/// compare equal. Past the first folder that does not exist yet, the rest
+/// of it is resolved by name alone.
+fn canonical_path(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    let mut exists = true;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component),
+            Component::CurDir => {}
+            Component::ParentDir if !exists => {
+                resolved.pop();
+            }
+            Component::ParentDir | Component::Normal(_) => {
+                resolved.push(component);
+                if exists {
+                    match fs::canonicalize(&resolved) {
+                        Ok(canonical) => resolved = canonical,
+                        Err(_) => exists = false,
+                    }
+                }
+            }
+        }
+    }
+    resolved
+}
+
+/// The journal of `vault`, whose app data is in `dir`.
+fn open_store(vault: &VaultConfig, dir: &Path) -> EntryStore {
+    EntryStore::new(&vault.root).with_sealed_dirs(Outbox::sealed_dirs(dir))
+}
+
+/// Where the app keeps its data about the vault `id`: the metadata index and
+/// sync state. The default vault keeps the files it had before there were several.
+fn vault_dir(data_dir: &Path, id: &str) -> PathBuf {
+    if id == DEFAULT_VAULT_ID {
+        data_dir.to_path_buf()
+    } else {
+        data_dir.join(VAULTS_DIR_NAME).join(id)
+    }
+}
+
+/// Cloud entries linked to any vault in `file` but `id`.
+fn foreign_cloud_ids(file: &VaultsFile, data_dir: &Path, id: &str) -> HashSet<String> {
+    file.vaults
+        .iter()
+        .filter(|vault| vault.id != id)
+        .flat_map(|vault| Outbox::linked_cloud_ids(&vault_dir(data_dir, &vault.id)))
+        .collect()
+}
+
+fn unknown(id: &str) -> Error {
+    Error::InvalidVault(format!("no vault with ID {id}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ApiClient, Credentials};
+    use crate::sync::queue::Operation;
+
+    fn load(dir: &Path) -> Vaults {
+        Vaults::load(
+            dir.join("config").join(VAULTS_FILE_NAME),
+            dir.join("data"),
+            dir.join(JOURNAL_FOLDER),
+        )
+    }
+
+    #[test]
+    fn switches_and_remembers_the_active_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let vaults = load(dir.path());
+        assert_eq!(vaults.active_id(), DEFAULT_VAULT_ID);
+        assert_eq!(vaults.store().root(), dir.path().join(JOURNAL_FOLDER));
+
+        let work_root = dir.path().join("Work");
+        let work = vaults.add(" Work ", &work_root, BTreeMap::new()).unwrap();
+        assert_eq!(work.name, "Work");
+        assert!(work_root.is_dir());
+        assert!(matches!(
+            vaults.add("Nested", &work_root.join("inner"), BTreeMap::new()),
+            Err(Error::InvalidVault(_))
+        ));
+        assert!(matches!(
+            vaults.add("Relative", Path::new("relative"), BTreeMap::new()),
+            Err(Error::InvalidVault(_))
+        ));
+
+        assert!(vaults.switch(&work.id).unwrap());
+        assert!(!vaults.switch(&work.id).unwrap());
+        assert_eq!(vaults.store().root(), work_root);
+        assert_eq!(vaults.stores().len(), 2);
+        assert!(matches!(
+            vaults.remove(&work.id),
+            Err(Error::InvalidVault(_))
+        ));
+        assert!(matches!(
+            vaults.switch("missing"),
+            Err(Error::InvalidVault(_))
+        ));
+
+        // The vault list and the active vault survive a restart.
+        let settings = BTreeMap::from([("theme".to_string(), Value::from("dark"))]);
+        vaults.set_settings(&work.id, settings.clone()).unwrap();
+        let reloaded = load(dir.path());
+        assert_eq!(reloaded.active_id(), work.id);
+        let info = reloaded.info(&work.id).unwrap();
+        assert!(info.active);
+        assert!(!info.encrypted);
+        assert_eq!(info.config.settings, settings);
+
+        // Removing a vault forgets it but keeps its journal.
+        reloaded.remove(DEFAULT_VAULT_ID).unwrap();
+        assert_eq!(reloaded.list().len(), 1);
+        assert_eq!(load(dir.path()).list().len(), 1);
+        assert!(work_root.is_dir());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn overlap_check_sees_through_other_spellings_of_a_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let vaults = load(dir.path());
+        let work_root = dir.path().join("Work");
+        vaults.add("Work", &work_root, BTreeMap::new()).unwrap();
+
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&work_root, &link).unwrap();
+        for root in [
+            link.join("inner"),
+            link.clone(),
+            dir.path().join("new").join("..").join("Work").join("inner"),
+        ] {
+            assert!(
+                matches!(
+                    vaults.add("Alias", &root, BTreeMap::new()),
+                    Err(Error::InvalidVault(_))
+                ),
+                "{} was accepted",
+                root.display()
+            );
+        }
+        assert!(!link.join("inner").exists());
+        assert_eq!(vaults.list().len(), 2);
+    }
+
+    #[test]
+    fn keeps_sync_state_per_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let vaults = load(dir.path());
+        let credentials = Credentials {
+            user_id: "user-1".into(),
+            access_token: "token".into(),
+        };
+        vaults
+            .outbox()
+            .connect(Some(ApiClient::new("http://127.0.0.1:1", credentials)));
+        vaults.outbox().link("day", "cloud-1").unwrap();
+        vaults.outbox().enqueue("day", Operation::Update).unwrap();
+
+        let work_root = dir.path().join("Work");
+        let work = vaults.add("Work", &work_root, BTreeMap::new()).unwrap();
+        assert!(vaults.switch(&work.id).unwrap());
+        // The update waits for its own journal instead of failing against this one.
+        let (store, outbox) = vaults.store_and_outbox();
+        assert_eq!(store.root(), work_root);
+        assert!(outbox.status().pending.is_empty());
+        assert!(outbox.mappings().get("day").is_none());
+        assert!(outbox.status().connected);
+
+        assert!(vaults.switch(DEFAULT_VAULT_ID).unwrap());
+        let pending = vaults.outbox().status().pending;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].entry_id, "day");
+        assert_eq!(pending[0].operations, [Operation::Update]);
+    }
+}
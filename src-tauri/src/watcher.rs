@@ -0,0 +1,460 @@
+//! Native journal watcher.
+//!
+//! Owns a debounced filesystem watcher on the journal folder, classifies raw
+//! notifications into typed [`EntryChange`]s and emits them to the webview as
+//! the [`ENTRY_CHANGES_EVENT`] event. Writes made through [`EntryStore`] are
+//! filtered out so the UI only hears about external edits; external edits are
+//! also fed into the [`SearchEngine`]. Files are reported by entry ID, so a
+//! renamed file keeps its entry and is reported with its old and new path.
+//! Notebook folders are watched too; moving or deleting one reports every
+//! entry inside it.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, FileIdMap};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::Result;
+use crate::search::SearchEngine;
+use crate::store::{notebooks, EntryStore};
+
+/// Name of the Tauri event carrying a batch of [`EntryChange`]s.
+pub const ENTRY_CHANGES_EVENT: &str = "entry-changes";
+
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// A classified change to a journal entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "lowercase",
+    rename_all_fields = "camelCase"
+)]
+pub enum EntryChange {
+    Create {
+        id: String,
+    },
+    Modify {
+        id: String,
+    },
+    Remove {
+        id: String,
+    },
+    /// The file of `id` moved; paths are relative to the journal root.
+    Rename {
+        id: String,
+        old_path: String,
+        new_path: String,
+    },
+}
+
+impl EntryChange {
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Create { id } | Self::Modify { id } | Self::Remove { id } => id,
+            Self::Rename { id, .. } => id,
+        }
+    }
+}
+
+/// Managed state holding the running watcher, if any.
+#[derive(Default)]
+pub struct JournalWatcher {
+    debouncer: Mutex<Option<Debouncer<RecommendedWatcher, FileIdMap>>>,
+}
+
+impl JournalWatcher {
+    /// Start watching the store's root, replacing any previous watcher.
+    pub fn start(&self, app: AppHandle, store: EntryStore) -> Result<()> {
+        store.ensure_dir()?;
+        let root = store.root().to_path_buf();
+        let classifier = Arc::new(Mutex::new(Classifier::new(store.clone())?));
+
+        let mut debouncer = new_debouncer(DEBOUNCE, None, move |result: DebounceEventResult| {
+            let events = match result {
+                Ok(events) => events,
+                Err(errors) => {
+                    for error in errors {
+                        log::warn!("journal watcher error: {error}");
+                    }
+                    return;
+                }
+            };
+
+            let mut classifier = classifier.lock().unwrap_or_else(|e| e.into_inner());
+            let changes: Vec<EntryChange> = events
+                .iter()
+                .flat_map(|event| classifier.classify(&event.kind, &event.paths))
+                .filter(|change| !is_own_write(&store, change))
+                .collect();
+            drop(classifier);
+
+            if !changes.is_empty() {
+                if let Some(search) = app.try_state::<SearchEngine>() {
+                    search.apply_changes(&store, &changes);
+                }
+                if let Err(error) = app.emit(ENTRY_CHANGES_EVENT, &changes) {
+                    log::warn!("failed to emit entry changes: {error}");
+                }
+            }
+        })
+        .map_err(to_io)?;
+
+        debouncer
+            .watcher()
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(to_io)?;
+        debouncer.cache().add_root(&root, RecursiveMode::Recursive);
+
+        *self.debouncer.lock().unwrap_or_else(|e| e.into_inner()) = Some(debouncer);
+        Ok(())
+    }
+
+    /// Stop the watcher. Dropping the debouncer unregisters it.
+    pub fn stop(&self) {
+        self.debouncer
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+}
+
+/// Turns raw notify events into entry changes, tracking which file stems
+/// exist and the IDs they belong to, so atomic saves (temp file renamed over
+/// the target) read as modifications and removed files can still be named.
+struct Classifier {
+    store: EntryStore,
+    /// The journal root as configured and as the platform may report it,
+    /// with symlinks resolved.
+    roots: Vec<PathBuf>,
+    known: HashMap<String, String>,
+}
+
+impl Classifier {
+    fn new(store: EntryStore) -> Result<Self> {
+        let ids = store.entry_ids()?;
+        let known = ids
+            .into_iter()
+            .filter_map(|id| Some((store.ids().stem(&id)?, id)))
+            .collect();
+        let mut roots = vec![store.root().to_path_buf()];
+        if let Ok(canonical) = store.root().canonicalize() {
+            if canonical != roots[0] {
+                roots.push(canonical);
+            }
+        }
+        Ok(Self {
+            store,
+            roots,
+            known,
+        })
+    }
+
+    fn classify(&mut self, kind: &EventKind, paths: &[PathBuf]) -> Vec<EntryChange> {
+        let Some(first) = paths.first() else {
+            return Vec::new();
+        };
+        match kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() == 2 => {
+                match (self.entry_stem(first), self.entry_stem(&paths[1])) {
+                    (Some(old_stem), Some(new_stem)) => {
+                        self.renamed(&old_stem, new_stem).into_iter().collect()
+                    }
+                    (None, Some(stem)) => self.appeared(stem).into_iter().collect(),
+                    (Some(stem), None) => self.vanished(&stem).into_iter().collect(),
+                    (None, None) => self.moved_notebook(first, &paths[1]),
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) | EventKind::Remove(_) => {
+                match self.entry_stem(first) {
+                    Some(stem) => self.vanished(&stem).into_iter().collect(),
+                    None => self.removed_notebook(first),
+                }
+            }
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)) => {
+                match self.entry_stem(first) {
+                    Some(stem) => self.appeared(stem).into_iter().collect(),
+                    // A folder moved in from elsewhere reports none of its files.
+                    None if first.is_dir() => self.added_notebook(first),
+                    None => Vec::new(),
+                }
+            }
+            EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any) => self
+                .entry_stem(first)
+                .and_then(|stem| self.appeared(stem))
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn appeared(&mut self, stem: String) -> Option<EntryChange> {
+        if let Some(id) = self.known.get(&stem) {
+            return Some(EntryChange::Modify { id: id.clone() });
+        }
+        let id = self.assign(&stem)?;
+        self.known.insert(stem, id.clone());
+        Some(EntryChange::Create { id })
+    }
+
+    fn vanished(&mut self, stem: &str) -> Option<EntryChange> {
+        let id = self.known.remove(stem)?;
+        Some(EntryChange::Remove { id })
+    }
+
+    /// A file renamed outside the app keeps its entry's ID.
+    fn renamed(&mut self, old_stem: &str, new_stem: String) -> Option<EntryChange> {
+        let Some(id) = self.known.remove(old_stem) else {
+            return self.appeared(new_stem);
+        };
+        if let Err(error) = self.store.ids().set(&id, &new_stem) {
+            log::warn!("failed to record the rename of {id}: {error}");
+        }
+        let change = EntryChange::Rename {
+            id: id.clone(),
+            old_path: EntryStore::display_path(old_stem),
+            new_path: EntryStore::display_path(&new_stem),
+        };
+        self.known.insert(new_stem, id);
+        Some(change)
+    }
+
+    /// Entries of a notebook folder that appeared, as new or, when already
+    /// known, modified entries.
+    fn added_notebook(&mut self, path: &Path) -> Vec<EntryChange> {
+        let Some(notebook) = self.notebook(path) else {
+            return Vec::new();
+        };
+        match self.store.notebook_stems(&notebook) {
+            Ok(stems) => stems
+                .into_iter()
+                .filter_map(|stem| self.appeared(stem))
+                .collect(),
+            Err(error) => {
+                log::warn!("failed to scan notebook {notebook}: {error}");
+                Vec::new()
+            }
+        }
+    }
+
+    fn removed_notebook(&mut self, path: &Path) -> Vec<EntryChange> {
+        let Some(notebook) = self.notebook(path) else {
+            return Vec::new();
+        };
+        self.stems_in(&notebook)
+            .into_iter()
+            .filter_map(|(stem, _)| self.vanished(&stem))
+            .collect()
+    }
+
+    /// A notebook renamed outside the app: its entries keep their IDs.
+    fn moved_notebook(&mut self, from: &Path, to: &Path) -> Vec<EntryChange> {
+        let (Some(old), Some(new)) = (self.notebook(from), self.notebook(to)) else {
+            let mut changes = self.removed_notebook(from);
+            changes.extend(self.added_notebook(to));
+            return changes;
+        };
+        self.stems_in(&old)
+            .into_iter()
+            .filter_map(|(old_stem, rest)| self.renamed(&old_stem, format!("{new}/{rest}")))
+            .collect()
+    }
+
+    /// Known stems inside `notebook`, with the part after it.
+    fn stems_in(&self, notebook: &str) -> Vec<(String, String)> {
+        let prefix = format!("{notebook}/");
+        self.known
+            .keys()
+            .filter_map(|stem| Some((stem.clone(), stem.strip_prefix(&prefix)?.to_string())))
+            .collect()
+    }
+
+    /// ID of the entry at `stem`, assigning one to a file created elsewhere.
+    fn assign(&self, stem: &str) -> Option<String> {
+        match self.store.ids().ensure(&[stem.to_string()]) {
+            Ok(mut ids) => ids.pop(),
+            Err(error) => {
+                log::warn!("failed to assign an ID to {stem}: {error}");
+                None
+            }
+        }
+    }
+
+    /// `path` relative to the journal root, if it is inside it.
+    fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        self.roots
+            .iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+    }
+
+    /// Map a path to an entry's stem, ignoring temp files, non-markdown
+    /// files and hidden or system folders.
+    fn entry_stem(&self, path: &Path) -> Option<String> {
+        notebooks::relative_stem(self.relative(path)?)
+    }
+
+    fn notebook(&self, path: &Path) -> Option<String> {
+        notebooks::relative_notebook(self.relative(path)?)
+    }
+}
+
+fn is_own_write(store: &EntryStore, change: &EntryChange) -> bool {
+    store.is_own_write(change.id())
+}
+
+fn to_io(error: notify_debouncer_full::notify::Error) -> std::io::Error {
+    std::io::Error::other(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use notify_debouncer_full::notify::event::{CreateKind, DataChange, RemoveKind};
+
+    use super::*;
+
+    const RENAME: EventKind = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+    const CREATE: EventKind = EventKind::Create(CreateKind::File);
+    const REMOVE: EventKind = EventKind::Remove(RemoveKind::File);
+
+    fn store() -> (tempfile::TempDir, EntryStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EntryStore::new(dir.path().join("journal"));
+        store.ensure_dir().unwrap();
+        (dir, store)
+    }
+
+    fn classifier(store: &EntryStore) -> Classifier {
+        Classifier::new(store.clone()).unwrap()
+    }
+
+    fn rename(id: &str, old_path: &str, new_path: &str) -> EntryChange {
+        EntryChange::Rename {
+            id: id.to_string(),
+            old_path: old_path.to_string(),
+            new_path: new_path.to_string(),
+        }
+    }
+
+    #[test]
+    fn atomic_save_reads_as_modify() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Day").unwrap();
+        let mut classifier = classifier(&store);
+        let temp = store.root().join(".diaryx-tmp-day.md");
+        let target = store.root().join("day.md");
+
+        assert!(classifier.classify(&CREATE, &[temp.clone()]).is_empty());
+        assert_eq!(
+            classifier.classify(&RENAME, &[temp, target]),
+            vec![EntryChange::Modify { id }]
+        );
+    }
+
+    #[test]
+    fn new_files_get_an_id_and_later_edits_keep_it() {
+        let (_dir, store) = store();
+        let mut classifier = classifier(&store);
+        let path = store.root().join("new.md");
+        fs::write(&path, "").unwrap();
+
+        let changes = classifier.classify(&CREATE, &[path.clone()]);
+        let id = store.ids().id_of("new").unwrap();
+        assert_eq!(changes, vec![EntryChange::Create { id: id.clone() }]);
+        let modify = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        assert_eq!(
+            classifier.classify(&modify, &[path]),
+            vec![EntryChange::Modify { id }]
+        );
+    }
+
+    #[test]
+    fn rename_keeps_the_id() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Day").unwrap();
+        let mut classifier = classifier(&store);
+        let paths = [store.root().join("day.md"), store.root().join("night.md")];
+
+        assert_eq!(
+            classifier.classify(&RENAME, &paths),
+            vec![rename(&id, "day.md", "night.md")]
+        );
+        assert_eq!(store.ids().stem(&id).as_deref(), Some("night"));
+    }
+
+    #[test]
+    fn unpaired_rename_halves_read_as_remove_and_create() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Day").unwrap();
+        let mut classifier = classifier(&store);
+        let from = EventKind::Modify(ModifyKind::Name(RenameMode::From));
+        let to = EventKind::Modify(ModifyKind::Name(RenameMode::To));
+
+        assert_eq!(
+            classifier.classify(&from, &[store.root().join("day.md")]),
+            vec![EntryChange::Remove { id }]
+        );
+        let changes = classifier.classify(&to, &[store.root().join("night.md")]);
+        let id = store.ids().id_of("night").unwrap();
+        assert_eq!(changes, vec![EntryChange::Create { id }]);
+    }
+
+    #[test]
+    fn removing_an_unknown_file_reports_nothing() {
+        let (_dir, store) = store();
+        let id = store.create_entry("Day").unwrap();
+        let mut classifier = classifier(&store);
+
+        assert!(classifier
+            .classify(&REMOVE, &[store.root().join("ghost.md")])
+            .is_empty());
+        assert_eq!(
+            classifier.classify(&REMOVE, &[store.root().join("day.md")]),
+            vec![EntryChange::Remove { id }]
+        );
+    }
+
+    #[test]
+    fn notebook_moves_rename_their_entries() {
+        let (_dir, store) = store();
+        store.create_notebook("work").unwrap();
+        let id = store.create_entry_in("work", "Plan").unwrap();
+        let mut classifier = classifier(&store);
+        let (from, to) = (store.root().join("work"), store.root().join("play"));
+        fs::rename(&from, &to).unwrap();
+
+        assert_eq!(
+            classifier.classify(&RENAME, &[from, to.clone()]),
+            vec![rename(&id, "work/plan.md", "play/plan.md")]
+        );
+        let remove = EventKind::Remove(RemoveKind::Folder);
+        assert_eq!(
+            classifier.classify(&remove, &[to]),
+            vec![EntryChange::Remove { id }]
+        );
+    }
+
+    #[test]
+    fn temp_hidden_and_other_files_are_ignored() {
+        let (_dir, store) = store();
+        let mut classifier = classifier(&store);
+        let root = store.root();
+
+        for path in [
+            root.join(".diaryx-tmp-1234"),
+            root.join(".hidden.md"),
+            root.join(".trash").join("old.md"),
+            root.join("notes.txt"),
+        ] {
+            assert!(classifier.classify(&CREATE, &[path]).is_empty());
+        }
+        assert!(store.entry_ids().unwrap().is_empty());
+    }
+}
@@ -10,7 +10,7 @@
  * 
  * Key features:
  * - Secure key generation using cryptographically secure random number generation
- * - Password-based encryption for secure local storage of private keys
+ * - Password-based encryption (Argon2id) for secure storage of private keys
  * - Key format conversion between binary and Base64 representations
  * - Key validation and memory cleanup utilities
  * 
@@ -37,7 +37,80 @@
  */
 
 import nacl from 'tweetnacl';
-import { encodeUTF8, encodeBase64, decodeBase64 } from 'tweetnacl-util';
+import { encodeBase64, decodeBase64 } from 'tweetnacl-util';
+import { argon2id } from '@noble/hashes/argon2';
+
+/**
+ * Argon2id cost parameters for password-protected key envelopes
+ * 
+ * Mirrors `KdfParams` in `crypto/envelope.rs`.
+ */
+export interface KdfParams {
+  /** Memory cost in KiB */
+  memoryKib: number;
+  iterations: number;
+  parallelism: number;
+}
+
+/** OWASP's recommended minimum for Argon2id */
+export const DEFAULT_KDF_PARAMS: KdfParams = {
+  memoryKib: 19 * 1024,
+  iterations: 2,
+  parallelism: 1
+};
+
+const ENVELOPE_PREFIX = '$dxk$';
+const ENVELOPE_SALT_LENGTH = 16;
+/** Refuse envelopes demanding more than 1 GiB, to bound unlock cost */
+const ENVELOPE_MAX_MEMORY_KIB = 1024 * 1024;
+
+interface ParsedEnvelope {
+  params: KdfParams;
+  salt: Uint8Array;
+  payload: Uint8Array;
+}
+
+/**
+ * Parse a versioned key envelope. Returns null for legacy envelopes and
+ * throws for malformed or unsupported versioned ones.
+ */
+function parseEnvelope(envelope: string): ParsedEnvelope | null {
+  if (!envelope.startsWith(ENVELOPE_PREFIX)) return null;
+
+  const parts = envelope.slice(ENVELOPE_PREFIX.length).split('$');
+  if (parts.length !== 5 || parts[0] !== 'v=2' || parts[1] !== 'argon2id') {
+    throw new Error('Unsupported key envelope');
+  }
+
+  const values = new Map(parts[2].split(',').map((pair) => {
+    const [name, value] = pair.split('=');
+    return [name, Number(value)] as const;
+  }));
+  const params: KdfParams = {
+    memoryKib: values.get('m') ?? NaN,
+    iterations: values.get('t') ?? NaN,
+    parallelism: values.get('p') ?? NaN
+  };
+  if (!Object.values(params).every(Number.isInteger) || params.memoryKib > ENVELOPE_MAX_MEMORY_KIB) {
+    throw new Error('Invalid key envelope parameters');
+  }
+
+  return { params, salt: decodeBase64(parts[3]), payload: decodeBase64(parts[4]) };
+}
+
+function deriveArgon2idKey(password: string, salt: Uint8Array, params: KdfParams): Uint8Array {
+  return argon2id(new TextEncoder().encode(password), salt, {
+    m: params.memoryKib,
+    t: params.iterations,
+    p: params.parallelism,
+    dkLen: nacl.secretbox.keyLength
+  });
+}
+
+/** Key derivation used by envelopes written before versioning */
+function deriveLegacyKey(password: string): Uint8Array {
+  return nacl.hash(new TextEncoder().encode(password)).slice(0, nacl.secretbox.keyLength);
+}
 
 /**
  * User key pair in binary format
@@ -118,13 +191,15 @@ export class KeyManager {
   /**
    * Securely encrypt the user's private key for storage in browser's Local Storage
    * 
-   * Uses password-based encryption to secure the user's private key for local storage.
-   * The password is hashed using SHA-512 and the resulting key is used with XSalsa20-Poly1305
-   * authenticated encryption. A random nonce is generated for each encryption.
+   * Derives a wrapping key from the password with Argon2id over a random salt and
+   * seals the secret key with XSalsa20-Poly1305. The result is a versioned envelope
+   * that records its KDF parameters, shared with `crypto/envelope.rs`:
+   * `$dxk$v=2$argon2id$m=<KiB>,t=<iterations>,p=<lanes>$<salt>$<nonce + ciphertext>`.
    * 
    * @param {Uint8Array | string} secretKey - The user's secret key (binary or Base64)
    * @param {string} password - The user's password (minimum 8 characters recommended)
-   * @returns {string} Base64 string containing nonce + encrypted secret key
+   * @param {KdfParams} params - Argon2id cost parameters (defaults to DEFAULT_KDF_PARAMS)
+   * @returns {string} Versioned key envelope
    * 
    * @example
    * ```typescript
@@ -137,47 +212,39 @@ export class KeyManager {
    * localStorage.setItem('encryptedSecretKey', encryptedKey);
    * ```
    */
-  static encryptSecretKey(secretKey: Uint8Array | string, password: string): string {
+  static encryptSecretKey(
+    secretKey: Uint8Array | string,
+    password: string,
+    params: KdfParams = DEFAULT_KDF_PARAMS
+  ): string {
     // Convert secretKey to Uint8Array if it's a string
     const secretKeyBytes = typeof secretKey === 'string' ? decodeBase64(secretKey) : secretKey;
     
-    // Generate a random nonce for this encryption
+    const salt = nacl.randomBytes(ENVELOPE_SALT_LENGTH);
     const nonce = nacl.randomBytes(nacl.secretbox.nonceLength);
-    
-    // Create a key from the user's password using nacl.hash
-    const passwordBytes = encodeUTF8(password);
-    // Ensure passwordBytes is a Uint8Array for nacl.hash - handle different build environments
-    let passwordBytesArray: Uint8Array;
-    if (passwordBytes instanceof Uint8Array) {
-      passwordBytesArray = passwordBytes;
-    } else if (typeof passwordBytes === 'string') {
-      // In some builds, encodeUTF8 returns a string, convert it manually
-      passwordBytesArray = new TextEncoder().encode(password);
-    } else {
-      // Fallback: assume it's array-like
-      passwordBytesArray = new Uint8Array(passwordBytes);
-    }
-    const passwordKey = nacl.hash(passwordBytesArray).slice(0, nacl.secretbox.keyLength);
-    
-    // Encrypt the secret key
+    const passwordKey = deriveArgon2idKey(password, salt, params);
     const encryptedSecretKey = nacl.secretbox(secretKeyBytes, nonce, passwordKey);
+    KeyManager.clearKey(passwordKey);
     
-    // Combine nonce + encrypted key and encode as Base64
+    // Combine nonce + encrypted key
     const combined = new Uint8Array(nonce.length + encryptedSecretKey.length);
     combined.set(nonce);
     combined.set(encryptedSecretKey, nonce.length);
     
-    return encodeBase64(combined);
+    const { memoryKib, iterations, parallelism } = params;
+    return `${ENVELOPE_PREFIX}v=2$argon2id$m=${memoryKib},t=${iterations},p=${parallelism}` +
+      `$${encodeBase64(salt)}$${encodeBase64(combined)}`;
   }
 
   /**
    * Decrypt the stored private key on login
    * 
-   * Decrypts a password-encrypted private key using the same algorithm as encryptSecretKey.
-   * The password is hashed and used to decrypt the key. Returns null if the password
-   * is incorrect or the data is corrupted.
+   * Accepts both versioned Argon2id envelopes and legacy envelopes, which are a bare
+   * Base64 nonce + ciphertext keyed by an unsalted SHA-512 of the password. Returns
+   * null if the password is incorrect or the data is corrupted. Callers should
+   * re-encrypt the key when `envelopeNeedsUpgrade` reports true.
    * 
-   * @param {string} encryptedKeyB64 - Base64 encoded nonce + encrypted secret key
+   * @param {string} encryptedKeyB64 - Key envelope from encryptSecretKey
    * @param {string} password - The user's password
    * @returns {Uint8Array | null} The plaintext secret key or null if decryption fails
    * 
@@ -197,30 +264,24 @@ export class KeyManager {
    */
   static decryptSecretKey(encryptedKeyB64: string, password: string): Uint8Array | null {
     try {
-      // Decode the Base64 string
-      const combined = decodeBase64(encryptedKeyB64);
+      let combined: Uint8Array;
+      let passwordKey: Uint8Array;
+
+      const envelope = parseEnvelope(encryptedKeyB64);
+      if (envelope) {
+        combined = envelope.payload;
+        passwordKey = deriveArgon2idKey(password, envelope.salt, envelope.params);
+      } else {
+        combined = decodeBase64(encryptedKeyB64);
+        passwordKey = deriveLegacyKey(password);
+      }
       
       // Extract nonce and encrypted key
       const nonce = combined.slice(0, nacl.secretbox.nonceLength);
       const encryptedSecretKey = combined.slice(nacl.secretbox.nonceLength);
       
-      // Derive the password key (must be exact same method as encryption)
-      const passwordBytes = encodeUTF8(password);
-      // Ensure passwordBytes is a Uint8Array for nacl.hash - handle different build environments
-      let passwordBytesArray: Uint8Array;
-      if (passwordBytes instanceof Uint8Array) {
-        passwordBytesArray = passwordBytes;
-      } else if (typeof passwordBytes === 'string') {
-        // In some builds, encodeUTF8 returns a string, convert it manually
-        passwordBytesArray = new TextEncoder().encode(password);
-      } else {
-        // Fallback: assume it's array-like
-        passwordBytesArray = new Uint8Array(passwordBytes);
-      }
-      const passwordKey = nacl.hash(passwordBytesArray).slice(0, nacl.secretbox.keyLength);
-      
-      // Decrypt the secret key
       const decryptedSecretKey = nacl.secretbox.open(encryptedSecretKey, nonce, passwordKey);
+      KeyManager.clearKey(passwordKey);
       
       return decryptedSecretKey;
     } catch (error) {
@@ -229,6 +290,26 @@ export class KeyManager {
     }
   }
 
+  /**
+   * Check whether a key envelope should be re-encrypted with encryptSecretKey
+   * 
+   * True for legacy SHA-512 envelopes and for envelopes sealed with weaker
+   * parameters than DEFAULT_KDF_PARAMS.
+   * 
+   * @param {string} encryptedKeyB64 - Key envelope to inspect
+   * @returns {boolean} True if the envelope is outdated
+   */
+  static envelopeNeedsUpgrade(encryptedKeyB64: string): boolean {
+    try {
+      const envelope = parseEnvelope(encryptedKeyB64);
+      if (!envelope) return true;
+      return envelope.params.memoryKib < DEFAULT_KDF_PARAMS.memoryKib ||
+        envelope.params.iterations < DEFAULT_KDF_PARAMS.iterations;
+    } catch {
+      return false;
+    }
+  }
+
   /**
    * Convert a Base64 public key to Uint8Array
    * 
@@ -0,0 +1,28 @@
+import { describe, it, expect } from 'vitest';
+import { decodeBase64 } from 'tweetnacl-util';
+import { KeyManager } from '../KeyManager';
+import fixture from '../../../../src-tauri/tests/fixtures/key-envelope.json';
+
+// The same envelopes are opened by src-tauri/src/crypto/envelope.rs.
+const secretKey = decodeBase64(fixture.secretKey);
+const fastParams = { memoryKib: 64, iterations: 1, parallelism: 1 };
+
+describe('KeyManager key envelopes', () => {
+  it('opens legacy SHA-512 envelopes and flags them for upgrade', () => {
+    expect(KeyManager.decryptSecretKey(fixture.legacyEnvelope, fixture.password)).toEqual(secretKey);
+    expect(KeyManager.envelopeNeedsUpgrade(fixture.legacyEnvelope)).toBe(true);
+  });
+
+  it('opens Argon2id envelopes produced by Rust', () => {
+    expect(KeyManager.decryptSecretKey(fixture.v2Envelope, fixture.password)).toEqual(secretKey);
+    expect(KeyManager.envelopeNeedsUpgrade(fixture.v2Envelope)).toBe(false);
+  });
+
+  it('round-trips and rejects a wrong password', () => {
+    const envelope = KeyManager.encryptSecretKey(secretKey, 'pw', fastParams);
+    expect(envelope.startsWith('$dxk$v=2$argon2id$m=64,t=1,p=1$')).toBe(true);
+    expect(KeyManager.decryptSecretKey(envelope, 'pw')).toEqual(secretKey);
+    expect(KeyManager.decryptSecretKey(envelope, 'wrong')).toBeNull();
+    expect(KeyManager.envelopeNeedsUpgrade(envelope)).toBe(true);
+  });
+});
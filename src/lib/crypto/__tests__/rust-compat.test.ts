@@ -0,0 +1,77 @@
+import { describe, it, expect, vi, afterEach } from 'vitest';
+import nacl from 'tweetnacl';
+import { decodeBase64 } from 'tweetnacl-util';
+import { EntryCryptor } from '../EntryCryptor';
+import fixture from '../../../../src-tauri/tests/fixtures/entry-cryptor.json';
+
+// The same fixture is decrypted by the Rust crypto module (src-tauri/src/crypto),
+// so both implementations are pinned to one wire format. EntryCryptor reproduces
+// it byte for byte from its key and nonces, so Rust decrypts exactly what TS encrypts.
+const owner = {
+  publicKey: decodeBase64(fixture.owner.publicKey),
+  secretKey: decodeBase64(fixture.owner.secretKey)
+};
+const recipient = {
+  publicKey: decodeBase64(fixture.recipient.publicKey),
+  secretKey: decodeBase64(fixture.recipient.secretKey)
+};
+
+/** Make `nacl.randomBytes` return `values` in turn, as the fixture was encrypted with them */
+function replayRandomBytes(...values: Uint8Array[]) {
+  const spy = vi.spyOn(nacl, 'randomBytes');
+  // EntryCryptor zeroes the entry key after use, so hand out copies.
+  for (const value of values) spy.mockReturnValueOnce(value.slice());
+}
+
+const entryKey = nacl.box.open(
+  decodeBase64(fixture.encrypted.encryptedEntryKeyB64),
+  decodeBase64(fixture.encrypted.keyNonceB64),
+  owner.publicKey,
+  owner.secretKey
+)!;
+
+describe('EntryCryptor / Rust fixture compatibility', () => {
+  afterEach(() => {
+    vi.restoreAllMocks();
+  });
+
+  it('decrypts the shared fixture', () => {
+    const entry = EntryCryptor.decryptEntry(fixture.encrypted, owner.secretKey, owner.publicKey);
+    expect(entry).toEqual(fixture.entry);
+  });
+
+  it('decrypts the fixture key re-wrapped for a recipient', () => {
+    const entry = EntryCryptor.decryptEntry(
+      { ...fixture.encrypted, ...fixture.sharedWithRecipient },
+      recipient.secretKey,
+      owner.publicKey
+    );
+    expect(entry).toEqual(fixture.entry);
+  });
+
+  it('encrypts the fixture byte for byte', () => {
+    replayRandomBytes(
+      entryKey,
+      decodeBase64(fixture.encrypted.contentNonceB64),
+      decodeBase64(fixture.encrypted.keyNonceB64)
+    );
+    expect(EntryCryptor.encryptEntry(fixture.entry, owner)).toEqual(fixture.encrypted);
+  });
+
+  it('re-wraps the fixture key for the recipient byte for byte', () => {
+    replayRandomBytes(decodeBase64(fixture.sharedWithRecipient.keyNonceB64));
+    const shared = EntryCryptor.rewrapEntryKey(
+      fixture.encrypted.encryptedEntryKeyB64,
+      fixture.encrypted.keyNonceB64,
+      owner,
+      recipient.publicKey
+    );
+    expect(shared).toEqual(fixture.sharedWithRecipient);
+  });
+
+  it('produces output in the fixture shape', () => {
+    const encrypted = EntryCryptor.encryptEntry(fixture.entry, owner);
+    expect(Object.keys(encrypted).sort()).toEqual(Object.keys(fixture.encrypted).sort());
+    expect(EntryCryptor.decryptEntry(encrypted, owner.secretKey, owner.publicKey)).toEqual(fixture.entry);
+  });
+});
@@ -9,6 +9,7 @@ import { browser } from '$app/environment';
 import { KeyManager, type UserKeyPairB64 } from '../../crypto/KeyManager.js';
 import { sessionManager } from './session-manager.service.js';
 import { e2eStorage } from './storage.service.js';
+import { nativeSession, type NativeStoredKeys } from './native-session.service.js';
 import type { StoredUserKeys } from './types.js';
 
 /**
@@ -93,6 +94,34 @@ export class E2EAuthService {
     }
   }
 
+  /**
+   * Generate keys in the native session (Tauri), leaving it unlocked
+   * 
+   * The secret key is generated and sealed by the Rust backend and never enters JS.
+   * 
+   * @param {string} userId - Unique identifier for the user
+   * @param {string} password - User's password (minimum 8 characters)
+   * @returns {Promise<NativeStoredKeys | null>} The public key and envelope to back up, or null if failed
+   */
+  async createNativeKeys(userId: string, password: string): Promise<NativeStoredKeys | null> {
+    if (!userId || typeof userId !== 'string' || userId.trim().length === 0) {
+      console.error('Invalid user ID provided');
+      return null;
+    }
+
+    if (!password || typeof password !== 'string' || password.length < 8) {
+      console.error('Invalid password provided - must be at least 8 characters');
+      return null;
+    }
+
+    try {
+      return await nativeSession.createKeys(userId, password);
+    } catch (error) {
+      console.error('Native key creation failed:', error);
+      return null;
+    }
+  }
+
   /**
    * Login with password - decrypt stored keys
    * 
@@ -100,15 +129,20 @@ export class E2EAuthService {
    * Decrypts the stored secret key using the provided password.
    * 
    * @param {string} password - User's password
-   * @returns {boolean} True if login successful and session unlocked
+   * @returns {Promise<boolean>} True if login successful and session unlocked
    */
-  login(password: string): boolean {
+  async login(password: string): Promise<boolean> {
     // Input validation
     if (!password || typeof password !== 'string' || password.length === 0) {
       console.error('Invalid password provided');
       return false;
     }
 
+    if (sessionManager.isNative()) {
+      await sessionManager.ready();
+      return sessionManager.unlockNative(password);
+    }
+
     try {
       const storedKeys = e2eStorage.getStoredKeys();
       if (!storedKeys) {
@@ -136,6 +170,13 @@ export class E2EAuthService {
         return false;
       }
       
+      // Re-encrypt legacy or weak envelopes now that we know the password
+      if (KeyManager.envelopeNeedsUpgrade(storedKeys.encryptedSecretKeyB64)) {
+        e2eStorage.updateStoredKeys({
+          encryptedSecretKeyB64: KeyManager.encryptSecretKey(secretKey, password)
+        });
+      }
+      
       sessionManager.createSession(storedKeys.userId, userKeyPair, storedKeys.publicKeyB64);
       return true;
     } catch (error) {
@@ -152,9 +193,9 @@ export class E2EAuthService {
    * 
    * @param {string} oldPassword - Current password
    * @param {string} newPassword - New password (minimum 8 characters)
-   * @returns {boolean} True if password change successful
+   * @returns {Promise<boolean>} True if password change successful
    */
-  changePassword(oldPassword: string, newPassword: string): boolean {
+  async changePassword(oldPassword: string, newPassword: string): Promise<boolean> {
     // Input validation
     if (!oldPassword || typeof oldPassword !== 'string') {
       console.error('Invalid old password provided');
@@ -171,6 +212,16 @@ export class E2EAuthService {
       return false;
     }
 
+    if (sessionManager.isNative()) {
+      try {
+        await nativeSession.changePassword(oldPassword, newPassword);
+        return true;
+      } catch (error) {
+        console.error('Password change failed:', error);
+        return false;
+      }
+    }
+
     try {
       const storedKeys = e2eStorage.getStoredKeys();
       if (!storedKeys) {
@@ -234,7 +285,7 @@ export class E2EAuthService {
    * @returns {boolean} True if stored keys exist
    */
   hasStoredKeys(): boolean {
-    return e2eStorage.hasStoredKeys();
+    return sessionManager.hasStoredKeys();
   }
 
   /**
@@ -245,6 +296,10 @@ export class E2EAuthService {
    */
   clearStoredKeys(): void {
     e2eStorage.clearStoredKeys();
+    if (sessionManager.isNative()) {
+      nativeSession.forget().catch((error) => console.error('Failed to forget native keys:', error));
+      return;
+    }
     sessionManager.logout();
   }
 
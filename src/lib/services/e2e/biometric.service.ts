@@ -75,7 +75,7 @@ export class E2EBiometricService {
       }
       
       // Verify the password works first
-      if (!e2eAuth.login(password)) {
+      if (!(await e2eAuth.login(password))) {
         console.error('Invalid password provided');
         return false;
       }
@@ -190,7 +190,7 @@ export class E2EBiometricService {
       );
 
       // Use decrypted password for normal login
-      const loginSuccess = e2eAuth.login(password);
+      const loginSuccess = await e2eAuth.login(password);
       
       // Clear password from memory immediately
       password.replace(/./g, '0'); // Overwrite string content
@@ -9,6 +9,7 @@ import { KeyManager, type UserKeyPairB64 } from '../../crypto/KeyManager.js';
 import { fetch } from '../../utils/fetch.js';
 import { sessionManager } from './session-manager.service.js';
 import { e2eStorage } from './storage.service.js';
+import { nativeSession } from './native-session.service.js';
 import type { StoredUserKeys } from './types.js';
 
 /**
@@ -165,11 +166,35 @@ export class E2ECloudSyncService {
     }
   }
 
+  /**
+   * Backup an already sealed key to the cloud, e.g. one created by the native session
+   * 
+   * Only backs up if the user doesn't already have keys in the cloud.
+   * 
+   * @param {string} userId - User identifier
+   * @param {string} publicKeyB64 - Base64-encoded public key
+   * @param {string} encryptedSecretKeyB64 - Password-protected secret key envelope
+   * @returns {Promise<boolean>} True if backup successful
+   */
+  async backupEnvelopeToCloud(userId: string, publicKeyB64: string, encryptedSecretKeyB64: string): Promise<boolean> {
+    try {
+      if (await this.hasCloudEncryptionKeys(userId)) {
+        console.log('User already has encryption keys in cloud, skipping backup to prevent overwrite');
+        return true;
+      }
+      return await this.updateUserEncryptionKeys(userId, publicKeyB64, encryptedSecretKeyB64);
+    } catch (error) {
+      console.error('Key backup to cloud failed:', error);
+      return false;
+    }
+  }
+
   /**
    * Restore encryption keys from the cloud
    * 
    * Downloads and decrypts user's encryption keys from the backend.
-   * Stores them locally and creates an active session.
+   * Stores them locally and creates an active session. Under Tauri the keys are
+   * handed to the native session instead, which decrypts them itself.
    * 
    * @param {string} userId - User identifier
    * @param {string} password - User's password for decryption
@@ -227,15 +252,29 @@ export class E2ECloudSyncService {
         return false;
       }
       
-      // Validate key formats (basic check for Base64)
+      // Validate key format (basic check for Base64). The private key may be a
+      // versioned envelope, which decryptSecretKey validates itself.
       try {
         atob(userData.public_key);
-        atob(userData.encrypted_private_key);
       } catch (error) {
         console.error('Invalid key format in cloud data');
         return false;
       }
 
+      if (sessionManager.isNative()) {
+        const status = await nativeSession.refresh();
+        if (status.state === 'noKeys' || status.publicKeyB64 !== userData.public_key) {
+          await nativeSession.importKeys({
+            userId,
+            publicKeyB64: userData.public_key,
+            envelope: userData.encrypted_private_key
+          });
+        }
+        const unlocked = await sessionManager.unlockNative(password);
+        console.log(unlocked ? '=== Restored encryption keys into the native session ===' : 'Failed to unlock restored keys - invalid password?');
+        return unlocked;
+      }
+
       console.log('Attempting to decrypt private key with provided password...');
       
       // Try to decrypt the private key with the provided password
@@ -247,9 +286,17 @@ export class E2ECloudSyncService {
 
       console.log('Private key decrypted successfully');
 
+      // Re-encrypt legacy or weak envelopes and replace the cloud copy
+      let encryptedSecretKeyB64: string = userData.encrypted_private_key;
+      if (KeyManager.envelopeNeedsUpgrade(encryptedSecretKeyB64)) {
+        encryptedSecretKeyB64 = KeyManager.encryptSecretKey(secretKey, password);
+        const uploaded = await this.updateUserEncryptionKeys(userId, userData.public_key, encryptedSecretKeyB64);
+        console.log(uploaded ? 'Upgraded key envelope in cloud' : 'Failed to upgrade key envelope in cloud');
+      }
+
       // Store the keys locally
       const storedKeys: StoredUserKeys = {
-        encryptedSecretKeyB64: userData.encrypted_private_key,
+        encryptedSecretKeyB64,
         publicKeyB64: userData.public_key,
         userId
       };
@@ -7,7 +7,8 @@
  * @description This service acts as the main facade for the E2E encryption system,
  * coordinating between authentication, session management, storage, biometrics, cloud sync,
  * and debug utilities. It provides the core encryption/decryption methods and maintains
- * backward compatibility with the original service interface.
+ * backward compatibility with the original service interface. Under Tauri the crypto
+ * runs in the native session, so the secret key never enters JS memory.
  */
 
 import { KeyManager, type UserKeyPairB64 } from '../../crypto/KeyManager.js';
@@ -21,6 +22,7 @@ import { e2eAuth } from './auth.service.js';
 import { e2eBiometric } from './biometric.service.js';
 import { e2eCloudSync } from './cloud-sync.service.js';
 import { e2eDebug } from './debug.service.js';
+import { nativeSession } from './native-session.service.js';
 
 /**
  * End-to-End Encryption Service
@@ -41,9 +43,9 @@ export class E2EEncryptionService {
    * Encrypt a new entry for the current user
    * 
    * @param {EntryObject} entryObject - The entry data to encrypt
-   * @returns {EncryptedEntryData | null} Encrypted entry data or null if failed
+   * @returns {Promise<EncryptedEntryData | null>} Encrypted entry data or null if failed
    */
-  encryptEntry(entryObject: EntryObject): EncryptedEntryData | null {
+  async encryptEntry(entryObject: EntryObject): Promise<EncryptedEntryData | null> {
     const session = sessionManager.getCurrentSession();
     if (!session || !session.isUnlocked) {
       console.error('Cannot encrypt entry - session not unlocked');
@@ -56,6 +58,9 @@ export class E2EEncryptionService {
     }
 
     try {
+      if (sessionManager.isNative()) {
+        return await nativeSession.encryptEntry(entryObject);
+      }
       return EntryCryptor.encryptEntry(entryObject, session.userKeyPair);
     } catch (error) {
       console.error('Entry encryption failed:', error);
@@ -88,6 +93,15 @@ export class E2EEncryptionService {
       return null;
     }
 
+    if (sessionManager.isNative()) {
+      try {
+        return await nativeSession.encryptEntryWithKey(entryObject, existingEncryptedEntryKeyB64, existingKeyNonceB64);
+      } catch (error) {
+        console.error('Failed to encrypt with existing key:', error);
+        return null;
+      }
+    }
+
     try {
       console.log('=== Encrypting with existing key ===');
       
@@ -151,9 +165,9 @@ export class E2EEncryptionService {
    * 
    * @param {EncryptedEntryData} encryptedData - The encrypted entry data
    * @param {string} authorPublicKeyB64 - Public key of the entry's author
-   * @returns {EntryObject | null} Decrypted entry object or null if failed
+   * @returns {Promise<EntryObject | null>} Decrypted entry object or null if failed
    */
-  decryptEntry(encryptedData: EncryptedEntryData, authorPublicKeyB64: string): EntryObject | null {
+  async decryptEntry(encryptedData: EncryptedEntryData, authorPublicKeyB64: string): Promise<EntryObject | null> {
     const session = sessionManager.getCurrentSession();
     if (!session || !session.isUnlocked) {
       console.error('Cannot decrypt entry - session not unlocked');
@@ -170,6 +184,15 @@ export class E2EEncryptionService {
       return null;
     }
 
+    if (sessionManager.isNative()) {
+      try {
+        return await nativeSession.decryptEntry(encryptedData, authorPublicKeyB64);
+      } catch (error) {
+        console.error('Entry decryption failed:', error);
+        return null;
+      }
+    }
+
     try {
       console.log('=== E2E Service Decryption Debug ===');
       console.log('Encrypted data structure:', {
@@ -237,13 +260,13 @@ export class E2EEncryptionService {
    * @param {string} encryptedEntryKey - The encrypted entry key to re-wrap
    * @param {string} keyNonce - The nonce used with the entry key
    * @param {string} recipientPublicKeyB64 - Public key of the recipient user
-   * @returns {Object | null} New encrypted key and nonce for recipient, or null if failed
+   * @returns {Promise<Object | null>} New encrypted key and nonce for recipient, or null if failed
    */
-  rewrapEntryKeyForUser(
+  async rewrapEntryKeyForUser(
     encryptedEntryKey: string,
     keyNonce: string,
     recipientPublicKeyB64: string
-  ): { encryptedEntryKeyB64: string; keyNonceB64: string } | null {
+  ): Promise<{ encryptedEntryKeyB64: string; keyNonceB64: string } | null> {
     const session = sessionManager.getCurrentSession();
     if (!session || !session.isUnlocked) {
       console.error('Cannot rewrap entry key - session not unlocked');
@@ -261,6 +284,9 @@ export class E2EEncryptionService {
     }
 
     try {
+      if (sessionManager.isNative()) {
+        return await nativeSession.rewrapEntryKey(encryptedEntryKey, keyNonce, recipientPublicKeyB64);
+      }
       const recipientPublicKey = KeyManager.publicKeyFromB64(recipientPublicKeyB64);
       return EntryCryptor.rewrapEntryKey(
         encryptedEntryKey,
@@ -274,6 +300,36 @@ export class E2EEncryptionService {
     }
   }
 
+  /**
+   * Decrypt the key of one of the user's own entries, for share links
+   * 
+   * @param {string} encryptedEntryKeyB64 - The entry key wrapped for the user
+   * @param {string} keyNonceB64 - The nonce used with the entry key
+   * @returns {Promise<Uint8Array | null>} The raw entry key, or null if failed
+   */
+  async openEntryKey(encryptedEntryKeyB64: string, keyNonceB64: string): Promise<Uint8Array | null> {
+    const session = sessionManager.getCurrentSession();
+    if (!session || !session.isUnlocked) {
+      console.error('Cannot open entry key - session not unlocked');
+      return null;
+    }
+
+    try {
+      if (sessionManager.isNative()) {
+        return decodeBase64(await nativeSession.openEntryKey(encryptedEntryKeyB64, keyNonceB64));
+      }
+      return nacl.box.open(
+        decodeBase64(encryptedEntryKeyB64),
+        decodeBase64(keyNonceB64),
+        session.userKeyPair.publicKey,
+        session.userKeyPair.secretKey
+      );
+    } catch (error) {
+      console.error('Failed to open entry key:', error);
+      return null;
+    }
+  }
+
   /**
    * Generate content hashes for indexing
    * 
@@ -319,6 +375,26 @@ export class E2EEncryptionService {
     return e2eAuth.generateUserKeys();
   }
 
+  /**
+   * Create and back up keys for a first-time user, natively under Tauri
+   */
+  async createUserKeys(userId: string, password: string): Promise<boolean> {
+    if (!sessionManager.isNative()) {
+      return this.completeSignup(userId, this.generateUserKeys(), password);
+    }
+
+    const stored = await e2eAuth.createNativeKeys(userId, password);
+    if (!stored) {
+      return false;
+    }
+    const backedUp = await e2eCloudSync.backupEnvelopeToCloud(userId, stored.publicKeyB64, stored.envelope);
+    if (!backedUp) {
+      // Don't fail the signup if cloud backup fails
+      console.error('Failed to backup keys to cloud after signup');
+    }
+    return true;
+  }
+
   async completeSignup(userId: string, keyPair: UserKeyPairB64, password: string): Promise<boolean> {
     const result = await e2eAuth.completeSignup(userId, keyPair, password);
     
@@ -341,7 +417,7 @@ export class E2EEncryptionService {
     return result;
   }
 
-  login(password: string): boolean {
+  async login(password: string): Promise<boolean> {
     return e2eAuth.login(password);
   }
 
@@ -349,7 +425,7 @@ export class E2EEncryptionService {
     e2eAuth.logout();
   }
 
-  changePassword(oldPassword: string, newPassword: string): boolean {
+  async changePassword(oldPassword: string, newPassword: string): Promise<boolean> {
     return e2eAuth.changePassword(oldPassword, newPassword);
   }
 
@@ -389,7 +465,7 @@ export class E2EEncryptionService {
     sessionManager.lockSession();
   }
 
-  unlockSession(password: string): boolean {
+  async unlockSession(password: string): Promise<boolean> {
     return sessionManager.unlockSession(password);
   }
 
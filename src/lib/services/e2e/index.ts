@@ -35,6 +35,9 @@ export { e2eCloudSync } from './cloud-sync.service.js';
 // Debug utilities
 export { e2eDebug } from './debug.service.js';
 
+// Rust-owned session (Tauri only)
+export { nativeSession } from './native-session.service.js';
+
 // ============================================================================
 // TYPE EXPORTS
 // ============================================================================
@@ -57,4 +60,5 @@ export { E2ESessionManager } from './session-manager.service.js';
 export { E2EStorageService } from './storage.service.js';
 export { E2EBiometricService } from './biometric.service.js';
 export { E2ECloudSyncService } from './cloud-sync.service.js';
-export { E2EDebugService } from './debug.service.js';
\ No newline at end of file
+export { E2EDebugService } from './debug.service.js';
+export { NativeSessionService } from './native-session.service.js';
\ No newline at end of file
@@ -0,0 +1,235 @@
+/**
+ * Native E2E Session Service
+ * 
+ * Client for the Rust-owned session in `diaryx_lib` (Tauri only). The secret key
+ * stays in the native process: this service only sees session status, public keys
+ * and password-protected key envelopes, and encryption runs through native commands.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { writable, type Readable } from 'svelte/store';
+import type { EncryptedEntryData, EntryObject } from '../../crypto/EntryCryptor.js';
+
+/** Name of the event emitted by the native session on every state change */
+export const SESSION_EVENT = 'session-changed';
+
+export type NativeSessionState = 'noKeys' | 'locked' | 'unlocked';
+
+/** Mirrors `SessionStatus` in `session/mod.rs` */
+export interface NativeSessionStatus {
+  state: NativeSessionState;
+  userId: string | null;
+  publicKeyB64: string | null;
+  idleTimeoutSecs: number | null;
+  hasRecoveryPhrase: boolean;
+}
+
+/** Keys as persisted by the native session; safe to back up to the cloud */
+export interface NativeStoredKeys {
+  userId: string;
+  publicKeyB64: string;
+  envelope: string;
+  recoveryEnvelope?: string;
+}
+
+/** A new recovery phrase and the 1-based word positions the user must confirm */
+export interface RecoveryExport {
+  phrase: string;
+  challenge: number[];
+}
+
+/** Mirrors `RotationReport` in `rotation/mod.rs` */
+export interface KeyRotationReport {
+  publicKeyB64: string;
+  envelope: string;
+  vaultFiles: number;
+  cloudEntries: number;
+  recipientKeys: number;
+  resumed: boolean;
+}
+
+export interface RewrappedKey {
+  encryptedEntryKeyB64: string;
+  keyNonceB64: string;
+}
+
+/**
+ * Native Session Service
+ * 
+ * Thin typed wrapper over the session and crypto commands, with a reactive status.
+ */
+export class NativeSessionService {
+  private status = writable<NativeSessionStatus | null>(null);
+  private unlisten: UnlistenFn | null = null;
+
+  /** Reactive session status, updated on native lock/unlock (including auto-lock) */
+  get store(): Readable<NativeSessionStatus | null> {
+    return { subscribe: this.status.subscribe };
+  }
+
+  /**
+   * Start listening for native session changes and load the current status
+   */
+  async initialize(): Promise<NativeSessionStatus> {
+    if (!this.unlisten) {
+      this.unlisten = await listen<NativeSessionStatus>(SESSION_EVENT, (event) => {
+        this.status.set(event.payload);
+      });
+    }
+    return this.refresh();
+  }
+
+  dispose(): void {
+    this.unlisten?.();
+    this.unlisten = null;
+  }
+
+  async refresh(): Promise<NativeSessionStatus> {
+    return this.update(await invoke<NativeSessionStatus>('session_status'));
+  }
+
+  /** Generate keys for a new user; returns the envelope to back up */
+  async createKeys(userId: string, password: string): Promise<NativeStoredKeys> {
+    const stored = await invoke<NativeStoredKeys>('create_session_keys', { userId, password });
+    await this.refresh();
+    return stored;
+  }
+
+  /** Adopt keys restored from the cloud; the session is left locked */
+  async importKeys(keys: NativeStoredKeys): Promise<NativeSessionStatus> {
+    return this.update(await invoke<NativeSessionStatus>('import_session_keys', {
+      userId: keys.userId,
+      publicKeyB64: keys.publicKeyB64,
+      envelope: keys.envelope,
+      recoveryEnvelope: keys.recoveryEnvelope ?? null
+    }));
+  }
+
+  async unlock(password: string): Promise<NativeSessionStatus> {
+    return this.update(await invoke<NativeSessionStatus>('unlock_session', { password }));
+  }
+
+  async lock(): Promise<NativeSessionStatus> {
+    return this.update(await invoke<NativeSessionStatus>('lock_session'));
+  }
+
+  /** Lock and delete the device's stored keys */
+  async forget(): Promise<NativeSessionStatus> {
+    return this.update(await invoke<NativeSessionStatus>('forget_session'));
+  }
+
+  /** Re-seal the key under a new password; returns the envelope to back up */
+  async changePassword(oldPassword: string, newPassword: string): Promise<NativeStoredKeys> {
+    return await invoke<NativeStoredKeys>('change_session_password', { oldPassword, newPassword });
+  }
+
+  /** Set and persist the idle auto-lock timeout; `null` disables it */
+  async setIdleTimeout(seconds: number | null): Promise<NativeSessionStatus> {
+    return this.update(await invoke<NativeSessionStatus>('set_idle_timeout', { seconds }));
+  }
+
+  /** Generate a recovery phrase to show once; nothing is saved until confirmed */
+  async exportRecoveryPhrase(): Promise<RecoveryExport> {
+    return await invoke<RecoveryExport>('export_recovery_phrase');
+  }
+
+  /** Confirm the challenged words, in challenge order; returns the keys to back up */
+  async confirmRecoveryPhrase(words: string[]): Promise<NativeStoredKeys> {
+    const stored = await invoke<NativeStoredKeys>('confirm_recovery_phrase', { words });
+    await this.refresh();
+    return stored;
+  }
+
+  /**
+   * Restore keys from a recovery phrase under a new password, without network access.
+   * Pass `backup` when this device has no stored keys (e.g. keys fetched earlier).
+   */
+  async restoreFromRecoveryPhrase(
+    phrase: string,
+    newPassword: string,
+    backup?: NativeStoredKeys
+  ): Promise<NativeStoredKeys> {
+    const stored = await invoke<NativeStoredKeys>('restore_from_recovery_phrase', {
+      phrase,
+      newPassword,
+      backup: backup ?? null
+    });
+    await this.refresh();
+    return stored;
+  }
+
+  /**
+   * Rotate the key pair, re-wrapping every owned entry key locally and in the cloud.
+   * Resumes an interrupted rotation; the recovery phrase must be exported again.
+   */
+  async rotateMasterKey(
+    apiBaseUrl: string,
+    accessToken: string,
+    password: string
+  ): Promise<KeyRotationReport> {
+    const report = await invoke<KeyRotationReport>('rotate_master_key', {
+      apiBaseUrl,
+      accessToken,
+      password
+    });
+    await this.refresh();
+    return report;
+  }
+
+  /** Whether an interrupted key rotation is waiting to be resumed */
+  async isKeyRotationPending(): Promise<boolean> {
+    return await invoke<boolean>('key_rotation_pending');
+  }
+
+  /** Report user activity to postpone the idle lock */
+  async touch(): Promise<void> {
+    await invoke('touch_session');
+  }
+
+  async encryptEntry(entry: EntryObject): Promise<EncryptedEntryData> {
+    return await invoke<EncryptedEntryData>('encrypt_entry', { entry });
+  }
+
+  /** Re-encrypt an own entry under its existing entry key, keeping issued access keys valid */
+  async encryptEntryWithKey(
+    entry: EntryObject,
+    encryptedEntryKeyB64: string,
+    keyNonceB64: string
+  ): Promise<EncryptedEntryData> {
+    return await invoke<EncryptedEntryData>('encrypt_entry_with_key', {
+      entry,
+      encryptedEntryKeyB64,
+      keyNonceB64
+    });
+  }
+
+  async decryptEntry(data: EncryptedEntryData, authorPublicKeyB64: string): Promise<EntryObject> {
+    return await invoke<EntryObject>('decrypt_entry', { data, authorPublicKeyB64 });
+  }
+
+  async rewrapEntryKey(
+    encryptedEntryKeyB64: string,
+    keyNonceB64: string,
+    recipientPublicKeyB64: string
+  ): Promise<RewrappedKey> {
+    return await invoke<RewrappedKey>('rewrap_entry_key', {
+      encryptedEntryKeyB64,
+      keyNonceB64,
+      recipientPublicKeyB64
+    });
+  }
+
+  /** The raw key of an own entry, for share links; returned as base64 */
+  async openEntryKey(encryptedEntryKeyB64: string, keyNonceB64: string): Promise<string> {
+    return await invoke<string>('open_entry_key', { encryptedEntryKeyB64, keyNonceB64 });
+  }
+
+  private update(status: NativeSessionStatus): NativeSessionStatus {
+    this.status.set(status);
+    return status;
+  }
+}
+
+// Export singleton instance
+export const nativeSession = new NativeSessionService();
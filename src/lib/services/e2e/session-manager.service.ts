@@ -3,12 +3,18 @@
  * 
  * Manages E2E encryption session state and reactive updates.
  * Handles session creation, validation, locking/unlocking, and store management.
+ * 
+ * Under Tauri the session is owned by the Rust backend: the secret key never enters
+ * JS memory, the session mirrors the native status, and keys left in localStorage by
+ * earlier versions are handed to the native session once and then removed.
  */
 
 import { writable, type Writable } from 'svelte/store';
 import { browser } from '$app/environment';
 import { KeyManager } from '../../crypto/KeyManager.js';
+import { detectTauri } from '../../utils/tauri.js';
 import { e2eStorage } from './storage.service.js';
+import { nativeSession, type NativeSessionStatus } from './native-session.service.js';
 import type { E2ESession, StoredUserKeys } from './types.js';
 
 /**
@@ -19,13 +25,85 @@ import type { E2ESession, StoredUserKeys } from './types.js';
 export class E2ESessionManager {
   private currentSession: E2ESession | null = null;
   private sessionStore: Writable<E2ESession | null> = writable(null);
+  private readonly native = browser && detectTauri();
+  private nativeStatus: NativeSessionStatus | null = null;
+  private nativeReady: Promise<void> = Promise.resolve();
 
   constructor() {
-    if (browser) {
+    if (this.native) {
+      this.nativeReady = this.initializeNative();
+    } else if (browser) {
       this.initializeFromStorage();
     }
   }
 
+  /**
+   * Whether the session is owned by the Rust backend (Tauri)
+   */
+  isNative(): boolean {
+    return this.native;
+  }
+
+  /**
+   * Resolves once the native session status is known (immediately outside Tauri)
+   */
+  ready(): Promise<void> {
+    return this.nativeReady;
+  }
+
+  /**
+   * Follow the native session and migrate keys stored by earlier versions
+   */
+  private async initializeNative(): Promise<void> {
+    try {
+      nativeSession.store.subscribe((status) => {
+        if (status) this.applyNativeStatus(status);
+      });
+      const status = await nativeSession.initialize();
+      await this.migrateStoredKeys(status);
+    } catch (error) {
+      console.error('Failed to initialize native session:', error);
+    }
+  }
+
+  /**
+   * Hand keys kept in localStorage to the native session, then remove them
+   * 
+   * Runs once: the keys are only removed after the native session holds keys.
+   */
+  private async migrateStoredKeys(status: NativeSessionStatus): Promise<void> {
+    const storedKeys = e2eStorage.getStoredKeys();
+    if (!storedKeys) return;
+
+    if (status.state === 'noKeys') {
+      await nativeSession.importKeys({
+        userId: storedKeys.userId,
+        publicKeyB64: storedKeys.publicKeyB64,
+        envelope: storedKeys.encryptedSecretKeyB64
+      });
+      console.log('Moved stored encryption keys to the native session');
+    }
+    e2eStorage.clearStoredKeys();
+  }
+
+  /**
+   * Mirror a native status; the key pair is left empty as it stays native
+   */
+  private applyNativeStatus(status: NativeSessionStatus): void {
+    this.nativeStatus = status;
+    if (status.state === 'noKeys' || !status.userId || !status.publicKeyB64) {
+      this.currentSession = null;
+    } else {
+      this.currentSession = {
+        userId: status.userId,
+        userKeyPair: { publicKey: KeyManager.publicKeyFromB64(status.publicKeyB64), secretKey: new Uint8Array() },
+        publicKeyB64: status.publicKeyB64,
+        isUnlocked: status.state === 'unlocked'
+      };
+    }
+    this.sessionStore.set(this.currentSession);
+  }
+
   /**
    * Initialize session from stored data
    * 
@@ -124,6 +202,9 @@ export class E2ESessionManager {
     if (!this.currentSession || !this.currentSession.isUnlocked) {
       return false;
     }
+    if (this.native) {
+      return true;
+    }
     
     try {
       return KeyManager.validateKeyPair(this.currentSession.userKeyPair);
@@ -140,6 +221,10 @@ export class E2ESessionManager {
    * Keeps keys in memory but marks session as locked.
    */
   lockSession(): void {
+    if (this.native) {
+      nativeSession.lock().catch((error) => console.error('Failed to lock native session:', error));
+      return;
+    }
     if (this.currentSession) {
       this.currentSession.isUnlocked = false;
       this.sessionStore.set(this.currentSession);
@@ -150,9 +235,9 @@ export class E2ESessionManager {
    * Unlock session with password verification
    * 
    * @param {string} password - User's password for verification
-   * @returns {boolean} True if unlock successful
+   * @returns {Promise<boolean>} True if unlock successful
    */
-  unlockSession(password: string): boolean {
+  async unlockSession(password: string): Promise<boolean> {
     if (!this.currentSession || this.currentSession.isUnlocked) {
       return false;
     }
@@ -160,6 +245,10 @@ export class E2ESessionManager {
     if (!password || typeof password !== 'string') {
       return false;
     }
+
+    if (this.native) {
+      return this.unlockNative(password);
+    }
     
     try {
       const storedKeys = e2eStorage.getStoredKeys();
@@ -185,6 +274,13 @@ export class E2ESessionManager {
         return false;
       }
 
+      // Re-encrypt legacy or weak envelopes now that we know the password
+      if (KeyManager.envelopeNeedsUpgrade(storedKeys.encryptedSecretKeyB64)) {
+        e2eStorage.updateStoredKeys({
+          encryptedSecretKeyB64: KeyManager.encryptSecretKey(secretKey, password)
+        });
+      }
+
       this.currentSession.userKeyPair = userKeyPair;
       this.currentSession.isUnlocked = true;
       this.sessionStore.set(this.currentSession);
@@ -195,12 +291,33 @@ export class E2ESessionManager {
     }
   }
 
+  /**
+   * Unlock the native session; the vault and sync resume natively
+   * 
+   * @param {string} password - User's password
+   * @returns {Promise<boolean>} True if unlock successful
+   */
+  async unlockNative(password: string): Promise<boolean> {
+    try {
+      const status = await nativeSession.unlock(password);
+      return status.state === 'unlocked';
+    } catch (error) {
+      console.error('Native session unlock failed:', error);
+      return false;
+    }
+  }
+
   /**
    * Logout - clear session and sensitive data
    * 
    * Securely clears the current session and removes sensitive key material from memory.
+   * The native session is locked instead; its keys stay stored like local ones do.
    */
   logout(): void {
+    if (this.native) {
+      this.lockSession();
+      return;
+    }
     if (this.currentSession && this.currentSession.userKeyPair) {
       // Clear sensitive data from memory
       KeyManager.clearKeyPair(this.currentSession.userKeyPair);
@@ -243,9 +360,21 @@ export class E2ESessionManager {
       hasSession: this.hasSession(),
       isUnlocked: this.isUnlocked(),
       userId: this.getCurrentUserId(),
-      hasStoredKeys: e2eStorage.hasStoredKeys()
+      hasStoredKeys: this.hasStoredKeys()
     };
   }
+
+  /**
+   * Check if this device has stored keys, natively or in localStorage
+   * 
+   * @returns {boolean} True if stored keys exist
+   */
+  hasStoredKeys(): boolean {
+    if (this.native) {
+      return !!this.nativeStatus && this.nativeStatus.state !== 'noKeys';
+    }
+    return e2eStorage.hasStoredKeys();
+  }
 }
 
 // Export singleton instance
@@ -121,7 +121,7 @@ class EntrySharingService {
 
         try {
           // Use the e2e encryption service to rewrap the key for this user
-          const rewrappedKey = e2eEncryptionService.rewrapEntryKeyForUser(
+          const rewrappedKey = await e2eEncryptionService.rewrapEntryKeyForUser(
             shareData.encryptedEntryKey,
             shareData.keyNonce,
             user.public_key
@@ -567,7 +567,7 @@ class EntrySharingService {
       }
 
       // Use the e2e encryption service to rewrap the key for the new user
-      const rewrappedKey = e2eEncryptionService.rewrapEntryKeyForUser(
+      const rewrappedKey = await e2eEncryptionService.rewrapEntryKeyForUser(
         authorAccessKey.encrypted_entry_key,
         authorAccessKey.key_nonce,
         targetUser.public_key
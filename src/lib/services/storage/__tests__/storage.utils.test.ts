@@ -4,6 +4,8 @@ import { titleToSafeFilename, generateUniqueFilename, createTitleFromId, hasCorr
 describe('storage.utils', () => {
   it('titleToSafeFilename sanitizes & normalizes', () => {
     expect(titleToSafeFilename('Hello  World!! ** DiaryX')).toBe('hello-world-diaryx');
+    expect(titleToSafeFilename('日記 Дневник')).toBe('日記-дневник');
+    expect(titleToSafeFilename('!!!')).toBe('untitled');
   });
 
   it('generateUniqueFilename increments when collision', async () => {
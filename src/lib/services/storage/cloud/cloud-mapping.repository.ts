@@ -56,6 +56,25 @@ export class CloudMappingRepository {
     return match ? match.localId : null;
   }
 
+  async getAllMappings(): Promise<CloudEntryMapping[]> {
+    const db = await this.initDB();
+    return db.getAll('cloudMappings');
+  }
+
+  /** Move mappings from old to new local IDs, e.g. once entries get stable IDs */
+  async renameLocalIds(renames: Record<string, string>): Promise<void> {
+    const db = await this.initDB();
+    const tx = db.transaction('cloudMappings', 'readwrite');
+    const store = tx.objectStore('cloudMappings');
+    for (const mapping of await store.getAll()) {
+      const localId = renames[mapping.localId];
+      if (!localId) continue;
+      await store.delete(mapping.localId);
+      await store.put({ ...mapping, localId });
+    }
+    await tx.done;
+  }
+
   async removeMapping(localId: string): Promise<void> {
     const db = await this.initDB();
     await db.delete('cloudMappings', localId);
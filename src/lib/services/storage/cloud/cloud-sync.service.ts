@@ -50,7 +50,7 @@ export class CloudSyncServiceImpl {
         if (!entry) return false;
         const parsed = FrontmatterService.parseContent(entry.content);
         const obj: EntryObject = { title: entry.title, content: entry.content, frontmatter: parsed.frontmatter, tags: FrontmatterService.extractTags(parsed.frontmatter) };
-        const enc = e2eEncryptionService.encryptEntry(obj);
+        const enc = await e2eEncryptionService.encryptEntry(obj);
         if (!enc) return false;
         const hashes = e2eEncryptionService.generateHashes(obj);
         const meta = { ...e2eEncryptionService.createEncryptionMetadata(), contentNonceB64: enc.contentNonceB64 };
@@ -196,7 +196,7 @@ export class CloudSyncServiceImpl {
             keyNonceB64: cloudEntry.access_key.key_nonce
           };
           if (!validateEncryptedData(encryptedData)) continue;
-          const decrypted = e2eEncryptionService.decryptEntry(encryptedData, authorPk);
+          const decrypted = await e2eEncryptionService.decryptEntry(encryptedData, authorPk);
           if (!decrypted || !decrypted.content) continue;
           let localId = existingLocalId;
           if (!localId) localId = await this.deps.generateUniqueImportId(decrypted.title || 'Untitled Entry');
@@ -0,0 +1,228 @@
+/**
+ * Sync Queue Service
+ *
+ * Client for the native outbound queue in `diaryx_lib` (Tauri only). Publish, update,
+ * unpublish, share and revoke requests are persisted natively and retried with backoff,
+ * so they survive going offline and restarting the app.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { writable, type Readable } from 'svelte/store';
+import { apiAuthStore } from '../../api-auth.service.js';
+
+/** Name of the event emitted by the native queue */
+export const SYNC_EVENT = 'sync-queue';
+
+/** Mirrors `Operation` in `sync/queue.rs` */
+export type SyncOperation =
+  | { kind: 'publish'; tagIds: string[] }
+  | { kind: 'update' }
+  | { kind: 'unpublish' }
+  | { kind: 'share'; tagIds: string[] }
+  | { kind: 'revoke'; userIds: string[] };
+
+/** Mirrors `PendingEntry` in `sync/queue.rs` */
+export interface PendingSyncEntry {
+  entryId: string;
+  operations: SyncOperation[];
+  attempts: number;
+  nextAttemptAt: string | null;
+  lastError: string | null;
+}
+
+/** Mirrors `SyncQueueStatus` in `sync/mod.rs` */
+export interface SyncQueueStatus {
+  online: boolean;
+  connected: boolean;
+  pending: PendingSyncEntry[];
+}
+
+/** Mirrors `SyncEvent` in `sync/mod.rs` */
+export type SyncQueueEvent =
+  | { kind: 'progress'; entryId: string; operation: SyncOperation; cloudId: string | null; remaining: number }
+  | { kind: 'failed'; entryId: string; operation: SyncOperation; error: string; willRetry: boolean; nextAttemptAt: string | null }
+  | { kind: 'conflict'; entryId: string; copyId: string | null }
+  | { kind: 'imported'; entryId: string; cloudId: string }
+  | { kind: 'removed'; entryId: string }
+  | { kind: 'shared'; cloudId: string; deleted: boolean }
+  | { kind: 'drained' };
+
+/** Mirrors `PullReport` in `sync/mod.rs` */
+export interface PullReport {
+  merged: string[];
+  conflicts: string[];
+  imported: string[];
+  removed: string[];
+  shared: string[];
+}
+
+/** Mirrors `ConflictStyle` in `sync/conflicts.rs` */
+export type ConflictStyle = 'markers' | 'copy';
+
+/** Mirrors `ConflictRecord` in `sync/conflicts.rs` */
+export interface SyncConflict {
+  entryId: string;
+  style: ConflictStyle;
+  copyId: string | null;
+  frontmatterKeys: string[];
+  bodyRegions: number;
+  remoteUpdatedAt: string;
+  detectedAt: string;
+}
+
+/** Mirrors `Resolution` in `sync/conflicts.rs` */
+export type ConflictResolution =
+  | { kind: 'local' }
+  | { kind: 'remote' }
+  | { kind: 'merged'; content: string };
+
+/** Mirrors `CrdtStatus` in `crdt/mod.rs` */
+export interface CrdtStatus {
+  enabled: boolean;
+  documents: number;
+}
+
+export class SyncQueueService {
+  private status = writable<SyncQueueStatus | null>(null);
+  private listeners = new Set<(event: SyncQueueEvent) => void>();
+  private cleanup: Array<() => void> = [];
+
+  /** Reactive queue status, refreshed on every native event */
+  get store(): Readable<SyncQueueStatus | null> {
+    return { subscribe: this.status.subscribe };
+  }
+
+  /**
+   * Forward native events, connectivity and credentials to the queue
+   */
+  async initialize(apiBaseUrl: string): Promise<void> {
+    if (this.cleanup.length) return;
+
+    const unlisten: UnlistenFn = await listen<SyncQueueEvent>(SYNC_EVENT, (event) => {
+      this.listeners.forEach((listener) => listener(event.payload));
+      void this.refresh();
+    });
+    this.cleanup.push(unlisten);
+
+    const reportConnectivity = () => void this.setOnline(navigator.onLine);
+    window.addEventListener('online', reportConnectivity);
+    window.addEventListener('offline', reportConnectivity);
+    this.cleanup.push(() => {
+      window.removeEventListener('online', reportConnectivity);
+      window.removeEventListener('offline', reportConnectivity);
+    });
+
+    this.cleanup.push(apiAuthStore.subscribe((session) => {
+      if (session?.isAuthenticated) {
+        void invoke('set_sync_credentials', {
+          apiBaseUrl,
+          userId: session.user.id,
+          accessToken: session.accessToken
+        });
+      } else {
+        void invoke('clear_sync_credentials');
+      }
+    }));
+
+    await this.setOnline(navigator.onLine);
+  }
+
+  dispose(): void {
+    this.cleanup.forEach((fn) => fn());
+    this.cleanup = [];
+  }
+
+  /** Subscribe to progress and failure events; returns an unsubscribe function */
+  onEvent(listener: (event: SyncQueueEvent) => void): () => void {
+    this.listeners.add(listener);
+    return () => this.listeners.delete(listener);
+  }
+
+  /**
+   * Queue an operation for an entry. Pass `cloudId` for entries published before
+   * the native queue existed.
+   */
+  async enqueue(entryId: string, operation: SyncOperation, cloudId?: string | null): Promise<SyncQueueStatus> {
+    return this.update(await invoke<SyncQueueStatus>('enqueue_sync_operation', {
+      entryId,
+      operation,
+      cloudId: cloudId ?? null
+    }));
+  }
+
+  async refresh(): Promise<SyncQueueStatus> {
+    return this.update(await invoke<SyncQueueStatus>('sync_queue_status'));
+  }
+
+  async setOnline(online: boolean): Promise<SyncQueueStatus> {
+    return this.update(await invoke<SyncQueueStatus>('set_sync_online', { online }));
+  }
+
+  /** Retry every queued entry now, ignoring backoff */
+  async retryNow(): Promise<SyncQueueStatus> {
+    return this.update(await invoke<SyncQueueStatus>('retry_sync_queue'));
+  }
+
+  /**
+   * Link entries published before the native queue existed, so pulling does
+   * not import them again
+   */
+  async linkCloudEntries(links: Array<{ entryId: string; cloudId: string }>): Promise<void> {
+    if (links.length) await invoke('link_cloud_entries', { links });
+  }
+
+  /**
+   * Read the server's change log from this device's cursor: merge remote edits,
+   * import entries published elsewhere and unlink deleted ones
+   */
+  async pullCloudChanges(): Promise<PullReport> {
+    return invoke<PullReport>('pull_cloud_changes');
+  }
+
+  async listConflicts(): Promise<SyncConflict[]> {
+    return invoke<SyncConflict[]>('list_sync_conflicts');
+  }
+
+  /** Local and remote side of a conflict, for a side-by-side view */
+  async getConflictSides(entryId: string): Promise<{ local: string; remote: string }> {
+    return invoke('get_conflict_sides', { entryId });
+  }
+
+  async resolveConflict(entryId: string, resolution: ConflictResolution): Promise<void> {
+    await invoke('resolve_conflict', { entryId, resolution });
+    await this.refresh();
+  }
+
+  async getConflictStyle(): Promise<ConflictStyle> {
+    return invoke<ConflictStyle>('get_conflict_style');
+  }
+
+  async setConflictStyle(style: ConflictStyle): Promise<void> {
+    await invoke('set_conflict_style', { style });
+  }
+
+  async getCrdtStatus(): Promise<CrdtStatus> {
+    return invoke<CrdtStatus>('crdt_status');
+  }
+
+  /**
+   * Keep every entry as a CRDT document so edits from several devices merge
+   * without conflicts. Returns the number of documents created.
+   */
+  async enableCrdtMode(): Promise<number> {
+    return invoke<number>('enable_crdt_mode');
+  }
+
+  async disableCrdtMode(): Promise<void> {
+    await invoke('disable_crdt_mode');
+  }
+
+  private update(status: SyncQueueStatus): SyncQueueStatus {
+    this.status.set(status);
+    return status;
+  }
+}
+
+// Export singleton instance
+export const syncQueueService = new SyncQueueService();
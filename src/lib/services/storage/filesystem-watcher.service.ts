@@ -1,30 +1,29 @@
-import { watch } from '@tauri-apps/plugin-fs';
-import { STORAGE_CONFIG } from './utils';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 
-export type FileChangeEvent = {
-  path: string;
-  event: 'create' | 'modify' | 'remove';
-};
+/**
+ * Typed entry change emitted by the native journal watcher.
+ * Changes made by the app itself are filtered out on the Rust side.
+ * A renamed file keeps its entry ID; its paths are relative to the journal root.
+ */
+export type EntryChange =
+  | { kind: 'create'; id: string }
+  | { kind: 'modify'; id: string }
+  | { kind: 'remove'; id: string }
+  | { kind: 'rename'; id: string; oldPath: string; newPath: string };
+
+/** Tauri event name used by the native watcher (see `watcher.rs`). */
+export const ENTRY_CHANGES_EVENT = 'entry-changes';
 
 export class FilesystemWatcherService {
-  private watcherStop: (() => void) | null = null;
+  private unlisten: UnlistenFn | null = null;
   constructor(private environment: () => string) {}
 
-  async start(onChange: (changes: FileChangeEvent[], eventType: FileChangeEvent['event']) => void): Promise<void> {
-    if (this.environment() !== 'tauri' || this.watcherStop) return;
-    this.watcherStop = await watch(
-      STORAGE_CONFIG.journalFolder,
-      (change: any) => {
-        const changedFiles = (change.paths || [])
-          .filter((p: string) => p.endsWith(STORAGE_CONFIG.fileExtension))
-          .map((p: string) => ({ path: p, event: 'modify' as const }));
-        if (!changedFiles.length) return;
-        // Basic heuristic: if count increased vs internal state we could refine; default to modify
-        onChange(changedFiles, 'modify');
-      },
-      { baseDir: STORAGE_CONFIG.baseDir, delayMs: 500 }
-    );
+  async start(onChange: (changes: EntryChange[]) => void): Promise<void> {
+    if (this.environment() !== 'tauri' || this.unlisten) return;
+    this.unlisten = await listen<EntryChange[]>(ENTRY_CHANGES_EVENT, (event) => {
+      if (event.payload.length) onChange(event.payload);
+    });
   }
 
-  stop(): void { if (this.watcherStop) { this.watcherStop(); this.watcherStop = null; } }
+  stop(): void { if (this.unlisten) { this.unlisten(); this.unlisten = null; } }
 }
@@ -65,7 +65,8 @@ export class LocalEntryService {
     if (!oldEntry) return;
     const updated: JournalEntry = { ...oldEntry, id: newId, title: newTitle, modified_at: new Date().toISOString() };
     await this.deps.entryCache.cacheEntry(updated);
-    await this.deps.entryCache.deleteCachedEntry(oldId);
+    // Desktop renames keep the ID, and with it the cloud mapping.
+    if (newId !== oldId) await this.deps.entryCache.deleteCachedEntry(oldId);
     await this.updateMetadataFromEntry(updated);
   }
 
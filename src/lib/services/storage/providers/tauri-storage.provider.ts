@@ -2,26 +2,21 @@
  * Tauri Storage Provider
  * 
  * Handles journal entry storage operations for Tauri desktop environment
- * using the filesystem API for persistence.
+ * by delegating to the native `EntryStore` commands in `diaryx_lib`.
  */
 
-import { 
-	readTextFile, 
-	writeTextFile, 
-	exists, 
-	remove, 
-	BaseDirectory 
-} from '@tauri-apps/plugin-fs';
+import { invoke } from '@tauri-apps/api/core';
 import type { StorageProvider } from './storage-provider.interface.js';
 import type { JournalEntry, JournalEntryMetadata } from '../../../storage/types.js';
 import { PreviewService } from '../../../storage/preview.service.js';
-import { 
-	STORAGE_CONFIG,
-	generateUniqueFilename,
-	createTitleFromId,
-	getEntryFilePath,
-	fileExistsByFilename
-} from '../utils/index.js';
+
+/** Mirrors `Notebook` in `store/notebooks.rs` */
+export interface Notebook {
+	/** Path relative to the journal root, empty for the root itself */
+	path: string;
+	notebooks: string[];
+	entries: JournalEntryMetadata[];
+}
 
 /**
  * Storage provider implementation for Tauri filesystem operations
@@ -33,7 +28,14 @@ export class TauriStorageProvider implements StorageProvider {
 	 * Ensures the journal directory exists in the filesystem.
 	 */
 	async initialize(): Promise<void> {
-		await this.ensureDirectoryExists();
+		// The native store creates the journal directory on first write
+	}
+
+	/**
+	 * New stable IDs of entries that were known by their filename, by old ID
+	 */
+	async getLegacyEntryIds(): Promise<Record<string, string>> {
+		return await invoke<Record<string, string>>('legacy_entry_ids');
 	}
 
 	/**
@@ -43,14 +45,7 @@ export class TauriStorageProvider implements StorageProvider {
 	 * @returns Promise resolving to entry or null if not found
 	 */
 	async getEntry(id: string): Promise<JournalEntry | null> {
-		const filePath = getEntryFilePath(id);
-		if (!(await exists(filePath, { baseDir: STORAGE_CONFIG.baseDir }))) {
-			return null;
-		}
-		const content = await readTextFile(filePath, { baseDir: STORAGE_CONFIG.baseDir });
-		const title = createTitleFromId(id);
-		const now = new Date().toISOString();
-		return { id, title, content, created_at: now, modified_at: now, file_path: filePath };
+		return await invoke<JournalEntry | null>('get_entry', { id });
 	}
 
 	/**
@@ -61,9 +56,7 @@ export class TauriStorageProvider implements StorageProvider {
 	 * @returns Promise resolving to true if save was successful
 	 */
 	async saveEntry(id: string, content: string): Promise<boolean> {
-		await this.ensureDirectoryExists();
-		const filePath = getEntryFilePath(id);
-		await writeTextFile(filePath, content, { baseDir: STORAGE_CONFIG.baseDir });
+		await invoke('save_entry', { id, content });
 		return true;
 	}
 
@@ -71,15 +64,11 @@ export class TauriStorageProvider implements StorageProvider {
 	 * Create a new journal entry in the filesystem
 	 * 
 	 * @param title - Entry title
+	 * @param notebook - Notebook path to create the entry in, the journal root by default
 	 * @returns Promise resolving to new entry ID or null if creation failed
 	 */
-	async createEntry(title: string): Promise<string | null> {
-		await this.ensureDirectoryExists();
-		const filename = await generateUniqueFilename(title, fileExistsByFilename);
-		const content = '';
-		const filePath = getEntryFilePath(filename);
-		await writeTextFile(filePath, content, { baseDir: STORAGE_CONFIG.baseDir });
-		return filename;
+	async createEntry(title: string, notebook?: string): Promise<string | null> {
+		return await invoke<string>('create_entry', { title, notebook });
 	}
 
 	/**
@@ -89,22 +78,17 @@ export class TauriStorageProvider implements StorageProvider {
 	 * @returns Promise resolving to true if deletion was successful
 	 */
 	async deleteEntry(id: string): Promise<boolean> {
-		const filePath = getEntryFilePath(id);
-		if (await exists(filePath, { baseDir: STORAGE_CONFIG.baseDir })) {
-			await remove(filePath, { baseDir: STORAGE_CONFIG.baseDir });
-		}
+		await invoke('delete_entry', { id });
 		return true;
 	}
 
 	/**
-	 * Get all entry metadata (not implemented for Tauri - handled by main service)
+	 * Get all entry metadata from the native store
 	 * 
-	 * @returns Promise resolving to empty array (metadata handled by main service)
+	 * @returns Promise resolving to metadata sorted by modification date
 	 */
 	async getAllEntryMetadata(): Promise<JournalEntryMetadata[]> {
-		// This is handled by the main service through file watching and caching
-		// The Tauri provider focuses on individual file operations
-		return [];
+		return await invoke<JournalEntryMetadata[]>('list_entries');
 	}
 
 	/**
@@ -114,22 +98,14 @@ export class TauriStorageProvider implements StorageProvider {
 	 * @returns Promise resolving to metadata or null if not found
 	 */
 	async getEntryMetadata(id: string): Promise<JournalEntryMetadata | null> {
-		const filePath = getEntryFilePath(id);
-		if (!(await exists(filePath, { baseDir: STORAGE_CONFIG.baseDir }))) {
+		const entry = await this.getEntry(id);
+		if (!entry) {
 			return null;
 		}
-		const content = await readTextFile(filePath, { baseDir: STORAGE_CONFIG.baseDir });
-		const title = createTitleFromId(id);
-		const preview = PreviewService.createPreview(content);
-		const now = new Date().toISOString();
-		
+		const { content, ...rest } = entry;
 		return { 
-			id, 
-			title, 
-			created_at: now, 
-			modified_at: now, 
-			file_path: filePath, 
-			preview,
+			...rest,
+			preview: PreviewService.createPreview(content),
 			isPublished: undefined // Publish status handled by main service
 		};
 	}
@@ -141,8 +117,7 @@ export class TauriStorageProvider implements StorageProvider {
 	 * @returns Promise resolving to true if entry exists
 	 */
 	async entryExists(id: string): Promise<boolean> {
-		const filePath = getEntryFilePath(id);
-		return await exists(filePath, { baseDir: STORAGE_CONFIG.baseDir });
+		return (await this.getEntry(id)) !== null;
 	}
 
 	/**
@@ -150,38 +125,42 @@ export class TauriStorageProvider implements StorageProvider {
 	 * 
 	 * @param oldId - Current entry ID
 	 * @param newTitle - New title
-	 * @returns Promise resolving to new entry ID or null if failed
+	 * @returns Promise resolving to the entry ID, which a rename keeps, or null if failed
 	 */
 	async renameEntry(oldId: string, newTitle: string): Promise<string | null> {
-		const oldFilePath = getEntryFilePath(oldId);
-		if (!(await exists(oldFilePath, { baseDir: STORAGE_CONFIG.baseDir }))) {
+		if (!(await this.entryExists(oldId))) {
 			return null;
 		}
-		const newId = await generateUniqueFilename(newTitle, fileExistsByFilename);
-		const newFilePath = getEntryFilePath(newId);
-		const content = await readTextFile(oldFilePath, { baseDir: STORAGE_CONFIG.baseDir });
-		await writeTextFile(newFilePath, content, { baseDir: STORAGE_CONFIG.baseDir });
-		await remove(oldFilePath, { baseDir: STORAGE_CONFIG.baseDir });
-		return newId;
+		return await invoke<string>('rename_entry', { oldId, newTitle });
 	}
 
 	/**
-	 * Ensure the journal directory exists in the filesystem
+	 * Create a notebook folder, along with missing parents
 	 * 
-	 * @private
+	 * @param path - Notebook path relative to the journal root, e.g. `2024/march`
+	 * @returns Promise resolving to the normalized notebook path
 	 */
-	private async ensureDirectoryExists(): Promise<void> {
-		// The mkdir function from Tauri plugin-fs will create the directory and parents when recursive is true
-		const { mkdir } = await import('@tauri-apps/plugin-fs');
-		try {
-			await mkdir(STORAGE_CONFIG.journalFolder, { 
-				baseDir: STORAGE_CONFIG.baseDir,
-				recursive: true 
-			});
-		} catch (error) {
-			if (!(error as any)?.message?.includes?.('File exists')) {
-				console.warn('Failed to create journal directory:', error);
-			}
-		}
+	async createNotebook(path: string): Promise<string> {
+		return await invoke<string>('create_notebook', { path });
+	}
+
+	/**
+	 * List the notebooks and entries directly inside a notebook
+	 * 
+	 * @param path - Notebook path, empty for the journal root
+	 */
+	async listNotebook(path = ''): Promise<Notebook> {
+		return await invoke<Notebook>('list_notebook', { path });
+	}
+
+	/**
+	 * Move an entry into another notebook; its ID is unchanged
+	 * 
+	 * @param id - Entry identifier
+	 * @param notebook - Target notebook path, empty for the journal root
+	 * @returns Promise resolving to the entry's new file path
+	 */
+	async moveEntry(id: string, notebook: string): Promise<string> {
+		return await invoke<string>('move_entry', { id, notebook });
 	}
 }
@@ -0,0 +1,60 @@
+/**
+ * Revision History Service
+ *
+ * Client for the native revision history in `diaryx_lib` (Tauri only). Every save is
+ * recorded as a compressed snapshot, encrypted when the vault is on, and thinned out
+ * by a retention policy.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+
+/** Mirrors `Revision` in `store/history.rs` */
+export interface EntryRevision {
+  id: number;
+  hash: string;
+  savedAt: string;
+  size: number;
+}
+
+/** Mirrors `RetentionPolicy` in `store/history.rs` */
+export interface RevisionRetention {
+  keepAllMinutes: number;
+  keepHourlyHours: number;
+  keepDailyDays: number;
+  maxRevisions: number;
+}
+
+export class RevisionHistoryService {
+  /** Revisions of an entry, newest first */
+  async listRevisions(entryId: string): Promise<EntryRevision[]> {
+    return invoke<EntryRevision[]>('list_revisions', { id: entryId });
+  }
+
+  /**
+   * Unified diff from revision `from` to revision `to`, or to the current content
+   * when `to` is omitted. Empty when nothing changed.
+   */
+  async diffRevisions(entryId: string, from: number, to?: number): Promise<string> {
+    return invoke<string>('diff_revisions', { id: entryId, from, to: to ?? null });
+  }
+
+  /**
+   * Make a revision the entry's content again and return that content. The replaced
+   * content stays in the history, so a restore can be undone.
+   */
+  async restoreRevision(entryId: string, revision: number): Promise<string> {
+    return invoke<string>('restore_revision', { id: entryId, revision });
+  }
+
+  async getRetention(): Promise<RevisionRetention> {
+    return invoke<RevisionRetention>('get_revision_retention');
+  }
+
+  /** Change the retention policy; revisions it no longer covers are pruned right away */
+  async setRetention(retention: RevisionRetention): Promise<void> {
+    await invoke('set_revision_retention', { retention });
+  }
+}
+
+// Export singleton instance
+export const revisionHistoryService = new RevisionHistoryService();
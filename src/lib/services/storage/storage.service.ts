@@ -2,7 +2,8 @@
 // Provides unified interface while delegating concerns to specialized services.
 
 import { openDB, type IDBPDatabase } from 'idb';
-import { writeTextFile, exists, mkdir, watch } from '@tauri-apps/plugin-fs';
+import { invoke } from '@tauri-apps/api/core';
+import { exists, mkdir } from '@tauri-apps/plugin-fs';
 import { fetch } from '../../utils/fetch';
 import type { JournalEntry, JournalEntryMetadata, DBSchema, StorageEnvironment } from '../../storage/types';
 import { metadataStore } from '../../stores/metadata';
@@ -26,10 +27,13 @@ import {
 import { LocalEntryService } from './local-entry.service';
 import { MetadataCacheService, EntryCacheService } from './metadata-cache.service';
 import { FilesystemWatcherService } from './filesystem-watcher.service';
-import { TauriEntryScannerService } from './tauri-entry-scanner.service';
 import { CloudMappingRepository } from './cloud/cloud-mapping.repository';
 import { CloudSyncServiceImpl } from './cloud/cloud-sync.service';
 import { SyncConflictService } from './sync-conflict.service';
+import { syncQueueService, type SyncQueueEvent } from './cloud/sync-queue.service';
+import { trashService, type TrashedEntry } from './trash.service';
+
+const LEGACY_IDS_MIGRATED_KEY = 'diaryx_legacy_entry_ids_migrated';
 
 export interface StorageServiceOptions {
   environment?: StorageEnvironment;
@@ -41,14 +45,12 @@ export interface StorageServiceOptions {
 export class StorageService {
   public environment: StorageEnvironment;
   private db: IDBPDatabase<DBSchema> | null = null;
-  private fileWatcher: (() => void) | null = null; // kept for UI usage
   private storageProvider: StorageProvider;
   private cloudMappingRepo: CloudMappingRepository;
   private cloudSync: CloudSyncServiceImpl;
   private metadataCache = new MetadataCacheService(() => this.initDB());
   private entryCache = new EntryCacheService(() => this.initDB());
   private fsWatcher = new FilesystemWatcherService(() => this.environment);
-  private tauriScanner = new TauriEntryScannerService(() => this.storageProvider as TauriStorageProvider);
   private conflictService = new SyncConflictService((id) => this.getCloudId(id), (id) => this.removeCloudMapping(id));
   private localEntryService = new LocalEntryService({
     environment: () => this.environment as 'tauri' | 'web',
@@ -68,6 +70,7 @@ export class StorageService {
     }
 
     this.initializeStorageProvider();
+    if (this.environment === 'tauri') this.migrateLegacyEntryIds().finally(() => this.initializeSyncQueue());
 
     this.cloudSync = options.cloudSync ?? new CloudSyncServiceImpl({
       getEntry: (id) => this.getEntry(id),
@@ -77,14 +80,13 @@ export class StorageService {
       checkSyncConflicts: (id, mod) => this.checkSyncConflicts(id, mod),
       environment: () => this.environment as 'tauri' | 'web',
       saveFileForImport: async (id, content) => {
-        const filePath = `${STORAGE_CONFIG.journalFolder}/${id}${STORAGE_CONFIG.fileExtension}`;
-        await writeTextFile(filePath, content, { baseDir: STORAGE_CONFIG.baseDir });
+        await this.storageProvider.saveEntry(id, content);
       },
       putIDBEntryForImport: async (entry) => {
         const db = await this.initDB();
         await db.put('entries', entry);
       },
-      generateUniqueImportId: (title) => this.generateUniqueFilenameForImport(title),
+      generateUniqueImportId: (title) => this.generateUniqueImportId(title),
       entryExistsForImport: (filename) => this.entryExistsForImport(filename)
     });
   }
@@ -93,6 +95,40 @@ export class StorageService {
   private async initializeStorageProvider(): Promise<void> {
     try { await this.storageProvider.initialize(); } catch (e) { console.error('Failed init storage provider', e); }
   }
+  // Entries used to be addressed by filename; move what is kept per entry to their stable IDs once.
+  private async migrateLegacyEntryIds(): Promise<void> {
+    if (!(this.storageProvider instanceof TauriStorageProvider) || localStorage.getItem(LEGACY_IDS_MIGRATED_KEY)) return;
+    try {
+      const renames = await this.storageProvider.getLegacyEntryIds();
+      if (Object.keys(renames).length) {
+        await this.cloudMappingRepo.renameLocalIds(renames);
+        tagSyncService.renameEntries(renames);
+      }
+      localStorage.setItem(LEGACY_IDS_MIGRATED_KEY, 'true');
+    } catch (e) { console.error('Failed to migrate entry IDs', e); }
+  }
+  private async initializeSyncQueue(): Promise<void> {
+    try {
+      syncQueueService.onEvent((e) => this.handleSyncQueueEvent(e));
+      await syncQueueService.initialize(import.meta.env.VITE_API_BASE_URL);
+    } catch (e) { console.error('Failed init sync queue', e); }
+  }
+  private async handleSyncQueueEvent(event: SyncQueueEvent): Promise<void> {
+    if (event.kind === 'imported') {
+      await this.cloudMappingRepo.storeMapping(event.entryId, event.cloudId); await this.updateEntryPublishStatusInMetadata(event.entryId, true);
+      return;
+    }
+    if (event.kind === 'removed') {
+      await this.removeCloudMapping(event.entryId); await this.updateEntryPublishStatusInMetadata(event.entryId, false);
+      return;
+    }
+    if (event.kind !== 'progress') return;
+    if (event.operation.kind === 'unpublish') {
+      await this.removeCloudMapping(event.entryId); await this.updateEntryPublishStatusInMetadata(event.entryId, false);
+    } else if (event.operation.kind === 'publish' && event.cloudId) {
+      await this.cloudMappingRepo.storeMapping(event.entryId, event.cloudId); await this.updateEntryPublishStatusInMetadata(event.entryId, true);
+    }
+  }
   public getJournalPath(): string { return getJournalDisplayPath(this.environment, STORAGE_CONFIG.journalFolder); }
   private async initDB(): Promise<IDBPDatabase<DBSchema>> {
     if (!this.db) {
@@ -147,6 +183,12 @@ export class StorageService {
   private async deleteEntryWithCloudSync(id: string): Promise<boolean> {
     return concurrencyManager.acquireCloudLock(id, async () => {
       try {
+        // On desktop the entry goes to the trash; its cloud copy stays until the trash is emptied.
+        if (this.environment === 'tauri') {
+          const localOk = await this.storageProvider.deleteEntry(id);
+          if (localOk) await this.entryCache.deleteCachedEntry(id);
+          return localOk;
+        }
         const cloudId = await this.getCloudId(id);
         if (cloudId && apiAuthService.isAuthenticated()) { await this.deleteFromCloud(cloudId); }
         const localOk = await this.storageProvider.deleteEntry(id);
@@ -165,22 +207,26 @@ export class StorageService {
     } catch (e) { console.error('Cloud delete failed', e); return false; }
   }
 
-  // Watching (legacy direct)
+  // Trash (Tauri only)
+  async listTrash(): Promise<TrashedEntry[]> { return this.environment === 'tauri' ? trashService.listTrash() : []; }
+  async restoreEntry(trashId: string): Promise<string | null> {
+    if (this.environment !== 'tauri') return null;
+    try { const id = await trashService.restoreEntry(trashId); try { await this.getAllEntries(); } catch {} return id; }
+    catch (e) { console.error('Restore failed', e); return null; }
+  }
+  async emptyTrash(): Promise<number> {
+    if (this.environment !== 'tauri') return 0;
+    // Link mappings the native queue has not seen yet, so purged entries get unpublished.
+    const mappings = await this.cloudMappingRepo.getAllMappings();
+    await syncQueueService.linkCloudEntries(mappings.map((m) => ({ entryId: m.localId, cloudId: m.cloudId })));
+    return (await trashService.emptyTrash()).length;
+  }
+
+  // Watching: the native watcher reports external changes by entry ID
   async startFileWatching(onChange: (changed?: string[], type?: string) => void): Promise<void> {
-    if (this.environment === 'tauri' && !this.fileWatcher) {
-      this.fileWatcher = await watch([STORAGE_CONFIG.journalFolder], (event) => {
-        if (!event.type || typeof event.type !== 'object') return;
-        if ('access' in event.type) return;
-        const hasModify = 'modify' in event.type; const hasCreate = 'create' in event.type; const hasRemove = 'remove' in event.type;
-        if (!hasModify && !hasCreate && !hasRemove) return;
-        const changed = event.paths || [];
-        let kind = 'unknown';
-        if (hasModify) kind = (event.type as any)?.modify?.kind || 'modify'; else if (hasCreate) kind = 'create'; else if (hasRemove) kind = 'remove';
-        onChange(changed, kind);
-      }, { baseDir: STORAGE_CONFIG.baseDir, delayMs: 500 });
-    }
+    await this.fsWatcher.start((changes) => onChange(changes.map((c) => c.id), changes[changes.length - 1].kind));
   }
-  stopFileWatching(): void { if (this.fileWatcher) { this.fileWatcher(); this.fileWatcher = null; } }
+  stopFileWatching(): void { this.fsWatcher.stop(); }
 
   // Cache helpers
   private async cacheMetadata(entries: JournalEntryMetadata[]): Promise<void> {
@@ -199,19 +245,44 @@ export class StorageService {
     catch (e) { console.warn('Publish status update failed', e); }
   }
   private async ensureDirectoryExists(): Promise<void> { if (!(await exists(STORAGE_CONFIG.journalFolder, { baseDir: STORAGE_CONFIG.baseDir }))) await mkdir(STORAGE_CONFIG.journalFolder, { baseDir: STORAGE_CONFIG.baseDir, recursive: true }); }
-  private async getTauriEntries(): Promise<JournalEntryMetadata[]> { await this.ensureDirectoryExists(); return this.tauriScanner.scan(); }
+  private async getTauriEntries(): Promise<JournalEntryMetadata[]> { await this.ensureDirectoryExists(); return invoke<JournalEntryMetadata[]>('scan_journal'); }
 
   // Cloud sync delegation
   private async checkSyncConflicts(entryId: string, localModified: string): Promise<{ hasConflict: boolean; cloudEntry?: any }> { return this.conflictService.check(entryId, localModified); }
-  async publishEntry(entryId: string, tagIds: string[] = []): Promise<boolean> { return this.cloudSync.publishEntry(entryId, tagIds); }
-  async unpublishEntry(entryId: string): Promise<boolean> { return this.cloudSync.unpublishEntry(entryId); }
+  // On desktop, uploads go through the durable native queue and apply once online.
+  async publishEntry(entryId: string, tagIds: string[] = []): Promise<boolean> {
+    if (this.environment !== 'tauri') return this.cloudSync.publishEntry(entryId, tagIds);
+    return this.enqueueSync(entryId, { kind: 'publish', tagIds });
+  }
+  async unpublishEntry(entryId: string): Promise<boolean> {
+    if (this.environment !== 'tauri') return this.cloudSync.unpublishEntry(entryId);
+    return this.enqueueSync(entryId, { kind: 'unpublish' });
+  }
   async getEntryPublishStatus(entryId: string): Promise<boolean | null> { return this.cloudSync.getEntryPublishStatus(entryId); }
-  async syncEntryToCloud(entryId: string, tagIds: string[] = []): Promise<boolean> { return this.cloudSync.syncEntryToCloud(entryId, tagIds); }
+  async syncEntryToCloud(entryId: string, tagIds: string[] = []): Promise<boolean> {
+    if (this.environment !== 'tauri') return this.cloudSync.syncEntryToCloud(entryId, tagIds);
+    if (!(await this.getCloudId(entryId))) return false;
+    const queued = await this.enqueueSync(entryId, { kind: 'update' });
+    return queued && (!tagIds.length || this.enqueueSync(entryId, { kind: 'share', tagIds }));
+  }
+  private async enqueueSync(entryId: string, operation: Parameters<typeof syncQueueService.enqueue>[1]): Promise<boolean> {
+    try { await syncQueueService.enqueue(entryId, operation, await this.getCloudId(entryId)); return true; }
+    catch (e) { console.error('Failed to queue sync operation', e); return false; }
+  }
   async fetchCloudEntries(): Promise<any[]> { return this.cloudSync.fetchCloudEntries(); }
   async importCloudEntries(): Promise<number> { return this.cloudSync.importCloudEntries(); }
   async hasCloudEntries(): Promise<boolean> { return this.cloudSync.hasCloudEntries(); }
   async syncAfterLogin(): Promise<number> { return this.cloudSync.syncAfterLogin(); }
-  async performBidirectionalSync(): Promise<{ imported: number; uploaded: number; conflicts: number }> { return this.cloudSync.performBidirectionalSync(); }
+  async performBidirectionalSync(): Promise<{ imported: number; uploaded: number; conflicts: number }> {
+    if (this.environment !== 'tauri') return this.cloudSync.performBidirectionalSync();
+    // Native sync reads only what changed since this device's cursor, merges remote
+    // edits three-way and queues the results for upload.
+    const mappings = await this.cloudMappingRepo.getAllMappings();
+    await syncQueueService.linkCloudEntries(mappings.map((m) => ({ entryId: m.localId, cloudId: m.cloudId })));
+    const report = await syncQueueService.pullCloudChanges();
+    if (report.imported.length || report.removed.length) { try { await this.getAllEntries(); } catch {} }
+    return { imported: report.imported.length, uploaded: report.merged.length, conflicts: report.conflicts.length };
+  }
 
   // Cloud mapping
   async getCloudId(localId: string): Promise<string | null> { return this.cloudMappingRepo.getCloudId(localId); }
@@ -242,7 +313,7 @@ export class StorageService {
   }
   private async decryptCloudEntry(cloudEntry: any): Promise<EntryObject | null> {
     if (!e2eEncryptionService.isUnlocked()) throw new Error('E2E encryption not unlocked');
-    try { const accessKey = await entrySharingService.getEntryAccessKey(cloudEntry.id); if (!accessKey) throw new Error('No access key'); const entryKey = e2eEncryptionService.rewrapEntryKeyForUser(accessKey.encrypted_entry_key, accessKey.key_nonce, cloudEntry.author_public_key); if (!entryKey) throw new Error('Entry key decrypt failed'); const encryptedData = { encryptedContentB64: cloudEntry.encrypted_content, contentNonceB64: cloudEntry.encryption_metadata?.contentNonceB64, encryptedEntryKeyB64: entryKey.encryptedEntryKeyB64, keyNonceB64: entryKey.keyNonceB64 }; return e2eEncryptionService.decryptEntry(encryptedData, cloudEntry.author_public_key); } catch (e) { console.error('Decrypt cloud entry failed', e); return null; }
+    try { const accessKey = await entrySharingService.getEntryAccessKey(cloudEntry.id); if (!accessKey) throw new Error('No access key'); const entryKey = await e2eEncryptionService.rewrapEntryKeyForUser(accessKey.encrypted_entry_key, accessKey.key_nonce, cloudEntry.author_public_key); if (!entryKey) throw new Error('Entry key decrypt failed'); const encryptedData = { encryptedContentB64: cloudEntry.encrypted_content, contentNonceB64: cloudEntry.encryption_metadata?.contentNonceB64, encryptedEntryKeyB64: entryKey.encryptedEntryKeyB64, keyNonceB64: entryKey.keyNonceB64 }; return await e2eEncryptionService.decryptEntry(encryptedData, cloudEntry.author_public_key); } catch (e) { console.error('Decrypt cloud entry failed', e); return null; }
   }
 
   // Concurrency status
@@ -260,9 +331,13 @@ export class StorageService {
   async getFrontmatterTags(entryId: string): Promise<string[]> { try { const entry = await this.getEntry(entryId); return entry ? tagSyncService.getFrontmatterTags(entry.content) : []; } catch { return []; } }
   async needsTagSync(entryId: string, backendTagIds: string[]): Promise<boolean> { try { const tags = await this.getFrontmatterTags(entryId); return tagSyncService.needsSync(entryId, tags, backendTagIds); } catch { return false; } }
 
-  // Import helpers
+  // Import helpers; on desktop the native store names the file and assigns the ID
+  private async generateUniqueImportId(title: string): Promise<string> {
+    if (this.environment !== 'tauri') return this.generateUniqueFilenameForImport(title);
+    const id = await this.storageProvider.createEntry(title); if (!id) throw new Error('Create failed'); return id;
+  }
   private async generateUniqueFilenameForImport(baseTitle: string): Promise<string> { const safe = titleToSafeFilename(baseTitle); let name = safe; let c = 1; while (await this.entryExistsForImport(name)) { name = `${safe}-${c++}`; } return name; }
-  private async entryExistsForImport(filename: string): Promise<boolean> { if (this.environment === 'tauri') { const filePath = `${STORAGE_CONFIG.journalFolder}/${filename}${STORAGE_CONFIG.fileExtension}`; return await exists(filePath, { baseDir: STORAGE_CONFIG.baseDir }); } const db = await this.initDB(); return !!(await db.get('entries', filename)); }
+  private async entryExistsForImport(filename: string): Promise<boolean> { if (this.environment === 'tauri') return this.storageProvider.entryExists(filename); const db = await this.initDB(); return !!(await db.get('entries', filename)); }
 }
 
 export const storageService = new StorageService();
@@ -0,0 +1,49 @@
+/**
+ * Trash Service
+ *
+ * Client for the native trash in `diaryx_lib` (Tauri only). Deleted entries are moved
+ * into the journal's trash and can be restored until the trash is emptied or they
+ * expire. Their cloud copies are unpublished only then.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+
+/** Mirrors `TrashedEntry` in `store/trash.rs` */
+export interface TrashedEntry {
+  trashId: string;
+  entryId: string;
+  title: string;
+  filePath: string;
+  createdAt: string;
+  modifiedAt: string;
+  deletedAt: string;
+}
+
+export class TrashService {
+  /** Trashed entries, most recently deleted first; expired ones are purged first */
+  async listTrash(): Promise<TrashedEntry[]> {
+    return invoke<TrashedEntry[]>('list_trash');
+  }
+
+  /** Move a trashed entry back into the journal and return its entry ID */
+  async restoreEntry(trashId: string): Promise<string> {
+    return invoke<string>('restore_entry', { trashId });
+  }
+
+  /** Delete every trashed entry for good and return them */
+  async emptyTrash(): Promise<TrashedEntry[]> {
+    return invoke<TrashedEntry[]>('empty_trash');
+  }
+
+  /** Days a trashed entry is kept before it is purged */
+  async getRetentionDays(): Promise<number> {
+    return invoke<number>('get_trash_retention');
+  }
+
+  async setRetentionDays(days: number): Promise<void> {
+    await invoke('set_trash_retention', { days });
+  }
+}
+
+// Export singleton instance
+export const trashService = new TrashService();
@@ -13,6 +13,7 @@ import type { JournalEntry } from '../../../storage/types';
  */
 export const STORAGE_CONFIG = {
 	fileExtension: '.md',
+	/** Default journal folder; on desktop the active vault decides (see `vault.service.ts`) */
 	journalFolder: 'Diaryx',
 	baseDir: BaseDirectory.Document,
 	maxFilenameLength: 50,
@@ -23,21 +24,27 @@ export const STORAGE_CONFIG = {
 /**
  * Convert a title to a safe filename
  * 
- * Removes special characters, converts to lowercase, and limits length
- * for use as a filesystem-safe filename.
+ * Keeps letters, digits and marks of every script, lowercased and NFC
+ * normalized, drops other characters and limits length. On Tauri the native
+ * store names files itself (`store/slug.rs`); this is the web fallback.
  * 
  * @param title - The title to convert
- * @returns Safe filename string
+ * @returns Safe filename string, never empty
  */
 export function titleToSafeFilename(title: string): string {
-	return title
+	const slug = title
+		.normalize('NFC')
 		.trim()
 		.toLowerCase()
-		.replace(/[^a-z0-9\s-]/g, '')
+		.replace(/[^\p{L}\p{N}\p{M}\s-]/gu, '')
 		.replace(/\s+/g, '-')
 		.replace(/-+/g, '-')
-		.replace(/^-|-$/g, '')
-		.substring(0, STORAGE_CONFIG.maxFilenameLength);
+		.replace(/^-|-$/g, '');
+	const truncated = Array.from(slug)
+		.slice(0, STORAGE_CONFIG.maxFilenameLength)
+		.join('')
+		.replace(/-$/, '');
+	return truncated || 'untitled';
 }
 
 /**
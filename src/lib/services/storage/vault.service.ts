@@ -0,0 +1,69 @@
+/**
+ * Vault Service
+ *
+ * Client for the native vaults in `diaryx_lib` (Tauri only). Each vault is a journal
+ * folder with its own settings, sync queue and optional encryption at rest; the native
+ * side remembers which one is active and points every entry and sync command at it.
+ * Switching restarts the watcher and search index and emits `VAULT_EVENT`, after which
+ * entries, conflicts and the sync status should be reloaded.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+/** Name of the event emitted by the native side when the active vault changes */
+export const VAULT_EVENT = 'vault-changed';
+
+/** Mirrors `VaultConfig` in `vaults/mod.rs` */
+export interface VaultConfig {
+  id: string;
+  name: string;
+  root: string;
+  settings: Record<string, unknown>;
+}
+
+/** Mirrors `VaultInfo` in `vaults/mod.rs` */
+export interface VaultInfo extends VaultConfig {
+  active: boolean;
+  /** Entry content is encrypted at rest; file and folder names still follow entry titles */
+  encrypted: boolean;
+}
+
+export class VaultService {
+  async listVaults(): Promise<VaultInfo[]> {
+    return invoke<VaultInfo[]>('list_vaults');
+  }
+
+  /**
+   * Add a vault for the folder at the absolute path `root`, creating it if needed. With
+   * `encrypt`, entry content is encrypted at rest, but titles remain visible in file names.
+   */
+  async addVault(
+    name: string,
+    root: string,
+    options: { settings?: Record<string, unknown>; encrypt?: boolean } = {}
+  ): Promise<VaultConfig> {
+    return invoke<VaultConfig>('add_vault', { name, root, settings: options.settings, encrypt: options.encrypt });
+  }
+
+  async setVaultSettings(id: string, settings: Record<string, unknown>): Promise<VaultConfig> {
+    return invoke<VaultConfig>('set_vault_settings', { id, settings });
+  }
+
+  /** Make `id` the active vault and return it */
+  async switchVault(id: string): Promise<VaultInfo> {
+    return invoke<VaultInfo>('switch_vault', { id });
+  }
+
+  /** Forget a vault other than the active one; its folder is left on disk */
+  async removeVault(id: string): Promise<VaultConfig> {
+    return invoke<VaultConfig>('remove_vault', { id });
+  }
+
+  async onVaultChanged(listener: (vault: VaultInfo) => void): Promise<UnlistenFn> {
+    return listen<VaultInfo>(VAULT_EVENT, (event) => listener(event.payload));
+  }
+}
+
+// Export singleton instance
+export const vaultService = new VaultService();
@@ -475,6 +475,21 @@ class TagSyncService {
     return a.every((val, i) => val === b[i]);
   }
 
+  /**
+   * Move sync metadata from old to new entry IDs, e.g. once entries get stable IDs
+   */
+  renameEntries(renames: Record<string, string>): void {
+    this.syncMetadataStore.update((map) => {
+      const renamed = new Map<string, TagSyncMetadata>();
+      map.forEach((metadata, entryId) => {
+        const newId = renames[entryId] ?? entryId;
+        renamed.set(newId, { ...metadata, entryId: newId });
+      });
+      return renamed;
+    });
+    this.saveSyncMetadata();
+  }
+
   /**
    * Get sync metadata store for reactive updates
    */